@@ -0,0 +1,49 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Benchmark of locking and unlocking with many simultaneously held ranges.
+//!
+//! Run with: `cargo bench --bench many_ranges`
+
+use range_lock::VecRangeLock;
+use std::{hint::black_box, time::Instant};
+
+const ITERATIONS: usize = 100_000;
+
+fn bench(held: usize) {
+    // Each held range has 2 elements, followed by a free element.
+    let lock = VecRangeLock::new(vec![0_u8; held * 3]);
+    let guards: Vec<_> = (0..held)
+        .map(|i| lock.try_lock(i * 3..i * 3 + 2).expect("Failed to lock"))
+        .collect();
+
+    let begin = Instant::now();
+    for i in 0..ITERATIONS {
+        // Lock a free element in between the held ranges.
+        let pos = (i % held) * 3 + 2;
+        black_box(lock.try_lock(pos..pos + 1).expect("Failed to lock"));
+        // Try to lock a contended range.
+        assert!(black_box(lock.try_lock(pos - 1..pos + 1)).is_err());
+    }
+    let elapsed = begin.elapsed();
+
+    drop(guards);
+    println!(
+        "{held:>6} held ranges: {:>8.1} ns per lock/unlock + contended attempt",
+        elapsed.as_nanos() as f64 / ITERATIONS as f64
+    );
+}
+
+fn main() {
+    for held in [10, 100, 1_000, 10_000] {
+        bench(held);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
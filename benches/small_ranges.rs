@@ -0,0 +1,80 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Benchmark of VecRangeLock against SmallVecRangeLock with few held ranges.
+//!
+//! Run with: `cargo bench --bench small_ranges`
+
+use range_lock::VecRangeLock;
+use std::{hint::black_box, thread, time::Instant};
+
+const ITERATIONS: usize = 1_000_000;
+const THREADS: usize = 4;
+
+/// Lock and unlock a range next to `held` held ranges.
+macro_rules! bench_single {
+    ($name:literal, $lock:expr, $held:expr) => {{
+        let held = $held;
+        let lock = $lock;
+        let guards: Vec<_> = (0..held)
+            .map(|i| lock.try_lock(i * 2..i * 2 + 1).expect("Failed to lock"))
+            .collect();
+        let pos = held * 2;
+        let begin = Instant::now();
+        for _ in 0..ITERATIONS {
+            black_box(lock.try_lock(pos..pos + 1).expect("Failed to lock"));
+        }
+        let elapsed = begin.elapsed();
+        drop(guards);
+        println!(
+            "{:>18}, {held} held ranges: {:>6.1} ns per lock/unlock",
+            $name,
+            elapsed.as_nanos() as f64 / ITERATIONS as f64
+        );
+    }};
+}
+
+/// Lock and unlock disjoint ranges from multiple threads.
+macro_rules! bench_threads {
+    ($name:literal, $lock:expr) => {{
+        let lock = $lock;
+        let begin = Instant::now();
+        thread::scope(|s| {
+            for t in 0..THREADS {
+                let lock = &lock;
+                s.spawn(move || {
+                    for _ in 0..ITERATIONS {
+                        black_box(lock.try_lock(t..t + 1).expect("Failed to lock"));
+                    }
+                });
+            }
+        });
+        let elapsed = begin.elapsed();
+        println!(
+            "{:>18}, {THREADS} threads:       {:>6.1} ns per lock/unlock",
+            $name,
+            elapsed.as_nanos() as f64 / ITERATIONS as f64
+        );
+    }};
+}
+
+fn main() {
+    for held in 0..4 {
+        bench_single!("VecRangeLock", VecRangeLock::new(vec![0_u8; 16]), held);
+        bench_single!(
+            "SmallVecRangeLock",
+            VecRangeLock::new_small(vec![0_u8; 16]),
+            held
+        );
+    }
+    bench_threads!("VecRangeLock", VecRangeLock::new(vec![0_u8; 16]));
+    bench_threads!("SmallVecRangeLock", VecRangeLock::new_small(vec![0_u8; 16]));
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,205 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard, Storage};
+use allocator_api2::{alloc::Allocator, vec::Vec};
+use std::{
+    cell::UnsafeCell,
+    marker::PhantomData,
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::TryLockResult,
+};
+
+/// Data of an [AllocVecRangeLock].
+#[derive(Debug)]
+struct AllocVec<T, A: Allocator>(UnsafeCell<Vec<T, A>>);
+
+// SAFETY: The Vec is never modified while it is shared.
+unsafe impl<T, A: Allocator> Storage for AllocVec<T, A> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+        //         The Vec is never modified while the lock is shared.
+        unsafe { (*self.0.get()).len() }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        // SAFETY: See data_len().
+        // The pointer returned by as_ptr() carries the provenance of the data buffer.
+        // Therefore, it may be used for mutable access.
+        unsafe { (*self.0.get()).as_ptr().cast_mut() }
+    }
+}
+
+/// Multi-thread range lock for a [Vec](allocator_api2::vec::Vec) with a custom allocator.
+///
+/// This is a variant of [VecRangeLock](crate::VecRangeLock)
+/// for data that is allocated with the allocator `A` instead of the global allocator.
+/// The [Allocator] trait and the [Vec](allocator_api2::vec::Vec) type
+/// are provided by the `allocator-api2` crate.
+/// On nightly Rust with the `nightly` feature of `allocator-api2`
+/// they are the types of the standard library.
+///
+/// This type is available with the `allocator_api` feature.
+///
+/// # Example
+///
+/// ```
+/// use allocator_api2::{alloc::Global, vec::Vec};
+/// use range_lock::AllocVecRangeLock;
+///
+/// let mut data = Vec::new_in(Global);
+/// data.extend([1, 2, 3, 4]);
+/// let lock = AllocVecRangeLock::new_in(data);
+/// let mut guard = lock.try_lock(1..3).expect("Failed to lock 1..3");
+/// guard[0] = 20;
+/// drop(guard);
+/// let data: Vec<i32, Global> = lock.into_inner();
+/// assert_eq!(data[..], [1, 20, 3, 4]);
+/// ```
+#[derive(Debug)]
+pub struct AllocVecRangeLock<T, A: Allocator> {
+    /// The lock state and the protected data.
+    raw: RawRangeLock<AllocVec<T, A>>,
+
+    /// The allocator is used through a shared reference. See allocator().
+    /// Therefore, the lock is only Sync, if the allocator is Sync.
+    _alloc: PhantomData<A>,
+}
+
+impl<'a, T, A: Allocator> AllocVecRangeLock<T, A> {
+    /// Construct a new [AllocVecRangeLock].
+    ///
+    /// * `data`: The data [Vec](allocator_api2::vec::Vec) to protect.
+    ///   It keeps its allocator.
+    pub fn new_in(data: Vec<T, A>) -> AllocVecRangeLock<T, A> {
+        AllocVecRangeLock {
+            raw: RawRangeLock::new(AllocVec(UnsafeCell::new(data))),
+            _alloc: PhantomData,
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec](allocator_api2::vec::Vec).
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Get a reference to the allocator of the data.
+    #[inline]
+    pub fn allocator(&self) -> &A {
+        // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+        //         The Vec is never modified while the lock is shared.
+        unsafe { (*self.raw.data.0.get()).allocator() }
+    }
+
+    /// Unwrap the [AllocVecRangeLock] into the contained data.
+    /// The data keeps its allocator.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T, A> {
+        self.raw.data.0.into_inner()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// See [VecRangeLock::try_lock](crate::VecRangeLock::try_lock) for the return values.
+    /// Panics, if the range is out of bounds of the data.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<AllocVecRangeLockGuard<'a, T, A>> {
+        wrap_guard(self.raw.try_lock(range), AllocVecRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [AllocVecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+#[derive(Debug)]
+pub struct AllocVecRangeLockGuard<'a, T, A: Allocator>(RawRangeLockGuard<'a, AllocVec<T, A>>);
+
+impl<'a, T, A: Allocator> Deref for AllocVecRangeLockGuard<'a, T, A> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T, A: Allocator> DerefMut for AllocVecRangeLockGuard<'a, T, A> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use allocator_api2::alloc::{AllocError, Global, Layout};
+    use std::{
+        ptr::NonNull,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            TryLockError,
+        },
+    };
+
+    /// Allocator that counts the live allocations.
+    #[derive(Debug, Default)]
+    struct CountingAlloc(AtomicUsize);
+
+    unsafe impl Allocator for &CountingAlloc {
+        fn allocate(&self, layout: Layout) -> Result<NonNull<[u8]>, AllocError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Global.allocate(layout)
+        }
+
+        unsafe fn deallocate(&self, ptr: NonNull<u8>, layout: Layout) {
+            self.0.fetch_sub(1, Ordering::Relaxed);
+            Global.deallocate(ptr, layout)
+        }
+    }
+
+    #[test]
+    fn test_alloc_rangelock() {
+        let alloc = CountingAlloc::default();
+        {
+            let mut data = Vec::with_capacity_in(4, &alloc);
+            data.extend([1_i32, 2, 3, 4]);
+            assert_eq!(alloc.0.load(Ordering::Relaxed), 1);
+            let a = AllocVecRangeLock::new_in(data);
+            assert!(std::ptr::eq(*a.allocator(), &alloc));
+            {
+                let mut g0 = a.try_lock(0..2).unwrap();
+                assert!(matches!(a.try_lock(1..3), Err(TryLockError::WouldBlock)));
+                g0[1] = 20;
+            }
+            let data = a.into_inner();
+            assert_eq!(data[..], [1, 20, 3, 4]);
+        }
+        assert_eq!(alloc.0.load(Ordering::Relaxed), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_alloc_rangelock_oob() {
+        let mut data = Vec::new_in(Global);
+        data.extend([1_i32, 2, 3, 4]);
+        let a = AllocVecRangeLock::new_in(data);
+        let _ = a.try_lock(3..5);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,153 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard};
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::TryLockResult,
+};
+
+/// General purpose multi-thread range lock for arrays `[T; N]`.
+///
+/// This is a variant of [VecRangeLock](crate::VecRangeLock)
+/// that stores the data inline instead of in a heap allocated [Vec].
+///
+/// # Example
+///
+/// ```
+/// use range_lock::ArrayRangeLock;
+/// use std::thread;
+///
+/// let lock = ArrayRangeLock::new([1, 2, 3, 4]);
+/// thread::scope(|s| {
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+///         guard[0] = 10;
+///     });
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+///         guard[1] = 40;
+///     });
+/// });
+/// assert_eq!(lock.into_inner(), [10, 2, 3, 40]);
+/// ```
+#[derive(Debug)]
+pub struct ArrayRangeLock<T, const N: usize> {
+    /// The lock state and the underlying data.
+    raw: RawRangeLock<UnsafeCell<[T; N]>>,
+}
+
+impl<'a, T, const N: usize> ArrayRangeLock<T, N> {
+    /// Construct a new [ArrayRangeLock].
+    ///
+    /// * `data`: The data array to protect.
+    pub fn new(data: [T; N]) -> ArrayRangeLock<T, N> {
+        ArrayRangeLock {
+            raw: RawRangeLock::new(UnsafeCell::new(data)),
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded array.
+    #[inline]
+    pub const fn data_len(&self) -> usize {
+        N
+    }
+
+    /// Unwrap this [ArrayRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> [T; N] {
+        debug_assert!(self.raw.is_all_unlocked());
+        self.raw.data.into_inner()
+    }
+
+    /// Get a mutable slice of the whole data.
+    ///
+    /// The mutable borrow statically guarantees that no guards exist.
+    /// Therefore, no locking is required.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut [T; N] {
+        self.raw.data.get_mut()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// Panics, if the range is out of bounds of the array.
+    ///
+    /// * On success: Returns an [ArrayRangeLockGuard] that can be used to access the locked region.
+    ///   Dereferencing [ArrayRangeLockGuard] yields a slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock](std::sync::TryLockError::WouldBlock),
+    ///   if the range is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned](std::sync::TryLockError::Poisoned),
+    ///   if the lock is poisoned. The poisoned guard holds the range.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<ArrayRangeLockGuard<'a, T, N>> {
+        wrap_guard(self.raw.try_lock(range), ArrayRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [ArrayRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See the documentation of [ArrayRangeLock] for usage examples of [ArrayRangeLockGuard].
+#[derive(Debug)]
+pub struct ArrayRangeLockGuard<'a, T, const N: usize>(RawRangeLockGuard<'a, UnsafeCell<[T; N]>>);
+
+impl<'a, T, const N: usize> Deref for ArrayRangeLockGuard<'a, T, N> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T, const N: usize> DerefMut for ArrayRangeLockGuard<'a, T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_array() {
+        let a = ArrayRangeLock::new([1_i32, 2, 3, 4, 5, 6]);
+        assert_eq!(a.data_len(), 6);
+        {
+            let mut g0 = a.try_lock(2..4).unwrap();
+            assert!(a.try_lock(3..5).is_err());
+            let mut g1 = a.try_lock(4..).unwrap();
+            let g2 = a.try_lock(6..6).unwrap();
+            assert_eq!(g0[..], [3, 4]);
+            assert_eq!(g1[..], [5, 6]);
+            assert!(g2.is_empty());
+            g0[1] = 40;
+            g1[0] = 50;
+        }
+        assert!(a.raw.is_all_unlocked());
+        assert_eq!(a.into_inner(), [1, 2, 3, 40, 50, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_array_oob() {
+        let a = ArrayRangeLock::new([1_i32, 2, 3]);
+        let _ = a.try_lock(2..4);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
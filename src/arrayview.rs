@@ -0,0 +1,106 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLockGuard;
+use ndarray::{ArrayView, ArrayViewMut, Dimension, ShapeBuilder, ShapeError};
+
+impl<'a, T> VecRangeLockGuard<'a, T> {
+    /// Get an [ndarray::ArrayView] of the locked slice with the given `shape`.
+    ///
+    /// The view borrows the guard.
+    /// Therefore, it can't outlive the locked range.
+    ///
+    /// Returns an error, if the number of elements of `shape`
+    /// does not match the length of the locked slice.
+    ///
+    /// This method is available with the `ndarray` feature.
+    pub fn as_array_view<Sh>(&self, shape: Sh) -> Result<ArrayView<'_, T, Sh::Dim>, ShapeError>
+    where
+        Sh: ShapeBuilder,
+        Sh::Dim: Dimension,
+    {
+        let shape = shape.into_shape_with_order();
+        check_len(shape.raw_dim().size(), self.len())?;
+        ArrayView::from_shape(shape, &self[..])
+    }
+
+    /// Get an [ndarray::ArrayViewMut] of the locked slice with the given `shape`.
+    ///
+    /// The view mutably borrows the guard.
+    /// Therefore, it can't outlive the locked range.
+    ///
+    /// Returns an error, if the number of elements of `shape`
+    /// does not match the length of the locked slice.
+    ///
+    /// This method is available with the `ndarray` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// // A 3x2 matrix.
+    /// let lock = VecRangeLock::new(vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0]);
+    /// let mut guard = lock.try_lock(2..6).expect("Failed to lock rows 1 and 2");
+    /// let mut rows = guard.as_array_view_mut((2, 2)).expect("Invalid shape");
+    /// rows *= 10.0;
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![1.0, 2.0, 30.0, 40.0, 50.0, 60.0]);
+    /// ```
+    pub fn as_array_view_mut<Sh>(
+        &mut self,
+        shape: Sh,
+    ) -> Result<ArrayViewMut<'_, T, Sh::Dim>, ShapeError>
+    where
+        Sh: ShapeBuilder,
+        Sh::Dim: Dimension,
+    {
+        let shape = shape.into_shape_with_order();
+        check_len(shape.raw_dim().size(), self.len())?;
+        ArrayViewMut::from_shape(shape, &mut self[..])
+    }
+}
+
+/// Check that the number of elements of the shape matches the slice length.
+fn check_len(shape_len: usize, slice_len: usize) -> Result<(), ShapeError> {
+    if shape_len == slice_len {
+        Ok(())
+    } else {
+        Err(ShapeError::from_kind(ndarray::ErrorKind::IncompatibleShape))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rangelock::VecRangeLock;
+    use ndarray::ErrorKind;
+
+    #[test]
+    fn test_array_view() {
+        let a = VecRangeLock::new((0..12).collect::<Vec<i32>>());
+        {
+            let mut g = a.try_lock(6..12).unwrap();
+            assert_eq!(
+                g.as_array_view((4, 2)).unwrap_err().kind(),
+                ErrorKind::IncompatibleShape
+            );
+            assert_eq!(
+                g.as_array_view_mut((2, 2)).unwrap_err().kind(),
+                ErrorKind::IncompatibleShape
+            );
+            let v = g.as_array_view((2, 3)).unwrap();
+            assert_eq!(v[[1, 0]], 9);
+            let mut v = g.as_array_view_mut((3, 2)).unwrap();
+            v[[2, 1]] = 110;
+        }
+        assert_eq!(a.into_inner()[11], 110);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,250 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Lock-free set of a small number of locked ranges.
+//!
+//! Each range is published in one of `N` atomic slots.
+//! An inserting thread first publishes its range and then scans all other slots for overlaps.
+//! All slot state accesses are sequentially consistent.
+//! Therefore, of two threads inserting overlapping ranges concurrently,
+//! at least one of them sees the range of the other one and backs off.
+//!
+//! If all slots are occupied, the range is inserted into a mutex protected [LockedRanges].
+
+use crate::{
+    lockedranges::{overlaps, LockedRanges},
+    mutex::Mutex,
+    rawrangelock::RangeSet,
+};
+use std::{
+    hint,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LockResult, PoisonError,
+    },
+};
+
+/// The slot is not used.
+const FREE: usize = 0;
+/// The slot has been claimed and its range is being written.
+const WRITING: usize = 1;
+/// The slot holds a locked range.
+const PUBLISHED: usize = 2;
+
+/// Atomic slot of one locked range.
+#[derive(Debug)]
+struct Slot {
+    /// State of the slot. Only modified by read-modify-write operations,
+    /// so that every later state continues the release sequence of the last unlock.
+    state: AtomicUsize,
+    /// Inclusive start of the range.
+    start: AtomicUsize,
+    /// Exclusive end of the range.
+    end: AtomicUsize,
+}
+
+impl Slot {
+    const fn new() -> Self {
+        Self {
+            state: AtomicUsize::new(FREE),
+            start: AtomicUsize::new(0),
+            end: AtomicUsize::new(0),
+        }
+    }
+}
+
+/// Location of a range inserted into [AtomicRanges].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Entry {
+    /// The range is stored in the slot with this index.
+    Slot(usize),
+    /// The range is stored in the overflow set.
+    Overflow,
+}
+
+/// Lock-free set of up to `N` non-overlapping ranges,
+/// with a mutex protected overflow set for more ranges.
+#[derive(Debug)]
+pub struct AtomicRanges<const N: usize> {
+    /// The atomic range slots.
+    slots: [Slot; N],
+    /// Number of ranges in the overflow set.
+    overflow_count: AtomicUsize,
+    /// Ranges that did not fit into the slots.
+    overflow: Mutex<LockedRanges>,
+}
+
+impl<const N: usize> AtomicRanges<N> {
+    pub fn new() -> Self {
+        Self {
+            slots: [const { Slot::new() }; N],
+            overflow_count: AtomicUsize::new(0),
+            overflow: Mutex::new(LockedRanges::new()),
+        }
+    }
+
+    /// Get a published slot range that overlaps with `range`.
+    /// The slot `skip` is not checked.
+    fn find_in_slots(&self, range: &Range<usize>, skip: Option<usize>) -> Option<Range<usize>> {
+        for (idx, slot) in self.slots.iter().enumerate() {
+            if Some(idx) == skip {
+                continue;
+            }
+            let mut state = slot.state.load(Ordering::SeqCst);
+            while state == WRITING {
+                // The range of this slot is about to be published.
+                // This only takes two stores. Wait for it.
+                hint::spin_loop();
+                state = slot.state.load(Ordering::SeqCst);
+            }
+            if state == PUBLISHED {
+                // The slot may be freed and reused concurrently.
+                // A reused slot holds a range that has been published after our range.
+                // Its owner will see our range. Therefore, a torn read is harmless.
+                let locked = slot.start.load(Ordering::SeqCst)..slot.end.load(Ordering::SeqCst);
+                if overlaps(&locked, range) {
+                    return Some(locked);
+                }
+            }
+        }
+        None
+    }
+
+    /// Get a range of the overflow set that overlaps with `range`.
+    fn find_in_overflow(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        if self.overflow_count.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let overflow = self.overflow.lock().unwrap_or_else(PoisonError::into_inner);
+        overflow.find_conflicting(range)
+    }
+
+    /// Insert the non-empty `range`.
+    /// Returns an overlapping range on conflict, if known.
+    pub fn insert(&self, range: &Range<usize>) -> Result<Entry, Option<Range<usize>>> {
+        debug_assert!(!range.is_empty());
+        let claimed = self.slots.iter().position(|slot| {
+            slot.state
+                .compare_exchange(FREE, WRITING, Ordering::SeqCst, Ordering::Relaxed)
+                .is_ok()
+        });
+        let Some(idx) = claimed else {
+            return self.insert_overflow(range);
+        };
+        let slot = &self.slots[idx];
+        slot.start.store(range.start, Ordering::SeqCst);
+        slot.end.store(range.end, Ordering::SeqCst);
+        slot.state.swap(PUBLISHED, Ordering::SeqCst);
+
+        // Our range is published. Now check for overlaps.
+        let conflicting = self
+            .find_in_slots(range, Some(idx))
+            .or_else(|| self.find_in_overflow(range));
+        if conflicting.is_some() {
+            slot.state.swap(FREE, Ordering::SeqCst);
+            return Err(conflicting);
+        }
+        Ok(Entry::Slot(idx))
+    }
+
+    /// Insert the non-empty `range` into the overflow set.
+    fn insert_overflow(&self, range: &Range<usize>) -> Result<Entry, Option<Range<usize>>> {
+        let mut overflow = self.overflow.lock().unwrap_or_else(PoisonError::into_inner);
+        overflow.insert(range).map_err(Some)?;
+        // Announce the overflow range before scanning the slots.
+        // A concurrent slot insertion either sees the announcement
+        // or has published its range before our scan.
+        self.overflow_count.fetch_add(1, Ordering::SeqCst);
+        if let Some(conflicting) = self.find_in_slots(range, None) {
+            overflow.remove(range);
+            self.overflow_count.fetch_sub(1, Ordering::SeqCst);
+            return Err(Some(conflicting));
+        }
+        Ok(Entry::Overflow)
+    }
+
+    /// Remove the `range` that has been inserted at `entry`.
+    pub fn remove(&self, range: &Range<usize>, entry: Entry) {
+        match entry {
+            Entry::Slot(idx) => {
+                let prev = self.slots[idx].state.swap(FREE, Ordering::SeqCst);
+                debug_assert_eq!(prev, PUBLISHED);
+            }
+            Entry::Overflow => {
+                let mut overflow = self.overflow.lock().unwrap_or_else(PoisonError::into_inner);
+                overflow.remove(range);
+                self.overflow_count.fetch_sub(1, Ordering::SeqCst);
+            }
+        }
+    }
+
+    /// Check whether no range is inserted.
+    pub fn is_empty(&self) -> bool {
+        self.slots
+            .iter()
+            .all(|slot| slot.state.load(Ordering::SeqCst) == FREE)
+            && self.overflow_count.load(Ordering::SeqCst) == 0
+    }
+}
+
+impl<const N: usize> RangeSet for AtomicRanges<N> {
+    type Entry = Entry;
+
+    #[inline]
+    fn new() -> Self {
+        AtomicRanges::new()
+    }
+
+    /// The overflow set is never poisoned, because it is only modified by this type.
+    #[inline]
+    fn insert(&self, range: &Range<usize>) -> LockResult<Option<Entry>> {
+        Ok(AtomicRanges::insert(self, range).ok())
+    }
+
+    #[inline]
+    fn remove(&self, range: &Range<usize>, entry: Entry) {
+        AtomicRanges::remove(self, range, entry);
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        AtomicRanges::is_empty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_remove() {
+        let a: AtomicRanges<2> = AtomicRanges::new();
+        let e0 = a.insert(&(0..10)).unwrap();
+        let e1 = a.insert(&(20..30)).unwrap();
+        assert_eq!(e0, Entry::Slot(0));
+        assert_eq!(e1, Entry::Slot(1));
+        assert_eq!(a.insert(&(5..6)), Err(Some(0..10)));
+        // The slots are full. The range goes to the overflow set.
+        let e2 = a.insert(&(10..20)).unwrap();
+        assert_eq!(e2, Entry::Overflow);
+        assert_eq!(a.insert(&(19..21)), Err(Some(10..20)));
+        assert_eq!(a.insert(&(25..26)), Err(Some(20..30)));
+        a.remove(&(0..10), e0);
+        assert_eq!(a.insert(&(15..16)), Err(Some(10..20)));
+        let e3 = a.insert(&(0..10)).unwrap();
+        assert_eq!(e3, Entry::Slot(0));
+        a.remove(&(10..20), e2);
+        a.remove(&(20..30), e1);
+        a.remove(&(0..10), e3);
+        assert!(a.is_empty());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,124 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Backoff strategies for blocking locks.
+
+use std::hint;
+
+/// Backoff strategy of a blocking lock on contention.
+///
+/// See [VecRangeLock::lock_with_backoff](crate::VecRangeLock::lock_with_backoff).
+pub trait Backoff {
+    /// Back off after a failed locking attempt.
+    ///
+    /// Returns true, if the locking attempt shall be retried without parking the thread.
+    /// Returns false, if the thread shall be parked until the lock has been released.
+    fn spin(&mut self) -> bool;
+}
+
+/// Exponential spinning backoff, followed by parking.
+///
+/// Each backoff step spins twice as long as the previous one.
+/// After `max_steps` steps, the thread is parked.
+///
+/// The [Default] spins for a few hundred nanoseconds before parking.
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    /// The current step.
+    step: u32,
+    /// The number of spinning steps before parking.
+    max_steps: u32,
+}
+
+impl ExponentialBackoff {
+    /// Construct a new [ExponentialBackoff].
+    ///
+    /// * `max_steps`: The number of spinning steps before parking. Must be <=16.
+    pub fn new(max_steps: u32) -> ExponentialBackoff {
+        if max_steps > 16 {
+            panic!("ExponentialBackoff: max_steps out of range.");
+        }
+        ExponentialBackoff { step: 0, max_steps }
+    }
+}
+
+impl Default for ExponentialBackoff {
+    fn default() -> Self {
+        Self::new(7)
+    }
+}
+
+impl Backoff for ExponentialBackoff {
+    fn spin(&mut self) -> bool {
+        if self.step >= self.max_steps {
+            return false;
+        }
+        for _ in 0..(1_u32 << self.step) {
+            hint::spin_loop();
+        }
+        self.step += 1;
+        true
+    }
+}
+
+/// Pure spinning backoff. The thread is never parked.
+#[derive(Clone, Debug, Default)]
+pub struct SpinBackoff;
+
+impl Backoff for SpinBackoff {
+    #[inline]
+    fn spin(&mut self) -> bool {
+        hint::spin_loop();
+        true
+    }
+}
+
+/// Decision of a contention policy.
+///
+/// See [VecRangeLock::try_lock_with](crate::VecRangeLock::try_lock_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Decision {
+    /// Retry the locking attempt immediately.
+    Retry,
+    /// Give up and return [std::sync::TryLockError::WouldBlock].
+    GiveUp,
+    /// Park the thread until the range has been unlocked.
+    Wait,
+}
+
+/// No backoff. The thread is parked immediately.
+#[derive(Clone, Debug, Default)]
+pub struct ParkBackoff;
+
+impl Backoff for ParkBackoff {
+    #[inline]
+    fn spin(&mut self) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exponential() {
+        let mut b = ExponentialBackoff::new(3);
+        assert!(b.spin());
+        assert!(b.spin());
+        assert!(b.spin());
+        assert!(!b.spin());
+        assert!(!b.spin());
+        assert!(!ExponentialBackoff::new(0).spin());
+        assert!(SpinBackoff.spin());
+        assert!(!ParkBackoff.spin());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,276 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    rangelock::{VecRangeLock, VecRangeLockGuard},
+    util::get_bounds,
+};
+use std::{
+    ops::{Deref, DerefMut, Range, RangeBounds},
+    sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+};
+
+/// Number of bits in a word.
+const WORD_BITS: usize = u64::BITS as usize;
+
+/// Multi-thread range lock for a bitset stored in [u64] words.
+///
+/// This is a variant of [VecRangeLock] that accepts ranges of bits.
+/// A bit range is locked by locking all words that contain at least one bit of the range.
+/// Therefore, two bit ranges conflict, if they share a word.
+///
+/// Bit `n` is the bit `n % 64` (LSB first) of the word `n / 64`.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::BitRangeLock;
+///
+/// let lock = BitRangeLock::new(vec![0; 2]);
+/// let mut guard = lock.try_lock(60..70).expect("Failed to lock bits 60..70");
+/// guard.set(65, true);
+/// assert!(guard.get(65));
+/// // Bit 10 shares the first word with the locked bits.
+/// assert!(lock.try_lock(10..11).is_err());
+/// drop(guard);
+/// assert_eq!(lock.into_inner(), vec![0, 1 << 1]);
+/// ```
+#[derive(Debug)]
+pub struct BitRangeLock {
+    /// The underlying range lock of the words.
+    lock: VecRangeLock<u64>,
+}
+
+impl<'a> BitRangeLock {
+    /// Construct a new [BitRangeLock].
+    ///
+    /// * `words`: The words of the bitset to protect.
+    pub fn new(words: Vec<u64>) -> BitRangeLock {
+        BitRangeLock {
+            lock: VecRangeLock::new(words),
+        }
+    }
+
+    /// Get the length (in number of bits) of the bitset.
+    #[inline]
+    pub fn bit_len(&self) -> usize {
+        self.lock
+            .data_len()
+            .checked_mul(WORD_BITS)
+            .expect("BitRangeLock: Bit length overflow.")
+    }
+
+    /// Get the underlying [VecRangeLock] of the words.
+    #[inline]
+    pub fn as_vec_range_lock(&self) -> &VecRangeLock<u64> {
+        &self.lock
+    }
+
+    /// Unwrap this [BitRangeLock] into the contained words.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<u64> {
+        self.lock.into_inner()
+    }
+
+    /// Convert a range of bits into the range of the covering words.
+    fn word_range(&self, bits: &impl RangeBounds<usize>) -> (Range<usize>, Range<usize>) {
+        let bit_len = self.bit_len();
+        let (start, end) = get_bounds(bits, bit_len);
+        if start > end {
+            panic!("Invalid range. Start is bigger than end.");
+        }
+        if end > bit_len {
+            panic!("Range is out of bounds.");
+        }
+        let words = if start == end {
+            start / WORD_BITS..start / WORD_BITS
+        } else {
+            start / WORD_BITS..end.div_ceil(WORD_BITS)
+        };
+        (start..end, words)
+    }
+
+    /// Try to lock the given `bits` range.
+    ///
+    /// The words containing the bits are locked.
+    /// See [VecRangeLock::try_lock] for details.
+    pub fn try_lock(
+        &'a self,
+        bits: impl RangeBounds<usize>,
+    ) -> TryLockResult<BitRangeLockGuard<'a>> {
+        let (bits, words) = self.word_range(&bits);
+        let wrap = |guard| BitRangeLockGuard {
+            guard,
+            bits: bits.clone(),
+            first_word: words.start,
+        };
+        match self.lock.try_lock_poisonable(words.clone()) {
+            Ok(guard) => Ok(wrap(guard)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(wrap(
+                e.into_inner(),
+            )))),
+        }
+    }
+
+    /// Lock the given `bits` range.
+    /// Blocks, until the words containing the bits are available.
+    ///
+    /// See [VecRangeLock::lock] for details.
+    pub fn lock(&'a self, bits: impl RangeBounds<usize>) -> LockResult<BitRangeLockGuard<'a>> {
+        let (bits, words) = self.word_range(&bits);
+        let wrap = |guard| BitRangeLockGuard {
+            guard,
+            bits: bits.clone(),
+            first_word: words.start,
+        };
+        match self.lock.lock(words.clone()) {
+            Ok(guard) => Ok(wrap(guard)),
+            Err(e) => Err(PoisonError::new(wrap(e.into_inner()))),
+        }
+    }
+}
+
+/// Lock guard variable type for [BitRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// Dereferencing yields the locked words.
+#[derive(Debug)]
+pub struct BitRangeLockGuard<'a> {
+    /// The guard of the locked words.
+    guard: VecRangeLockGuard<'a, u64>,
+    /// The locked bits.
+    bits: Range<usize>,
+    /// The index of the first locked word.
+    first_word: usize,
+}
+
+impl<'a> BitRangeLockGuard<'a> {
+    /// Get the range of bits that has been requested.
+    ///
+    /// The guard may access all bits of the locked words.
+    #[inline]
+    pub fn bit_range(&self) -> Range<usize> {
+        self.bits.clone()
+    }
+
+    /// Get the word index and bit mask of the absolute `bit`.
+    fn position(&self, bit: usize) -> (usize, u64) {
+        let word = bit / WORD_BITS;
+        if word < self.first_word || word - self.first_word >= self.guard.len() {
+            panic!("BitRangeLockGuard: Bit {bit} is not locked.");
+        }
+        (word - self.first_word, 1 << (bit % WORD_BITS))
+    }
+
+    /// Get the value of the absolute `bit`.
+    ///
+    /// Panics, if the bit is not in one of the locked words.
+    #[inline]
+    pub fn get(&self, bit: usize) -> bool {
+        let (index, mask) = self.position(bit);
+        self.guard[index] & mask != 0
+    }
+
+    /// Set the absolute `bit` to `value`.
+    ///
+    /// Panics, if the bit is not in one of the locked words.
+    #[inline]
+    pub fn set(&mut self, bit: usize, value: bool) {
+        let (index, mask) = self.position(bit);
+        if value {
+            self.guard[index] |= mask;
+        } else {
+            self.guard[index] &= !mask;
+        }
+    }
+}
+
+impl<'a> Deref for BitRangeLockGuard<'a> {
+    type Target = [u64];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a> DerefMut for BitRangeLockGuard<'a> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_bitrangelock() {
+        let a = BitRangeLock::new(vec![0; 4]);
+        assert_eq!(a.bit_len(), 256);
+        {
+            let mut g0 = a.try_lock(0..64).unwrap();
+            assert_eq!(g0.len(), 1);
+            assert!(a.try_lock(63..65).is_err());
+            let mut g1 = a.try_lock(64..=128).unwrap();
+            assert_eq!(g1.len(), 2);
+            assert_eq!(g1.bit_range(), 64..129);
+            assert!(a.try_lock(190..191).is_err());
+            let g2 = a.try_lock(192..).unwrap();
+            assert_eq!(g2.len(), 1);
+            let g3 = a.try_lock(70..70).unwrap();
+            assert!(g3.is_empty());
+            g0.set(3, true);
+            g1.set(130, true);
+            g1.set(64, true);
+            g1.set(64, false);
+            assert!(g1.get(130));
+            assert!(!g1.get(64));
+        }
+        assert!(a.as_vec_range_lock().is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![1 << 3, 0, 1 << 2, 0]);
+    }
+
+    #[test]
+    fn test_bitrangelock_threads() {
+        let a = BitRangeLock::new(vec![0; 2]);
+        thread::scope(|s| {
+            for t in 0..8 {
+                let a = &a;
+                s.spawn(move || {
+                    for bit in (t..128).step_by(8) {
+                        a.lock(bit..bit + 1).unwrap().set(bit, true);
+                    }
+                });
+            }
+        });
+        assert_eq!(a.into_inner(), vec![u64::MAX; 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bit 64 is not locked")]
+    fn test_bitrangelock_unlocked_bit() {
+        let a = BitRangeLock::new(vec![0; 2]);
+        let g = a.try_lock(0..10).unwrap();
+        let _ = g.get(64);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_bitrangelock_oob() {
+        let a = BitRangeLock::new(vec![0; 2]);
+        let _ = a.try_lock(100..129);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
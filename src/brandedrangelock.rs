@@ -0,0 +1,215 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    error::LockError,
+    rangelock::{VecRangeLock, VecRangeLockGuard},
+};
+use std::{
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::{LockResult, PoisonError},
+};
+
+/// Multi-thread range lock for [std::vec::Vec] that is tagged with a brand type.
+///
+/// This is a variant of [VecRangeLock] with a marker type parameter `B`.
+/// The guards carry the same brand.
+/// Therefore, a function that expects a guard of one brand
+/// can't be called with a guard of another lock with a different brand.
+/// The brand is usually a zero-sized type. It only exists at compile time.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::{BrandedVecRangeLock, BrandedVecRangeLockGuard};
+///
+/// struct Input;
+/// struct Output;
+///
+/// fn process(
+///     input: &BrandedVecRangeLockGuard<Input, i32>,
+///     output: &mut BrandedVecRangeLockGuard<Output, i32>,
+/// ) {
+///     output.copy_from_slice(input);
+/// }
+///
+/// let input: BrandedVecRangeLock<Input, _> = BrandedVecRangeLock::new(vec![1, 2]);
+/// let output: BrandedVecRangeLock<Output, _> = BrandedVecRangeLock::new(vec![0, 0]);
+/// let i = input.try_lock(..).expect("Failed to lock input");
+/// let mut o = output.try_lock(..).expect("Failed to lock output");
+/// process(&i, &mut o);
+/// drop(o);
+/// assert_eq!(output.into_inner(), vec![1, 2]);
+/// ```
+///
+/// Mixing up the guards doesn't compile:
+///
+/// ```compile_fail
+/// use range_lock::{BrandedVecRangeLock, BrandedVecRangeLockGuard};
+///
+/// struct Input;
+/// struct Output;
+///
+/// fn process(output: &mut BrandedVecRangeLockGuard<Output, i32>) {}
+///
+/// let input: BrandedVecRangeLock<Input, i32> = BrandedVecRangeLock::new(vec![1, 2]);
+/// let mut i = input.try_lock(..).expect("Failed to lock input");
+/// process(&mut i);
+/// ```
+pub struct BrandedVecRangeLock<B, T> {
+    /// The underlying range lock.
+    lock: VecRangeLock<T>,
+    _brand: PhantomData<fn() -> B>,
+}
+
+impl<'a, B, T> BrandedVecRangeLock<B, T> {
+    /// Construct a new [BrandedVecRangeLock].
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new(data: Vec<T>) -> BrandedVecRangeLock<B, T> {
+        BrandedVecRangeLock {
+            lock: VecRangeLock::new(data),
+            _brand: PhantomData,
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec].
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.lock.data_len()
+    }
+
+    /// Get the underlying [VecRangeLock].
+    #[inline]
+    pub fn as_vec_range_lock(&self) -> &VecRangeLock<T> {
+        &self.lock
+    }
+
+    /// Unwrap this [BrandedVecRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        self.lock.into_inner()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// See [VecRangeLock::try_lock] for details.
+    #[inline]
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> Result<BrandedVecRangeLockGuard<'a, B, T>, LockError> {
+        self.lock.try_lock(range).map(BrandedVecRangeLockGuard::new)
+    }
+
+    /// Lock the given data `range`.
+    /// Blocks, until the range is available.
+    ///
+    /// See [VecRangeLock::lock] for details.
+    #[inline]
+    pub fn lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> LockResult<BrandedVecRangeLockGuard<'a, B, T>> {
+        match self.lock.lock(range) {
+            Ok(guard) => Ok(BrandedVecRangeLockGuard::new(guard)),
+            Err(e) => Err(PoisonError::new(BrandedVecRangeLockGuard::new(
+                e.into_inner(),
+            ))),
+        }
+    }
+}
+
+// The brand is a marker. It does not need to implement Debug.
+impl<B, T> fmt::Debug for BrandedVecRangeLock<B, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrandedVecRangeLock")
+            .field("lock", &self.lock)
+            .finish()
+    }
+}
+
+/// Lock guard variable type for [BrandedVecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// The guard carries the brand `B` of its lock.
+pub struct BrandedVecRangeLockGuard<'a, B, T> {
+    /// The guard of the underlying lock.
+    guard: VecRangeLockGuard<'a, T>,
+    _brand: PhantomData<fn() -> B>,
+}
+
+impl<'a, B, T> BrandedVecRangeLockGuard<'a, B, T> {
+    #[inline]
+    fn new(guard: VecRangeLockGuard<'a, T>) -> Self {
+        Self {
+            guard,
+            _brand: PhantomData,
+        }
+    }
+
+    /// Remove the brand from the guard.
+    #[inline]
+    pub fn into_unbranded(self) -> VecRangeLockGuard<'a, T> {
+        self.guard
+    }
+}
+
+impl<'a, B, T: fmt::Debug> fmt::Debug for BrandedVecRangeLockGuard<'a, B, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BrandedVecRangeLockGuard")
+            .field("guard", &self.guard)
+            .finish()
+    }
+}
+
+impl<'a, B, T> Deref for BrandedVecRangeLockGuard<'a, B, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, B, T> DerefMut for BrandedVecRangeLockGuard<'a, B, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Brand;
+
+    #[test]
+    fn test_branded_rangelock() {
+        let a: BrandedVecRangeLock<Brand, i32> = BrandedVecRangeLock::new(vec![1, 2, 3, 4]);
+        assert_eq!(a.data_len(), 4);
+        {
+            let mut g0 = a.try_lock(0..2).unwrap();
+            let g1 = a.lock(2..).unwrap();
+            g0[1] = 20;
+            assert_eq!(g1[..], [3, 4]);
+            assert!(a.try_lock(1..3).unwrap_err().is_would_block());
+            let g1 = g1.into_unbranded();
+            assert_eq!(g1[..], [3, 4]);
+        }
+        assert!(a.as_vec_range_lock().is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![1, 20, 3, 4]);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
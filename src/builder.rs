@@ -0,0 +1,136 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLock;
+
+/// Builder for a [VecRangeLock] with a custom configuration.
+///
+/// The default configuration is the configuration of [VecRangeLock::new].
+///
+/// The internal mutex backend is selected at compile time
+/// with the `parking_lot` or `spinlock` feature.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::RangeLockBuilder;
+///
+/// let lock = RangeLockBuilder::new()
+///     .fair(true)
+///     .shards(4)
+///     .poison_data(false)
+///     .build(vec![0; 100]);
+/// assert_eq!(lock.data_len(), 100);
+/// ```
+#[derive(Clone, Debug)]
+pub struct RangeLockBuilder {
+    /// Number of shards. See [VecRangeLock::with_shards].
+    pub(crate) shards: usize,
+    /// Fair queueing of blocked threads. See [VecRangeLock::new_fair].
+    pub(crate) fair: bool,
+    /// Poison the data of guards dropped during a panic.
+    pub(crate) poison_data: bool,
+}
+
+impl Default for RangeLockBuilder {
+    fn default() -> Self {
+        Self {
+            shards: 1,
+            fair: false,
+            poison_data: true,
+        }
+    }
+}
+
+impl RangeLockBuilder {
+    /// Construct a new [RangeLockBuilder] with the default configuration.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable or disable fair queueing of blocked threads.
+    ///
+    /// Default: disabled. See [VecRangeLock::new_fair].
+    #[inline]
+    pub fn fair(mut self, fair: bool) -> Self {
+        self.fair = fair;
+        self
+    }
+
+    /// Set the number of shards of the locked ranges. Must be >0.
+    ///
+    /// Default: 1. See [VecRangeLock::with_shards].
+    #[inline]
+    pub fn shards(mut self, shard_count: usize) -> Self {
+        self.shards = shard_count;
+        self
+    }
+
+    /// Enable or disable poisoning of the data of guards dropped during a panic.
+    ///
+    /// Default: enabled.
+    /// If disabled, a panic while holding a guard does not make
+    /// subsequent locking attempts of the range return a poisoned error.
+    #[inline]
+    pub fn poison_data(mut self, poison_data: bool) -> Self {
+        self.poison_data = poison_data;
+        self
+    }
+
+    /// Construct the [VecRangeLock] protecting `data`.
+    ///
+    /// Panics, if the number of shards is 0.
+    #[inline]
+    pub fn build<T>(&self, data: Vec<T>) -> VecRangeLock<T> {
+        VecRangeLock::from_builder(self, data)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::LockError;
+    use std::panic::{self, AssertUnwindSafe};
+
+    #[test]
+    fn test_builder() {
+        let a = RangeLockBuilder::new().shards(3).build(vec![0_i32; 9]);
+        let _g0 = a.try_lock(0..3).unwrap();
+        let _g1 = a.try_lock(3..9).unwrap();
+
+        let a = RangeLockBuilder::new().build(vec![1_i32, 2, 3]);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _g = a.try_lock(1..2).unwrap();
+            panic!("Panicking while holding the guard");
+        }));
+        assert!(res.is_err());
+        assert_eq!(a.try_lock(..).unwrap_err(), LockError::Poisoned);
+
+        let a = RangeLockBuilder::new()
+            .poison_data(false)
+            .fair(true)
+            .build(vec![1_i32, 2, 3]);
+        let res = panic::catch_unwind(AssertUnwindSafe(|| {
+            let _g = a.try_lock(1..2).unwrap();
+            panic!("Panicking while holding the guard");
+        }));
+        assert!(res.is_err());
+        assert!(!a.is_poisoned());
+        assert_eq!(a.lock(..).unwrap()[..], [1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "shard_count must not be 0")]
+    fn test_builder_no_shards() {
+        let _ = RangeLockBuilder::new().shards(0).build(vec![0_i32; 9]);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
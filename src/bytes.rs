@@ -0,0 +1,108 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLockGuard;
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// Primitive types that a locked byte slice can be viewed as.
+///
+/// Every bit pattern is a valid value of these types and they don't have padding.
+/// This trait is sealed and can't be implemented outside of this crate.
+pub trait FromBytes: sealed::Sealed + Copy {}
+
+macro_rules! impl_from_bytes {
+    ($($t:ty),*) => {
+        $(
+            impl sealed::Sealed for $t {}
+            impl FromBytes for $t {}
+        )*
+    };
+}
+
+impl_from_bytes!(u8, u16, u32, u64, u128, usize, i8, i16, i32, i64, i128, isize, f32, f64);
+
+impl<'a> VecRangeLockGuard<'a, u8> {
+    /// Reinterpret the locked bytes as a mutable slice of `U`.
+    ///
+    /// The returned slice mutably borrows the guard.
+    /// Therefore, it can't outlive the locked range.
+    ///
+    /// Returns `None`, if the locked bytes are not aligned to `U`
+    /// or if their length is not a multiple of the size of `U`.
+    pub fn as_mut_of<U: FromBytes>(&mut self) -> Option<&mut [U]> {
+        // SAFETY:
+        // U is a primitive type without padding that is valid for every bit pattern.
+        // The bytes of the middle part are properly aligned for U.
+        let (prefix, mid, suffix) = unsafe { self[..].align_to_mut::<U>() };
+        if prefix.is_empty() && suffix.is_empty() {
+            Some(mid)
+        } else {
+            None
+        }
+    }
+
+    /// Reinterpret the locked bytes as a mutable slice of [u32].
+    ///
+    /// See [VecRangeLockGuard::as_mut_of].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0_u8; 64]);
+    /// let mut guard = lock.try_lock(..).expect("Failed to lock range");
+    /// if let Some(words) = guard.as_u32_mut() {
+    ///     words[0] = u32::MAX;
+    /// }
+    /// ```
+    #[inline]
+    pub fn as_u32_mut(&mut self) -> Option<&mut [u32]> {
+        self.as_mut_of()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rangelock::VecRangeLock;
+
+    #[test]
+    fn test_as_mut_of() {
+        let a = VecRangeLock::new(vec![0_u8; 16]);
+        // Find the first u32 aligned offset.
+        let base = a.try_lock(..).unwrap().as_ptr() as usize;
+        let start = base.next_multiple_of(4) - base;
+        {
+            let mut g = a.try_lock(start..start + 8).unwrap();
+            let w = g.as_u32_mut().unwrap();
+            assert_eq!(w.len(), 2);
+            w[1] = 0x0102_0304;
+            assert_eq!(g.as_mut_of::<i16>().unwrap().len(), 4);
+        }
+        {
+            let mut g = a.try_lock(start..start + 7).unwrap();
+            assert!(g.as_u32_mut().is_none());
+            assert_eq!(g.as_mut_of::<u8>().unwrap().len(), 7);
+        }
+        {
+            let mut g = a.try_lock(start + 1..start + 5).unwrap();
+            assert!(g.as_u32_mut().is_none());
+        }
+        let data = a.into_inner();
+        assert_eq!(
+            u32::from_ne_bytes(data[start + 4..start + 8].try_into().unwrap()),
+            0x0102_0304
+        );
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
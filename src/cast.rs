@@ -0,0 +1,86 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLockGuard;
+use bytemuck::Pod;
+
+impl<'a, T: Pod> VecRangeLockGuard<'a, T> {
+    /// Reinterpret the locked slice as a slice of `U`.
+    ///
+    /// The returned slice borrows the guard.
+    /// Therefore, it can't outlive the locked range.
+    ///
+    /// This method is available with the `bytemuck` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the size or alignment of the locked slice
+    /// is not compatible with `U`. See [bytemuck::cast_slice].
+    pub fn cast_slice<U: Pod>(&self) -> &[U] {
+        bytemuck::cast_slice(&self[..])
+    }
+
+    /// Reinterpret the locked slice as a mutable slice of `U`.
+    ///
+    /// The returned slice mutably borrows the guard.
+    /// Therefore, it can't outlive the locked range.
+    ///
+    /// This method is available with the `bytemuck` feature.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the size or alignment of the locked slice
+    /// is not compatible with `U`. See [bytemuck::cast_slice_mut].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0_u32; 4]);
+    /// let mut guard = lock.try_lock(0..2).expect("Failed to lock range");
+    /// let bytes: &mut [u8] = guard.cast_slice_mut();
+    /// bytes.fill(0xFF);
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![u32::MAX, u32::MAX, 0, 0]);
+    /// ```
+    pub fn cast_slice_mut<U: Pod>(&mut self) -> &mut [U] {
+        bytemuck::cast_slice_mut(&mut self[..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::rangelock::VecRangeLock;
+
+    #[test]
+    fn test_cast_slice() {
+        let a = VecRangeLock::new(vec![0_u32; 8]);
+        {
+            let mut g = a.try_lock(4..8).unwrap();
+            let w: &mut [u16] = g.cast_slice_mut();
+            assert_eq!(w.len(), 8);
+            w[0] = 0x1234;
+            w[1] = 0x1234;
+            let r: &[u64] = g.cast_slice();
+            assert_eq!(r.len(), 2);
+        }
+        assert_eq!(a.into_inner()[4], 0x1234_1234);
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_cast_slice_mismatch() {
+        let a = VecRangeLock::new(vec![0_u8; 8]);
+        let mut g = a.try_lock(0..3).unwrap();
+        let _: &mut [u16] = g.cast_slice_mut();
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,170 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLockGuard;
+use std::io::{self, Read, Write};
+
+/// Byte cursor over a locked range of a `VecRangeLock<u8>`.
+///
+/// The cursor implements [Read] and [Write].
+/// Each read and write advances the cursor position.
+/// Writing beyond the end of the locked range writes nothing.
+/// Therefore, [Write::write_all] fails with [io::ErrorKind::WriteZero], if the range is full.
+/// Reading at the end of the locked range returns `Ok(0)` (end of file).
+///
+/// # Example
+///
+/// ```
+/// use range_lock::{VecRangeLock, VecRangeLockCursor};
+/// use std::io::Write;
+///
+/// let lock = VecRangeLock::new(vec![0_u8; 8]);
+/// let guard = lock.try_lock(2..6).expect("Failed to lock 2..6");
+/// let mut cursor = VecRangeLockCursor::new(guard);
+/// write!(cursor, "ab").expect("Failed to write");
+/// cursor.write_all(b"cd").expect("Failed to write");
+/// assert!(cursor.write_all(b"e").is_err()); // The range is full.
+/// drop(cursor);
+/// assert_eq!(lock.into_inner(), b"\0\0abcd\0\0");
+/// ```
+///
+/// Reading the locked range:
+///
+/// ```
+/// use range_lock::{VecRangeLock, VecRangeLockCursor};
+/// use std::io::Read;
+///
+/// let lock = VecRangeLock::new(b"hello world".to_vec());
+/// let guard = lock.try_lock(6..).expect("Failed to lock 6..");
+/// let mut text = String::new();
+/// VecRangeLockCursor::new(guard)
+///     .read_to_string(&mut text)
+///     .expect("Failed to read");
+/// assert_eq!(text, "world");
+/// ```
+#[derive(Debug)]
+pub struct VecRangeLockCursor<'a> {
+    /// The guard of the locked range.
+    guard: VecRangeLockGuard<'a, u8>,
+    /// The current position, relative to the start of the locked range.
+    pos: usize,
+}
+
+impl<'a> VecRangeLockCursor<'a> {
+    /// Construct a new [VecRangeLockCursor] at the start of the locked range.
+    #[inline]
+    pub fn new(guard: VecRangeLockGuard<'a, u8>) -> VecRangeLockCursor<'a> {
+        VecRangeLockCursor { guard, pos: 0 }
+    }
+
+    /// Get the current position, relative to the start of the locked range.
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Set the current position, relative to the start of the locked range.
+    ///
+    /// The position may be set beyond the end of the range.
+    #[inline]
+    pub fn set_position(&mut self, pos: usize) {
+        self.pos = pos;
+    }
+
+    /// Get the number of bytes between the current position and the end of the locked range.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.guard.len().saturating_sub(self.pos)
+    }
+
+    /// Unwrap this cursor into the underlying guard.
+    #[inline]
+    pub fn into_inner(self) -> VecRangeLockGuard<'a, u8> {
+        self.guard
+    }
+}
+
+impl<'a> Read for VecRangeLockCursor<'a> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count = buf.len().min(self.remaining());
+        if count > 0 {
+            buf[..count].copy_from_slice(&self.guard[self.pos..self.pos + count]);
+            self.pos += count;
+        }
+        Ok(count)
+    }
+}
+
+impl<'a> Write for VecRangeLockCursor<'a> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let count = buf.len().min(self.remaining());
+        if count > 0 {
+            self.guard[self.pos..self.pos + count].copy_from_slice(&buf[..count]);
+            self.pos += count;
+        }
+        Ok(count)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rangelock::VecRangeLock;
+
+    #[test]
+    fn test_cursor_write() {
+        let a = VecRangeLock::new(vec![0_u8; 6]);
+        {
+            let mut c = VecRangeLockCursor::new(a.try_lock(1..5).unwrap());
+            assert_eq!(c.write(b"xyz").unwrap(), 3);
+            assert_eq!(c.position(), 3);
+            assert_eq!(c.write(b"uvw").unwrap(), 1);
+            assert_eq!(c.remaining(), 0);
+            assert_eq!(c.write(b"a").unwrap(), 0);
+            assert_eq!(
+                c.write_all(b"a").unwrap_err().kind(),
+                io::ErrorKind::WriteZero
+            );
+            c.set_position(10);
+            assert_eq!(c.write(b"a").unwrap(), 0);
+            c.set_position(0);
+            c.write_all(b"X").unwrap();
+            let g = c.into_inner();
+            assert_eq!(g[..], *b"Xyzu");
+        }
+        assert_eq!(a.into_inner(), b"\0Xyzu\0");
+    }
+
+    #[test]
+    fn test_cursor_read() {
+        let a = VecRangeLock::new(b"abcdef".to_vec());
+        let mut c = VecRangeLockCursor::new(a.try_lock(1..5).unwrap());
+        let mut buf = [0_u8; 3];
+        assert_eq!(c.read(&mut buf).unwrap(), 3);
+        assert_eq!(buf, *b"bcd");
+        assert_eq!(c.read(&mut buf).unwrap(), 1);
+        assert_eq!(buf[0], b'e');
+        assert_eq!(c.read(&mut buf).unwrap(), 0);
+        c.set_position(2);
+        let mut rest = Vec::new();
+        assert_eq!(c.read_to_end(&mut rest).unwrap(), 2);
+        assert_eq!(rest, b"de");
+        assert_eq!(
+            c.read_exact(&mut buf).unwrap_err().kind(),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,384 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Deadlock detection diagnostics for the blocking methods of [VecRangeLock](crate::VecRangeLock).
+//!
+//! With the `deadlock_detection` feature in debug builds,
+//! all [VecRangeLock](crate::VecRangeLock)s record in a global registry:
+//! * which thread holds which range and
+//! * which thread waits in a blocking method (e.g. [VecRangeLock::lock](crate::VecRangeLock::lock))
+//!   for which range.
+//!
+//! [wait_for_graph] dumps the resulting wait-for graph
+//! and [detect_deadlock] searches it for a cycle.
+//! The recording is compiled out in release builds.
+//! The graph is always empty there.
+//!
+//! The holder of a range is the thread that has locked it.
+//! A guard that has been sent to another thread is still attributed to the locking thread.
+//! The ranges of leaked guards stay recorded.
+//! Other lock types, e.g. [RepVecRangeLock](crate::RepVecRangeLock), are not recorded.
+
+#[cfg(feature = "deadlock_detection")]
+use crate::lockedranges::overlaps;
+use std::ops::Range;
+#[cfg(all(feature = "deadlock_detection", debug_assertions))]
+use std::thread;
+#[cfg(feature = "deadlock_detection")]
+use std::{
+    fmt,
+    sync::{Mutex, MutexGuard, PoisonError},
+    thread::ThreadId,
+};
+
+/// A range of one lock that is held by or waited for by one thread.
+#[cfg(feature = "deadlock_detection")]
+#[derive(Debug)]
+struct Entry {
+    /// The holding or waiting thread.
+    thread: ThreadId,
+    /// The address of the lock.
+    lock: usize,
+    /// The held or wanted range.
+    range: Range<usize>,
+}
+
+/// The global registry of held and wanted ranges.
+#[cfg(feature = "deadlock_detection")]
+#[derive(Debug)]
+struct Registry {
+    /// The currently held ranges.
+    held: Vec<Entry>,
+    /// The ranges that blocked threads wait for. At most one per thread.
+    waiting: Vec<Entry>,
+}
+
+#[cfg(feature = "deadlock_detection")]
+static REGISTRY: Mutex<Registry> = Mutex::new(Registry {
+    held: Vec::new(),
+    waiting: Vec::new(),
+});
+
+/// Lock the global registry.
+#[cfg(feature = "deadlock_detection")]
+fn registry() -> MutexGuard<'static, Registry> {
+    REGISTRY.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Record that the current thread has locked `range` of `lock`.
+/// Must be called after the range has been inserted.
+#[inline]
+pub(crate) fn held(lock: usize, range: &Range<usize>) {
+    #[cfg(all(feature = "deadlock_detection", debug_assertions))]
+    if !range.is_empty() {
+        let thread = thread::current().id();
+        let mut registry = registry();
+        // The thread doesn't wait anymore, if it has got the range.
+        registry
+            .waiting
+            .retain(|w| !(w.thread == thread && w.lock == lock && w.range == *range));
+        registry.held.push(Entry {
+            thread,
+            lock,
+            range: range.clone(),
+        });
+    }
+    let _ = (lock, range);
+}
+
+/// Record that `range` of `lock` is about to be unlocked.
+/// Must be called before the range is removed.
+#[inline]
+pub(crate) fn released(lock: usize, range: &Range<usize>) {
+    #[cfg(all(feature = "deadlock_detection", debug_assertions))]
+    if !range.is_empty() {
+        let thread = thread::current().id();
+        let mut registry = registry();
+        let matches = |h: &Entry| h.lock == lock && h.range == *range;
+        // Shared ranges may be held by multiple threads.
+        // Prefer the entry of the current thread.
+        let pos = registry
+            .held
+            .iter()
+            .position(|h| matches(h) && h.thread == thread)
+            .or_else(|| registry.held.iter().position(matches));
+        if let Some(pos) = pos {
+            registry.held.swap_remove(pos);
+        }
+    }
+    let _ = (lock, range);
+}
+
+/// Record that all ranges of `lock` overlapping with `range` have been forcibly unlocked.
+#[inline]
+pub(crate) fn released_overlapping(lock: usize, range: &Range<usize>) {
+    #[cfg(all(feature = "deadlock_detection", debug_assertions))]
+    registry()
+        .held
+        .retain(|h| !(h.lock == lock && overlaps(&h.range, range)));
+    let _ = (lock, range);
+}
+
+/// Record that the current thread waits for a range, while this exists.
+#[derive(Debug)]
+pub(crate) struct Waiting(());
+
+impl Waiting {
+    /// Record that the current thread waits for `range` of `lock`.
+    #[inline]
+    pub(crate) fn new(lock: usize, range: &Range<usize>) -> Waiting {
+        #[cfg(all(feature = "deadlock_detection", debug_assertions))]
+        if !range.is_empty() {
+            let thread = thread::current().id();
+            let mut registry = registry();
+            registry.waiting.retain(|w| w.thread != thread);
+            registry.waiting.push(Entry {
+                thread,
+                lock,
+                range: range.clone(),
+            });
+        }
+        let _ = (lock, range);
+        Waiting(())
+    }
+}
+
+#[cfg(all(feature = "deadlock_detection", debug_assertions))]
+impl Drop for Waiting {
+    fn drop(&mut self) {
+        let thread = thread::current().id();
+        registry().waiting.retain(|w| w.thread != thread);
+    }
+}
+
+/// An edge of the wait-for graph.
+///
+/// The thread `waiter` waits for the range `wanted` of a lock.
+/// `wanted` overlaps with the range `held` of the same lock, which is held by the thread `holder`.
+#[cfg(feature = "deadlock_detection")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WaitForEdge {
+    /// The waiting thread.
+    pub waiter: ThreadId,
+    /// The address of the lock.
+    pub lock: usize,
+    /// The range that the waiting thread waits for.
+    pub wanted: Range<usize>,
+    /// The thread that holds the conflicting range.
+    pub holder: ThreadId,
+    /// The conflicting range.
+    pub held: Range<usize>,
+}
+
+#[cfg(feature = "deadlock_detection")]
+impl fmt::Display for WaitForEdge {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "thread {:?} waits for range {:?} of lock {:#x}, which is blocked by range {:?} held by thread {:?}",
+            self.waiter, self.wanted, self.lock, self.held, self.holder
+        )
+    }
+}
+
+/// Get a snapshot of the current wait-for graph.
+///
+/// There is one edge for each pair of a waiting thread and a thread
+/// that holds a range conflicting with the wanted range.
+/// The graph is always empty in release builds.
+#[cfg(feature = "deadlock_detection")]
+pub fn wait_for_graph() -> Vec<WaitForEdge> {
+    let registry = registry();
+    let mut edges = Vec::new();
+    for w in &registry.waiting {
+        for h in registry
+            .held
+            .iter()
+            .filter(|h| h.lock == w.lock && overlaps(&h.range, &w.range))
+        {
+            edges.push(WaitForEdge {
+                waiter: w.thread,
+                lock: w.lock,
+                wanted: w.range.clone(),
+                holder: h.thread,
+                held: h.range.clone(),
+            });
+        }
+    }
+    edges
+}
+
+/// Search the wait-for graph for a path from `edges[path.last()].holder` back to `origin`.
+///
+/// `path` is extended by the edges of the found path.
+#[cfg(feature = "deadlock_detection")]
+fn find_cycle(
+    edges: &[WaitForEdge],
+    origin: ThreadId,
+    path: &mut Vec<usize>,
+    visited: &mut Vec<ThreadId>,
+) -> bool {
+    let holder = edges[*path.last().unwrap()].holder;
+    if holder == origin {
+        return true;
+    }
+    if visited.contains(&holder) {
+        return false;
+    }
+    visited.push(holder);
+    for (i, _) in edges.iter().enumerate().filter(|(_, e)| e.waiter == holder) {
+        path.push(i);
+        if find_cycle(edges, origin, path, visited) {
+            return true;
+        }
+        path.pop();
+    }
+    false
+}
+
+/// Search the current wait-for graph for a deadlock.
+///
+/// Returns the edges of a cycle in the wait-for graph, if there is one.
+/// All threads of the cycle wait for each other and none of them can make progress.
+/// A thread waiting for a range that it holds itself is a cycle of one edge.
+///
+/// Returns `None`, if no deadlock has been found.
+/// This is always the case in release builds.
+///
+/// # Example
+///
+/// A watchdog thread that reports deadlocks:
+///
+/// ```no_run
+/// use range_lock::deadlock::detect_deadlock;
+/// use std::{thread, time::Duration};
+///
+/// thread::spawn(|| loop {
+///     thread::sleep(Duration::from_secs(1));
+///     if let Some(cycle) = detect_deadlock() {
+///         for edge in &cycle {
+///             eprintln!("{edge}");
+///         }
+///         panic!("Deadlock detected.");
+///     }
+/// });
+/// ```
+#[cfg(feature = "deadlock_detection")]
+pub fn detect_deadlock() -> Option<Vec<WaitForEdge>> {
+    let edges = wait_for_graph();
+    for start in 0..edges.len() {
+        let mut path = vec![start];
+        if find_cycle(&edges, edges[start].waiter, &mut path, &mut Vec::new()) {
+            return Some(path.into_iter().map(|i| edges[i].clone()).collect());
+        }
+    }
+    None
+}
+
+#[cfg(all(test, feature = "deadlock_detection", debug_assertions))]
+mod tests {
+    use super::*;
+    use crate::rangelock::VecRangeLock;
+    use std::{sync::Barrier, time::Duration};
+
+    /// Serializes the tests that create cycles in the global wait-for graph.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    /// Get the edges of the wait-for graph of `lock`.
+    /// Other tests may run concurrently.
+    fn edges_of<T>(lock: &VecRangeLock<T>) -> Vec<WaitForEdge> {
+        wait_for_graph()
+            .into_iter()
+            .filter(|e| e.lock == lock.id())
+            .collect()
+    }
+
+    #[test]
+    fn test_wait_for_graph() {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let g = a.try_lock(0..4).unwrap();
+        let g2 = a.try_lock(5..6).unwrap();
+        thread::scope(|s| {
+            let h = s.spawn(|| {
+                let _g = a.lock(2..6).unwrap();
+                thread::current().id()
+            });
+            let edges = loop {
+                let edges = edges_of(&a);
+                if edges.len() == 2 {
+                    break edges;
+                }
+                thread::sleep(Duration::from_millis(1));
+            };
+            assert!(edges.iter().all(|e| e.wanted == (2..6)));
+            assert!(edges.iter().any(|e| e.held == (0..4)));
+            assert!(edges.iter().any(|e| e.held == (5..6)));
+            assert!(edges
+                .iter()
+                .all(|e| e.holder == thread::current().id() && e.waiter != e.holder));
+            assert!(edges[0].to_string().contains("waits for range 2..6"));
+            drop((g, g2));
+            let waiter = h.join().unwrap();
+            assert!(edges.iter().all(|e| e.waiter == waiter));
+        });
+        assert!(edges_of(&a).is_empty());
+        assert!(!registry().held.iter().any(|h| h.lock == a.id()));
+    }
+
+    #[test]
+    fn test_detect_deadlock() {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let g = a.try_lock(0..2).unwrap();
+        let barrier = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let _g1 = a.try_lock(5..7).unwrap();
+                barrier.wait();
+                let _g2 = a.lock(0..2).unwrap();
+            });
+            barrier.wait();
+            while edges_of(&a).is_empty() {
+                thread::sleep(Duration::from_millis(1));
+            }
+            assert!(detect_deadlock().is_none());
+            // The current thread can't block here.
+            // Record it as waiting for the range held by the other thread.
+            let waiting = Waiting::new(a.id(), &(5..7));
+            let cycle = detect_deadlock().unwrap();
+            assert_eq!(cycle.len(), 2);
+            assert_eq!(cycle[0].holder, cycle[1].waiter);
+            assert_eq!(cycle[1].holder, cycle[0].waiter);
+            assert!(cycle.iter().any(|e| e.wanted == (5..7)));
+            assert!(cycle.iter().any(|e| e.wanted == (0..2)));
+            drop(waiting);
+            assert!(detect_deadlock().is_none());
+            drop(g);
+        });
+        assert!(edges_of(&a).is_empty());
+    }
+
+    #[test]
+    fn test_self_deadlock() {
+        let _serial = SERIAL.lock().unwrap_or_else(PoisonError::into_inner);
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let _g = a.try_lock(0..4).unwrap();
+        let _waiting = Waiting::new(a.id(), &(3..5));
+        let cycle = detect_deadlock().unwrap();
+        assert_eq!(cycle.len(), 1);
+        assert_eq!(cycle[0].waiter, cycle[0].holder);
+        assert_eq!(
+            (cycle[0].wanted.clone(), cycle[0].held.clone()),
+            (3..5, 0..4)
+        );
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
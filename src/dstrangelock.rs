@@ -0,0 +1,261 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    lockedranges::LockedRanges,
+    mutex::Mutex,
+    rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard},
+};
+use std::{
+    alloc::{self, Layout},
+    cell::UnsafeCell,
+    mem::ManuallyDrop,
+    ops::{Deref, DerefMut, RangeBounds},
+    ptr,
+    sync::TryLockResult,
+};
+
+/// General purpose multi-thread range lock that stores its data inline.
+///
+/// This is a variant of [VecRangeLock](crate::VecRangeLock)
+/// that holds the data directly instead of in a [Vec].
+/// `RangeLock<[T]>` is a dynamically sized type.
+/// It is constructed with [RangeLock::from_box] or by unsized coercion
+/// from a `RangeLock<[T; N]>` constructed with [RangeLock::new].
+///
+/// # Example
+///
+/// ```
+/// use range_lock::RangeLock;
+/// use std::{sync::Arc, thread};
+///
+/// let lock: Arc<RangeLock<[i32]>> = RangeLock::from_box(vec![1, 2, 3, 4].into()).into();
+/// thread::scope(|s| {
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+///         guard[0] = 10;
+///     });
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+///         guard[1] = 40;
+///     });
+/// });
+/// assert_eq!(lock.try_lock(..).unwrap()[..], [10, 2, 3, 40]);
+///
+/// let array: Arc<RangeLock<[i32]>> = Arc::new(RangeLock::new([1, 2, 3]));
+/// assert_eq!(array.data_len(), 3);
+/// ```
+#[derive(Debug)]
+#[repr(C)]
+pub struct RangeLock<D: ?Sized> {
+    /// The lock state and the protected data.
+    /// The data is the last field of the raw lock, because it may be unsized.
+    raw: RawRangeLock<UnsafeCell<D>>,
+}
+
+impl<T, const N: usize> RangeLock<[T; N]> {
+    /// Construct a new [RangeLock] from an array.
+    ///
+    /// A reference or smart pointer to the returned lock
+    /// coerces to the unsized `RangeLock<[T]>`.
+    pub fn new(data: [T; N]) -> RangeLock<[T; N]> {
+        RangeLock {
+            raw: RawRangeLock::new(UnsafeCell::new(data)),
+        }
+    }
+}
+
+impl<'a, T> RangeLock<[T]> {
+    /// Get the memory layout of a `RangeLock<[T]>` with `len` elements
+    /// and the offset of the first element.
+    fn layout(len: usize) -> (Layout, usize) {
+        let (layout, offset) = Layout::new::<Mutex<LockedRanges>>()
+            .extend(Layout::array::<T>(len).expect("RangeLock: Data is too big."))
+            .expect("RangeLock: Data is too big.");
+        (layout.pad_to_align(), offset)
+    }
+
+    /// Construct a new [RangeLock] from a boxed slice.
+    ///
+    /// The elements are moved into a single new allocation
+    /// that holds both the lock state and the data.
+    pub fn from_box(data: Box<[T]>) -> Box<RangeLock<[T]>> {
+        let len = data.len();
+        let (layout, offset) = Self::layout(len);
+        // SAFETY: The layout has a non-zero size, because it contains the mutex.
+        let mem = unsafe { alloc::alloc(layout) };
+        if mem.is_null() {
+            alloc::handle_alloc_error(layout);
+        }
+        // SAFETY:
+        // The structs are repr(C). Therefore, the layout equals the layout computed above.
+        // The mutex is placed at offset 0 and the elements at `offset`.
+        // The elements are moved out of the box with a bitwise copy.
+        // The box memory is freed afterwards without dropping the elements.
+        // The fat pointer carries the number of elements of the trailing slice.
+        unsafe {
+            ptr::write(
+                mem.cast::<Mutex<LockedRanges>>(),
+                Mutex::new(LockedRanges::new()),
+            );
+            let src = Box::into_raw(data);
+            ptr::copy_nonoverlapping(src.cast::<T>(), mem.add(offset).cast::<T>(), len);
+            drop(Box::from_raw(src as *mut [ManuallyDrop<T>]));
+            Box::from_raw(ptr::slice_from_raw_parts_mut(mem.cast::<T>(), len) as *mut RangeLock<[T]>)
+        }
+    }
+
+    /// Unwrap the [RangeLock] into the contained data.
+    ///
+    /// The elements are moved into a new boxed slice.
+    pub fn into_box(this: Box<RangeLock<[T]>>) -> Box<[T]> {
+        let len = this.data_len();
+        let (layout, offset) = Self::layout(len);
+        let mut data: Vec<T> = Vec::with_capacity(len);
+        let raw = Box::into_raw(this);
+        // SAFETY:
+        // The elements are moved out of the lock with a bitwise copy.
+        // The lock state is dropped and the lock memory is freed afterwards
+        // without dropping the elements.
+        // The lock memory has been allocated with the same layout by the global allocator.
+        unsafe {
+            let mem = raw.cast::<u8>();
+            ptr::copy_nonoverlapping(mem.add(offset).cast::<T>(), data.as_mut_ptr(), len);
+            data.set_len(len);
+            ptr::drop_in_place(ptr::addr_of_mut!((*raw).raw.ranges));
+            alloc::dealloc(mem, layout);
+        }
+        data.into_boxed_slice()
+    }
+
+    /// Get the length (in number of elements) of the contained slice.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Get a mutable reference to the contained data.
+    ///
+    /// No locking is needed, because the lock is exclusively borrowed.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut [T] {
+        self.raw.data.get_mut()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// Panics, if the range is out of bounds of the data.
+    ///
+    /// * On success: Returns a [RangeLockGuard] that can be used to access the locked region.
+    ///   Dereferencing [RangeLockGuard] yields a slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock](std::sync::TryLockError::WouldBlock),
+    ///   if the range is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned](std::sync::TryLockError::Poisoned),
+    ///   if the lock is poisoned. The poisoned guard holds the range.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<RangeLockGuard<'a, T>> {
+        wrap_guard(self.raw.try_lock(range), RangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [RangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See the documentation of [RangeLock] for usage examples of [RangeLockGuard].
+#[derive(Debug)]
+pub struct RangeLockGuard<'a, T>(RawRangeLockGuard<'a, UnsafeCell<[T]>>);
+
+impl<'a, T> Deref for RangeLockGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T> DerefMut for RangeLockGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{rc::Rc, sync::Arc, thread};
+
+    #[test]
+    fn test_from_box() {
+        let a = RangeLock::from_box(vec![1_i32, 2, 3, 4, 5].into());
+        assert_eq!(a.data_len(), 5);
+        {
+            let mut g0 = a.try_lock(0..2).unwrap();
+            assert!(a.try_lock(1..3).is_err());
+            let mut g1 = a.try_lock(2..).unwrap();
+            assert_eq!(g0[..], [1, 2]);
+            assert_eq!(g1[..], [3, 4, 5]);
+            g0[0] = 10;
+            g1[2] = 50;
+        }
+        assert!(a.raw.is_all_unlocked());
+        assert_eq!(*RangeLock::into_box(a), [10, 2, 3, 4, 50]);
+
+        let mut a = RangeLock::from_box(Box::<[u64]>::default());
+        assert_eq!(a.data_len(), 0);
+        assert!(a.try_lock(..).is_ok());
+        assert!(a.get_mut().is_empty());
+        assert!(RangeLock::into_box(a).is_empty());
+    }
+
+    #[test]
+    fn test_array_coercion() {
+        let a: Arc<RangeLock<[u8]>> = Arc::new(RangeLock::new([1, 2, 3, 4]));
+        thread::scope(|s| {
+            for i in 0..4 {
+                let a = &a;
+                s.spawn(move || {
+                    a.try_lock(i..i + 1).unwrap()[0] *= 10;
+                });
+            }
+        });
+        assert_eq!(a.try_lock(..).unwrap()[..], [10, 20, 30, 40]);
+
+        let b: Box<RangeLock<[u8]>> = Box::new(RangeLock::new([5; 3]));
+        assert_eq!(*RangeLock::into_box(b), [5, 5, 5]);
+    }
+
+    #[test]
+    fn test_drop_elements() {
+        let rc = Rc::new(());
+        let a = RangeLock::from_box(vec![rc.clone(), rc.clone()].into());
+        assert_eq!(Rc::strong_count(&rc), 3);
+        let b = RangeLock::into_box(a);
+        assert_eq!(Rc::strong_count(&rc), 3);
+        drop(b);
+        assert_eq!(Rc::strong_count(&rc), 1);
+        let a = RangeLock::from_box(vec![rc.clone(); 3].into());
+        assert_eq!(Rc::strong_count(&rc), 4);
+        drop(a);
+        assert_eq!(Rc::strong_count(&rc), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_oob() {
+        let a = RangeLock::from_box(vec![1_i32, 2, 3].into());
+        let _ = a.try_lock(2..4);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,297 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Lock-free set of locked ranges.
+//!
+//! The ranges are stored in a singly linked list, sorted by their start.
+//! Nodes are inserted and removed with compare-and-swap on the link to the next node
+//! (Harris-Michael list).
+//! A node is removed by first marking its own link to the next node (logical removal)
+//! and then unlinking it from its predecessor (physical removal).
+//! Marked nodes are unlinked by any thread that walks over them.
+//! Unlinked nodes are freed via [crossbeam_epoch], after no thread can access them anymore.
+//!
+//! The ranges in the list don't overlap with each other.
+//! Therefore, a range only conflicts with its direct predecessor or successor in the list.
+//! An insertion swaps the link of the predecessor from the observed successor to the new node.
+//! The swap fails, if the predecessor has been removed or another node has been linked in between.
+
+use crate::rawrangelock::RangeSet;
+use crossbeam_epoch::{self as epoch, Atomic, Guard, Owned, Shared};
+use std::{
+    ops::Range,
+    sync::{atomic::Ordering, LockResult},
+};
+
+/// Tag of the link to the next node, if the node has been removed logically.
+const REMOVED: usize = 1;
+
+/// List node of one locked range.
+#[derive(Debug)]
+struct Node {
+    /// Inclusive start of the range.
+    start: usize,
+    /// Exclusive end of the range.
+    end: usize,
+    /// Link to the next node. Tagged with [REMOVED], if this node has been removed.
+    next: Atomic<Node>,
+}
+
+/// Position in the list.
+struct Position<'g> {
+    /// The link to `curr`.
+    prev: &'g Atomic<Node>,
+    /// The range of the node owning `prev`, or None for the list head.
+    pred: Option<Range<usize>>,
+    /// The first node with a start bigger than or equal to the searched start.
+    curr: Shared<'g, Node>,
+}
+
+/// Lock-free set of non-overlapping ranges.
+#[derive(Debug, Default)]
+pub struct EpochRanges {
+    /// Link to the first node.
+    head: Atomic<Node>,
+}
+
+impl EpochRanges {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Find the position of `start` in the list.
+    /// Removed nodes on the way are unlinked.
+    fn search<'g>(&'g self, start: usize, guard: &'g Guard) -> Position<'g> {
+        'retry: loop {
+            let mut prev = &self.head;
+            let mut pred = None;
+            let mut curr = prev.load(Ordering::Acquire, guard);
+            loop {
+                // SAFETY: The node is protected by the pinned epoch.
+                let Some(node) = (unsafe { curr.as_ref() }) else {
+                    return Position { prev, pred, curr };
+                };
+                let next = node.next.load(Ordering::Acquire, guard);
+                if next.tag() == REMOVED {
+                    // Unlink the removed node.
+                    // This fails, if the predecessor has been removed
+                    // or if a node has been inserted before the removed node.
+                    let next = next.with_tag(0);
+                    match prev.compare_exchange(
+                        curr,
+                        next,
+                        Ordering::AcqRel,
+                        Ordering::Acquire,
+                        guard,
+                    ) {
+                        Ok(_) => {
+                            // SAFETY: The node is unlinked. No new references can be created.
+                            // Existing references are protected by their pinned epochs.
+                            unsafe { guard.defer_destroy(curr) };
+                            curr = next;
+                            continue;
+                        }
+                        Err(_) => continue 'retry,
+                    }
+                }
+                if node.start >= start {
+                    return Position { prev, pred, curr };
+                }
+                prev = &node.next;
+                pred = Some(node.start..node.end);
+                curr = next;
+            }
+        }
+    }
+
+    /// Insert the non-empty `range`.
+    /// Returns an overlapping range on conflict.
+    ///
+    /// A range that is being removed concurrently may be reported as conflict.
+    pub fn insert(&self, range: &Range<usize>) -> Result<(), Range<usize>> {
+        debug_assert!(!range.is_empty());
+        let guard = &epoch::pin();
+        let mut new = Owned::new(Node {
+            start: range.start,
+            end: range.end,
+            next: Atomic::null(),
+        });
+        loop {
+            let pos = self.search(range.start, guard);
+            if let Some(pred) = pos.pred.filter(|pred| pred.end > range.start) {
+                return Err(pred);
+            }
+            // SAFETY: The node is protected by the pinned epoch.
+            if let Some(succ) = unsafe { pos.curr.as_ref() } {
+                if succ.start < range.end {
+                    return Err(succ.start..succ.end);
+                }
+            }
+            new.next.store(pos.curr, Ordering::Relaxed);
+            match pos.prev.compare_exchange(
+                pos.curr,
+                new,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+                guard,
+            ) {
+                Ok(_) => return Ok(()),
+                Err(e) => new = e.new,
+            }
+        }
+    }
+
+    /// Remove the `range`, which must have been inserted before.
+    pub fn remove(&self, range: &Range<usize>) {
+        let guard = &epoch::pin();
+        loop {
+            let pos = self.search(range.start, guard);
+            // SAFETY: The node is protected by the pinned epoch.
+            let node = unsafe { pos.curr.as_ref() }.expect("Removed range is not locked.");
+            // The caller must ensure that the removed range
+            // has been passed successfully to insert() before.
+            debug_assert_eq!(node.start..node.end, *range);
+            let next = node.next.load(Ordering::Acquire, guard);
+            debug_assert_ne!(next.tag(), REMOVED);
+            // Remove the node logically.
+            // This fails, if a node has been inserted after it. Search again then.
+            if node
+                .next
+                .compare_exchange(
+                    next,
+                    next.with_tag(REMOVED),
+                    Ordering::AcqRel,
+                    Ordering::Acquire,
+                    guard,
+                )
+                .is_err()
+            {
+                continue;
+            }
+            // Try to unlink the node. On failure, a later search unlinks it.
+            if pos
+                .prev
+                .compare_exchange(pos.curr, next, Ordering::AcqRel, Ordering::Acquire, guard)
+                .is_ok()
+            {
+                // SAFETY: The node is unlinked. See search().
+                unsafe { guard.defer_destroy(pos.curr) };
+            }
+            return;
+        }
+    }
+
+    /// Check whether no range is inserted.
+    pub fn is_empty(&self) -> bool {
+        let guard = &epoch::pin();
+        let mut curr = self.head.load(Ordering::Acquire, guard);
+        // SAFETY: The nodes are protected by the pinned epoch.
+        while let Some(node) = unsafe { curr.as_ref() } {
+            let next = node.next.load(Ordering::Acquire, guard);
+            if next.tag() != REMOVED {
+                return false;
+            }
+            curr = next.with_tag(0);
+        }
+        true
+    }
+}
+
+impl Drop for EpochRanges {
+    fn drop(&mut self) {
+        // SAFETY: We have exclusive access. No other thread can access the nodes.
+        unsafe {
+            let guard = epoch::unprotected();
+            let mut curr = self.head.load(Ordering::Relaxed, guard);
+            while !curr.is_null() {
+                let next = curr.deref().next.load(Ordering::Relaxed, guard);
+                drop(curr.into_owned());
+                curr = next.with_tag(0);
+            }
+        }
+    }
+}
+
+impl RangeSet for EpochRanges {
+    type Entry = ();
+
+    #[inline]
+    fn new() -> Self {
+        EpochRanges::new()
+    }
+
+    /// The list is never poisoned, because it is not protected by a mutex.
+    #[inline]
+    fn insert(&self, range: &Range<usize>) -> LockResult<Option<()>> {
+        Ok(EpochRanges::insert(self, range).ok())
+    }
+
+    #[inline]
+    fn remove(&self, range: &Range<usize>, _entry: ()) {
+        EpochRanges::remove(self, range);
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        EpochRanges::is_empty(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_insert_remove() {
+        let a = EpochRanges::new();
+        assert!(a.is_empty());
+        a.insert(&(10..20)).unwrap();
+        a.insert(&(30..40)).unwrap();
+        a.insert(&(0..10)).unwrap();
+        assert_eq!(a.insert(&(5..6)), Err(0..10));
+        assert_eq!(a.insert(&(19..21)), Err(10..20));
+        assert_eq!(a.insert(&(25..31)), Err(30..40));
+        assert_eq!(a.insert(&(30..31)), Err(30..40));
+        a.insert(&(20..30)).unwrap();
+        a.remove(&(10..20));
+        assert_eq!(a.insert(&(15..25)), Err(20..30));
+        a.insert(&(15..20)).unwrap();
+        a.remove(&(0..10));
+        a.remove(&(30..40));
+        a.remove(&(20..30));
+        assert!(!a.is_empty());
+        a.remove(&(15..20));
+        assert!(a.is_empty());
+    }
+
+    #[test]
+    fn test_concurrent() {
+        let a = EpochRanges::new();
+        thread::scope(|s| {
+            for t in 0..8 {
+                let a = &a;
+                s.spawn(move || {
+                    for i in 0..2000 {
+                        let start = (t * 7 + i) % 50;
+                        let range = start..start + 1 + i % 3;
+                        if a.insert(&range).is_ok() {
+                            // No other thread can hold an overlapping range.
+                            assert!(a.insert(&(range.start..range.start + 1)).is_err());
+                            a.remove(&range);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(a.is_empty());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
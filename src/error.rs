@@ -0,0 +1,269 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::sync::{PoisonError, TryLockError};
+use core::{error::Error, fmt, ops::Range};
+
+/// Detailed error of a failed locking attempt.
+///
+/// In contrast to [TryLockError] this error carries information about the contention.
+/// It can be converted into a [TryLockError].
+pub enum DetailedLockError<G> {
+    /// The requested range is contended.
+    Contended {
+        /// An already locked range that overlaps with the requested range.
+        conflicting: Range<usize>,
+    },
+    /// The lock is poisoned.
+    Poisoned(PoisonError<G>),
+}
+
+impl<G> fmt::Debug for DetailedLockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contended { conflicting } => f
+                .debug_struct("Contended")
+                .field("conflicting", conflicting)
+                .finish(),
+            Self::Poisoned(_) => f.write_str("Poisoned(..)"),
+        }
+    }
+}
+
+impl<G> fmt::Display for DetailedLockError<G> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Contended { conflicting } => write!(
+                f,
+                "range is contended by locked range {}..{}",
+                conflicting.start, conflicting.end
+            ),
+            Self::Poisoned(_) => f.write_str("lock is poisoned"),
+        }
+    }
+}
+
+impl<G> Error for DetailedLockError<G> {}
+
+impl<G> From<DetailedLockError<G>> for TryLockError<G> {
+    fn from(e: DetailedLockError<G>) -> TryLockError<G> {
+        match e {
+            DetailedLockError::Contended { .. } => TryLockError::WouldBlock,
+            DetailedLockError::Poisoned(e) => TryLockError::Poisoned(e),
+        }
+    }
+}
+
+/// Error of a failed [VecRangeLock::try_lock](crate::VecRangeLock::try_lock) attempt.
+///
+/// In contrast to [TryLockError] the poisoned variant doesn't carry a guard.
+/// The range is not locked, if this error is returned.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockError {
+    /// The requested range is contended.
+    /// The locking attempt may be retried.
+    WouldBlock {
+        /// An already locked range that overlaps with the requested range, if known.
+        conflicting: Option<Range<usize>>,
+    },
+    /// The lock is poisoned.
+    Poisoned,
+    /// The requested range exceeds the data.
+    OutOfBounds,
+    /// The start of the requested range is bigger than its end.
+    InvalidRange,
+}
+
+impl LockError {
+    /// Returns `true`, if the error is caused by contention and the call may be retried.
+    #[inline]
+    pub fn is_would_block(&self) -> bool {
+        matches!(self, Self::WouldBlock { .. })
+    }
+}
+
+impl fmt::Display for LockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock {
+                conflicting: Some(conflicting),
+            } => write!(
+                f,
+                "range is contended by locked range {}..{}",
+                conflicting.start, conflicting.end
+            ),
+            Self::WouldBlock { conflicting: None } => f.write_str("range is contended"),
+            Self::Poisoned => f.write_str("lock is poisoned"),
+            Self::OutOfBounds => f.write_str("range is out of bounds"),
+            Self::InvalidRange => f.write_str("range start is bigger than its end"),
+        }
+    }
+}
+
+impl Error for LockError {}
+
+impl<G> From<TryLockError<G>> for LockError {
+    /// Convert a [TryLockError] into a [LockError].
+    ///
+    /// The guard of a poisoned error is dropped.
+    fn from(e: TryLockError<G>) -> LockError {
+        match e {
+            TryLockError::WouldBlock => LockError::WouldBlock { conflicting: None },
+            TryLockError::Poisoned(_) => LockError::Poisoned,
+        }
+    }
+}
+
+impl<G> From<DetailedLockError<G>> for LockError {
+    /// Convert a [DetailedLockError] into a [LockError].
+    ///
+    /// The guard of a poisoned error is dropped.
+    fn from(e: DetailedLockError<G>) -> LockError {
+        match e {
+            DetailedLockError::Contended { conflicting } => LockError::WouldBlock {
+                conflicting: Some(conflicting),
+            },
+            DetailedLockError::Poisoned(_) => LockError::Poisoned,
+        }
+    }
+}
+
+impl TryFrom<LockError> for TryLockError<()> {
+    type Error = LockError;
+
+    /// Convert a [LockError] into a [TryLockError] without a guard.
+    ///
+    /// [LockError::OutOfBounds] and [LockError::InvalidRange] have no equivalent
+    /// and are returned as error.
+    fn try_from(e: LockError) -> Result<TryLockError<()>, LockError> {
+        match e {
+            LockError::WouldBlock { .. } => Ok(TryLockError::WouldBlock),
+            LockError::Poisoned => Ok(TryLockError::Poisoned(PoisonError::new(()))),
+            LockError::OutOfBounds | LockError::InvalidRange => Err(e),
+        }
+    }
+}
+
+/// Error of an invalid range passed to a checked locking call.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeError {
+    /// The start of the range is bigger than its end.
+    StartAfterEnd {
+        /// Inclusive start of the range.
+        start: usize,
+        /// Exclusive end of the range.
+        end: usize,
+    },
+    /// The range exceeds the data.
+    OutOfBounds {
+        /// Length of the data.
+        data_len: usize,
+    },
+}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::StartAfterEnd { start, end } => {
+                write!(f, "range start {start} is bigger than its end {end}")
+            }
+            Self::OutOfBounds { data_len } => {
+                write!(f, "range is out of bounds of the data length {data_len}")
+            }
+        }
+    }
+}
+
+impl Error for RangeError {}
+
+/// Error of an invalid layout passed to [RepVecRangeLock::try_new](crate::RepVecRangeLock::try_new)
+/// or [RepVecRangeLock::try_with_stride](crate::RepVecRangeLock::try_with_stride).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RepLockError {
+    /// The slice length is 0.
+    ZeroSliceLen,
+    /// The cycle length is 0 or bigger than usize::MAX-31.
+    CycleLenOutOfRange {
+        /// The requested cycle length.
+        cycle_len: usize,
+    },
+    /// The stride is smaller than the slice length.
+    StrideTooSmall {
+        /// The requested stride.
+        stride: usize,
+        /// The requested slice length.
+        slice_len: usize,
+    },
+    /// The number of elements in one repeat cycle overflows usize.
+    CycleOverflow,
+}
+
+impl fmt::Display for RepLockError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ZeroSliceLen => f.write_str("slice length must not be 0"),
+            Self::CycleLenOutOfRange { cycle_len } => {
+                write!(f, "cycle length {cycle_len} is out of range")
+            }
+            Self::StrideTooSmall { stride, slice_len } => {
+                write!(
+                    f,
+                    "stride {stride} is smaller than the slice length {slice_len}"
+                )
+            }
+            Self::CycleOverflow => f.write_str("repeat cycle length overflows"),
+        }
+    }
+}
+
+impl Error for RepLockError {}
+
+/// Error of a scoped locking call with a fallible closure.
+///
+/// Distinguishes between failing to lock the range and a failure of the user closure.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LockOrUserError<E> {
+    /// The requested range is contended.
+    /// The closure has not been called.
+    WouldBlock,
+    /// The lock is poisoned.
+    /// The closure has not been called.
+    Poisoned,
+    /// The closure returned an error.
+    User(E),
+}
+
+impl<E> LockOrUserError<E> {
+    /// Returns `true`, if the error is caused by contention and the call may be retried.
+    #[inline]
+    pub fn is_would_block(&self) -> bool {
+        matches!(self, Self::WouldBlock)
+    }
+}
+
+impl<E: fmt::Display> fmt::Display for LockOrUserError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::WouldBlock => f.write_str("range is contended"),
+            Self::Poisoned => f.write_str("lock is poisoned"),
+            Self::User(e) => e.fmt(f),
+        }
+    }
+}
+
+impl<E: Error + 'static> Error for LockOrUserError<E> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::User(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
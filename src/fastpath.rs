@@ -0,0 +1,320 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Uncontended fast path of [crate::VecRangeLock].
+//!
+//! While no range is locked, one single range can be locked
+//! without taking the shard mutexes.
+//! The fast range is not inserted into the shard trees.
+//! All other operations on the shard trees (the slow path) are counted.
+//! The first slow path operation moves the fast range into the shard trees.
+
+use std::{
+    hint::spin_loop,
+    mem,
+    ops::Range,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+/// No range is locked and no slow path operation is in progress.
+const IDLE: usize = 0;
+/// One range is locked via the fast path.
+const FAST: usize = 1;
+/// The fast range is being published or moved into the shard trees.
+const BUSY: usize = 2;
+/// Slow path mode with a count of 1.
+/// Each further count increments the state by one.
+/// The count is the number of ranges in the shard trees
+/// plus the number of slow path operations in progress.
+const SLOW: usize = 3;
+
+/// State of the uncontended fast path.
+#[derive(Debug, Default)]
+pub(crate) struct FastPath {
+    /// IDLE, FAST, BUSY or the slow path count.
+    state: AtomicUsize,
+    /// Start of the fast range. Valid in the FAST state.
+    start: AtomicUsize,
+    /// End of the fast range. Valid in the FAST state.
+    end: AtomicUsize,
+}
+
+/// A slow path operation in progress. See [FastPath::enter].
+///
+/// The operation is left on drop, also on unwinding.
+#[must_use]
+#[derive(Debug)]
+pub(crate) struct SlowPath<'a> {
+    fast: &'a FastPath,
+}
+
+impl SlowPath<'_> {
+    /// Leave the operation and count the `n` ranges that it has inserted into the shard trees.
+    ///
+    /// The ranges have to be removed with [FastPath::leave] later.
+    #[inline]
+    pub fn into_ranges(self, n: usize) {
+        let fast = self.fast;
+        mem::forget(self);
+        // Replace the count of this operation by the count of the inserted ranges.
+        match n {
+            0 => fast.leave(1),
+            n => fast.add(n - 1),
+        }
+    }
+}
+
+impl Drop for SlowPath<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.fast.leave(1);
+    }
+}
+
+/// Moving the fast range into the shard trees. See [FastPath::enter].
+///
+/// Leaves the BUSY state on drop, also if `migrate` unwinds.
+struct Migration<'a> {
+    fast: &'a FastPath,
+    /// The fast range has been inserted into the shard trees.
+    migrated: bool,
+}
+
+impl Drop for Migration<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        let state = if self.migrated {
+            // Count the migrated range and the slow path operation.
+            SLOW + 1
+        } else {
+            // The range is still locked via the fast path.
+            FAST
+        };
+        self.fast.state.store(state, Ordering::Release);
+    }
+}
+
+impl FastPath {
+    /// Try to lock the non-empty `range` via the fast path.
+    ///
+    /// This only succeeds, if no other range is locked and no slow path operation is in progress.
+    #[inline]
+    pub fn try_lock(&self, range: &Range<usize>) -> bool {
+        if self
+            .state
+            .compare_exchange(IDLE, BUSY, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+        self.start.store(range.start, Ordering::Relaxed);
+        self.end.store(range.end, Ordering::Relaxed);
+        self.state.store(FAST, Ordering::Release);
+        true
+    }
+
+    /// Try to unlock the non-empty exclusively locked `range` via the fast path.
+    ///
+    /// Returns `false`, if the range is in the shard trees.
+    /// It has to be removed from the shard trees by the caller then.
+    #[inline]
+    pub fn try_unlock(&self, range: &Range<usize>) -> bool {
+        loop {
+            match self
+                .state
+                .compare_exchange(FAST, IDLE, Ordering::Release, Ordering::Relaxed)
+            {
+                Ok(_) => return true,
+                // The held range prevents the IDLE state.
+                // Therefore, BUSY means that our range is being moved into the shard trees.
+                // Wait for it to arrive there.
+                Err(BUSY) => spin_loop(),
+                Err(state) => {
+                    debug_assert!(state >= SLOW, "Unlocking {range:?} in IDLE state.");
+                    return false;
+                }
+            }
+        }
+    }
+
+    /// Enter a slow path operation.
+    ///
+    /// A range locked via the fast path is moved into the shard trees with `migrate`.
+    /// Afterwards, no range can be locked via the fast path,
+    /// until the returned [SlowPath] has been dropped.
+    /// This must be called before taking any shard mutex.
+    ///
+    /// If `migrate` unwinds, the range stays locked via the fast path.
+    /// `migrate` must not leave the range partially inserted then.
+    pub fn enter(&self, migrate: impl FnOnce(&Range<usize>)) -> SlowPath<'_> {
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            let new = match state {
+                IDLE => SLOW,
+                FAST => BUSY,
+                BUSY => {
+                    spin_loop();
+                    state = self.state.load(Ordering::Relaxed);
+                    continue;
+                }
+                count => count + 1,
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(FAST) => {
+                    let mut migration = Migration {
+                        fast: self,
+                        migrated: false,
+                    };
+                    let range =
+                        self.start.load(Ordering::Relaxed)..self.end.load(Ordering::Relaxed);
+                    migrate(&range);
+                    migration.migrated = true;
+                    drop(migration);
+                    return SlowPath { fast: self };
+                }
+                Ok(_) => return SlowPath { fast: self },
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Count `n` additional ranges that have been inserted into the shard trees.
+    ///
+    /// Must only be called during a slow path operation.
+    #[inline]
+    fn add(&self, n: usize) {
+        if n > 0 {
+            let old = self.state.fetch_add(n, Ordering::Relaxed);
+            debug_assert!(old >= SLOW);
+        }
+    }
+
+    /// Leave a slow path operation or remove `n` ranges from the count.
+    ///
+    /// The fast path becomes available again, when the count drops to zero.
+    pub fn leave(&self, n: usize) {
+        if n == 0 {
+            return;
+        }
+        let mut state = self.state.load(Ordering::Relaxed);
+        loop {
+            debug_assert!(state >= SLOW && state - SLOW >= n - 1);
+            let new = if state - SLOW == n - 1 {
+                IDLE
+            } else {
+                state - n
+            };
+            match self
+                .state
+                .compare_exchange_weak(state, new, Ordering::AcqRel, Ordering::Relaxed)
+            {
+                Ok(_) => return,
+                Err(s) => state = s,
+            }
+        }
+    }
+
+    /// Get a snapshot of the range locked via the fast path, if any.
+    ///
+    /// The snapshot may be outdated immediately.
+    pub fn snapshot(&self) -> Option<Range<usize>> {
+        if self.state.load(Ordering::Acquire) != FAST {
+            return None;
+        }
+        let range = self.start.load(Ordering::Relaxed)..self.end.load(Ordering::Relaxed);
+        (self.state.load(Ordering::Acquire) == FAST).then_some(range)
+    }
+
+    /// Check whether a range is locked via the fast path.
+    #[inline]
+    pub fn is_fast(&mut self) -> bool {
+        *self.state.get_mut() == FAST
+    }
+
+    /// Reset to the IDLE state.
+    ///
+    /// The caller must ensure that no range is locked.
+    #[inline]
+    pub fn reset(&mut self) {
+        *self.state.get_mut() = IDLE;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fastpath() {
+        let f = FastPath::default();
+        assert!(f.try_lock(&(2..4)));
+        assert_eq!(f.snapshot(), Some(2..4));
+        assert!(!f.try_lock(&(6..8)));
+        assert!(f.try_unlock(&(2..4)));
+        assert_eq!(f.snapshot(), None);
+
+        // Migrate the fast range on entering the slow path.
+        assert!(f.try_lock(&(2..4)));
+        let mut migrated = None;
+        let slow = f.enter(|r| migrated = Some(r.clone()));
+        assert_eq!(migrated, Some(2..4));
+        assert!(!f.try_lock(&(6..8)));
+        slow.into_ranges(2);
+        assert!(!f.try_unlock(&(2..4)));
+        f.leave(1);
+        assert!(!f.try_lock(&(6..8)));
+        f.leave(2);
+        assert_eq!(f.state.load(Ordering::Relaxed), IDLE);
+
+        // Nothing to migrate.
+        drop(f.enter(|_| panic!("Nothing to migrate")));
+        assert!(f.try_lock(&(6..8)));
+        assert!(f.try_unlock(&(6..8)));
+
+        // An operation without inserted ranges.
+        f.enter(|_| ()).into_ranges(0);
+        assert_eq!(f.state.load(Ordering::Relaxed), IDLE);
+
+        // The operation is left on unwinding.
+        let result = std::panic::catch_unwind(|| {
+            let _slow = f.enter(|_| ());
+            panic!("Unwinding");
+        });
+        assert!(result.is_err());
+        assert_eq!(f.state.load(Ordering::Relaxed), IDLE);
+    }
+
+    #[test]
+    fn test_fastpath_migrate_panic() {
+        let f = FastPath::default();
+        assert!(f.try_lock(&(2..4)));
+        let result = std::panic::catch_unwind(|| {
+            drop(f.enter(|_| panic!("Migration failed")));
+        });
+        assert!(result.is_err());
+        // The range is still locked via the fast path.
+        assert_eq!(f.snapshot(), Some(2..4));
+        assert!(!f.try_lock(&(6..8)));
+
+        // The lock is still usable.
+        let mut migrated = None;
+        f.enter(|r| migrated = Some(r.clone())).into_ranges(0);
+        assert_eq!(migrated, Some(2..4));
+        assert!(!f.try_unlock(&(2..4)));
+        f.leave(1);
+        assert_eq!(f.state.load(Ordering::Relaxed), IDLE);
+        assert!(f.try_lock(&(6..8)));
+        assert!(f.try_unlock(&(6..8)));
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
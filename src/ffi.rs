@@ -0,0 +1,180 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! C interface to a [VecRangeLock] of bytes.
+//!
+//! This module is available with the `ffi` feature.
+//! A `cdylib` or `staticlib` crate depending on this crate exports the functions to C code.
+//!
+//! The lock and the guards are opaque handles:
+//!
+//! ```c
+//! typedef struct rangelock rangelock;
+//! typedef struct rangelock_guard rangelock_guard;
+//!
+//! rangelock *rangelock_new(size_t len);
+//! void rangelock_free(rangelock *lock);
+//! size_t rangelock_data_len(const rangelock *lock);
+//! uint8_t *rangelock_data_ptr(const rangelock *lock);
+//! rangelock_guard *rangelock_try_lock(const rangelock *lock, size_t start, size_t len);
+//! uint8_t *rangelock_guard_ptr(rangelock_guard *guard);
+//! void rangelock_unlock(rangelock_guard *guard);
+//! ```
+//!
+//! A guard keeps its lock alive.
+//! Therefore, the lock may be freed before all guards are unlocked.
+
+use crate::rangelock::{OwnedVecRangeLockGuard, VecRangeLock};
+use std::{
+    ptr,
+    sync::{Arc, TryLockError},
+};
+
+/// Opaque lock handle.
+pub type RangeLockHandle = VecRangeLock<u8>;
+
+/// Opaque guard handle.
+pub type RangeLockGuardHandle = OwnedVecRangeLockGuard<u8>;
+
+/// Create a new lock of `len` zero bytes.
+///
+/// The returned handle must be freed with [rangelock_free].
+#[no_mangle]
+pub extern "C" fn rangelock_new(len: usize) -> *const RangeLockHandle {
+    Arc::into_raw(Arc::new(VecRangeLock::new(vec![0; len])))
+}
+
+/// Free a lock handle.
+///
+/// The data is freed, when all guards of the lock have been unlocked.
+///
+/// # Safety
+///
+/// `lock` must be a handle returned by [rangelock_new] that has not been freed, yet.
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_free(lock: *const RangeLockHandle) {
+    drop(Arc::from_raw(lock));
+}
+
+/// Get the length (in bytes) of the data.
+///
+/// # Safety
+///
+/// `lock` must be a valid handle returned by [rangelock_new].
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_data_len(lock: *const RangeLockHandle) -> usize {
+    (*lock).data_len()
+}
+
+/// Get a pointer to the first byte of the data.
+///
+/// See [VecRangeLock::data_ptr].
+///
+/// # Safety
+///
+/// `lock` must be a valid handle returned by [rangelock_new].
+/// The returned pointer must only be dereferenced within ranges locked by the caller.
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_data_ptr(lock: *const RangeLockHandle) -> *mut u8 {
+    (*lock).data_ptr()
+}
+
+/// Try to lock `len` bytes starting at `start`.
+///
+/// Returns a guard handle on success.
+/// The guard must be released with [rangelock_unlock].
+/// Returns NULL, if the range is contended or out of bounds.
+/// A poisoned lock is ignored.
+///
+/// # Safety
+///
+/// `lock` must be a valid handle returned by [rangelock_new].
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_try_lock(
+    lock: *const RangeLockHandle,
+    start: usize,
+    len: usize,
+) -> *mut RangeLockGuardHandle {
+    let Some(end) = start.checked_add(len) else {
+        return ptr::null_mut();
+    };
+    if end > (*lock).data_len() {
+        return ptr::null_mut();
+    }
+    Arc::increment_strong_count(lock);
+    let lock = Arc::from_raw(lock);
+    match lock.try_lock_owned(start..end) {
+        Ok(guard) => Box::into_raw(Box::new(guard)),
+        Err(TryLockError::Poisoned(e)) => Box::into_raw(Box::new(e.into_inner())),
+        Err(TryLockError::WouldBlock) => ptr::null_mut(),
+    }
+}
+
+/// Get a pointer to the first byte of the locked range.
+///
+/// # Safety
+///
+/// `guard` must be a valid guard handle returned by [rangelock_try_lock].
+/// The returned pointer must only be dereferenced within the locked range
+/// and only until the guard is unlocked.
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_guard_ptr(guard: *mut RangeLockGuardHandle) -> *mut u8 {
+    (*guard).as_mut_ptr()
+}
+
+/// Unlock the range and free the guard handle.
+///
+/// # Safety
+///
+/// `guard` must be a guard handle returned by [rangelock_try_lock] that has not been unlocked, yet.
+#[no_mangle]
+pub unsafe extern "C" fn rangelock_unlock(guard: *mut RangeLockGuardHandle) {
+    drop(Box::from_raw(guard));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ffi() {
+        unsafe {
+            let lock = rangelock_new(8);
+            assert_eq!(rangelock_data_len(lock), 8);
+            let g0 = rangelock_try_lock(lock, 2, 4);
+            assert!(!g0.is_null());
+            assert!(rangelock_try_lock(lock, 5, 1).is_null());
+            assert!(rangelock_try_lock(lock, 7, 2).is_null());
+            assert!(rangelock_try_lock(lock, usize::MAX, 2).is_null());
+            *rangelock_guard_ptr(g0).add(1) = 42;
+            assert_eq!(*rangelock_data_ptr(lock).add(3), 42);
+
+            // The guard keeps the lock alive.
+            rangelock_free(lock);
+            assert_eq!((&*g0)[..], [0, 42, 0, 0]);
+            rangelock_unlock(g0);
+        }
+    }
+
+    #[test]
+    fn test_ffi_unlock() {
+        unsafe {
+            let lock = rangelock_new(4);
+            let g0 = rangelock_try_lock(lock, 0, 4);
+            rangelock_unlock(g0);
+            let g1 = rangelock_try_lock(lock, 0, 4);
+            assert!(!g1.is_null());
+            rangelock_unlock(g1);
+            assert!((*lock).is_all_unlocked());
+            rangelock_free(lock);
+        }
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
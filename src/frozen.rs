@@ -0,0 +1,121 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLock;
+use std::{ops::Deref, sync::Arc};
+
+/// Immutable shareable data of a frozen [VecRangeLock].
+///
+/// See [VecRangeLock::freeze].
+/// Cloning a [FrozenVec] is cheap. All clones share the same data.
+/// Reading the data doesn't need any locking.
+///
+/// The [Deref] trait is implemented for this struct.
+/// Dereferencing yields an immutable slice of the whole data.
+#[derive(Debug)]
+pub struct FrozenVec<T> {
+    /// The shared data.
+    data: Arc<[T]>,
+}
+
+impl<T> FrozenVec<T> {
+    /// Unwrap this [FrozenVec] into the shared data.
+    #[inline]
+    pub fn into_arc(self) -> Arc<[T]> {
+        self.data
+    }
+}
+
+impl<T> Clone for FrozenVec<T> {
+    #[inline]
+    fn clone(&self) -> Self {
+        FrozenVec {
+            data: Arc::clone(&self.data),
+        }
+    }
+}
+
+impl<T> Deref for FrozenVec<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.data
+    }
+}
+
+impl<T> AsRef<[T]> for FrozenVec<T> {
+    #[inline]
+    fn as_ref(&self) -> &[T] {
+        &self.data
+    }
+}
+
+impl<T> VecRangeLock<T> {
+    /// Convert this [VecRangeLock] into an immutable [FrozenVec] of the same data.
+    /// This method consumes self.
+    ///
+    /// No range can be locked anymore, because self is consumed.
+    /// The [FrozenVec] can be cloned and shared between threads
+    /// and its data is read without any locking.
+    ///
+    /// The data is moved into a new allocation of the shared [Arc].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 4]);
+    /// lock.try_lock(1..3).expect("Failed to lock 1..3").fill(5);
+    /// let frozen = lock.freeze();
+    /// thread::scope(|s| {
+    ///     s.spawn(|| assert_eq!(frozen[1], 5));
+    /// });
+    /// assert_eq!(frozen[..], [0, 5, 5, 0]);
+    /// ```
+    pub fn freeze(self) -> FrozenVec<T> {
+        FrozenVec {
+            data: self.into_inner().into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_freeze() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<i32>>(), 4);
+        a.try_lock(10..20).unwrap().fill(-1);
+        let frozen = a.freeze();
+        assert_eq!(frozen.len(), 100);
+        thread::scope(|s| {
+            for _ in 0..4 {
+                let frozen = frozen.clone();
+                s.spawn(move || {
+                    assert_eq!(frozen[9], 9);
+                    assert!(frozen[10..20].iter().all(|&x| x == -1));
+                    assert_eq!(frozen.as_ref()[20], 20);
+                });
+            }
+        });
+        let arc = frozen.into_arc();
+        assert_eq!(Arc::strong_count(&arc), 1);
+        assert_eq!(arc[99], 99);
+
+        let empty: FrozenVec<u8> = VecRangeLock::new(vec![]).freeze();
+        assert!(empty.is_empty());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,147 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    rangelock::{VecRangeLock, VecRangeLockGuard},
+    util::LockIndex,
+};
+use std::{
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+    sync::{LockResult, TryLockResult},
+};
+
+/// Multi-thread range lock for [std::vec::Vec] with a custom index type.
+///
+/// This is a variant of [VecRangeLock] that accepts ranges of the index type `I`,
+/// for example `Range<u64>`, independent of the width of [usize] on the target.
+///
+/// Only ranges within the data can be locked.
+/// The length of the data is limited by [usize].
+/// Therefore, the ranges are checked against the data length
+/// and then locked as [usize] ranges by the underlying [VecRangeLock].
+///
+/// # Example
+///
+/// ```
+/// use range_lock::IndexRangeLock;
+///
+/// let lock: IndexRangeLock<u64, i32> = IndexRangeLock::new(vec![1, 2, 3, 4]);
+/// let mut guard = lock.try_lock(1_u64..3).expect("Failed to lock 1..3");
+/// guard[0] = 20;
+/// assert!(lock.try_lock(2_u64..4).is_err());
+/// drop(guard);
+/// assert_eq!(lock.into_inner(), vec![1, 20, 3, 4]);
+/// ```
+#[derive(Debug)]
+pub struct IndexRangeLock<I, T> {
+    /// The underlying range lock.
+    lock: VecRangeLock<T>,
+    _index: PhantomData<fn(I)>,
+}
+
+impl<'a, I: LockIndex, T> IndexRangeLock<I, T> {
+    /// Construct a new [IndexRangeLock].
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new(data: Vec<T>) -> IndexRangeLock<I, T> {
+        IndexRangeLock {
+            lock: VecRangeLock::new(data),
+            _index: PhantomData,
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec].
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.lock.data_len()
+    }
+
+    /// Get the underlying [VecRangeLock].
+    #[inline]
+    pub fn as_vec_range_lock(&self) -> &VecRangeLock<T> {
+        &self.lock
+    }
+
+    /// Unwrap this [IndexRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        self.lock.into_inner()
+    }
+
+    /// Convert a range of the index type into a [usize] range.
+    /// Indices that don't fit into [usize] are beyond the end of the data.
+    fn convert_range(range: &impl RangeBounds<I>) -> (Bound<usize>, Bound<usize>) {
+        let convert = |index: I| {
+            index
+                .to_usize()
+                .unwrap_or_else(|| panic!("Range is out of bounds."))
+        };
+        (
+            range.start_bound().map(|i| convert(*i)),
+            range.end_bound().map(|i| convert(*i)),
+        )
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// See [VecRangeLock::try_lock] for details.
+    #[inline]
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<I>,
+    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        self.lock.try_lock_poisonable(Self::convert_range(&range))
+    }
+
+    /// Lock the given data `range`.
+    /// Blocks, until the range is available.
+    ///
+    /// See [VecRangeLock::lock] for details.
+    #[inline]
+    pub fn lock(&'a self, range: impl RangeBounds<I>) -> LockResult<VecRangeLockGuard<'a, T>> {
+        self.lock.lock(Self::convert_range(&range))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_rangelock() {
+        let a: IndexRangeLock<u8, i32> = IndexRangeLock::new(vec![1, 2, 3, 4]);
+        {
+            let g0 = a.try_lock(0_u8..=1).unwrap();
+            let g1 = a.lock(2_u8..).unwrap();
+            assert_eq!(g0[..], [1, 2]);
+            assert_eq!(g1[..], [3, 4]);
+            assert!(a.try_lock(..).is_err());
+        }
+        assert!(a.as_vec_range_lock().is_all_unlocked());
+        let _g = a.try_lock(..).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_index_rangelock_oob() {
+        let a: IndexRangeLock<u64, i32> = IndexRangeLock::new(vec![1, 2, 3, 4]);
+        let _ = a.try_lock(2_u64..5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_index_rangelock_oob_huge() {
+        let a: IndexRangeLock<u128, i32> = IndexRangeLock::new(vec![1, 2, 3, 4]);
+        let _ = a.try_lock(2_u128..u128::MAX);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -15,6 +15,8 @@
 //! For a more complex example, please see the [VecRangeLock] struct documentation.
 //!
 //! ```
+//! # #[cfg(feature = "std")]
+//! # {
 //! use range_lock::VecRangeLock;
 //! use std::sync::Arc;
 //! use std::thread;
@@ -26,6 +28,7 @@
 //!     assert_eq!(guard[0], 3);
 //!     guard[0] = 10;
 //! });
+//! # }
 //! ```
 //!
 //! # RepVecRangeLock: Restricted interleaved pattern range lock
@@ -59,13 +62,145 @@
 //!     guard[1][0] = 50;               // Cycle 1, Slice element 0
 //! });
 //! ```
+//!
+//! # no_std
+//!
+//! The crate requires `std` with the default `std` feature.
+//! Without it, the crate is `no_std` and only needs `alloc`.
+//! Only [RepVecRangeLock] is available then, without its blocking methods.
+//! The locking methods return the crate-local equivalents of the [std::sync] result types.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
 
+#[cfg(feature = "allocator_api")]
+mod allocrangelock;
+#[cfg(feature = "std")]
+mod arrayrangelock;
+#[cfg(feature = "ndarray")]
+mod arrayview;
+#[cfg(feature = "std")]
+mod atomicranges;
+#[cfg(feature = "std")]
+pub mod backoff;
+#[cfg(feature = "std")]
+mod bitrangelock;
+#[cfg(feature = "std")]
+mod brandedrangelock;
+#[cfg(feature = "std")]
+mod builder;
+#[cfg(feature = "std")]
+mod bytes;
+#[cfg(feature = "bytemuck")]
+mod cast;
+#[cfg(feature = "std")]
+mod cursor;
+#[cfg(feature = "deadlock_detection")]
+pub mod deadlock;
+#[cfg(all(feature = "std", not(feature = "deadlock_detection")))]
+mod deadlock;
+#[cfg(feature = "std")]
+mod dstrangelock;
+#[cfg(feature = "lock_free")]
+mod epochranges;
+mod error;
+#[cfg(feature = "std")]
+mod fastpath;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+mod frozen;
+#[cfg(feature = "std")]
+mod indexrangelock;
+#[cfg(feature = "std")]
 mod lockedranges;
+#[cfg(feature = "lock_free")]
+mod lockfreerangelock;
+#[cfg(feature = "std")]
+mod lockset;
+#[cfg(feature = "std")]
+mod matrix;
+#[cfg(feature = "std")]
+mod mutex;
+#[cfg(feature = "rayon")]
+mod par;
+#[cfg(feature = "std")]
 mod rangelock;
+#[cfg(feature = "std")]
+mod rawrangelock;
+#[cfg(feature = "std")]
+mod reentrant;
+#[cfg(feature = "std")]
+mod replanering;
 mod reprangelock;
-mod util;
+#[cfg(feature = "std")]
+mod scoped;
+#[cfg(feature = "std")]
+mod slicerangelock;
+#[cfg(feature = "std")]
+mod smallrangelock;
+#[cfg(feature = "std")]
+mod stats;
+#[cfg(feature = "futures")]
+mod stream;
+#[cfg(feature = "std")]
+mod subrangelock;
+mod sync;
+#[cfg(feature = "std")]
+pub mod util;
+#[cfg(feature = "std")]
+mod waitqueue;
 
-pub use rangelock::{VecRangeLock, VecRangeLockGuard};
-pub use reprangelock::{RepVecRangeLock, RepVecRangeLockGuard};
+#[cfg(feature = "allocator_api")]
+pub use allocrangelock::{AllocVecRangeLock, AllocVecRangeLockGuard};
+#[cfg(feature = "std")]
+pub use arrayrangelock::{ArrayRangeLock, ArrayRangeLockGuard};
+#[cfg(feature = "std")]
+pub use bitrangelock::{BitRangeLock, BitRangeLockGuard};
+#[cfg(feature = "std")]
+pub use brandedrangelock::{BrandedVecRangeLock, BrandedVecRangeLockGuard};
+#[cfg(feature = "std")]
+pub use builder::RangeLockBuilder;
+#[cfg(feature = "std")]
+pub use bytes::FromBytes;
+#[cfg(feature = "std")]
+pub use cursor::VecRangeLockCursor;
+#[cfg(feature = "std")]
+pub use dstrangelock::{RangeLock, RangeLockGuard};
+pub use error::{DetailedLockError, LockError, LockOrUserError, RangeError, RepLockError};
+#[cfg(feature = "std")]
+pub use frozen::FrozenVec;
+#[cfg(feature = "std")]
+pub use indexrangelock::IndexRangeLock;
+#[cfg(feature = "lock_free")]
+pub use lockfreerangelock::{LockFreeVecRangeLock, LockFreeVecRangeLockGuard};
+#[cfg(feature = "std")]
+pub use lockset::{try_lock_pair, LockSet};
+#[cfg(feature = "std")]
+pub use matrix::{MatrixRangeLock, MatrixRangeLockGuard};
+#[cfg(feature = "std")]
+pub use rangelock::{
+    MappedVecRangeLockGuard, OwnedVecRangeLockGuard, VecRangeLock, VecRangeLockElementGuard,
+    VecRangeLockExactReadGuard, VecRangeLockGuard, VecRangeLockIterMut, VecRangeLockMultiGuard,
+    VecRangeLockReadGuard, VecRangeLockStridedGuard, VecRangeLockUpgradableReadGuard,
+    VecRangeLockWriteGuard, WeakVecRangeLockReadGuard,
+};
+#[cfg(feature = "std")]
+pub use reentrant::{ReentrantVecRangeLock, ReentrantVecRangeLockGuard};
+#[cfg(feature = "std")]
+pub use replanering::RepLaneRing;
+pub use reprangelock::{
+    OwnedRepVecRangeLockGuard, RepVecRangeLock, RepVecRangeLockGuard, RepVecRangeLockMultiGuard,
+    RepVecRangeLockReadGuard,
+};
+#[cfg(feature = "std")]
+pub use slicerangelock::{SliceRangeLock, SliceRangeLockGuard};
+#[cfg(feature = "std")]
+pub use smallrangelock::{SmallVecRangeLock, SmallVecRangeLockGuard};
+#[cfg(feature = "std")]
+pub use subrangelock::{SubRangeLock, SubRangeLockGuard};
+#[cfg(not(feature = "std"))]
+pub use sync::{PoisonError, TryLockError, TryLockResult};
 
 // vim: ts=4 sw=4 expandtab
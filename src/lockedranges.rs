@@ -7,16 +7,50 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 //
 
-use std::{collections::BTreeMap, ops::Range};
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    iter,
+    ops::Range,
+};
 
+/// Check whether the ranges `a` and `b` overlap.
+///
+/// The non-empty ranges overlap, if they have at least one element in common.
+/// Empty ranges are never locked. Therefore, they are never checked for overlap.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::util::overlaps;
+///
+/// assert!(overlaps(&(0..3), &(2..4)));
+/// assert!(!overlaps(&(0..2), &(2..4)));
+/// ```
 #[inline]
 pub fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
     a.end > b.start && a.start < b.end
 }
 
+/// The set of locked ranges.
+///
+/// The ranges within each tree never overlap with each other.
+/// Therefore, an overlap query only has to check the last range
+/// that starts before the end of the queried range.
+/// This is a single O(log n) tree lookup, even with many densely packed ranges.
+/// An augmented interval tree would not improve on that and is deliberately not used.
+/// See the `many_ranges` benchmark for the lookup cost with up to 10k held ranges.
 #[derive(Debug)]
 pub struct LockedRanges {
+    /// The exclusively locked ranges. Maps start to end.
     tree: BTreeMap<usize, usize>,
+    /// The shared locked segments. Maps start to (end, number of readers).
+    /// The segments don't overlap with each other.
+    shared: BTreeMap<usize, (usize, usize)>,
+    /// The upgradable shared locked ranges. Maps start to end.
+    upgradable: BTreeMap<usize, usize>,
+    /// The exact shared locked ranges. Maps start to (end, number of readers).
+    /// Only identical ranges are shared. The ranges don't overlap with each other.
+    exact: BTreeMap<usize, (usize, usize)>,
 }
 
 impl LockedRanges {
@@ -24,39 +58,363 @@ impl LockedRanges {
     pub fn new() -> Self {
         Self {
             tree: BTreeMap::new(),
+            shared: BTreeMap::new(),
+            upgradable: BTreeMap::new(),
+            exact: BTreeMap::new(),
         }
     }
 
     #[inline]
     pub fn is_empty(&self) -> bool {
         self.tree.is_empty()
+            && self.shared.is_empty()
+            && self.upgradable.is_empty()
+            && self.exact.is_empty()
     }
 
-    #[inline]
-    pub fn insert(&mut self, range: &Range<usize>) -> bool {
-        // Check if this range overlaps with an existing one in the tree.
-        for (begin, end) in self.tree.range(..range.end).rev() {
-            if *end <= range.start {
-                break;
-            }
-            if overlaps(&(*begin..*end), range) {
-                // The range overlaps with an existing one in the tree.
-                return false;
+    /// Get an iterator over all locked ranges.
+    ///
+    /// This includes exclusive, shared, upgradable and exact shared ranges.
+    /// The ranges are not sorted and may overlap with each other.
+    pub fn iter(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        self.tree
+            .iter()
+            .chain(self.upgradable.iter())
+            .map(|(begin, end)| *begin..*end)
+            .chain(self.shared.iter().map(|(begin, (end, _))| *begin..*end))
+            .chain(self.exact.iter().map(|(begin, (end, _))| *begin..*end))
+    }
+
+    /// Get an iterator over all locked ranges in ascending order of their start.
+    ///
+    /// This yields the same ranges as [LockedRanges::iter] without allocating.
+    pub fn iter_sorted(&self) -> impl Iterator<Item = Range<usize>> + '_ {
+        let mut tree = self.tree.iter().map(|(b, e)| *b..*e).peekable();
+        let mut upgradable = self.upgradable.iter().map(|(b, e)| *b..*e).peekable();
+        let mut shared = self.shared.iter().map(|(b, (e, _))| *b..*e).peekable();
+        let mut exact = self.exact.iter().map(|(b, (e, _))| *b..*e).peekable();
+        iter::from_fn(move || {
+            let starts = [
+                tree.peek().map(|r| r.start),
+                upgradable.peek().map(|r| r.start),
+                shared.peek().map(|r| r.start),
+                exact.peek().map(|r| r.start),
+            ];
+            let min = starts.iter().flatten().min().copied();
+            if min.is_none() {
+                None
+            } else if starts[0] == min {
+                tree.next()
+            } else if starts[1] == min {
+                upgradable.next()
+            } else if starts[2] == min {
+                shared.next()
+            } else {
+                exact.next()
             }
+        })
+    }
+
+    /// Get an exclusively locked range that overlaps with `range`.
+    #[inline]
+    fn find_exclusive(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        // The ranges in the tree don't overlap with each other.
+        // Therefore, only the last range starting before the end of `range` can overlap.
+        let (begin, end) = self.tree.range(..range.end).next_back()?;
+        let locked = *begin..*end;
+        overlaps(&locked, range).then_some(locked)
+    }
+
+    /// Get an upgradable locked range that overlaps with `range`.
+    #[inline]
+    fn find_upgradable(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let (begin, end) = self.upgradable.range(..range.end).next_back()?;
+        let locked = *begin..*end;
+        overlaps(&locked, range).then_some(locked)
+    }
+
+    /// Get a shared locked segment that overlaps with `range`.
+    #[inline]
+    fn find_shared(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let (begin, (end, _)) = self.shared.range(..range.end).next_back()?;
+        let locked = *begin..*end;
+        overlaps(&locked, range).then_some(locked)
+    }
+
+    /// Get an exact shared locked range that overlaps with `range`.
+    #[inline]
+    fn find_exact(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        let (begin, (end, _)) = self.exact.range(..range.end).next_back()?;
+        let locked = *begin..*end;
+        overlaps(&locked, range).then_some(locked)
+    }
+
+    /// Get all locked ranges of any kind that overlap with `range`.
+    ///
+    /// Shared locked ranges are reported as their segments.
+    pub fn overlapping<'a>(
+        &'a self,
+        range: &'a Range<usize>,
+    ) -> impl Iterator<Item = Range<usize>> + 'a {
+        fn overlapping_in<'a, V>(
+            tree: &'a BTreeMap<usize, V>,
+            range: &'a Range<usize>,
+            end: fn(&V) -> usize,
+        ) -> impl Iterator<Item = Range<usize>> + 'a {
+            // The ranges in the tree don't overlap with each other.
+            // Therefore, the overlapping ranges are adjacent in the tree.
+            tree.range(..range.end)
+                .rev()
+                .map(move |(begin, v)| *begin..end(v))
+                .take_while(|locked| locked.end > range.start)
+        }
+        let range_ok = (!range.is_empty()).then_some(range);
+        range_ok.into_iter().flat_map(|range| {
+            overlapping_in(&self.tree, range, |end| *end)
+                .chain(overlapping_in(&self.upgradable, range, |end| *end))
+                .chain(overlapping_in(&self.shared, range, |(end, _)| *end))
+                .chain(overlapping_in(&self.exact, range, |(end, _)| *end))
+        })
+    }
+
+    /// Get a locked range of any kind that overlaps with `range`.
+    #[inline]
+    pub fn find_conflicting(&self, range: &Range<usize>) -> Option<Range<usize>> {
+        self.find_exclusive(range)
+            .or_else(|| self.find_shared(range))
+            .or_else(|| self.find_upgradable(range))
+            .or_else(|| self.find_exact(range))
+    }
+
+    /// Insert `range` into the set of locked ranges.
+    /// Returns an already locked range that overlaps with `range`, if `range` is contended.
+    #[inline]
+    pub fn insert(&mut self, range: &Range<usize>) -> Result<(), Range<usize>> {
+        // Check if this range overlaps with an existing one in the trees.
+        if let Some(locked) = self.find_conflicting(range) {
+            return Err(locked);
         }
-        // The range does not overlap with an existing one in the tree.
+        // The range does not overlap with an existing one in the trees.
         // Insert it into the tree.
         let old = self.tree.insert(range.start, range.end);
         debug_assert!(old.is_none());
-        true
+        Ok(())
+    }
+
+    /// Remove the entry `range` from `map`, if both its start and its end match.
+    /// Returns `false` and leaves `map` unchanged, if there is no such entry.
+    #[inline]
+    fn remove_exact(map: &mut BTreeMap<usize, usize>, range: &Range<usize>) -> bool {
+        match map.entry(range.start) {
+            Entry::Occupied(entry) if *entry.get() == range.end => {
+                entry.remove();
+                true
+            }
+            _ => false,
+        }
     }
 
     #[inline]
     pub fn remove(&mut self, range: &Range<usize>) {
-        let end = self.tree.remove(&range.start);
+        let removed = Self::remove_exact(&mut self.tree, range);
         // The caller must ensure that the removed range
         // has been passed successfully to insert() before.
-        debug_assert_eq!(end.unwrap(), range.end);
+        debug_assert!(removed, "Removed range {range:?} is not locked.");
+    }
+
+    /// Insert `range` as shared locked range.
+    /// Returns an exclusively locked range that overlaps with `range`, if `range` is contended.
+    pub fn insert_shared(&mut self, range: &Range<usize>) -> Result<(), Range<usize>> {
+        if let Some(locked) = self
+            .find_exclusive(range)
+            .or_else(|| self.find_exact(range))
+        {
+            return Err(locked);
+        }
+        self.split_shared(range.start);
+        self.split_shared(range.end);
+        // Add one reader to all segments within the range and fill the gaps.
+        let mut pos = range.start;
+        let mut gaps = Vec::new();
+        for (begin, (end, count)) in self.shared.range_mut(range.start..range.end) {
+            if *begin > pos {
+                gaps.push((pos, *begin));
+            }
+            *count += 1;
+            pos = *end;
+        }
+        if pos < range.end {
+            gaps.push((pos, range.end));
+        }
+        for (begin, end) in gaps {
+            self.shared.insert(begin, (end, 1));
+        }
+        self.merge_shared(range.start);
+        self.merge_shared(range.end);
+        Ok(())
+    }
+
+    /// Remove one reader of the shared locked `range`.
+    pub fn remove_shared(&mut self, range: &Range<usize>) {
+        self.split_shared(range.start);
+        self.split_shared(range.end);
+        let mut pos = range.start;
+        let mut unused = Vec::new();
+        for (begin, (end, count)) in self.shared.range_mut(range.start..range.end) {
+            // The caller must ensure that the removed range
+            // has been passed successfully to insert_shared() before.
+            debug_assert_eq!(*begin, pos);
+            *count -= 1;
+            if *count == 0 {
+                unused.push(*begin);
+            }
+            pos = *end;
+        }
+        debug_assert_eq!(pos, range.end);
+        for begin in unused {
+            self.shared.remove(&begin);
+        }
+        self.merge_shared(range.start);
+        self.merge_shared(range.end);
+    }
+
+    /// Convert the exclusively locked `range` into a shared locked range with one reader.
+    pub fn downgrade(&mut self, range: &Range<usize>) {
+        self.remove(range);
+        // There are no readers overlapping with the formerly exclusive range.
+        let res = self.insert_shared(range);
+        debug_assert!(res.is_ok());
+    }
+
+    /// Insert `range` as upgradable shared locked range.
+    /// Upgradable ranges conflict with exclusive and other upgradable ranges,
+    /// but not with shared ranges.
+    pub fn insert_upgradable(&mut self, range: &Range<usize>) -> Result<(), Range<usize>> {
+        if let Some(locked) = self.find_exclusive(range) {
+            return Err(locked);
+        }
+        if let Some(locked) = self
+            .find_upgradable(range)
+            .or_else(|| self.find_exact(range))
+        {
+            return Err(locked);
+        }
+        let old = self.upgradable.insert(range.start, range.end);
+        debug_assert!(old.is_none());
+        Ok(())
+    }
+
+    /// Remove the upgradable locked `range`.
+    pub fn remove_upgradable(&mut self, range: &Range<usize>) {
+        let removed = Self::remove_exact(&mut self.upgradable, range);
+        debug_assert!(removed, "Removed range {range:?} is not upgradable locked.");
+    }
+
+    /// Insert `range` as exact shared locked range.
+    /// Exact shared ranges only coexist with identical exact shared ranges.
+    /// They conflict with all other overlapping ranges.
+    /// Returns a locked range that overlaps with `range`, if `range` is contended.
+    pub fn insert_shared_exact(&mut self, range: &Range<usize>) -> Result<(), Range<usize>> {
+        if let Some((end, count)) = self.exact.get_mut(&range.start) {
+            if *end == range.end {
+                // The identical range can't overlap with any other range.
+                *count += 1;
+                return Ok(());
+            }
+        }
+        if let Some(locked) = self.find_conflicting(range) {
+            return Err(locked);
+        }
+        self.exact.insert(range.start, (range.end, 1));
+        Ok(())
+    }
+
+    /// Remove one reader of the exact shared locked `range`.
+    pub fn remove_shared_exact(&mut self, range: &Range<usize>) {
+        match self.exact.entry(range.start) {
+            Entry::Occupied(mut entry) if entry.get().0 == range.end => {
+                entry.get_mut().1 -= 1;
+                if entry.get().1 == 0 {
+                    entry.remove();
+                }
+            }
+            // The caller must ensure that the removed range
+            // has been passed successfully to insert_shared_exact() before.
+            _ => debug_assert!(false, "Removed range {range:?} is not exact shared locked."),
+        }
+    }
+
+    /// Check whether any shared locked segment overlaps with `range`.
+    #[inline]
+    pub fn is_shared(&self, range: &Range<usize>) -> bool {
+        self.find_shared(range).is_some()
+    }
+
+    /// Convert the upgradable locked `range` into an exclusively locked range.
+    /// The caller must ensure that no shared locked segment overlaps with `range`.
+    pub fn upgrade(&mut self, range: &Range<usize>) {
+        debug_assert!(!self.is_shared(range));
+        self.remove_upgradable(range);
+        // The upgradable range blocked all exclusive ranges.
+        let old = self.tree.insert(range.start, range.end);
+        debug_assert!(old.is_none());
+    }
+
+    /// Remove all locked ranges of any kind that overlap with `range`.
+    /// The overlapping ranges are removed completely,
+    /// including their parts outside of `range`.
+    pub fn remove_overlapping(&mut self, range: &Range<usize>) {
+        fn remove_from<V>(
+            tree: &mut BTreeMap<usize, V>,
+            range: &Range<usize>,
+            end: fn(&V) -> usize,
+        ) {
+            // The ranges in the tree don't overlap with each other.
+            // Therefore, the overlapping ranges are adjacent in the tree.
+            let overlapping: Vec<usize> = tree
+                .range(..range.end)
+                .rev()
+                .take_while(|(_, v)| end(v) > range.start)
+                .map(|(begin, _)| *begin)
+                .collect();
+            for begin in overlapping {
+                tree.remove(&begin);
+            }
+        }
+        if !range.is_empty() {
+            remove_from(&mut self.tree, range, |end| *end);
+            remove_from(&mut self.upgradable, range, |end| *end);
+            remove_from(&mut self.shared, range, |(end, _)| *end);
+            remove_from(&mut self.exact, range, |(end, _)| *end);
+        }
+    }
+
+    /// Split the shared segment containing `pos` into two segments at `pos`.
+    fn split_shared(&mut self, pos: usize) {
+        if let Some((&begin, &(end, count))) = self.shared.range(..pos).next_back() {
+            if end > pos {
+                self.shared.insert(begin, (pos, count));
+                self.shared.insert(pos, (end, count));
+            }
+        }
+    }
+
+    /// Merge the shared segment ending at `pos` with the segment starting at `pos`,
+    /// if both have the same reader count.
+    ///
+    /// All segments within an inserted or removed range change their count by the same amount.
+    /// Therefore, merging at the range boundaries keeps contiguous segments
+    /// with equal counts coalesced.
+    fn merge_shared(&mut self, pos: usize) {
+        let Some(&(end, count)) = self.shared.get(&pos) else {
+            return;
+        };
+        if let Some((_, (prev_end, prev_count))) = self.shared.range_mut(..pos).next_back() {
+            if *prev_end == pos && *prev_count == count {
+                *prev_end = end;
+                self.shared.remove(&pos);
+            }
+        }
     }
 }
 
@@ -91,32 +449,32 @@ mod tests {
     fn test_lockedranges() {
         let mut lr = LockedRanges::new();
         assert!(lr.is_empty());
-        assert!(lr.insert(&(10..20)));
-        assert!(lr.insert(&(30..40)));
-        assert!(lr.insert(&(100..200)));
-        assert!(lr.insert(&(1000..2000)));
-        assert!(lr.insert(&(10000..20000)));
+        assert!(lr.insert(&(10..20)).is_ok());
+        assert!(lr.insert(&(30..40)).is_ok());
+        assert!(lr.insert(&(100..200)).is_ok());
+        assert!(lr.insert(&(1000..2000)).is_ok());
+        assert!(lr.insert(&(10000..20000)).is_ok());
         assert!(!lr.is_empty());
 
-        assert!(!lr.insert(&(10..20)));
-        assert!(!lr.insert(&(30..40)));
-        assert!(!lr.insert(&(100..200)));
-        assert!(!lr.insert(&(1000..2000)));
-        assert!(!lr.insert(&(10000..20000)));
+        assert_eq!(lr.insert(&(10..20)), Err(10..20));
+        assert_eq!(lr.insert(&(30..40)), Err(30..40));
+        assert_eq!(lr.insert(&(100..200)), Err(100..200));
+        assert_eq!(lr.insert(&(1000..2000)), Err(1000..2000));
+        assert_eq!(lr.insert(&(10000..20000)), Err(10000..20000));
 
-        assert!(!lr.insert(&(9..11)));
-        assert!(!lr.insert(&(39..41)));
-        assert!(!lr.insert(&(100..101)));
-        assert!(!lr.insert(&(1999..2000)));
-        assert!(!lr.insert(&(15000..16000)));
+        assert_eq!(lr.insert(&(9..11)), Err(10..20));
+        assert_eq!(lr.insert(&(39..41)), Err(30..40));
+        assert_eq!(lr.insert(&(100..101)), Err(100..200));
+        assert_eq!(lr.insert(&(1999..2000)), Err(1000..2000));
+        assert_eq!(lr.insert(&(15000..16000)), Err(10000..20000));
 
         lr.remove(&(100..200));
 
-        assert!(!lr.insert(&(9..11)));
-        assert!(!lr.insert(&(39..41)));
-        assert!(lr.insert(&(100..101)));
-        assert!(!lr.insert(&(1999..2000)));
-        assert!(!lr.insert(&(15000..16000)));
+        assert_eq!(lr.insert(&(9..11)), Err(10..20));
+        assert_eq!(lr.insert(&(39..41)), Err(30..40));
+        assert!(lr.insert(&(100..101)).is_ok());
+        assert_eq!(lr.insert(&(1999..2000)), Err(1000..2000));
+        assert_eq!(lr.insert(&(15000..16000)), Err(10000..20000));
 
         lr.remove(&(10..20));
         lr.remove(&(30..40));
@@ -125,6 +483,198 @@ mod tests {
         lr.remove(&(10000..20000));
         assert!(lr.is_empty());
     }
+
+    #[test]
+    fn test_lockedranges_remove_exact() {
+        let mut map = BTreeMap::new();
+        map.insert(2, 5);
+        // A mismatching end must not remove the entry.
+        assert!(!LockedRanges::remove_exact(&mut map, &(2..4)));
+        assert!(!LockedRanges::remove_exact(&mut map, &(3..5)));
+        assert_eq!(map.get(&2), Some(&5));
+        assert!(LockedRanges::remove_exact(&mut map, &(2..5)));
+        assert!(map.is_empty());
+        assert!(!LockedRanges::remove_exact(&mut map, &(2..5)));
+    }
+
+    #[test]
+    fn test_lockedranges_overlapping() {
+        let mut lr = LockedRanges::new();
+        assert!(lr.insert(&(0..10)).is_ok());
+        assert!(lr.insert(&(10..20)).is_ok());
+        assert!(lr.insert(&(30..40)).is_ok());
+        assert!(lr.insert_shared(&(20..25)).is_ok());
+        assert!(lr.insert_shared(&(22..24)).is_ok());
+        assert!(lr.insert_upgradable(&(25..28)).is_ok());
+        let mut o: Vec<_> = lr.overlapping(&(5..26)).collect();
+        o.sort_by_key(|r| r.start);
+        assert_eq!(o, vec![0..10, 10..20, 20..22, 22..24, 24..25, 25..28]);
+        assert_eq!(lr.overlapping(&(28..30)).count(), 0);
+        assert_eq!(lr.overlapping(&(35..35)).count(), 0);
+    }
+
+    #[test]
+    fn test_lockedranges_remove_overlapping() {
+        let mut lr = LockedRanges::new();
+        assert!(lr.insert(&(0..10)).is_ok());
+        assert!(lr.insert(&(10..20)).is_ok());
+        assert!(lr.insert(&(30..40)).is_ok());
+        assert!(lr.insert_shared(&(20..25)).is_ok());
+        assert!(lr.insert_upgradable(&(25..28)).is_ok());
+        lr.remove_overlapping(&(15..15));
+        assert_eq!(lr.iter().count(), 5);
+        lr.remove_overlapping(&(15..27));
+        assert_eq!(lr.iter().collect::<Vec<_>>(), vec![0..10, 30..40]);
+        lr.remove_overlapping(&(0..100));
+        assert!(lr.is_empty());
+    }
+
+    #[test]
+    fn test_lockedranges_shared() {
+        let mut lr = LockedRanges::new();
+        assert!(lr.insert(&(10..20)).is_ok());
+        assert_eq!(lr.insert_shared(&(15..30)), Err(10..20));
+        assert!(lr.insert_shared(&(20..30)).is_ok());
+        assert!(lr.insert_shared(&(25..40)).is_ok());
+        assert!(lr.insert_shared(&(22..23)).is_ok());
+        assert_eq!(lr.insert(&(29..30)), Err(25..30));
+        assert_eq!(lr.insert(&(35..36)), Err(30..40));
+        assert!(lr.insert(&(40..50)).is_ok());
+        assert_eq!(lr.shared[&25], (30, 2));
+
+        lr.remove_shared(&(25..40));
+        assert!(lr.insert(&(30..40)).is_ok());
+        // 23..25 and 25..30 have been coalesced.
+        assert_eq!(lr.insert(&(29..30)), Err(23..30));
+        lr.remove_shared(&(20..30));
+        lr.remove_shared(&(22..23));
+        assert!(lr.shared.is_empty());
+
+        assert!(lr.insert(&(20..30)).is_ok());
+        lr.downgrade(&(20..30));
+        assert!(lr.insert_shared(&(20..30)).is_ok());
+        assert_eq!(lr.insert(&(20..21)), Err(20..30));
+        lr.remove_shared(&(20..30));
+        lr.remove_shared(&(20..30));
+
+        lr.remove(&(10..20));
+        lr.remove(&(30..40));
+        lr.remove(&(40..50));
+        assert!(lr.is_empty());
+    }
+
+    #[test]
+    fn test_lockedranges_shared_coalesce() {
+        let segments = |lr: &LockedRanges| {
+            lr.shared
+                .iter()
+                .map(|(begin, (end, count))| (*begin..*end, *count))
+                .collect::<Vec<_>>()
+        };
+        let mut lr = LockedRanges::new();
+
+        // Adjacent readers are coalesced into one segment.
+        for i in 0..100 {
+            assert!(lr.insert_shared(&(i..i + 1)).is_ok());
+        }
+        assert_eq!(segments(&lr), vec![(0..100, 1)]);
+
+        // Overlapping readers split the segment.
+        assert!(lr.insert_shared(&(10..20)).is_ok());
+        assert!(lr.insert_shared(&(15..30)).is_ok());
+        assert_eq!(
+            segments(&lr),
+            vec![
+                (0..10, 1),
+                (10..15, 2),
+                (15..20, 3),
+                (20..30, 2),
+                (30..100, 1)
+            ]
+        );
+
+        // Leaving readers split and coalesce again.
+        lr.remove_shared(&(15..16));
+        assert_eq!(
+            segments(&lr),
+            vec![
+                (0..10, 1),
+                (10..16, 2),
+                (16..20, 3),
+                (20..30, 2),
+                (30..100, 1)
+            ]
+        );
+        assert!(lr.insert_shared(&(15..16)).is_ok());
+        lr.remove_shared(&(10..20));
+        assert_eq!(segments(&lr), vec![(0..15, 1), (15..30, 2), (30..100, 1)]);
+        lr.remove_shared(&(15..30));
+        assert_eq!(segments(&lr), vec![(0..100, 1)]);
+
+        for i in 0..100 {
+            lr.remove_shared(&(i..i + 1));
+            if i < 99 {
+                assert_eq!(segments(&lr), vec![(i + 1..100, 1)]);
+            }
+        }
+        assert!(lr.is_empty());
+    }
+
+    #[test]
+    fn test_lockedranges_upgradable() {
+        let mut lr = LockedRanges::new();
+        assert!(lr.insert_upgradable(&(10..20)).is_ok());
+        assert_eq!(lr.insert_upgradable(&(15..25)), Err(10..20));
+        assert_eq!(lr.insert(&(19..25)), Err(10..20));
+        assert!(lr.insert_shared(&(15..25)).is_ok());
+        assert!(lr.is_shared(&(10..20)));
+        lr.remove_shared(&(15..25));
+        assert!(!lr.is_shared(&(10..20)));
+        lr.upgrade(&(10..20));
+        assert_eq!(lr.insert_shared(&(15..25)), Err(10..20));
+        lr.remove(&(10..20));
+        assert!(lr.is_empty());
+    }
+
+    #[test]
+    fn test_lockedranges_shared_exact() {
+        let mut lr = LockedRanges::new();
+        assert!(lr.insert_shared_exact(&(10..20)).is_ok());
+        assert!(lr.insert_shared_exact(&(10..20)).is_ok());
+        assert_eq!(lr.insert_shared_exact(&(10..21)), Err(10..20));
+        assert_eq!(lr.insert_shared_exact(&(15..16)), Err(10..20));
+        assert_eq!(lr.insert_shared(&(19..25)), Err(10..20));
+        assert_eq!(lr.insert_upgradable(&(5..11)), Err(10..20));
+        assert_eq!(lr.insert(&(0..30)), Err(10..20));
+        assert!(lr.insert_shared(&(20..30)).is_ok());
+        assert_eq!(lr.insert_shared_exact(&(20..30)), Err(20..30));
+        assert_eq!(lr.iter_sorted().collect::<Vec<_>>(), vec![10..20, 20..30]);
+        lr.remove_shared(&(20..30));
+        lr.remove_shared_exact(&(10..20));
+        assert_eq!(lr.insert(&(10..11)), Err(10..20));
+        lr.remove_shared_exact(&(10..20));
+        assert!(lr.is_empty());
+        assert!(lr.insert(&(10..11)).is_ok());
+    }
+
+    #[test]
+    fn test_lockedranges_iter_sorted() {
+        let mut lr = LockedRanges::new();
+        assert_eq!(lr.iter_sorted().next(), None);
+        assert!(lr.insert(&(30..40)).is_ok());
+        assert!(lr.insert_upgradable(&(20..25)).is_ok());
+        assert!(lr.insert_shared(&(0..10)).is_ok());
+        assert!(lr.insert_shared(&(5..15)).is_ok());
+        assert!(lr.insert(&(15..18)).is_ok());
+        assert!(lr.insert_upgradable(&(50..60)).is_ok());
+        assert!(lr.insert_shared(&(55..70)).is_ok());
+        let sorted: Vec<_> = lr.iter_sorted().collect();
+        assert_eq!(
+            sorted,
+            vec![0..5, 5..10, 10..15, 15..18, 20..25, 30..40, 50..60, 55..70]
+        );
+        assert_eq!(sorted.len(), lr.iter().count());
+    }
 }
 
 // vim: ts=4 sw=4 expandtab
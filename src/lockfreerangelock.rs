@@ -0,0 +1,194 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    epochranges::EpochRanges,
+    rangelock::VecRangeLock,
+    rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard},
+};
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::TryLockResult,
+};
+
+impl<T> VecRangeLock<T> {
+    /// Construct a new range lock that never takes a mutex.
+    ///
+    /// See [LockFreeVecRangeLock] for details.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new_lock_free(data: Vec<T>) -> LockFreeVecRangeLock<T> {
+        LockFreeVecRangeLock::new(data)
+    }
+}
+
+/// Lock-free multi-thread range lock for [std::vec::Vec].
+///
+/// This is an experimental variant of [VecRangeLock]
+/// that tracks the locked ranges in a lock-free list instead of mutex protected trees.
+/// Locking and unlocking never take a mutex.
+/// The memory of unlocked list nodes is reclaimed via [crossbeam_epoch].
+///
+/// It is constructed with [VecRangeLock::new_lock_free] or [LockFreeVecRangeLock::new].
+///
+/// Only exclusive locking is supported.
+/// Locking attempts may fail spuriously with [TryLockError::WouldBlock](std::sync::TryLockError::WouldBlock),
+/// if an overlapping range is being unlocked concurrently.
+///
+/// This type is only available with the `lock_free` feature.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::VecRangeLock;
+/// use std::thread;
+///
+/// let lock = VecRangeLock::new_lock_free(vec![1, 2, 3, 4]);
+/// thread::scope(|s| {
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+///         guard[0] = 10;
+///     });
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+///         guard[1] = 40;
+///     });
+/// });
+/// assert_eq!(lock.into_inner(), vec![10, 2, 3, 40]);
+/// ```
+#[derive(Debug)]
+pub struct LockFreeVecRangeLock<T> {
+    /// The lock-free set of locked ranges and the protected data.
+    raw: RawRangeLock<UnsafeCell<Vec<T>>, EpochRanges>,
+}
+
+impl<'a, T> LockFreeVecRangeLock<T> {
+    /// Construct a new [LockFreeVecRangeLock].
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new(data: Vec<T>) -> LockFreeVecRangeLock<T> {
+        LockFreeVecRangeLock {
+            raw: RawRangeLock::new(UnsafeCell::new(data)),
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec].
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Unwrap the [LockFreeVecRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        debug_assert!(self.raw.is_all_unlocked());
+        self.raw.data.into_inner()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// See [VecRangeLock::try_lock] for the return values.
+    /// Panics, if the range is out of bounds of the data.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<LockFreeVecRangeLockGuard<'a, T>> {
+        wrap_guard(self.raw.try_lock(range), LockFreeVecRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [LockFreeVecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+#[derive(Debug)]
+pub struct LockFreeVecRangeLockGuard<'a, T>(RawRangeLockGuard<'a, UnsafeCell<Vec<T>>, EpochRanges>);
+
+impl<'a, T> Deref for LockFreeVecRangeLockGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T> DerefMut for LockFreeVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            TryLockError,
+        },
+        thread,
+    };
+
+    #[test]
+    fn test_lock_free() {
+        let a = VecRangeLock::new_lock_free(vec![0_i32; 10]);
+        let _g0 = a.try_lock(0..2).unwrap();
+        let mut g1 = a.try_lock(4..6).unwrap();
+        assert!(matches!(a.try_lock(5..7), Err(TryLockError::WouldBlock)));
+        let g2 = a.try_lock(1..1).unwrap();
+        assert!(g2.is_empty());
+        g1[1] = 5;
+        drop((_g0, g1, g2));
+        assert!(a.raw.is_all_unlocked());
+        assert_eq!(a.into_inner()[5], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_lock_free_oob() {
+        let a = VecRangeLock::new_lock_free(vec![0_i32; 10]);
+        let _ = a.try_lock(9..11);
+    }
+
+    #[test]
+    fn test_thread_no_overlap() {
+        let a = LockFreeVecRangeLock::new(vec![0_usize; 64]);
+        let locked = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for t in 0..16 {
+                let (a, locked) = (&a, &locked);
+                s.spawn(move || {
+                    for i in 0..5000 {
+                        // Overlapping ranges. A lost update means a broken lock.
+                        let start = (t * 5 + i) % 62;
+                        let len = 1 + i % 3;
+                        if let Ok(mut g) = a.try_lock(start..start + len) {
+                            let v: Vec<usize> = g.to_vec();
+                            if i % 8 == 0 {
+                                thread::yield_now();
+                            }
+                            for (x, v) in g.iter_mut().zip(v) {
+                                *x = v + 1;
+                            }
+                            locked.fetch_add(len, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(a.raw.is_all_unlocked());
+        let sum: usize = a.into_inner().iter().sum();
+        assert_eq!(sum, locked.load(Ordering::Relaxed));
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,309 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    rangelock::{VecRangeLock, VecRangeLockGuard},
+    util::overlaps_any,
+};
+use std::{
+    ops::{Range, RangeBounds},
+    sync::{PoisonError, TryLockError, TryLockResult},
+};
+
+/// A set of ranges that are locked together in a canonical order.
+///
+/// The ranges are locked in the order of ascending range start.
+/// If all threads use a [LockSet] to lock multiple ranges,
+/// all threads acquire the ranges in the same order.
+/// Therefore, a cyclic wait between threads is impossible.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::{LockSet, VecRangeLock};
+///
+/// let lock = VecRangeLock::new(vec![0; 12]);
+///
+/// let mut set = LockSet::new();
+/// set.push(8..10);
+/// set.push(2..4);
+///
+/// let mut guards = set.lock(&lock).expect("Failed to lock the set");
+/// guards[0][0] = 2; // The guards are sorted by range start: data[2]
+/// guards[1][0] = 8; // data[8]
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct LockSet {
+    /// The ranges to lock.
+    ranges: Vec<Range<usize>>,
+}
+
+impl LockSet {
+    /// Construct a new empty [LockSet].
+    pub fn new() -> LockSet {
+        Default::default()
+    }
+
+    /// Add a range to the set.
+    pub fn push(&mut self, range: Range<usize>) -> &mut Self {
+        if range.start > range.end {
+            panic!("Invalid range. Start is bigger than end.");
+        }
+        self.ranges.push(range);
+        self
+    }
+
+    /// Get the number of ranges in the set.
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Check whether the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Try to lock all ranges of the set in `lock`.
+    ///
+    /// The ranges are locked in the order of ascending range start.
+    /// Panics, if ranges in the set overlap with each other.
+    ///
+    /// * On success: Returns the [VecRangeLockGuard]s of all ranges,
+    ///   sorted by ascending range start.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any range is contended.
+    ///   None of the ranges is locked in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    pub fn lock<'a, T>(
+        &self,
+        lock: &'a VecRangeLock<T>,
+    ) -> TryLockResult<Vec<VecRangeLockGuard<'a, T>>> {
+        let mut ranges = self.ranges.clone();
+        ranges.sort_by_key(|r| (r.start, r.end));
+        if overlaps_any(&ranges) {
+            panic!("LockSet: The ranges overlap with each other.");
+        }
+
+        let mut guards = Vec::with_capacity(ranges.len());
+        let mut poisoned = false;
+        for range in ranges {
+            match lock.try_lock_poisonable(range) {
+                Ok(guard) => guards.push(guard),
+                // Dropping the guards unlocks the already locked ranges.
+                Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+                Err(TryLockError::Poisoned(e)) => {
+                    guards.push(e.into_inner());
+                    poisoned = true;
+                }
+            }
+        }
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(guards)))
+        } else {
+            Ok(guards)
+        }
+    }
+}
+
+/// Split a locking result into the guard and the poison state.
+///
+/// Returns `None`, if the range is contended.
+fn split_poison<G>(result: TryLockResult<G>) -> Option<(G, bool)> {
+    match result {
+        Ok(guard) => Some((guard, false)),
+        Err(TryLockError::WouldBlock) => None,
+        Err(TryLockError::Poisoned(e)) => Some((e.into_inner(), true)),
+    }
+}
+
+/// Try to lock a range in each of two separate [VecRangeLock]s at once.
+///
+/// The two locks are acquired in the order of their addresses.
+/// If all threads use this function to lock ranges in both locks,
+/// all threads acquire them in the same order, regardless of the argument order.
+/// This is the two-lock analog of [VecRangeLock::try_lock_many].
+///
+/// * On success: Returns the [VecRangeLockGuard]s of `range_a` in `a` and `range_b` in `b`.
+/// * On failure: Returns [TryLockError::WouldBlock], if any of the ranges is contended.
+///   None of the ranges is locked in this case.
+///   Returns [TryLockError::Poisoned], if any of the locks is poisoned.
+///
+/// Panics, if a range is invalid or out of bounds.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::{try_lock_pair, VecRangeLock};
+///
+/// let a = VecRangeLock::new(vec![10; 4]);
+/// let b = VecRangeLock::new(vec![0_u64; 4]);
+/// let (mut ga, mut gb) = try_lock_pair(&a, 0..2, &b, 2..4).expect("Failed to lock");
+/// gb[0] = ga[0] as u64;
+/// ga[0] = 0;
+/// ```
+pub fn try_lock_pair<'a, T, U>(
+    a: &'a VecRangeLock<T>,
+    range_a: impl RangeBounds<usize>,
+    b: &'a VecRangeLock<U>,
+    range_b: impl RangeBounds<usize>,
+) -> TryLockResult<(VecRangeLockGuard<'a, T>, VecRangeLockGuard<'a, U>)> {
+    let a_first = (a as *const VecRangeLock<T>).addr() <= (b as *const VecRangeLock<U>).addr();
+    // Dropping a guard on contention unlocks the already locked range.
+    let (guard_a, guard_b, poisoned) = if a_first {
+        let (guard_a, poisoned_a) =
+            split_poison(a.try_lock_poisonable(range_a)).ok_or(TryLockError::WouldBlock)?;
+        let (guard_b, poisoned_b) =
+            split_poison(b.try_lock_poisonable(range_b)).ok_or(TryLockError::WouldBlock)?;
+        (guard_a, guard_b, poisoned_a || poisoned_b)
+    } else {
+        let (guard_b, poisoned_b) =
+            split_poison(b.try_lock_poisonable(range_b)).ok_or(TryLockError::WouldBlock)?;
+        let (guard_a, poisoned_a) =
+            split_poison(a.try_lock_poisonable(range_a)).ok_or(TryLockError::WouldBlock)?;
+        (guard_a, guard_b, poisoned_a || poisoned_b)
+    };
+    if poisoned {
+        Err(TryLockError::Poisoned(PoisonError::new((guard_a, guard_b))))
+    } else {
+        Ok((guard_a, guard_b))
+    }
+}
+
+impl FromIterator<Range<usize>> for LockSet {
+    fn from_iter<I: IntoIterator<Item = Range<usize>>>(iter: I) -> LockSet {
+        let mut set = LockSet::new();
+        for range in iter {
+            set.push(range);
+        }
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{sync::Barrier, thread};
+
+    #[test]
+    fn test_lockset() {
+        let a = VecRangeLock::new((0..12).collect::<Vec<i32>>());
+        let set: LockSet = [8..10, 2..4, 5..5].into_iter().collect();
+        assert_eq!(set.len(), 3);
+        {
+            let guards = set.lock(&a).unwrap();
+            assert_eq!(guards.len(), 3);
+            assert_eq!(guards[0][..], [2, 3]);
+            assert!(guards[1].is_empty());
+            assert_eq!(guards[2][..], [8, 9]);
+            assert!(a.try_lock(3..4).is_err());
+            assert!(a.try_lock(9..10).is_err());
+        }
+        {
+            let _g = a.try_lock(9..10).unwrap();
+            assert!(set.lock(&a).is_err());
+            // The first range has been unlocked again.
+            let _g = a.try_lock(2..4).unwrap();
+        }
+        assert!(LockSet::new().lock(&a).unwrap().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap with each other")]
+    fn test_lockset_overlap() {
+        let a = VecRangeLock::new((0..12).collect::<Vec<i32>>());
+        let set: LockSet = [8..10, 2..4, 3..5].into_iter().collect();
+        let _ = set.lock(&a);
+    }
+
+    #[test]
+    fn test_lockset_threads() {
+        let a = VecRangeLock::new(vec![0_u32; 12]);
+        let ba = Barrier::new(2);
+        thread::scope(|s| {
+            for set in [[2..4, 8..10], [8..10, 2..4]] {
+                let set: LockSet = set.into_iter().collect();
+                let (a, ba) = (&a, &ba);
+                s.spawn(move || {
+                    ba.wait();
+                    let mut done = 0;
+                    while done < 100 {
+                        if let Ok(mut guards) = set.lock(a) {
+                            guards[0][0] += 1;
+                            guards[1][0] += 1;
+                            done += 1;
+                        }
+                    }
+                });
+            }
+        });
+        let data = a.into_inner();
+        assert_eq!(data[2], 200);
+        assert_eq!(data[8], 200);
+    }
+
+    #[test]
+    fn test_try_lock_pair() {
+        let a = VecRangeLock::new((0..4).collect::<Vec<i32>>());
+        let b = VecRangeLock::new(vec![0_u64; 4]);
+        {
+            let (mut ga, mut gb) = try_lock_pair(&a, 1..3, &b, ..).unwrap();
+            gb[1] = ga[0] as u64;
+            ga[0] = 0;
+            assert!(a.try_lock(2..3).is_err());
+            assert!(b.try_lock(0..1).is_err());
+        }
+        {
+            let _g = b.try_lock(3..4).unwrap();
+            assert!(matches!(
+                try_lock_pair(&a, .., &b, 2..4),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert!(matches!(
+                try_lock_pair(&b, 2..4, &a, ..),
+                Err(TryLockError::WouldBlock)
+            ));
+            // The range in `a` has been unlocked again.
+            assert!(a.is_all_unlocked());
+        }
+        // Two disjoint ranges of the same lock.
+        let (g0, g1) = try_lock_pair(&a, 0..2, &a, 2..4).unwrap();
+        assert_eq!((g0[..].to_vec(), g1[..].to_vec()), (vec![0, 0], vec![2, 3]));
+        drop((g0, g1));
+        assert_eq!(b.into_inner(), vec![0, 1, 0, 0]);
+    }
+
+    #[test]
+    fn test_try_lock_pair_threads() {
+        let a = VecRangeLock::new(vec![0_u32; 4]);
+        let b = VecRangeLock::new(vec![0_u32; 4]);
+        thread::scope(|s| {
+            for swap in [false, true] {
+                let (a, b) = (&a, &b);
+                s.spawn(move || {
+                    let mut done = 0;
+                    while done < 100 {
+                        let res = if swap {
+                            try_lock_pair(b, 0..2, a, 0..2).map(|(gb, ga)| (ga, gb))
+                        } else {
+                            try_lock_pair(a, 0..2, b, 0..2)
+                        };
+                        if let Ok((mut ga, mut gb)) = res {
+                            ga[0] += 1;
+                            gb[0] += 1;
+                            done += 1;
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(a.into_inner()[0], 200);
+        assert_eq!(b.into_inner()[0], 200);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
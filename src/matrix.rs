@@ -0,0 +1,221 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    rangelock::{VecRangeLock, VecRangeLockGuard},
+    util::get_bounds,
+};
+use std::{
+    ops::{Deref, DerefMut, RangeBounds},
+    slice::{ChunksExact, ChunksExactMut},
+    sync::{PoisonError, TryLockError, TryLockResult},
+};
+
+/// Multi-thread row lock for a row-major matrix stored in a [std::vec::Vec].
+///
+/// Threads can lock individual rows or contiguous sets of rows.
+///
+/// Columns can't be locked, because the elements of a column
+/// are not contiguous in the row-major data.
+/// Therefore, this lock does not provide any column locking methods.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::MatrixRangeLock;
+/// use std::thread;
+///
+/// let lock = MatrixRangeLock::new(vec![1, 2, 3,
+///                                      4, 5, 6], 2, 3);
+/// thread::scope(|s| {
+///     for r in 0..2 {
+///         let lock = &lock;
+///         s.spawn(move || {
+///             let mut guard = lock.try_lock_row(r).expect("Failed to lock row");
+///             for x in guard.iter_mut() {
+///                 *x *= 10;
+///             }
+///         });
+///     }
+/// });
+/// assert_eq!(lock.into_inner(), vec![10, 20, 30, 40, 50, 60]);
+/// ```
+#[derive(Debug)]
+pub struct MatrixRangeLock<T> {
+    /// The underlying range lock.
+    lock: VecRangeLock<T>,
+    /// Number of rows.
+    rows: usize,
+    /// Number of columns (elements per row).
+    cols: usize,
+}
+
+impl<'a, T> MatrixRangeLock<T> {
+    /// Construct a new [MatrixRangeLock].
+    ///
+    /// * `data`: The row-major matrix data [Vec] to protect.
+    /// * `rows`: The number of rows.
+    /// * `cols`: The number of columns.
+    ///
+    /// The length of `data` must be `rows * cols`.
+    pub fn new(data: Vec<T>, rows: usize, cols: usize) -> MatrixRangeLock<T> {
+        let Some(len) = rows.checked_mul(cols) else {
+            panic!("MatrixRangeLock: Matrix size overflow.");
+        };
+        if data.len() != len {
+            panic!("MatrixRangeLock: Data length does not match rows * cols.");
+        }
+        MatrixRangeLock {
+            lock: VecRangeLock::new(data),
+            rows,
+            cols,
+        }
+    }
+
+    /// Get the number of rows.
+    #[inline]
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Get the number of columns.
+    #[inline]
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    /// Unwrap this [MatrixRangeLock] into the contained row-major data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        self.lock.into_inner()
+    }
+
+    /// Try to lock the row `row`.
+    ///
+    /// * On success: Returns a [MatrixRangeLockGuard] that can be used to access the locked row.
+    ///   Dereferencing [MatrixRangeLockGuard] yields a slice of the row.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the row is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    #[inline]
+    pub fn try_lock_row(&'a self, row: usize) -> TryLockResult<MatrixRangeLockGuard<'a, T>> {
+        if row >= self.rows {
+            panic!("MatrixRangeLock: Row is out of bounds.");
+        }
+        self.try_lock_rows(row..row + 1)
+    }
+
+    /// Try to lock the contiguous set of rows `rows`.
+    ///
+    /// Dereferencing the returned [MatrixRangeLockGuard] yields the row-major slice of all locked rows.
+    ///
+    /// See [MatrixRangeLock::try_lock_row] for the return values.
+    pub fn try_lock_rows(
+        &'a self,
+        rows: impl RangeBounds<usize>,
+    ) -> TryLockResult<MatrixRangeLockGuard<'a, T>> {
+        let (start, end) = get_bounds(&rows, self.rows);
+        if start > end || end > self.rows {
+            panic!("MatrixRangeLock: Rows are out of bounds.");
+        }
+        let cols = self.cols;
+        match self.lock.try_lock_poisonable(start * cols..end * cols) {
+            Ok(guard) => Ok(MatrixRangeLockGuard { guard, cols }),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(
+                MatrixRangeLockGuard {
+                    guard: e.into_inner(),
+                    cols,
+                },
+            ))),
+        }
+    }
+}
+
+/// Lock guard variable type for [MatrixRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// Dereferencing yields the row-major slice of the locked rows.
+#[derive(Debug)]
+pub struct MatrixRangeLockGuard<'a, T> {
+    /// The guard of the locked rows.
+    guard: VecRangeLockGuard<'a, T>,
+    /// Number of columns (elements per row).
+    cols: usize,
+}
+
+impl<'a, T> MatrixRangeLockGuard<'a, T> {
+    /// Get an iterator over the locked rows.
+    #[inline]
+    pub fn iter_rows(&self) -> ChunksExact<'_, T> {
+        self.guard.chunks_exact(self.cols.max(1))
+    }
+
+    /// Get a mutable iterator over the locked rows.
+    #[inline]
+    pub fn iter_rows_mut(&mut self) -> ChunksExactMut<'_, T> {
+        self.guard.chunks_exact_mut(self.cols.max(1))
+    }
+}
+
+impl<'a, T> Deref for MatrixRangeLockGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard
+    }
+}
+
+impl<'a, T> DerefMut for MatrixRangeLockGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matrix() {
+        let a = MatrixRangeLock::new((0..12).collect::<Vec<i32>>(), 4, 3);
+        assert_eq!(a.rows(), 4);
+        assert_eq!(a.cols(), 3);
+        {
+            let g0 = a.try_lock_row(1).unwrap();
+            assert_eq!(g0[..], [3, 4, 5]);
+            assert!(a.try_lock_rows(0..2).is_err());
+            let mut g1 = a.try_lock_rows(2..).unwrap();
+            assert_eq!(g1.iter_rows().count(), 2);
+            for row in g1.iter_rows_mut() {
+                row[0] = -1;
+            }
+            assert_eq!(g1[..], [-1, 7, 8, -1, 10, 11]);
+        }
+        let _g = a.try_lock_rows(..).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "Row is out of bounds")]
+    fn test_matrix_row_oob() {
+        let a = MatrixRangeLock::new(vec![0_i32; 6], 2, 3);
+        let _ = a.try_lock_row(2);
+    }
+
+    #[test]
+    #[should_panic(expected = "does not match")]
+    fn test_matrix_len() {
+        let _ = MatrixRangeLock::new(vec![0_i32; 7], 2, 3);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,224 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! The mutex that protects the sets of locked ranges.
+//!
+//! By default this is [std::sync::Mutex].
+//! With the `parking_lot` feature this is a `parking_lot::Mutex`
+//! behind the same interface. It is never poisoned.
+//!
+//! With the `spinlock` feature this is a minimal spinlock without dependencies.
+//! It busy-waits instead of blocking the thread in the operating system.
+//! It is never poisoned.
+//! A spinning thread may starve a preempted lower priority thread that holds the mutex
+//! (priority inversion). Only use it, if the threads are not preempted, e.g. pinned to cores.
+//! The `spinlock` feature takes precedence over the `parking_lot` feature.
+
+#[cfg(not(any(feature = "parking_lot", feature = "spinlock")))]
+pub(crate) use std::sync::Mutex;
+
+#[cfg(all(feature = "parking_lot", not(feature = "spinlock")))]
+pub(crate) use parking_lot_mutex::Mutex;
+
+#[cfg(feature = "spinlock")]
+pub(crate) use spin_mutex::Mutex;
+
+#[cfg(all(feature = "parking_lot", not(feature = "spinlock")))]
+mod parking_lot_mutex {
+    use std::sync::{LockResult, TryLockError, TryLockResult};
+
+    /// [parking_lot::Mutex] with the interface of [std::sync::Mutex].
+    #[derive(Debug)]
+    pub(crate) struct Mutex<T>(parking_lot::Mutex<T>);
+
+    impl<T> Mutex<T> {
+        #[inline]
+        pub(crate) const fn new(value: T) -> Self {
+            Self(parking_lot::Mutex::new(value))
+        }
+
+        /// Lock the mutex. This never fails.
+        #[inline]
+        pub(crate) fn lock(&self) -> LockResult<parking_lot::MutexGuard<'_, T>> {
+            Ok(self.0.lock())
+        }
+
+        /// Try to lock the mutex without blocking.
+        #[inline]
+        pub(crate) fn try_lock(&self) -> TryLockResult<parking_lot::MutexGuard<'_, T>> {
+            self.0.try_lock().ok_or(TryLockError::WouldBlock)
+        }
+
+        /// Get the protected value. This never fails.
+        #[inline]
+        pub(crate) fn get_mut(&mut self) -> LockResult<&mut T> {
+            Ok(self.0.get_mut())
+        }
+
+        /// A parking_lot mutex is never poisoned.
+        #[inline]
+        pub(crate) fn is_poisoned(&self) -> bool {
+            false
+        }
+
+        /// A parking_lot mutex is never poisoned.
+        #[inline]
+        pub(crate) fn clear_poison(&self) {}
+    }
+}
+
+#[cfg(feature = "spinlock")]
+mod spin_mutex {
+    use std::{
+        cell::UnsafeCell,
+        fmt, hint,
+        ops::{Deref, DerefMut},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            LockResult, TryLockError, TryLockResult,
+        },
+    };
+
+    /// Spinlock with the interface of [std::sync::Mutex].
+    pub(crate) struct Mutex<T> {
+        /// Whether the mutex is locked.
+        locked: AtomicBool,
+        /// The protected value.
+        value: UnsafeCell<T>,
+    }
+
+    // SAFETY:
+    // The value is only accessed via a MutexGuard or via get_mut().
+    // The locked flag ensures that at most one MutexGuard exists at a time.
+    unsafe impl<T: Send> Send for Mutex<T> {}
+    unsafe impl<T: Send> Sync for Mutex<T> {}
+
+    impl<T> Mutex<T> {
+        #[inline]
+        pub(crate) const fn new(value: T) -> Self {
+            Self {
+                locked: AtomicBool::new(false),
+                value: UnsafeCell::new(value),
+            }
+        }
+
+        /// Lock the mutex. Spins, until the mutex is available. This never fails.
+        #[inline]
+        pub(crate) fn lock(&self) -> LockResult<MutexGuard<'_, T>> {
+            loop {
+                if let Ok(guard) = self.try_lock() {
+                    return Ok(guard);
+                }
+                // Wait for the release without writing to the cache line.
+                while self.locked.load(Ordering::Relaxed) {
+                    hint::spin_loop();
+                }
+            }
+        }
+
+        /// Try to lock the mutex without spinning.
+        #[inline]
+        pub(crate) fn try_lock(&self) -> TryLockResult<MutexGuard<'_, T>> {
+            if self
+                .locked
+                .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                .is_ok()
+            {
+                Ok(MutexGuard { mutex: self })
+            } else {
+                Err(TryLockError::WouldBlock)
+            }
+        }
+
+        /// Get the protected value. This never fails.
+        #[inline]
+        pub(crate) fn get_mut(&mut self) -> LockResult<&mut T> {
+            Ok(self.value.get_mut())
+        }
+
+        /// A spinlock is never poisoned.
+        #[inline]
+        pub(crate) fn is_poisoned(&self) -> bool {
+            false
+        }
+
+        /// A spinlock is never poisoned.
+        #[inline]
+        pub(crate) fn clear_poison(&self) {}
+    }
+
+    impl<T: fmt::Debug> fmt::Debug for Mutex<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            let mut d = f.debug_struct("Mutex");
+            match self.try_lock() {
+                Ok(guard) => d.field("data", &&*guard),
+                Err(_) => d.field("data", &format_args!("<locked>")),
+            };
+            d.finish_non_exhaustive()
+        }
+    }
+
+    /// Guard of a locked [Mutex]. The mutex is unlocked on drop.
+    pub(crate) struct MutexGuard<'a, T> {
+        mutex: &'a Mutex<T>,
+    }
+
+    impl<T> Drop for MutexGuard<'_, T> {
+        #[inline]
+        fn drop(&mut self) {
+            self.mutex.locked.store(false, Ordering::Release);
+        }
+    }
+
+    impl<T> Deref for MutexGuard<'_, T> {
+        type Target = T;
+
+        #[inline]
+        fn deref(&self) -> &T {
+            // SAFETY: The guard holds the lock.
+            unsafe { &*self.mutex.value.get() }
+        }
+    }
+
+    impl<T> DerefMut for MutexGuard<'_, T> {
+        #[inline]
+        fn deref_mut(&mut self) -> &mut T {
+            // SAFETY: The guard holds the lock.
+            unsafe { &mut *self.mutex.value.get() }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use std::thread;
+
+        #[test]
+        fn test_spin_mutex() {
+            let m = Mutex::new(0_u32);
+            thread::scope(|s| {
+                for _ in 0..4 {
+                    s.spawn(|| {
+                        for _ in 0..1000 {
+                            *m.lock().unwrap() += 1;
+                        }
+                    });
+                }
+            });
+            let g = m.lock().unwrap();
+            assert!(matches!(m.try_lock(), Err(TryLockError::WouldBlock)));
+            assert_eq!(format!("{m:?}"), "Mutex { data: <locked>, .. }");
+            drop(g);
+            assert_eq!(format!("{m:?}"), "Mutex { data: 4000, .. }");
+            assert!(!m.is_poisoned());
+        }
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
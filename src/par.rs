@@ -0,0 +1,107 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::{VecRangeLock, VecRangeLockGuard};
+use rayon::prelude::*;
+use std::sync::TryLockError;
+
+impl<T> VecRangeLock<T>
+where
+    T: Send,
+{
+    /// Get a [rayon] parallel iterator over guards of disjoint chunks of the data.
+    ///
+    /// The chunks are `0..chunk_len`, `chunk_len..2*chunk_len` and so on.
+    /// The last chunk may be shorter than `chunk_len`.
+    /// `chunk_len` must be >0.
+    ///
+    /// Each chunk is locked, when it is produced by the iterator.
+    /// Panics, if a chunk is contended, because any of its elements is locked elsewhere.
+    ///
+    /// This method is only available with the `rayon` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use rayon::prelude::*;
+    ///
+    /// let lock = VecRangeLock::new(vec![1; 100]);
+    /// lock.par_chunks_mut(10).for_each(|mut guard| {
+    ///     for x in guard.iter_mut() {
+    ///         *x *= 2;
+    ///     }
+    /// });
+    /// assert_eq!(lock.into_inner(), vec![2; 100]);
+    /// ```
+    pub fn par_chunks_mut(
+        &self,
+        chunk_len: usize,
+    ) -> impl IndexedParallelIterator<Item = VecRangeLockGuard<'_, T>> {
+        if chunk_len == 0 {
+            panic!("chunk_len must not be 0.");
+        }
+        let data_len = self.data_len();
+        (0..data_len.div_ceil(chunk_len))
+            .into_par_iter()
+            .map(move |i| {
+                let start = i * chunk_len;
+                let end = start.saturating_add(chunk_len).min(data_len);
+                match self.try_lock_poisonable(start..end) {
+                    Ok(guard) => guard,
+                    Err(TryLockError::WouldBlock) => {
+                        panic!("VecRangeLock::par_chunks_mut: Chunk is contended.")
+                    }
+                    Err(TryLockError::Poisoned(_)) => {
+                        panic!("VecRangeLock::par_chunks_mut: Lock is poisoned.")
+                    }
+                }
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_par_chunks_mut() {
+        let a = VecRangeLock::with_shards((0..1001).collect::<Vec<u32>>(), 4);
+        let lens: Vec<usize> = a
+            .par_chunks_mut(100)
+            .map(|mut guard| {
+                for x in guard.iter_mut() {
+                    *x += 1;
+                }
+                guard.len()
+            })
+            .collect();
+        assert_eq!(lens.len(), 11);
+        assert!(lens[..10].iter().all(|&l| l == 100));
+        assert_eq!(lens[10], 1);
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), (1..1002).collect::<Vec<u32>>());
+    }
+
+    #[test]
+    fn test_par_chunks_mut_empty() {
+        let a: VecRangeLock<u32> = VecRangeLock::new(vec![]);
+        assert_eq!(a.par_chunks_mut(10).count(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk is contended")]
+    fn test_par_chunks_mut_contended() {
+        let a = VecRangeLock::new(vec![0_u32; 100]);
+        let _g = a.try_lock(55..56).unwrap();
+        a.par_chunks_mut(10).for_each(|_| ());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
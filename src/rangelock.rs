@@ -7,15 +7,73 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 //
 
-use crate::{lockedranges::LockedRanges, util::get_bounds};
+use crate::{
+    backoff::{Backoff, Decision, ExponentialBackoff},
+    builder::RangeLockBuilder,
+    deadlock::{self, Waiting},
+    error::{DetailedLockError, LockError, LockOrUserError, RangeError},
+    fastpath::{FastPath, SlowPath},
+    lockedranges::{overlaps, LockedRanges},
+    mutex::Mutex,
+    reprangelock::RepVecRangeLock,
+    stats::Stats,
+    util::overlaps_any,
+    waitqueue::WaitQueue,
+};
 use std::{
     cell::UnsafeCell,
-    hint::unreachable_unchecked,
+    fmt,
+    hash::{Hash, Hasher},
+    hint,
     marker::PhantomData,
-    ops::{Deref, DerefMut, Range, RangeBounds},
-    sync::{LockResult, Mutex, PoisonError, TryLockError, TryLockResult},
+    mem::{self, ManuallyDrop},
+    ops::{Bound, Deref, DerefMut, Index, IndexMut, Range, RangeBounds, RangeInclusive},
+    ptr::NonNull,
+    slice,
+    sync::{
+        atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+        Arc, LockResult, OnceLock, PoisonError, TryLockError, TryLockResult,
+    },
+    thread,
 };
 
+/// Insertion function of [LockedRanges].
+type InsertFn = fn(&mut LockedRanges, &Range<usize>) -> Result<(), Range<usize>>;
+
+/// Removal function of [LockedRanges].
+type RemoveFn = fn(&mut LockedRanges, &Range<usize>);
+
+/// Failure of [VecRangeLock::try_insert_all].
+#[derive(Debug)]
+enum InsertError {
+    /// A range is contended by the contained locked range.
+    Contended(Range<usize>),
+    /// The ranges have been inserted, but a shard mutex is poisoned.
+    Poisoned,
+}
+
+/// Publishes a changed length of the data [Vec] on drop.
+/// See [VecRangeLock::modify_unlocked].
+///
+/// Dropping it also on unwinding keeps the published length consistent with the data.
+struct LenUpdate<'a, T> {
+    lock: &'a VecRangeLock<T>,
+    /// Length of the data before the modification.
+    old_len: usize,
+}
+
+impl<T> Drop for LenUpdate<'_, T> {
+    fn drop(&mut self) {
+        // SAFETY: All shard mutexes are held, while this exists.
+        // Therefore, nobody else accesses the Vec.
+        let len = unsafe { (*self.lock.data.get()).len() };
+        if len != self.old_len {
+            self.lock.len.store(len, Ordering::Release);
+            self.lock.generation.fetch_add(1, Ordering::AcqRel);
+        }
+    }
+}
+
 /// General purpose multi-thread range lock for [std::vec::Vec].
 ///
 /// # Example
@@ -64,10 +122,32 @@ use std::{
 ///
 /// assert_eq!(data, vec![100, 11, 200, 13]);
 /// ```
-#[derive(Debug)]
 pub struct VecRangeLock<T> {
-    /// Set of the currently locked ranges.
-    ranges: Mutex<LockedRanges>,
+    /// Sets of the currently locked ranges.
+    /// Each shard tracks the locked ranges touching its region of the data.
+    ranges: Vec<Mutex<LockedRanges>>,
+    /// Uncontended fast path that bypasses the shard mutexes.
+    /// All accesses to the shard sets must happen while a SlowPath from enter_slow() exists.
+    fast: FastPath,
+    /// Length of the data region covered by one shard, in number of elements.
+    shard_stride: usize,
+    /// Threads blocking in lock().
+    waiters: WaitQueue,
+    /// Serve blocking threads in arrival order.
+    fair: bool,
+    /// Poison the data ranges of exclusive guards that are dropped during a panic.
+    poison_data: bool,
+    /// Length of the data, in number of elements.
+    /// The length may change, while the lock is shared. See modify_unlocked().
+    len: AtomicUsize,
+    /// Layout generation. Incremented on every change of the data length.
+    generation: AtomicU64,
+    /// Usage statistics.
+    stats: Stats,
+    /// Data ranges of exclusive guards that have been dropped during a panic.
+    poisoned_data: Mutex<Vec<Range<usize>>>,
+    /// Fast check whether `poisoned_data` is not empty.
+    data_poisoned: AtomicBool,
     /// The underlying data.
     data: UnsafeCell<Vec<T>>,
 }
@@ -84,291 +164,6311 @@ impl<'a, T> VecRangeLock<T> {
     ///
     /// * `data`: The data [Vec] to protect.
     pub fn new(data: Vec<T>) -> VecRangeLock<T> {
+        Self::with_shards(data, 1)
+    }
+
+    /// Construct a new [VecRangeLock] with a sharded set of locked ranges.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    /// * `shard_count`: The number of shards. Must be >0.
+    ///
+    /// The data is split into `shard_count` equally sized regions
+    /// and each region gets its own internal mutex.
+    /// Locking ranges in different regions does not contend on the same mutex.
+    /// This reduces contention, if many threads lock ranges simultaneously.
+    /// A range spanning multiple regions has to take the mutexes of all of these regions.
+    ///
+    /// [VecRangeLock::new] constructs a lock with one single shard.
+    pub fn with_shards(data: Vec<T>, shard_count: usize) -> VecRangeLock<T> {
+        if shard_count == 0 {
+            panic!("shard_count must not be 0.");
+        }
+        let shard_stride = data.len().div_ceil(shard_count).max(1);
+
+        let mut ranges = Vec::with_capacity(shard_count);
+        ranges.resize_with(shard_count, || Mutex::new(LockedRanges::new()));
+
         VecRangeLock {
-            ranges: Mutex::new(LockedRanges::new()),
+            ranges,
+            fast: FastPath::default(),
+            shard_stride,
+            waiters: WaitQueue::default(),
+            fair: false,
+            poison_data: true,
+            len: AtomicUsize::new(data.len()),
+            generation: AtomicU64::new(0),
+            stats: Stats::default(),
+            poisoned_data: Mutex::new(Vec::new()),
+            data_poisoned: AtomicBool::new(false),
             data: UnsafeCell::new(data),
         }
     }
 
-    /// Get the length (in number of elements) of the embedded [Vec].
+    /// Construct a new [VecRangeLock] with fair queueing of blocked threads.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    ///
+    /// Threads blocking in [VecRangeLock::lock] are served in arrival order.
+    /// A thread does not acquire a range,
+    /// while a thread that arrived earlier is waiting for an overlapping range.
+    /// This prevents starvation at the cost of throughput.
+    ///
+    /// [VecRangeLock::try_lock] does not queue and is not affected by fairness.
+    pub fn new_fair(data: Vec<T>) -> VecRangeLock<T> {
+        VecRangeLock {
+            fair: true,
+            ..Self::new(data)
+        }
+    }
+
+    /// Get a [RangeLockBuilder] to construct a [VecRangeLock] with a custom configuration.
+    #[inline]
+    pub fn builder() -> RangeLockBuilder {
+        RangeLockBuilder::new()
+    }
+
+    /// Construct a new [VecRangeLock] with the configuration of `builder`.
+    pub(crate) fn from_builder(builder: &RangeLockBuilder, data: Vec<T>) -> VecRangeLock<T> {
+        VecRangeLock {
+            fair: builder.fair,
+            poison_data: builder.poison_data,
+            ..Self::with_shards(data, builder.shards)
+        }
+    }
+
+    /// Get the current length (in number of elements) of the embedded [Vec].
+    ///
+    /// The length is updated by every operation that changes the length of the [Vec],
+    /// e.g. [VecRangeLock::try_append] or [VecRangeLock::try_truncate].
+    /// These only succeed, while no range is locked.
     #[inline]
     pub fn data_len(&self) -> usize {
-        // SAFETY: Multithreaded access is safe. len cannot change.
-        unsafe { (*self.data.get()).len() }
+        // The length only changes, while no range is locked.
+        // Locked ranges are checked against the length again
+        // after they have been inserted. See check_locked_range().
+        self.len.load(Ordering::Acquire)
+    }
+
+    /// Get the layout generation of the data.
+    ///
+    /// The generation is incremented on every change of the data length,
+    /// e.g. by [VecRangeLock::try_append] or [VecRangeLock::split_off].
+    /// A guard remembers the generation at the time it was locked.
+    /// See [VecRangeLockGuard::generation].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2]);
+    /// let generation = lock.try_lock(..).expect("Failed to lock").generation();
+    /// lock.try_append(&mut vec![3]).expect("Failed to append");
+    /// assert_ne!(lock.generation(), generation);
+    /// ```
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::Acquire)
     }
 
     /// Unwrap this [VecRangeLock] into the contained data.
     /// This method consumes self.
     #[inline]
     pub fn into_inner(self) -> Vec<T> {
-        debug_assert!(self.ranges.lock().unwrap().is_empty());
+        debug_assert!(self.is_all_unlocked());
         self.data.into_inner()
     }
 
-    /// Try to lock the given data `range`.
+    /// Convert this [VecRangeLock] into a [RepVecRangeLock] of the same data.
+    /// This method consumes self.
+    /// The data is moved without copying.
     ///
-    /// * On success: Returns a [VecRangeLockGuard] that can be used to access the locked region.
-    ///               Dereferencing [VecRangeLockGuard] yields a slice of the `data`.
-    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
-    ///               The locking attempt may be retried by the caller upon contention.
-    ///               Returns [TryLockError::Poisoned], if the lock is poisoned.
-    pub fn try_lock(
-        &'a self,
-        range: impl RangeBounds<usize>,
-    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
-        let data_len = self.data_len();
-        let (range_start, range_end) = get_bounds(&range, data_len);
-        if range_start >= data_len || range_end > data_len {
-            panic!("Range is out of bounds.");
-        }
-        if range_start > range_end {
-            panic!("Invalid range. Start is bigger than end.");
-        }
-        let range = range_start..range_end;
-
-        if range.is_empty() {
-            TryLockResult::Ok(VecRangeLockGuard::new(self, range))
-        } else if let LockResult::Ok(mut ranges) = self.ranges.lock() {
-            if ranges.insert(&range) {
-                TryLockResult::Ok(VecRangeLockGuard::new(self, range))
-            } else {
-                TryLockResult::Err(TryLockError::WouldBlock)
-            }
+    /// See [RepVecRangeLock::new] for the `slice_len` and `cycle_len` arguments.
+    ///
+    /// Returns `Err(self)`, if any range is still locked.
+    /// That can only happen, if a guard has been leaked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let lock = lock.into_rep(1, 2).expect("Ranges are locked");
+    /// assert_eq!(lock.try_lock(1).expect("Failed to lock offset 1")[1], [4]);
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn into_rep(
+        mut self,
+        slice_len: usize,
+        cycle_len: usize,
+    ) -> Result<RepVecRangeLock<T>, Self> {
+        let unlocked = !self.fast.is_fast()
+            && self.ranges.iter_mut().all(|shard| {
+                shard
+                    .get_mut()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .is_empty()
+            });
+        if unlocked {
+            Ok(RepVecRangeLock::new(
+                self.data.into_inner(),
+                slice_len,
+                cycle_len,
+            ))
         } else {
-            TryLockResult::Err(TryLockError::Poisoned(PoisonError::new(
-                VecRangeLockGuard::new(self, range),
-            )))
+            Err(self)
         }
     }
 
-    /// Unlock a range.
-    fn unlock(&self, range: &Range<usize>) {
-        if !range.is_empty() {
-            let mut ranges = self
-                .ranges
-                .lock()
-                .expect("VecRangeLock: Failed to take ranges mutex.");
-            ranges.remove(range);
+    /// Get a mutable slice of the whole data.
+    ///
+    /// The mutable borrow statically guarantees that no guards exist.
+    /// Therefore, no locking is required.
+    #[inline]
+    pub fn get_mut(&mut self) -> &mut [T] {
+        self.data.get_mut()
+    }
+
+    /// Split the lock into two independent locks at the element index `at`.
+    ///
+    /// `self` keeps the elements `0..at`.
+    /// The returned [VecRangeLock] contains the elements `at..data_len()`.
+    /// It has the same number of shards, the same fairness and the same poison policy as `self`.
+    ///
+    /// The mutable borrow statically guarantees that no guards exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if `at > data_len()`.
+    /// Panics, if ranges are still locked by leaked guards.
+    /// See [VecRangeLock::force_reset].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let mut head = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let tail = head.split_off(1);
+    /// let _g0 = head.try_lock(..).expect("Failed to lock head");
+    /// let _g1 = tail.try_lock(..).expect("Failed to lock tail");
+    /// assert_eq!(_g0[..], [1]);
+    /// assert_eq!(_g1[..], [2, 3, 4]);
+    /// ```
+    pub fn split_off(&mut self, at: usize) -> VecRangeLock<T> {
+        assert!(
+            self.is_all_unlocked(),
+            "VecRangeLock::split_off: Ranges are still locked."
+        );
+        let data = self.data.get_mut();
+        let tail = data.split_off(at);
+        *self.len.get_mut() = data.len();
+        *self.generation.get_mut() += 1;
+        // No range is locked. Therefore, the shard regions can be redistributed.
+        self.shard_stride = data.len().div_ceil(self.ranges.len()).max(1);
+        VecRangeLock {
+            fair: self.fair,
+            poison_data: self.poison_data,
+            ..Self::with_shards(tail, self.ranges.len())
         }
     }
 
-    /// Get an immutable slice to the specified range.
+    /// Move all elements of `other` to the end of the data.
+    /// This consumes `other`.
     ///
-    /// # SAFETY
+    /// This is the inverse of [VecRangeLock::split_off].
+    /// `self` keeps its number of shards, fairness and poison policy.
+    /// Poisoned data ranges of `other` stay poisoned at their new indices.
     ///
-    /// See get_mut_slice().
-    #[inline]
-    unsafe fn get_slice(&self, range: &Range<usize>) -> &[T] {
-        // SAFETY: We trust the slicing machinery of Vec to work correctly.
-        //         It must return the slice range that we requested.
-        //         Otherwise our non-overlap guarantees are gone.
-        &(*self.data.get())[range.clone()]
+    /// The mutable borrow and the ownership of `other` statically guarantee
+    /// that no guards of both locks exist.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if ranges of any of the locks are still locked by leaked guards.
+    /// See [VecRangeLock::force_reset].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let mut head = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let tail = head.split_off(1);
+    /// head.merge_into(tail);
+    /// assert_eq!(head.into_inner(), vec![1, 2, 3, 4]);
+    /// ```
+    pub fn merge_into(&mut self, mut other: VecRangeLock<T>) {
+        assert!(
+            self.is_all_unlocked() && other.is_all_unlocked(),
+            "VecRangeLock::merge_into: Ranges are still locked."
+        );
+        let data = self.data.get_mut();
+        let offset = data.len();
+        data.append(other.data.get_mut());
+        *self.len.get_mut() = data.len();
+        *self.generation.get_mut() += 1;
+        // No range is locked. Therefore, the shard regions can be redistributed.
+        self.shard_stride = data.len().div_ceil(self.ranges.len()).max(1);
+        let other_poisoned = other
+            .poisoned_data
+            .get_mut()
+            .unwrap_or_else(PoisonError::into_inner);
+        if !other_poisoned.is_empty() {
+            let poisoned = self
+                .poisoned_data
+                .get_mut()
+                .unwrap_or_else(PoisonError::into_inner);
+            poisoned.extend(
+                other_poisoned
+                    .drain(..)
+                    .map(|r| r.start + offset..r.end + offset),
+            );
+            *self.data_poisoned.get_mut() = true;
+        }
     }
 
-    /// Get a mutable slice to the specified range.
+    /// Move all elements of `other` to the end of the data.
     ///
-    /// # SAFETY
+    /// This can be called while the lock is shared.
+    /// It succeeds only, if no range is locked.
+    /// The shard regions are not redistributed.
+    /// The appended elements belong to the last shard.
     ///
-    /// The caller must ensure that:
-    /// * No overlapping slices must coexist on multiple threads.
-    /// * Immutable slices to overlapping ranges may only coexist on a single thread.
-    /// * Immutable and mutable slices must not coexist.
-    #[inline]
-    #[allow(clippy::mut_from_ref)] // Slices won't overlap. See SAFETY.
-    unsafe fn get_mut_slice(&self, range: &Range<usize>) -> &mut [T] {
-        let cptr = self.get_slice(range) as *const [T];
-        let mut_slice = (cptr as *mut [T]).as_mut();
-        // SAFETY: The pointer is never null, because it has been casted from a slice.
-        mut_slice.unwrap_or_else(|| unreachable_unchecked())
+    /// * On success: Returns `Ok(())`. `other` is empty afterwards.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any range is locked.
+    ///   `other` is not modified in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The elements have been appended in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2]);
+    /// lock.try_append(&mut vec![3, 4]).expect("Failed to append");
+    /// assert_eq!(lock.data_len(), 4);
+    ///
+    /// let guard = lock.try_lock(0..1).expect("Failed to lock 0..1");
+    /// assert!(lock.try_append(&mut vec![5]).is_err());
+    /// drop(guard);
+    /// ```
+    pub fn try_append(&self, other: &mut Vec<T>) -> TryLockResult<()> {
+        self.modify_unlocked(|data| data.append(other))
     }
-}
 
-/// Lock guard variable type for [VecRangeLock].
-///
-/// The [Deref] and [DerefMut] traits are implemented for this struct.
-/// See the documentation of [VecRangeLock] for usage examples of [VecRangeLockGuard].
-#[derive(Debug)]
-pub struct VecRangeLockGuard<'a, T> {
-    /// Reference to the underlying lock.
-    lock: &'a VecRangeLock<T>,
-    /// The locked range.
-    range: Range<usize>,
+    /// Append the elements of an iterator to the data.
+    ///
+    /// The iterator is collected, before the lock is taken.
+    /// See [VecRangeLock::try_append] for details.
+    /// The elements are dropped, if the append fails with [TryLockError::WouldBlock].
+    pub fn try_extend<I: IntoIterator<Item = T>>(&self, iter: I) -> TryLockResult<()> {
+        self.try_append(&mut iter.into_iter().collect())
+    }
 
-    /// Suppresses Send and Sync autotraits for VecRangeLockGuard.
-    _p: PhantomData<*mut T>,
-}
+    /// Shorten the data to `len` elements and drop the rest.
+    ///
+    /// This has no effect, if `len` is bigger than the current length.
+    /// This can be called while the lock is shared.
+    /// It succeeds only, if no range is locked.
+    /// See [VecRangeLock::try_append] for the returned errors.
+    ///
+    /// Later locking attempts check their range against the new length.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// lock.try_truncate(2).expect("Failed to truncate");
+    /// assert_eq!(lock.data_len(), 2);
+    /// assert!(lock.try_lock_checked(1..3).is_err());
+    /// ```
+    pub fn try_truncate(&self, len: usize) -> TryLockResult<()> {
+        self.modify_unlocked(|data| data.truncate(len))
+    }
 
-impl<'a, T> VecRangeLockGuard<'a, T> {
-    #[inline]
-    fn new(lock: &'a VecRangeLock<T>, range: Range<usize>) -> VecRangeLockGuard<'a, T> {
-        VecRangeLockGuard {
-            lock,
-            range,
-            _p: PhantomData,
+    /// Remove the elements of `range` from the data and return them.
+    ///
+    /// The elements after the range are shifted down.
+    /// This can be called while the lock is shared.
+    /// It succeeds only, if no range is locked.
+    /// See [VecRangeLock::try_append] for the returned errors.
+    ///
+    /// Later locking attempts check their range against the new length.
+    /// Panics, if the range is invalid or out of bounds.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5]);
+    /// let drained = lock.drain_region(1..3).expect("Failed to drain");
+    /// assert_eq!(drained, vec![2, 3]);
+    /// assert_eq!(lock.into_inner(), vec![1, 4, 5]);
+    /// ```
+    pub fn drain_region(&self, range: impl RangeBounds<usize>) -> TryLockResult<Vec<T>> {
+        let range = self.check_range(&range);
+        // The length may have been changed concurrently.
+        // Don't panic while holding the shard mutexes.
+        let drain = |data: &mut Vec<T>| {
+            (range.end <= data.len()).then(|| data.drain(range.clone()).collect::<Vec<T>>())
+        };
+        let unwrap =
+            |drained: Option<Vec<T>>| drained.unwrap_or_else(|| panic!("Range is out of bounds."));
+        match self.modify_unlocked(drain) {
+            Ok(drained) => Ok(unwrap(drained)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(
+                unwrap(e.into_inner()),
+            ))),
         }
     }
-}
 
-impl<'a, T> Drop for VecRangeLockGuard<'a, T> {
-    #[inline]
-    fn drop(&mut self) {
-        self.lock.unlock(&self.range);
+    /// Retain only the elements for which `f` returns `true` and drop the others.
+    ///
+    /// This is [Vec::retain] on the data.
+    /// The retained elements are shifted down.
+    /// This can be called while the lock is shared.
+    /// It succeeds only, if no range is locked.
+    /// See [VecRangeLock::try_append] for the returned errors.
+    /// `f` is not called, if any range is locked.
+    ///
+    /// Later locking attempts check their range against the new length.
+    ///
+    /// `f` is called while the internal mutexes of the lock are held.
+    /// `f` must not lock or unlock ranges of this lock or call any other method of it.
+    /// Doing so will deadlock.
+    /// If `f` panics, the elements removed so far stay removed
+    /// and the lock is poisoned. See [VecRangeLock::is_poisoned].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5]);
+    /// lock.retain(|x| x % 2 == 1).expect("Failed to retain");
+    /// assert_eq!(lock.into_inner(), vec![1, 3, 5]);
+    /// ```
+    pub fn retain(&self, f: impl FnMut(&T) -> bool) -> TryLockResult<()> {
+        self.modify_unlocked(|data| data.retain(f))
     }
-}
 
-impl<'a, T> Deref for VecRangeLockGuard<'a, T> {
-    type Target = [T];
+    /// Get the capacity (in number of elements) of the embedded [Vec].
+    pub fn capacity(&self) -> usize {
+        // The Vec is only modified while all shard mutexes are held.
+        let _locked_shards: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        // SAFETY: The Vec is not modified while we hold the shard mutexes.
+        unsafe { (*self.data.get()).capacity() }
+    }
 
-    #[inline]
-    fn deref(&self) -> &Self::Target {
-        // SAFETY: See deref_mut().
-        unsafe { self.lock.get_slice(&self.range) }
+    /// Get a raw pointer to the first element of the data.
+    ///
+    /// This is intended for handing the data to foreign code, e.g. via FFI.
+    /// Getting the pointer is safe, but dereferencing it is not.
+    /// The pointer must only be used to access elements within ranges
+    /// that the caller has locked and only while the guards exist.
+    ///
+    /// The data buffer may be reallocated by [VecRangeLock::try_append],
+    /// [VecRangeLock::try_extend] and [VecRangeLock::try_shrink_to_fit].
+    /// These only succeed, while no range is locked.
+    /// Therefore, the pointer stays valid, as long as the caller holds a guard.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+    /// let base = lock.data_ptr();
+    /// // SAFETY: The range 2..4 is locked by `guard`.
+    /// unsafe { *base.add(3) = 40 };
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 3, 40]);
+    /// ```
+    pub fn data_ptr(&self) -> *mut T {
+        // The Vec is only modified while all shard mutexes are held.
+        let _locked_shards: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        // SAFETY: The Vec is not modified while we hold the shard mutexes.
+        let data = unsafe { &*self.data.get() };
+        // The pointer carries the provenance of the data buffer. See get_ptr().
+        data.as_ptr().cast_mut()
     }
-}
 
-impl<'a, T> DerefMut for VecRangeLockGuard<'a, T> {
-    #[inline]
-    fn deref_mut(&mut self) -> &mut Self::Target {
-        // SAFETY:
-        // The lifetime of the slice is bounded by the lifetime of the guard.
-        // The lifetime of the guard is bounded by the lifetime of the range lock.
-        // The underlying data is owned by the range lock.
-        // Therefore the slice cannot outlive the data.
-        // The range lock ensures that no overlapping/conflicting guards
-        // can be constructed.
-        // The compiler ensures that the DerefMut result cannot be used,
-        // if there's also an immutable Deref result.
-        unsafe { self.lock.get_mut_slice(&self.range) }
+    /// Get a mutable slice of the data `range` without locking it.
+    ///
+    /// The locked ranges are neither checked nor modified.
+    /// This is intended for callers that guarantee exclusive access to the range
+    /// by other means, e.g. across an FFI boundary.
+    ///
+    /// Panics, if the range is invalid or out of bounds.
+    ///
+    /// # Safety
+    ///
+    /// While the returned slice exists:
+    /// * No guard of a range overlapping with `range` must exist or be created.
+    /// * No other slice returned by this method for an overlapping range must exist.
+    /// * The layout of the data must not be changed, e.g. by [VecRangeLock::try_append],
+    ///   [VecRangeLock::try_truncate] or [VecRangeLock::drain_region].
+    ///   These methods can't detect the unchecked access.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// // SAFETY: Nothing else accesses the range 1..3.
+    /// let slice = unsafe { lock.as_mut_slice_unchecked(1..3) };
+    /// slice[0] = 20;
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 3, 4]);
+    /// ```
+    #[allow(clippy::mut_from_ref)] // Slices won't overlap. See Safety.
+    pub unsafe fn as_mut_slice_unchecked(&self, range: impl RangeBounds<usize>) -> &mut [T] {
+        let range = self.check_range(&range);
+        // SAFETY: The caller guarantees exclusive access to the range.
+        unsafe { self.get_mut_slice(&range) }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::cell::RefCell;
-    use std::sync::{Arc, Barrier};
-    use std::thread;
+    /// Shrink the capacity of the embedded [Vec] as much as possible.
+    ///
+    /// This can be called while the lock is shared.
+    /// It succeeds only, if no range is locked.
+    /// See [VecRangeLock::try_append] for the returned errors.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let mut data = Vec::with_capacity(100);
+    /// data.extend([1, 2, 3]);
+    /// let lock = VecRangeLock::new(data);
+    /// assert!(lock.capacity() >= 100);
+    /// lock.try_shrink_to_fit().expect("Failed to shrink");
+    /// assert!(lock.capacity() < 100);
+    /// ```
+    pub fn try_shrink_to_fit(&self) -> TryLockResult<()> {
+        self.modify_unlocked(Vec::shrink_to_fit)
+    }
 
-    #[test]
-    fn test_base() {
-        {
-            // Range
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            {
-                let mut g = a.try_lock(2..4).unwrap();
-                assert!(!a.ranges.lock().unwrap().is_empty());
-                assert_eq!(g[0..2], [3, 4]);
-                g[1] = 10;
-                assert_eq!(g[0..2], [3, 10]);
-            }
-            assert!(a.ranges.lock().unwrap().is_empty());
-        }
-        {
-            // RangeInclusive
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            let g = a.try_lock(2..=4).unwrap();
-            assert_eq!(g[0..3], [3, 4, 5]);
-        }
-        {
-            // RangeTo
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            let g = a.try_lock(..4).unwrap();
-            assert_eq!(g[0..4], [1, 2, 3, 4]);
+    /// Modify the data [Vec], if no range is locked.
+    ///
+    /// The mutexes of all shards are held during the modification.
+    /// Therefore, no range can be locked concurrently.
+    /// The length is updated, even if `f` panics.
+    fn modify_unlocked<R>(&self, f: impl FnOnce(&mut Vec<T>) -> R) -> TryLockResult<R> {
+        let _slow = self.enter_slow();
+        self.modify_unlocked_slow(f)
+    }
+
+    /// See modify_unlocked().
+    fn modify_unlocked_slow<R>(&self, f: impl FnOnce(&mut Vec<T>) -> R) -> TryLockResult<R> {
+        let mut poisoned = false;
+        let locked_shards: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|shard| {
+                shard.lock().unwrap_or_else(|e| {
+                    poisoned = true;
+                    e.into_inner()
+                })
+            })
+            .collect();
+        if !locked_shards.iter().all(|ranges| ranges.is_empty()) {
+            return Err(TryLockError::WouldBlock);
         }
-        {
-            // RangeToInclusive
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            let g = a.try_lock(..=4).unwrap();
-            assert_eq!(g[0..5], [1, 2, 3, 4, 5]);
+        // SAFETY:
+        // No non-empty range is locked and no range can be locked,
+        // while we hold the shard mutexes.
+        // Guards of empty ranges don't access the Vec. See get_ptr().
+        // Therefore, nobody else accesses the Vec.
+        let data = unsafe { &mut *self.data.get() };
+        // Must be dropped before the shard mutexes are released.
+        let len_update = LenUpdate {
+            lock: self,
+            old_len: data.len(),
+        };
+        let ret = f(data);
+        drop(len_update);
+        drop(locked_shards);
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(ret)))
+        } else {
+            Ok(ret)
         }
-        {
-            // RangeFrom
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            let g = a.try_lock(2..).unwrap();
-            assert_eq!(g[0..4], [3, 4, 5, 6]);
+    }
+
+    /// Get the ranges of the data that are currently not locked.
+    ///
+    /// The returned ranges are sorted, non-empty and cover all unlocked elements
+    /// of `0..data_len()`.
+    ///
+    /// The result is a snapshot.
+    /// Other threads may lock any of the returned ranges,
+    /// before the caller locks it.
+    /// Therefore, locking a returned range may fail.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _g0 = lock.try_lock(2..4).expect("Failed to lock 2..4");
+    /// let _g1 = lock.try_read(6..7).expect("Failed to read-lock 6..7");
+    /// assert_eq!(lock.free_ranges(), vec![0..2, 4..6, 7..10]);
+    /// assert_eq!(lock.find_free(3), Some(7..10));
+    /// ```
+    pub fn free_ranges(&self) -> Vec<Range<usize>> {
+        // Take all shard mutexes in ascending order to get a consistent snapshot.
+        let slow = self.enter_slow();
+        let locked_shards: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        let mut locked: Vec<Range<usize>> = locked_shards
+            .iter()
+            .flat_map(|ranges| ranges.iter())
+            .collect();
+        drop(locked_shards);
+        drop(slow);
+        locked.sort_unstable_by_key(|r| r.start);
+
+        let mut free = Vec::new();
+        let mut pos = 0;
+        for range in locked {
+            if range.start > pos {
+                free.push(pos..range.start);
+            }
+            pos = pos.max(range.end);
         }
-        {
-            // RangeFull
-            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-            let g = a.try_lock(..).unwrap();
-            assert_eq!(g[0..6], [1, 2, 3, 4, 5, 6]);
+        let data_len = self.data_len();
+        if pos < data_len {
+            free.push(pos..data_len);
         }
+        free
     }
 
-    #[test]
-    fn test_empty_range() {
-        // Empty range doesn't cause conflicts.
-        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-        let g0 = a.try_lock(2..2).unwrap();
-        assert!(a.ranges.lock().unwrap().is_empty());
-        assert_eq!(g0[0..0], []);
-        let g1 = a.try_lock(2..2).unwrap();
-        assert!(a.ranges.lock().unwrap().is_empty());
+    /// Call `f` for each currently locked range in ascending order of the range start.
+    ///
+    /// This includes exclusive, shared, upgradable and exact shared ranges.
+    /// Shared ranges are reported as segments with a constant number of readers.
+    /// Ranges may overlap with each other, if they are locked for shared access.
+    /// Unlike [VecRangeLock::free_ranges] this does not allocate.
+    /// Therefore, it is suitable for frequent monitoring.
+    ///
+    /// The shards of the lock are visited one after the other.
+    /// The result is a snapshot per shard, but not necessarily a consistent snapshot
+    /// of the whole lock.
+    ///
+    /// `f` is called while an internal mutex of the lock is held.
+    /// `f` must not lock or unlock ranges of this lock or call any other method of it.
+    /// Doing so will deadlock.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _g0 = lock.try_lock(6..8).expect("Failed to lock 6..8");
+    /// let _g1 = lock.try_lock(1..3).expect("Failed to lock 1..3");
+    /// let mut locked = Vec::new();
+    /// lock.for_each_locked_range(|range| locked.push(range));
+    /// assert_eq!(locked, vec![1..3, 6..8]);
+    /// ```
+    pub fn for_each_locked_range(&self, mut f: impl FnMut(Range<usize>)) {
+        let _slow = self.enter_slow();
+        let last = self.ranges.len() - 1;
+        for (index, shard) in self.ranges.iter().enumerate() {
+            let ranges = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            // A range is stored in all shards that cover it.
+            // Report it only from the first one.
+            // The ranges of the following shards start later.
+            for range in ranges
+                .iter_sorted()
+                .filter(|range| (range.start / self.shard_stride).min(last) == index)
+            {
+                f(range);
+            }
+        }
+    }
+
+    /// Get the number of elements that are currently locked.
+    ///
+    /// Elements that are locked by multiple shared readers are counted once.
+    ///
+    /// The result is a momentary snapshot.
+    /// Other threads may lock or unlock ranges at any time.
+    /// Therefore, the result may be outdated as soon as it is returned.
+    /// See [VecRangeLock::for_each_locked_range] for the consistency across shards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _g0 = lock.try_read(0..4).expect("Failed to read-lock 0..4");
+    /// let _g1 = lock.try_read(2..5).expect("Failed to read-lock 2..5");
+    /// assert_eq!(lock.locked_element_count(), 5);
+    /// assert_eq!(lock.utilization(), 0.5);
+    /// ```
+    pub fn locked_element_count(&self) -> usize {
+        let mut count = 0;
+        let mut pos = 0;
+        // The ranges are reported in ascending order of their start.
+        self.for_each_locked_range(|range| {
+            count += range.end.saturating_sub(range.start.max(pos));
+            pos = pos.max(range.end);
+        });
+        count
+    }
+
+    /// Get the fraction of the data that is currently locked.
+    ///
+    /// This is [VecRangeLock::locked_element_count] divided by [VecRangeLock::data_len].
+    /// Returns 0.0, if the data is empty.
+    /// The result is a momentary snapshot. See [VecRangeLock::locked_element_count].
+    pub fn utilization(&self) -> f64 {
+        let data_len = self.data_len();
+        if data_len == 0 {
+            0.0
+        } else {
+            (self.locked_element_count() as f64 / data_len as f64).min(1.0)
+        }
+    }
+
+    /// Get the raw `(start, end)` entries of the currently locked ranges.
+    ///
+    /// This is intended for invariant checks in tests.
+    /// The entries are the ones reported by [VecRangeLock::for_each_locked_range].
+    /// They are collected, before the iterator is returned.
+    /// Therefore, the internal mutexes are not held while iterating.
+    ///
+    /// The format of the entries is not part of the stable API.
+    #[doc(hidden)]
+    pub fn debug_entries(&self) -> impl Iterator<Item = (usize, usize)> {
+        let mut entries = Vec::new();
+        self.for_each_locked_range(|range| entries.push((range.start, range.end)));
+        entries.into_iter()
+    }
+
+    /// Find the first unlocked range of at least `min_len` elements.
+    ///
+    /// Returns the whole unlocked gap, which may be longer than `min_len`.
+    /// See [VecRangeLock::free_ranges] for the snapshot semantics.
+    pub fn find_free(&self, min_len: usize) -> Option<Range<usize>> {
+        self.free_ranges()
+            .into_iter()
+            .find(|range| range.len() >= min_len)
+    }
+
+    /// Try to lock the lowest unlocked range of `len` elements.
+    ///
+    /// This searches the free gaps like [VecRangeLock::find_free]
+    /// and locks the first `len` elements of the lowest gap that is large enough.
+    /// The placement is deterministic for a given set of locked ranges.
+    ///
+    /// * On success: Returns the [VecRangeLockGuard] and the chosen range.
+    /// * On failure: Returns [TryLockError::WouldBlock], if no unlocked gap of `len` elements exists.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _g0 = lock.try_lock(1..4).expect("Failed to lock 1..4");
+    /// let (_g1, range) = lock.try_lock_biased_free(2).expect("No free gap");
+    /// assert_eq!(range, 4..6);
+    /// let (_g2, range) = lock.try_lock_biased_free(1).expect("No free gap");
+    /// assert_eq!(range, 0..1);
+    /// assert!(lock.try_lock_biased_free(5).is_err());
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn try_lock_biased_free(
+        &'a self,
+        len: usize,
+    ) -> TryLockResult<(VecRangeLockGuard<'a, T>, Range<usize>)> {
+        loop {
+            let Some(gap) = self.find_free(len) else {
+                if len == 0 {
+                    // An empty range never conflicts.
+                    return Ok((VecRangeLockGuard::new(self, 0..0), 0..0));
+                }
+                return Err(TryLockError::WouldBlock);
+            };
+            let range = gap.start..gap.start + len;
+            match self.try_lock_checked_range(range.clone()) {
+                Ok(guard) => return Ok((guard, range)),
+                Err(DetailedLockError::Poisoned(e)) => {
+                    return Err(TryLockError::Poisoned(PoisonError::new((
+                        e.into_inner(),
+                        range,
+                    ))));
+                }
+                // Another thread locked parts of the gap in the meantime.
+                // Search again with a fresh snapshot.
+                Err(DetailedLockError::Contended { .. }) => (),
+            }
+        }
+    }
+
+    /// Get the maximum number of simultaneously held ranges since the construction of the lock.
+    ///
+    /// All exclusive, shared and upgradable locks of non-empty ranges are counted.
+    /// A strided lock counts as one range per locked element.
+    /// The count may miss ranges that were being locked concurrently,
+    /// but it never overestimates.
+    ///
+    /// This method is available with the `stats` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let g0 = lock.try_lock(0..2).expect("Failed to lock 0..2");
+    /// let g1 = lock.try_read(4..6).expect("Failed to read-lock 4..6");
+    /// drop((g0, g1));
+    /// let _g2 = lock.try_lock(0..10).expect("Failed to lock 0..10");
+    /// assert_eq!(lock.max_concurrent_ranges(), 2);
+    /// ```
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub fn max_concurrent_ranges(&self) -> usize {
+        self.stats.max_held()
+    }
+
+    /// Check whether the lock is poisoned.
+    ///
+    /// The lock is poisoned, if a thread panicked while it was modifying the set of locked ranges.
+    /// Locking attempts on a poisoned lock return [TryLockError::Poisoned].
+    /// The guard contained in the error holds a valid lock of the range.
+    ///
+    /// The lock is also poisoned, if a thread panicked while holding an exclusive guard.
+    /// The data of the guard's range may be left in an inconsistent state.
+    /// Locking attempts of ranges overlapping with such a range return [TryLockError::Poisoned].
+    ///
+    /// With the `parking_lot` or `spinlock` feature, the internal mutexes are never poisoned.
+    /// The data is poisoned nevertheless.
+    ///
+    /// This allows choosing a recovery path before starting expensive work.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// if lock.is_poisoned() {
+    ///     lock.clear_poison();
+    /// }
+    /// let _guard = lock.try_lock(0..10).expect("Failed to lock");
+    /// ```
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.data_poisoned.load(Ordering::Acquire)
+            || self.ranges.iter().any(|shard| shard.is_poisoned())
+    }
+
+    /// Mark the data `range` as poisoned, if the current thread is panicking
+    /// and data poisoning is enabled. See [RangeLockBuilder::poison_data].
+    ///
+    /// This must be called before the range is unlocked.
+    #[inline]
+    pub(crate) fn poison_data_on_panic(&self, range: &Range<usize>) {
+        if self.poison_data && thread::panicking() && !range.is_empty() {
+            let mut poisoned = self
+                .poisoned_data
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner);
+            poisoned.push(range.clone());
+            self.data_poisoned.store(true, Ordering::Release);
+        }
+    }
+
+    /// Check whether the locked data `range` overlaps with a poisoned range.
+    #[inline]
+    fn is_data_poisoned(&self, range: &Range<usize>) -> bool {
+        // The poisoning thread marks the range before unlocking it.
+        // Locking the range afterwards synchronizes with the unlock.
+        if !self.data_poisoned.load(Ordering::Acquire) || range.is_empty() {
+            return false;
+        }
+        let poisoned = self
+            .poisoned_data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        poisoned.iter().any(|p| overlaps(p, range))
+    }
+
+    /// Forget all poisoned data ranges.
+    fn clear_data_poison(&self) {
+        let mut poisoned = self
+            .poisoned_data
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        poisoned.clear();
+        self.data_poisoned.store(false, Ordering::Release);
+    }
+
+    /// Clear the poisoned state of the lock.
+    ///
+    /// Afterwards, locking attempts succeed again.
+    ///
+    /// The set of locked ranges may be inconsistent after a panic.
+    /// Ranges that were being locked or unlocked during the panic
+    /// may be left behind as stale locked ranges.
+    /// Such stale ranges can't be locked anymore.
+    /// See [VecRangeLock::force_reset] to remove them.
+    #[inline]
+    pub fn clear_poison(&self) {
+        for shard in &self.ranges {
+            shard.clear_poison();
+        }
+        self.clear_data_poison();
+    }
+
+    /// Unlock all ranges and clear the poisoned state of the lock.
+    ///
+    /// The mutable borrow statically guarantees that no guards exist.
+    /// Therefore, all remaining locked ranges are stale.
+    /// They may be left behind by a panic or by leaked guards.
+    pub fn force_reset(&mut self) {
+        for shard in &mut self.ranges {
+            shard.clear_poison();
+            *shard.get_mut().unwrap_or_else(PoisonError::into_inner) = LockedRanges::new();
+        }
+        self.fast.reset();
+        self.clear_data_poison();
+        self.stats.reset_held();
+        deadlock::released_overlapping(self.id(), &(0..usize::MAX));
+    }
+
+    /// Check whether no range is locked in any of the shards.
+    pub(crate) fn is_all_unlocked(&self) -> bool {
+        let _slow = self.enter_slow();
+        self.ranges.iter().all(|shard| {
+            shard
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_empty()
+        })
+    }
+
+    /// Enter a slow path operation on the shard sets.
+    ///
+    /// A range locked via the fast path is inserted into the shard sets.
+    /// No shard mutex must be held by the caller.
+    /// The operation is left, when the returned [SlowPath] is dropped.
+    #[inline]
+    fn enter_slow(&self) -> SlowPath<'_> {
+        self.fast.enter(|range| {
+            for shard in self.shards(range) {
+                let res = shard
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .insert(range);
+                debug_assert!(res.is_ok());
+            }
+        })
+    }
+
+    /// Get the indices of the shards that cover the non-empty `range`.
+    #[inline]
+    fn shard_indices(&self, range: &Range<usize>) -> RangeInclusive<usize> {
+        debug_assert!(!range.is_empty());
+        let last = self.ranges.len() - 1;
+        let first = (range.start / self.shard_stride).min(last);
+        let end = ((range.end - 1) / self.shard_stride).min(last);
+        first..=end
+    }
+
+    /// Get the shards that cover the non-empty `range`.
+    #[inline]
+    fn shards(&self, range: &Range<usize>) -> &[Mutex<LockedRanges>] {
+        &self.ranges[self.shard_indices(range)]
+    }
+
+    /// Insert all `ranges` into the shards covering them.
+    ///
+    /// The `ranges` are inserted with `insert` and rolled back with `remove`.
+    /// Exclusive `ranges` must not overlap with each other.
+    /// Either all ranges are inserted or none of them.
+    fn try_insert_all(
+        &self,
+        ranges: &[Range<usize>],
+        insert: InsertFn,
+        remove: RemoveFn,
+    ) -> Result<(), InsertError> {
+        let slow = self.enter_slow();
+        // Take the mutexes of all shards covered by the ranges.
+        // The shards are always taken in ascending order.
+        // Therefore, multiple threads locking overlapping sets of shards can't deadlock.
+        let mut indices: Vec<usize> = ranges
+            .iter()
+            .filter(|r| !r.is_empty())
+            .flat_map(|r| self.shard_indices(r))
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+        let mut poisoned = false;
+        let mut locked_shards = Vec::with_capacity(indices.len());
+        for &idx in &indices {
+            locked_shards.push(self.ranges[idx].lock().unwrap_or_else(|e| {
+                poisoned = true;
+                e.into_inner()
+            }));
+        }
+
+        if !ranges.iter().all(|r| self.check_locked_range(r)) {
+            drop(locked_shards);
+            drop(slow);
+            panic!("Range is out of bounds.");
+        }
+
+        // Insert the ranges into all covered shards.
+        // A range that overlaps with one of our ranges touches at least one of these shards.
+        let mut inserted: Vec<(usize, &Range<usize>)> = Vec::new();
+        for range in ranges.iter().filter(|r| !r.is_empty()) {
+            for idx in self.shard_indices(range) {
+                // The index is in the sorted list of locked shards.
+                let pos = indices.binary_search(&idx).unwrap();
+                if let Err(conflicting) = insert(&mut locked_shards[pos], range) {
+                    // Roll back all previous insertions.
+                    for (pos, range) in inserted {
+                        remove(&mut locked_shards[pos], range);
+                    }
+                    drop(locked_shards);
+                    drop(slow);
+                    return Err(InsertError::Contended(conflicting));
+                }
+                inserted.push((pos, range));
+            }
+        }
+        drop(locked_shards);
+        let count = ranges.iter().filter(|r| !r.is_empty()).count();
+        slow.into_ranges(count);
+        self.stats.locked(count);
+        for range in ranges {
+            deadlock::held(self.id(), range);
+        }
+        if poisoned || ranges.iter().any(|r| self.is_data_poisoned(r)) {
+            Err(InsertError::Poisoned)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Get the queue of the blocking threads and tasks.
+    #[cfg(feature = "futures")]
+    pub(crate) fn waiters(&self) -> &WaitQueue {
+        &self.waiters
+    }
+
+    /// Check that the `range` is still within the data, after it has been checked by check_range().
+    ///
+    /// The data may have been truncated in the meantime.
+    /// This must be called while holding a shard mutex or after the range has been locked.
+    /// The length can't change then.
+    #[inline]
+    fn check_locked_range(&self, range: &Range<usize>) -> bool {
+        range.is_empty() || range.end <= self.data_len()
+    }
+
+    /// Convert `range` into a [Range] and check it against the data length.
+    /// Panics, if the range is invalid.
+    pub(crate) fn check_range(&self, range: &impl RangeBounds<usize>) -> Range<usize> {
+        match self.try_check_range(range) {
+            Ok(range) => range,
+            Err(RangeError::StartAfterEnd { .. }) => {
+                panic!("Invalid range. Start is bigger than end.")
+            }
+            Err(RangeError::OutOfBounds { .. }) => panic!("Range is out of bounds."),
+        }
+    }
+
+    /// Convert `range` into a [Range] and check it against the data length.
+    fn try_check_range(&self, range: &impl RangeBounds<usize>) -> Result<Range<usize>, RangeError> {
+        let data_len = self.data_len();
+        let out_of_bounds = RangeError::OutOfBounds { data_len };
+        let range_start = match range.start_bound() {
+            Bound::Included(x) => *x,
+            Bound::Excluded(x) => x.checked_add(1).ok_or(out_of_bounds.clone())?,
+            Bound::Unbounded => 0,
+        };
+        let range_end = match range.end_bound() {
+            Bound::Included(x) => x.checked_add(1).ok_or(out_of_bounds.clone())?,
+            Bound::Excluded(x) => *x,
+            Bound::Unbounded => data_len,
+        };
+        if range_start > range_end {
+            return Err(RangeError::StartAfterEnd {
+                start: range_start,
+                end: range_end,
+            });
+        }
+        // Empty ranges are valid anywhere in the data, including at its end.
+        if range_end > data_len {
+            return Err(out_of_bounds);
+        }
+        Ok(range_start..range_end)
+    }
+
+    /// Try to lock the given data `range` without panicking on an invalid `range`.
+    ///
+    /// This is useful, if the range comes from untrusted input.
+    ///
+    /// * Returns [RangeError], if `range` is invalid.
+    /// * Otherwise returns [TryLockError::WouldBlock] or [TryLockError::Poisoned] on failure.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{RangeError, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// assert!(matches!(
+    ///     lock.try_lock_checked(2..5),
+    ///     Err(RangeError::OutOfBounds { data_len: 4 })
+    /// ));
+    /// let guard = lock
+    ///     .try_lock_checked(2..4)
+    ///     .expect("Invalid range")
+    ///     .expect("Failed to lock 2..4");
+    /// assert_eq!(guard[..], [3, 4]);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn try_lock_checked(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> Result<TryLockResult<VecRangeLockGuard<'a, T>>, RangeError> {
+        let range = self.try_check_range(&range)?;
+        Ok(self.try_lock_poisonable(range))
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// * On success: Returns a [VecRangeLockGuard] that can be used to access the locked region.
+    ///   Dereferencing [VecRangeLockGuard] yields a slice of the `data`.
+    /// * On failure: Returns [LockError::WouldBlock], if the range is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [LockError::Poisoned], if the lock is poisoned.
+    ///   The range is not locked in that case.
+    ///   Returns [LockError::OutOfBounds], if the range exceeds the data.
+    ///   Returns [LockError::InvalidRange], if the start of `range` is bigger than its end.
+    ///
+    /// An empty `range` never conflicts with other ranges.
+    /// It may start anywhere in the data, including at its end.
+    ///
+    /// See [VecRangeLock::try_lock_detailed] for a variant that hands out the guard
+    /// of a poisoned lock.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{LockError, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _guard = lock.try_lock(2..5).expect("Failed to lock 2..5");
+    /// assert_eq!(
+    ///     lock.try_lock(4..8).unwrap_err(),
+    ///     LockError::WouldBlock { conflicting: Some(2..5) }
+    /// );
+    /// assert_eq!(lock.try_lock(8..11).unwrap_err(), LockError::OutOfBounds);
+    /// ```
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> Result<VecRangeLockGuard<'a, T>, LockError> {
+        match self.try_check_range(&range) {
+            Ok(range) => self.try_lock_detailed(range).map_err(Into::into),
+            Err(RangeError::OutOfBounds { .. }) => Err(LockError::OutOfBounds),
+            Err(RangeError::StartAfterEnd { .. }) => Err(LockError::InvalidRange),
+        }
+    }
+
+    /// Try to lock the given data `range` and discard the reason of a failure.
+    ///
+    /// Returns `None`, if [VecRangeLock::try_lock] fails for any reason,
+    /// i.e. contention, poisoning or an out of bounds or invalid range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// if let Some(mut guard) = lock.try_lock_opt(1..3) {
+    ///     guard[0] = 20;
+    /// }
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 3, 4]);
+    /// ```
+    #[inline]
+    pub fn try_lock_opt(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> Option<VecRangeLockGuard<'a, T>> {
+        self.try_lock(range).ok()
+    }
+
+    /// Try to lock the given data `range` with the [TryLockError] semantics.
+    ///
+    /// The guard of a poisoned lock is handed out in [TryLockError::Poisoned].
+    #[inline]
+    pub(crate) fn try_lock_poisonable(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        self.try_lock_detailed(range).map_err(Into::into)
+    }
+
+    /// Lock the given data `range`, which is known to be free.
+    ///
+    /// This is meant for phases where exclusive access to `range` is guaranteed by design.
+    /// In contrast to [VecRangeLock::try_lock] there is no [Result] to unwrap.
+    /// The range is unlocked, when the returned guard is dropped.
+    ///
+    /// Panics, if the range is contended or if the lock is poisoned.
+    /// Also panics, if the start of `range` is bigger than its end
+    /// or if the range exceeds the data.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// lock.get_unlocked_mut(1..3)[0] = 20;
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 3, 4]);
+    /// ```
+    pub fn get_unlocked_mut(&'a self, range: impl RangeBounds<usize>) -> VecRangeLockGuard<'a, T> {
+        let range = self.check_range(&range);
+        match self.try_lock_detailed(range.clone()) {
+            Ok(guard) => guard,
+            Err(DetailedLockError::Contended { conflicting }) => panic!(
+                "VecRangeLock::get_unlocked_mut: Range {range:?} is contended by {conflicting:?}."
+            ),
+            Err(DetailedLockError::Poisoned(_)) => {
+                panic!("VecRangeLock::get_unlocked_mut: Lock is poisoned.")
+            }
+        }
+    }
+
+    /// Try to lock the given data `range` and report details on failure.
+    ///
+    /// * On success: Returns a [VecRangeLockGuard] that can be used to access the locked region.
+    /// * On failure: Returns [DetailedLockError::Contended], if the range is contended.
+    ///   The error contains an already locked range that overlaps with `range`.
+    ///   Returns [DetailedLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// Panics, if the start of `range` is bigger than its end
+    /// or if the range exceeds the data.
+    /// See [VecRangeLock::try_lock] for a variant that reports these as [LockError].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{DetailedLockError, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _guard = lock.try_lock(2..5).expect("Failed to lock 2..5");
+    /// match lock.try_lock_detailed(4..8) {
+    ///     Err(DetailedLockError::Contended { conflicting }) => assert_eq!(conflicting, 2..5),
+    ///     _ => panic!("4..8 must be contended"),
+    /// };
+    /// ```
+    pub fn try_lock_detailed(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> Result<VecRangeLockGuard<'a, T>, DetailedLockError<VecRangeLockGuard<'a, T>>> {
+        let range = self.check_range(&range);
+        let result = self.try_lock_checked_range(range.clone());
+        #[cfg(feature = "tracing")]
+        match &result {
+            Ok(_) => tracing::trace!(range.start, range.end, "range locked"),
+            Err(DetailedLockError::Contended { conflicting }) => tracing::trace!(
+                range.start,
+                range.end,
+                conflicting.start,
+                conflicting.end,
+                "range contended"
+            ),
+            Err(DetailedLockError::Poisoned(_)) => {
+                tracing::trace!(range.start, range.end, "range locked, poisoned")
+            }
+        }
+        result
+    }
+
+    /// Try to lock the `range` that has already been checked by check_range().
+    #[allow(clippy::type_complexity)]
+    fn try_lock_checked_range(
+        &'a self,
+        range: Range<usize>,
+    ) -> Result<VecRangeLockGuard<'a, T>, DetailedLockError<VecRangeLockGuard<'a, T>>> {
+        if range.is_empty() {
+            return Ok(VecRangeLockGuard::new(self, range));
+        }
+
+        let shards = self.shards(&range);
+        if self.fast.try_lock(&range) {
+            // Uncontended fast path: No other range is locked.
+            self.stats.locked(1);
+            deadlock::held(self.id(), &range);
+            if !self.check_locked_range(&range) {
+                self.unlock(&range);
+                panic!("Range is out of bounds.");
+            }
+            let poisoned =
+                shards.iter().any(|shard| shard.is_poisoned()) || self.is_data_poisoned(&range);
+            let guard = VecRangeLockGuard::new(self, range);
+            return if poisoned {
+                Err(DetailedLockError::Poisoned(PoisonError::new(guard)))
+            } else {
+                Ok(guard)
+            };
+        }
+        if let [shard] = shards {
+            // Fast path: The range is covered by one single shard.
+            let slow = self.enter_slow();
+            let (mut ranges, poisoned) = match shard.lock() {
+                LockResult::Ok(ranges) => (ranges, false),
+                LockResult::Err(e) => (e.into_inner(), true),
+            };
+            if !self.check_locked_range(&range) {
+                drop(ranges);
+                drop(slow);
+                panic!("Range is out of bounds.");
+            }
+            if let Err(conflicting) = ranges.insert(&range) {
+                drop(ranges);
+                drop(slow);
+                return Err(DetailedLockError::Contended { conflicting });
+            }
+            drop(ranges);
+            slow.into_ranges(1);
+            self.stats.locked(1);
+            deadlock::held(self.id(), &range);
+            let poisoned = poisoned || self.is_data_poisoned(&range);
+            let guard = VecRangeLockGuard::new(self, range);
+            return if poisoned {
+                Err(DetailedLockError::Poisoned(PoisonError::new(guard)))
+            } else {
+                Ok(guard)
+            };
+        }
+
+        match self.try_insert_all(
+            slice::from_ref(&range),
+            LockedRanges::insert,
+            LockedRanges::remove,
+        ) {
+            Ok(()) => Ok(VecRangeLockGuard::new(self, range)),
+            Err(InsertError::Contended(conflicting)) => {
+                Err(DetailedLockError::Contended { conflicting })
+            }
+            Err(InsertError::Poisoned) => Err(DetailedLockError::Poisoned(PoisonError::new(
+                VecRangeLockGuard::new(self, range),
+            ))),
+        }
+    }
+
+    /// Try to lock the two data ranges `a` and `b` at once.
+    ///
+    /// Either both ranges are locked or none of them.
+    /// Panics, if `a` and `b` overlap with each other.
+    ///
+    /// * On success: Returns the tuple of the [VecRangeLockGuard]s of `a` and `b`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the ranges is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let (src, mut dst) = lock.try_lock2(0..2, 2..4).expect("Failed to lock");
+    /// dst.copy_from_slice(&src);
+    /// drop((src, dst));
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 1, 2]);
+    /// ```
+    #[allow(clippy::type_complexity)]
+    pub fn try_lock2(
+        &'a self,
+        a: impl RangeBounds<usize>,
+        b: impl RangeBounds<usize>,
+    ) -> TryLockResult<(VecRangeLockGuard<'a, T>, VecRangeLockGuard<'a, T>)> {
+        let a = self.check_range(&a);
+        let b = self.check_range(&b);
+        if !a.is_empty() && !b.is_empty() && overlaps(&a, &b) {
+            panic!("VecRangeLock::try_lock2: The ranges overlap with each other.");
+        }
+        let ranges = [a, b];
+        let result = self.try_insert_all(&ranges, LockedRanges::insert, LockedRanges::remove);
+        let [a, b] = ranges;
+        match result {
+            Ok(()) => Ok((
+                VecRangeLockGuard::new(self, a),
+                VecRangeLockGuard::new(self, b),
+            )),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => Err(TryLockError::Poisoned(PoisonError::new((
+                VecRangeLockGuard::new(self, a),
+                VecRangeLockGuard::new(self, b),
+            )))),
+        }
+    }
+
+    /// Try to lock all data `ranges` at once.
+    ///
+    /// Either all ranges are locked or none of them.
+    /// A failed attempt doesn't leave any of the ranges locked
+    /// and other threads never observe a partially locked set.
+    /// Panics, if any two of the `ranges` overlap with each other.
+    ///
+    /// * On success: Returns a [VecRangeLockMultiGuard] that holds all ranges.
+    ///   The ranges are accessed by their index in `ranges`.
+    ///   All ranges are unlocked together, when the guard is dropped.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the ranges is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5, 6]);
+    /// let mut guard = lock.try_lock_many(&[4..6, 0..2]).expect("Failed to lock");
+    /// assert_eq!(guard.get(0), [5, 6]);
+    /// guard.get_mut(1)[0] = 10;
+    /// assert!(lock.try_lock(1..5).is_err());
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![10, 2, 3, 4, 5, 6]);
+    /// ```
+    pub fn try_lock_many(
+        &'a self,
+        ranges: &[Range<usize>],
+    ) -> TryLockResult<VecRangeLockMultiGuard<'a, T>> {
+        let ranges: Vec<Range<usize>> = ranges.iter().map(|r| self.check_range(r)).collect();
+        let mut sorted = ranges.clone();
+        sorted.sort_by_key(|r| (r.start, r.end));
+        if overlaps_any(&sorted) {
+            panic!("VecRangeLock::try_lock_many: The ranges overlap with each other.");
+        }
+        let result = self.try_insert_all(&ranges, LockedRanges::insert, LockedRanges::remove);
+        let guard = |ranges| VecRangeLockMultiGuard {
+            lock: self,
+            ranges,
+            _p: PhantomData,
+        };
+        match result {
+            Ok(()) => Ok(guard(ranges)),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => {
+                Err(TryLockError::Poisoned(PoisonError::new(guard(ranges))))
+            }
+        }
+    }
+
+    /// Sort the data `ranges` in place and try to lock all of them at once.
+    ///
+    /// The `ranges` are sorted by their start.
+    /// This is the canonical order in which the ranges are acquired.
+    /// The sorted slice can be reused by the caller afterwards.
+    /// Panics, if any two of the `ranges` overlap with each other.
+    ///
+    /// See [VecRangeLock::try_lock_many] for the return values.
+    /// The ranges of the guard are accessed by their index in the sorted `ranges`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5, 6]);
+    /// let mut ranges = [4..6, 0..2];
+    /// let guard = lock.try_lock_sorted(&mut ranges).expect("Failed to lock");
+    /// assert_eq!(ranges, [0..2, 4..6]);
+    /// assert_eq!(guard.get(1), [5, 6]);
+    /// ```
+    pub fn try_lock_sorted(
+        &'a self,
+        ranges: &mut [Range<usize>],
+    ) -> TryLockResult<VecRangeLockMultiGuard<'a, T>> {
+        for range in ranges.iter_mut() {
+            *range = self.check_range(range);
+        }
+        ranges.sort_unstable_by_key(|r| (r.start, r.end));
+        if overlaps_any(ranges) {
+            panic!("VecRangeLock::try_lock_sorted: The ranges overlap with each other.");
+        }
+        let result = self.try_insert_all(ranges, LockedRanges::insert, LockedRanges::remove);
+        let guard = || VecRangeLockMultiGuard {
+            lock: self,
+            ranges: ranges.to_vec(),
+            _p: PhantomData,
+        };
+        match result {
+            Ok(()) => Ok(guard()),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => Err(TryLockError::Poisoned(PoisonError::new(guard()))),
+        }
+    }
+
+    /// Try to lock all data `ranges` at once or none of them.
+    ///
+    /// Same as [VecRangeLock::try_lock_many].
+    #[inline]
+    pub fn try_lock_all_or_none(
+        &'a self,
+        ranges: &[Range<usize>],
+    ) -> TryLockResult<VecRangeLockMultiGuard<'a, T>> {
+        self.try_lock_many(ranges)
+    }
+
+    /// Try to swap the contents of the two data ranges `a` and `b`.
+    ///
+    /// Both ranges are locked at once for the duration of the swap.
+    /// Therefore, no other thread can observe a partially swapped state.
+    ///
+    /// Panics, if `a` and `b` overlap with each other or if their lengths differ.
+    ///
+    /// * On success: Returns `Ok(())`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the ranges is contended.
+    ///   Nothing is swapped in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The ranges have been swapped in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5]);
+    /// lock.try_swap_ranges(0..2, 3..5).expect("Failed to swap");
+    /// assert_eq!(lock.into_inner(), vec![4, 5, 3, 1, 2]);
+    /// ```
+    pub fn try_swap_ranges(
+        &'a self,
+        a: impl RangeBounds<usize>,
+        b: impl RangeBounds<usize>,
+    ) -> TryLockResult<()> {
+        let a = self.check_range(&a);
+        let b = self.check_range(&b);
+        if a.len() != b.len() {
+            panic!("VecRangeLock::try_swap_ranges: The ranges have different lengths.");
+        }
+        match self.try_lock2(a, b) {
+            Ok((mut a, mut b)) => {
+                a.swap_with_slice(&mut b);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                let (mut a, mut b) = e.into_inner();
+                a.swap_with_slice(&mut b);
+                Err(TryLockError::Poisoned(PoisonError::new(())))
+            }
+        }
+    }
+
+    /// Split the data into `n` contiguous disjoint ranges that cover `0..data_len()`.
+    ///
+    /// The lengths of the ranges differ by at most one element.
+    /// The remainder of the division is distributed to the first ranges.
+    /// Panics, if `n` is 0.
+    ///
+    /// The ranges only depend on `n` and the data length.
+    /// Therefore, all workers computing them agree on the partition boundaries.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// assert_eq!(lock.partition_ranges(3), vec![0..4, 4..7, 7..10]);
+    /// ```
+    pub fn partition_ranges(&self, n: usize) -> Vec<Range<usize>> {
+        (0..n.max(1)).map(|i| self.partition_range(n, i)).collect()
+    }
+
+    /// Split the data into `n` contiguous disjoint ranges
+    /// with boundaries aligned to `align_bytes`.
+    ///
+    /// This avoids false sharing of cache lines between threads that write
+    /// to adjacent partitions. Use 64 bytes for the typical cache line size.
+    ///
+    /// The boundaries of [VecRangeLock::partition_ranges] are moved to the
+    /// nearest element whose address is a multiple of `align_bytes`.
+    /// Therefore, the lengths of the ranges may differ and some ranges may be empty.
+    /// If the element size does not allow aligned boundaries,
+    /// the boundaries are aligned relative to the start of the data.
+    /// Panics, if `n` or `align_bytes` is 0.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0_u32; 100]);
+    /// let ranges = lock.partition_ranges_aligned(3, 64);
+    /// assert_eq!(ranges.len(), 3);
+    /// assert_eq!(ranges[0].start, 0);
+    /// assert_eq!(ranges[2].end, 100);
+    /// for range in &ranges[1..] {
+    ///     let addr = lock.data_ptr().wrapping_add(range.start) as usize;
+    ///     assert_eq!(addr % 64, 0);
+    /// }
+    /// ```
+    pub fn partition_ranges_aligned(&self, n: usize, align_bytes: usize) -> Vec<Range<usize>> {
+        if align_bytes == 0 {
+            panic!("VecRangeLock::partition_ranges_aligned: align_bytes must not be 0.");
+        }
+        let data_len = self.data_len();
+        let elem_size = mem::size_of::<T>();
+        // Number of elements between two aligned boundaries.
+        let (unit, head) = if elem_size == 0 {
+            (1, 0)
+        } else {
+            let mut gcd = (align_bytes, elem_size);
+            while gcd.1 != 0 {
+                gcd = (gcd.1, gcd.0 % gcd.1);
+            }
+            let unit = align_bytes / gcd.0;
+            // Index of the first element with an aligned address.
+            let addr = self.data_ptr() as usize;
+            let head = (0..unit)
+                .find(|k| addr.wrapping_add(k * elem_size).is_multiple_of(align_bytes))
+                .unwrap_or(0);
+            (unit, head)
+        };
+        let mut ranges = self.partition_ranges(n);
+        let mut prev_end = 0;
+        for range in &mut ranges {
+            range.start = prev_end;
+            if range.end < data_len {
+                let snapped = if range.end < head {
+                    0
+                } else {
+                    let units = (range.end - head + unit / 2) / unit;
+                    head + units * unit
+                };
+                range.end = snapped.clamp(prev_end, data_len);
+            }
+            prev_end = range.end;
+        }
+        ranges
+    }
+
+    /// Get the `i`-th of the `n` ranges of [VecRangeLock::partition_ranges].
+    fn partition_range(&self, n: usize, i: usize) -> Range<usize> {
+        if n == 0 {
+            panic!("VecRangeLock::partition: n must not be 0.");
+        }
+        if i >= n {
+            panic!("VecRangeLock::partition: i must be smaller than n.");
+        }
+        let data_len = self.data_len();
+        let (part_len, rem) = (data_len / n, data_len % n);
+        let start = i * part_len + i.min(rem);
+        let end = start + part_len + usize::from(i < rem);
+        start..end
+    }
+
+    /// Try to lock the `i`-th of `n` disjoint partitions of the data.
+    ///
+    /// See [VecRangeLock::partition_ranges] for the partition boundaries.
+    /// Panics, if `n` is 0 or if `i` is not smaller than `n`.
+    ///
+    /// * On success: Returns the [VecRangeLockGuard] of the partition.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the partition is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// thread::scope(|s| {
+    ///     for i in 0..3 {
+    ///         let lock = &lock;
+    ///         s.spawn(move || {
+    ///             let mut part = lock.try_lock_partition(3, i).expect("Failed to lock");
+    ///             part.fill(i);
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(lock.into_inner(), vec![0, 0, 0, 0, 1, 1, 1, 2, 2, 2]);
+    /// ```
+    pub fn try_lock_partition(
+        &'a self,
+        n: usize,
+        i: usize,
+    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        let range = self.partition_range(n, i);
+        self.try_lock_poisonable(range)
+    }
+
+    /// Try to lock the whole data as `n` disjoint partitions at once.
+    ///
+    /// The partitions are contiguous and their lengths differ by at most one element.
+    /// See [VecRangeLock::partition_ranges] for the partition boundaries.
+    /// Either all partitions are locked or none of them.
+    /// Panics, if `n` is 0.
+    ///
+    /// * On success: Returns the [VecRangeLockGuard]s of the partitions in ascending order.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the partitions is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let parts = lock.partition(3).expect("Failed to partition");
+    /// assert_eq!(parts.iter().map(|p| p.len()).collect::<Vec<_>>(), vec![4, 3, 3]);
+    /// thread::scope(|s| {
+    ///     for (i, mut part) in parts.into_iter().enumerate() {
+    ///         s.spawn(move || part.fill(i));
+    ///     }
+    /// });
+    /// assert_eq!(lock.into_inner(), vec![0, 0, 0, 0, 1, 1, 1, 2, 2, 2]);
+    /// ```
+    pub fn partition(&'a self, n: usize) -> TryLockResult<Vec<VecRangeLockGuard<'a, T>>> {
+        let ranges = self.partition_ranges(n);
+        let result = self.try_insert_all(&ranges, LockedRanges::insert, LockedRanges::remove);
+        let into_guards = |ranges: Vec<Range<usize>>| {
+            ranges
+                .into_iter()
+                .map(|range| VecRangeLockGuard::new(self, range))
+                .collect()
+        };
+        match result {
+            Ok(()) => Ok(into_guards(ranges)),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => Err(TryLockError::Poisoned(PoisonError::new(
+                into_guards(ranges),
+            ))),
+        }
+    }
+
+    /// Get the one-element ranges of the strided elements.
+    ///
+    /// A step of 1 yields one single contiguous range.
+    #[allow(clippy::single_range_in_vec_init)]
+    fn strided_ranges(start: usize, step: usize, len: usize) -> Vec<Range<usize>> {
+        if step == 1 {
+            vec![start..start + len]
+        } else {
+            (0..len)
+                .map(|i| {
+                    let index = start + i * step;
+                    index..index + 1
+                })
+                .collect()
+        }
+    }
+
+    /// Try to lock every `step`-th element, beginning at `start`.
+    ///
+    /// This locks the elements `start`, `start + step`, `start + 2 * step`, etc.
+    /// up to the end of the data.
+    /// Strided locks with the same `step` and different `start` don't conflict.
+    /// A contiguous range that contains any of the elements conflicts.
+    ///
+    /// Panics, if `step` is 0 or if `start` is bigger than the data length.
+    ///
+    /// * On success: Returns a [VecRangeLockStridedGuard] that can be used to access the elements.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the elements is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 7]);
+    /// let mut even = lock.try_lock_strided(0, 2).expect("Failed to lock even");
+    /// let mut odd = lock.try_lock_strided(1, 2).expect("Failed to lock odd");
+    /// assert!(lock.try_lock(2..3).is_err());
+    /// thread::scope(|s| {
+    ///     s.spawn(move || (0..even.len()).for_each(|i| even[i] = 1));
+    ///     s.spawn(move || (0..odd.len()).for_each(|i| odd[i] = 2));
+    /// });
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 1, 2, 1, 2, 1]);
+    /// ```
+    pub fn try_lock_strided(
+        &'a self,
+        start: usize,
+        step: usize,
+    ) -> TryLockResult<VecRangeLockStridedGuard<'a, T>> {
+        if step == 0 {
+            panic!("step must not be 0.");
+        }
+        let data_len = self.data_len();
+        if start > data_len {
+            panic!("Range is out of bounds.");
+        }
+        let len = (data_len - start).div_ceil(step);
+        let new_guard = || VecRangeLockStridedGuard {
+            lock: self,
+            start,
+            step,
+            len,
+            _p: PhantomData,
+        };
+        let ranges = Self::strided_ranges(start, step, len);
+        match self.try_insert_all(&ranges, LockedRanges::insert, LockedRanges::remove) {
+            Ok(()) => Ok(new_guard()),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => {
+                Err(TryLockError::Poisoned(PoisonError::new(new_guard())))
+            }
+        }
+    }
+
+    /// Try to lock the given data `range` and run the closure `f` on the locked slice.
+    ///
+    /// The range is unlocked, before this method returns.
+    /// It is also unlocked, if `f` panics.
+    ///
+    /// * On success: Returns the return value of `f`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   `f` is not called in this case.
+    ///   Returns [TryLockError::Poisoned] with the return value of `f`, if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let sum = lock
+    ///     .with_locked(1..3, |slice| {
+    ///         slice[0] = 20;
+    ///         slice.iter().sum::<i32>()
+    ///     })
+    ///     .expect("Failed to lock 1..3");
+    /// assert_eq!(sum, 23);
+    /// ```
+    pub fn with_locked<R>(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        f: impl FnOnce(&mut [T]) -> R,
+    ) -> TryLockResult<R> {
+        match self.try_lock_poisonable(range) {
+            Ok(mut guard) => Ok(f(&mut guard)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                let mut guard = e.into_inner();
+                Err(TryLockError::Poisoned(PoisonError::new(f(&mut guard))))
+            }
+        }
+    }
+
+    /// Try to lock the given data `range` and run the fallible closure `f` on the locked slice.
+    ///
+    /// This is a variant of [VecRangeLock::with_locked] that composes with the `?` operator.
+    /// The range is unlocked, before this method returns.
+    ///
+    /// * On success: Returns the `Ok` value of `f`.
+    /// * On failure: Returns [LockOrUserError::WouldBlock], if the range is contended.
+    ///   Returns [LockOrUserError::Poisoned], if the lock is poisoned.
+    ///   `f` is not called in these cases.
+    ///   Returns [LockOrUserError::User], if `f` returned an error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{LockOrUserError, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let res: Result<(), LockOrUserError<&str>> = lock.try_with_locked(0..2, |slice| {
+    ///     if slice[0] == 1 {
+    ///         return Err("unexpected value");
+    ///     }
+    ///     Ok(())
+    /// });
+    /// assert_eq!(res, Err(LockOrUserError::User("unexpected value")));
+    /// ```
+    pub fn try_with_locked<R, E>(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        f: impl FnOnce(&mut [T]) -> Result<R, E>,
+    ) -> Result<R, LockOrUserError<E>> {
+        let res = match self.try_lock_poisonable(range) {
+            Ok(mut guard) => f(&mut guard),
+            Err(TryLockError::WouldBlock) => return Err(LockOrUserError::WouldBlock),
+            Err(TryLockError::Poisoned(_)) => return Err(LockOrUserError::Poisoned),
+        };
+        // The guard has been dropped here.
+        res.map_err(LockOrUserError::User)
+    }
+
+    /// Copy `src` into the data `range`.
+    ///
+    /// The range is locked only for the duration of the copy.
+    /// Panics, if the length of `src` differs from the length of `range`.
+    ///
+    /// * On success: Returns `Ok(())` after the copy.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   The data is not modified in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The data has been copied in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// lock.write_range_from(1..3, &[20, 30]).expect("Failed to write 1..3");
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 30, 4]);
+    /// ```
+    pub fn write_range_from(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        src: &[T],
+    ) -> TryLockResult<()>
+    where
+        T: Copy,
+    {
+        let range = self.check_range(&range);
+        if range.len() != src.len() {
+            panic!("VecRangeLock::write_range_from: Source length does not match.");
+        }
+        match self.try_lock_poisonable(range) {
+            Ok(mut guard) => {
+                guard.copy_from_slice(src);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                e.into_inner().copy_from_slice(src);
+                Err(TryLockError::Poisoned(PoisonError::new(())))
+            }
+        }
+    }
+
+    /// Lock the given data `range`.
+    ///
+    /// If the range is contended, this blocks the calling thread
+    /// until the range has been unlocked by its holders.
+    /// Locking a range that overlaps with a range held by the calling thread deadlocks.
+    ///
+    /// * On success: Returns a [VecRangeLockGuard] that can be used to access the locked region.
+    /// * On failure: Returns a [PoisonError], if the lock is poisoned.
+    ///   The guard contained in the error holds a valid lock of the range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 4]);
+    /// thread::scope(|s| {
+    ///     for _ in 0..4 {
+    ///         s.spawn(|| {
+    ///             let mut guard = lock.lock(1..3).expect("Lock is poisoned");
+    ///             guard[0] += 1;
+    ///         });
+    ///     }
+    /// });
+    /// assert_eq!(lock.into_inner(), vec![0, 4, 0, 0]);
+    /// ```
+    #[inline]
+    pub fn lock(&'a self, range: impl RangeBounds<usize>) -> LockResult<VecRangeLockGuard<'a, T>> {
+        self.lock_with_backoff(range, ExponentialBackoff::default())
+    }
+
+    /// Lock the given data `range` with the `backoff` strategy on contention.
+    ///
+    /// The `backoff` decides whether a contended locking attempt
+    /// is retried by spinning or whether the thread is parked.
+    /// [VecRangeLock::lock] uses the [ExponentialBackoff] strategy.
+    ///
+    /// Locks with fair queueing (see [VecRangeLock::new_fair]) never spin.
+    /// They always park the thread on contention.
+    ///
+    /// See [VecRangeLock::lock] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{backoff::SpinBackoff, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 4]);
+    /// let guard = lock.lock_with_backoff(1..3, SpinBackoff).expect("Lock is poisoned");
+    /// assert_eq!(guard.len(), 2);
+    /// ```
+    pub fn lock_with_backoff(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        mut backoff: impl Backoff,
+    ) -> LockResult<VecRangeLockGuard<'a, T>> {
+        let range = self.check_range(&range);
+        let try_acquire = || match self.try_lock_poisonable(range.clone()) {
+            Ok(guard) => Some(Ok(guard)),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(e)) => Some(Err(e)),
+        };
+        if !self.fair {
+            // Fast path: Try to lock without queueing.
+            loop {
+                if let Some(result) = try_acquire() {
+                    return result;
+                }
+                if !backoff.spin() {
+                    break;
+                }
+            }
+        }
+        let _waiting = Waiting::new(self.id(), &range);
+        self.waiters.wait(&range, self.fair, 0, try_acquire)
+    }
+
+    /// Lock the given data `range` by spinning. The thread is never parked.
+    ///
+    /// Between two locking attempts, the thread spins with exponentially growing pauses.
+    /// The pause is capped at a few microseconds.
+    ///
+    /// This is only appropriate for ranges that are held very briefly.
+    /// Spinning on ranges held for a long time wastes CPU time
+    /// and may slow down the holder of the range.
+    /// Use [VecRangeLock::lock] for long critical sections.
+    ///
+    /// Fair queueing (see [VecRangeLock::new_fair]) is bypassed.
+    ///
+    /// See [VecRangeLock::lock] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 4]);
+    /// let mut guard = lock.lock_spin(1..3).expect("Lock is poisoned");
+    /// guard[0] = 1;
+    /// ```
+    pub fn lock_spin(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> LockResult<VecRangeLockGuard<'a, T>> {
+        /// Maximum number of spin loop iterations between two locking attempts.
+        const MAX_SPINS: u32 = 1 << 10;
+
+        let range = self.check_range(&range);
+        let mut spins = 1;
+        let mut waiting = None;
+        loop {
+            match self.try_lock_poisonable(range.clone()) {
+                Ok(guard) => return Ok(guard),
+                Err(TryLockError::WouldBlock) => (),
+                Err(TryLockError::Poisoned(e)) => return Err(e),
+            }
+            waiting.get_or_insert_with(|| Waiting::new(self.id(), &range));
+            for _ in 0..spins {
+                hint::spin_loop();
+            }
+            spins = (spins * 2).min(MAX_SPINS);
+        }
+    }
+
+    /// Try to lock the given data `range` up to `max_attempts` times.
+    ///
+    /// Between two attempts the thread spins for a short time.
+    /// It never blocks.
+    /// The caller can fall back to other work,
+    /// if all attempts fail.
+    ///
+    /// Returns the guard of the first successful attempt.
+    /// Returns [TryLockError::WouldBlock], if all attempts failed due to contention
+    /// or if `max_attempts` is 0.
+    /// Returns [TryLockError::Poisoned], if the lock is poisoned.
+    pub fn try_lock_retry(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        max_attempts: usize,
+    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        let range = self.check_range(&range);
+        for attempt in 0..max_attempts {
+            if attempt > 0 {
+                hint::spin_loop();
+            }
+            match self.try_lock_poisonable(range.clone()) {
+                Err(TryLockError::WouldBlock) => (),
+                res => return res,
+            }
+        }
+        Err(TryLockError::WouldBlock)
+    }
+
+    /// Try to lock the given data `range` expanded to multiples of `align` elements.
+    ///
+    /// The start of the range is rounded down and the end is rounded up
+    /// to the nearest multiple of `align`.
+    /// The expanded end is clamped to [VecRangeLock::data_len].
+    /// An empty range is not expanded.
+    /// Panics, if `align` is not a power of two.
+    ///
+    /// The alignment is relative to the element indices of the data.
+    /// Combined with a suitably aligned allocation of the data
+    /// the locked slice starts on an aligned address.
+    ///
+    /// * On success: Returns the [VecRangeLockGuard] of the expanded range and the expanded range.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the expanded range is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0_f32; 30]);
+    /// let (guard, range) = lock.try_lock_aligned(5..17, 8).expect("Failed to lock");
+    /// assert_eq!(range, 0..24);
+    /// assert_eq!(guard.len(), 24);
+    /// drop(guard);
+    /// let (_guard, range) = lock.try_lock_aligned(20..29, 8).expect("Failed to lock");
+    /// assert_eq!(range, 16..30);
+    /// ```
+    pub fn try_lock_aligned(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        align: usize,
+    ) -> TryLockResult<(VecRangeLockGuard<'a, T>, Range<usize>)> {
+        if !align.is_power_of_two() {
+            panic!("VecRangeLock::try_lock_aligned: align is not a power of two.");
+        }
+        let range = self.check_range(&range);
+        let range = if range.is_empty() {
+            range
+        } else {
+            let start = range.start & !(align - 1);
+            let end = range
+                .end
+                .checked_next_multiple_of(align)
+                .map_or(self.data_len(), |end| end.min(self.data_len()));
+            start..end
+        };
+        match self.try_lock_poisonable(range.clone()) {
+            Ok(guard) => Ok((guard, range)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new((
+                e.into_inner(),
+                range,
+            )))),
+        }
+    }
+
+    /// Try to lock the given data `range` and iterate over its elements mutably.
+    ///
+    /// The returned [VecRangeLockIterMut] owns the guard of the range.
+    /// The range is unlocked, when the iterator is exhausted or dropped.
+    /// See [VecRangeLock::try_lock] for the returned errors.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut iter = lock.try_lock_iter_mut(1..3).expect("Failed to lock 1..3");
+    /// while let Some(x) = iter.next() {
+    ///     *x *= 10;
+    /// }
+    /// // The iterator is exhausted and the range has been unlocked.
+    /// assert!(lock.try_lock(1..3).is_ok());
+    /// drop(iter);
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 30, 4]);
+    /// ```
+    pub fn try_lock_iter_mut(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<VecRangeLockIterMut<'a, T>> {
+        let iter = |guard| VecRangeLockIterMut {
+            guard: Some(guard),
+            pos: 0,
+        };
+        match self.try_lock_poisonable(range) {
+            Ok(guard) => Ok(iter(guard)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(iter(
+                e.into_inner(),
+            )))),
+        }
+    }
+
+    /// Lock the given data `range` with the waiter `priority`.
+    ///
+    /// If the range is contended, this blocks the calling thread
+    /// until the range has been unlocked by its holders.
+    /// Blocked threads with a higher `priority` are granted an unlocked range first,
+    /// regardless of their arrival order.
+    /// Threads blocked in [VecRangeLock::lock] have the priority 0.
+    ///
+    /// The priority only orders the blocked threads.
+    /// Non-blocking attempts like [VecRangeLock::try_lock]
+    /// and the spinning phase of [VecRangeLock::lock] may still take a range first.
+    ///
+    /// Low priority threads starve, as long as higher priority threads
+    /// keep waiting for overlapping ranges.
+    /// There is no aging of the priorities.
+    ///
+    /// See [VecRangeLock::lock] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 4]);
+    /// let guard = lock.lock_with_priority(1..3, 10).expect("Lock is poisoned");
+    /// assert_eq!(guard.len(), 2);
+    /// ```
+    pub fn lock_with_priority(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        priority: u8,
+    ) -> LockResult<VecRangeLockGuard<'a, T>> {
+        let range = self.check_range(&range);
+        let try_acquire = || match self.try_lock_poisonable(range.clone()) {
+            Ok(guard) => Some(Ok(guard)),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(e)) => Some(Err(e)),
+        };
+        let _waiting = Waiting::new(self.id(), &range);
+        self.waiters.wait(&range, self.fair, priority, try_acquire)
+    }
+
+    /// Lock the given data `range` and let `policy` decide what to do on contention.
+    ///
+    /// On contention, `policy` is called with the locked ranges that conflict with `range`.
+    /// Shared locked ranges are reported as segments with a uniform reader count.
+    /// The conflicting ranges are a snapshot. They are collected under the shard mutexes,
+    /// but `policy` is called after releasing them.
+    ///
+    /// The [Decision] returned by `policy` is applied:
+    /// * [Decision::Retry]: Try to lock the range again.
+    ///   `policy` is called again, if the range is still contended.
+    /// * [Decision::GiveUp]: Return [TryLockError::WouldBlock].
+    /// * [Decision::Wait]: Block until the range has been locked, like [VecRangeLock::lock].
+    ///
+    /// Returns [TryLockError::Poisoned], if the lock is poisoned.
+    /// The guard contained in the error holds a valid lock of the range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{backoff::Decision, VecRangeLock};
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// let _guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+    /// let mut seen = Vec::new();
+    /// let result = lock.try_lock_with(0..5, &mut |conflicting| {
+    ///     seen.extend_from_slice(conflicting);
+    ///     Decision::GiveUp
+    /// });
+    /// assert!(result.is_err());
+    /// assert_eq!(seen, vec![2..4]);
+    /// ```
+    pub fn try_lock_with(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        policy: &mut dyn FnMut(&[Range<usize>]) -> Decision,
+    ) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        let range = self.check_range(&range);
+        let try_acquire = || match self.try_lock_poisonable(range.clone()) {
+            Ok(guard) => Some(Ok(guard)),
+            Err(TryLockError::WouldBlock) => None,
+            Err(TryLockError::Poisoned(e)) => Some(Err(e)),
+        };
+        loop {
+            if let Some(result) = try_acquire() {
+                return result.map_err(Into::into);
+            }
+            let conflicting = self.conflicting_ranges(&range);
+            if conflicting.is_empty() {
+                // The conflicting ranges have been unlocked in the meantime.
+                continue;
+            }
+            match policy(&conflicting) {
+                Decision::Retry => (),
+                Decision::GiveUp => return Err(TryLockError::WouldBlock),
+                Decision::Wait => {
+                    let _waiting = Waiting::new(self.id(), &range);
+                    return self
+                        .waiters
+                        .wait(&range, self.fair, 0, try_acquire)
+                        .map_err(Into::into);
+                }
+            }
+        }
+    }
+
+    /// Get all locked ranges that overlap with `range`, sorted by their start.
+    fn conflicting_ranges(&self, range: &Range<usize>) -> Vec<Range<usize>> {
+        // Move a range locked via the fast path into the shards.
+        let slow = self.enter_slow();
+        let mut conflicting = Vec::new();
+        for shard in self.shards(range) {
+            let ranges = shard.lock().unwrap_or_else(PoisonError::into_inner);
+            conflicting.extend(ranges.overlapping(range));
+        }
+        drop(slow);
+        // Ranges spanning multiple shards are stored in each of them.
+        conflicting.sort_unstable_by_key(|r| (r.start, r.end));
+        conflicting.dedup();
+        conflicting
+    }
+
+    /// Try to lock the whole data.
+    ///
+    /// This locks the range `0..data_len()`.
+    /// If the data is empty, the returned guard covers the empty range.
+    ///
+    /// Returns [TryLockError::WouldBlock], if the range is contended.
+    /// Returns [TryLockError::Poisoned], if the lock is poisoned.
+    /// The guard contained in the error holds a valid lock of the range.
+    #[inline]
+    pub fn try_lock_all(&'a self) -> TryLockResult<VecRangeLockGuard<'a, T>> {
+        self.try_lock_poisonable(..)
+    }
+
+    /// Try to lock the single data element at `index`.
+    ///
+    /// * On success: Returns a [VecRangeLockElementGuard] that can be used to access the locked element.
+    ///   Dereferencing [VecRangeLockElementGuard] yields a reference to the element.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the element is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    pub fn try_lock_index(
+        &'a self,
+        index: usize,
+    ) -> TryLockResult<VecRangeLockElementGuard<'a, T>> {
+        if index >= self.data_len() {
+            panic!("Index is out of bounds.");
+        }
+        match self.try_lock_poisonable(index..index + 1) {
+            Ok(guard) => Ok(VecRangeLockElementGuard { guard }),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(
+                VecRangeLockElementGuard {
+                    guard: e.into_inner(),
+                },
+            ))),
+        }
+    }
+
+    /// Unlock all `ranges` and notify the waiters once.
+    fn unlock_all(&self, ranges: &[Range<usize>]) {
+        self.stats
+            .unlocking(ranges.iter().filter(|r| !r.is_empty()).count());
+        for range in ranges.iter().filter(|r| !r.is_empty()) {
+            deadlock::released(self.id(), range);
+            for shard in self.shards(range) {
+                let mut ranges = shard.lock().unwrap_or_else(PoisonError::into_inner);
+                ranges.remove(range);
+            }
+        }
+        self.fast
+            .leave(ranges.iter().filter(|r| !r.is_empty()).count());
+        if !ranges.is_empty() {
+            self.waiters.notify();
+        }
+    }
+
+    /// Get the address of the lock.
+    /// It identifies the lock in the deadlock detection registry.
+    #[inline]
+    pub(crate) fn id(&self) -> usize {
+        (self as *const Self).addr()
+    }
+
+    /// Unlock a range.
+    pub(crate) fn unlock(&self, range: &Range<usize>) {
+        if !range.is_empty() && self.fast.try_unlock(range) {
+            self.stats.unlocking(1);
+            deadlock::released(self.id(), range);
+            self.waiters.notify();
+            return;
+        }
+        self.unlock_with(range, LockedRanges::remove);
+    }
+
+    /// Forcibly unlock all locked ranges that overlap with `range`.
+    ///
+    /// This is a last resort recovery tool for ranges whose guards have been lost.
+    /// All exclusive, shared and upgradable locks that overlap with `range` are removed completely,
+    /// including their parts outside of `range`.
+    /// Blocked threads are woken up.
+    ///
+    /// For the teardown of test cases that share a lock,
+    /// where a previous case may have leaked a guard,
+    /// `force_unlock(&(0..usize::MAX))` unlocks all ranges.
+    ///
+    /// See [VecRangeLock::force_reset] for a safe alternative, if exclusive access to the lock is available.
+    ///
+    /// # Safety
+    ///
+    /// No guard of any of the removed ranges must exist.
+    /// Otherwise, other threads may lock the range and access the data concurrently to the existing guard.
+    /// Dropping such an existing guard afterwards is also undefined behavior.
+    pub unsafe fn force_unlock(&self, range: &Range<usize>) {
+        // The removed ranges may extend beyond the shards covering `range`.
+        // Therefore, remove them from all shards.
+        // The shards are taken in ascending order.
+        // The removed ranges stay accounted in the fast path count.
+        // That only disables the fast path, but it is always safe.
+        let slow = self.enter_slow();
+        let mut locked_shards: Vec<_> = self
+            .ranges
+            .iter()
+            .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+            .collect();
+        for ranges in &mut locked_shards {
+            ranges.remove_overlapping(range);
+        }
+        // The number of removed ranges is unknown.
+        // Only an empty lock can be accounted for exactly.
+        if locked_shards.iter().all(|ranges| ranges.is_empty()) {
+            self.stats.reset_held();
+        }
+        deadlock::released_overlapping(self.id(), range);
+        drop(locked_shards);
+        drop(slow);
+        self.waiters.notify();
+    }
+
+    /// Remove the `range` from all shards covering it with `remove`.
+    fn unlock_with(&self, range: &Range<usize>, remove: RemoveFn) {
+        if !range.is_empty() {
+            self.stats.unlocking(1);
+            deadlock::released(self.id(), range);
+            for shard in self.shards(range) {
+                let mut ranges = shard.lock().unwrap_or_else(PoisonError::into_inner);
+                remove(&mut ranges, range);
+            }
+            self.fast.leave(1);
+            #[cfg(feature = "tracing")]
+            tracing::trace!(range.start, range.end, "range unlocked");
+            self.waiters.notify();
+        }
+    }
+
+    /// Get a raw pointer to the first element of the specified range.
+    ///
+    /// The pointer is derived from the [Vec]'s data buffer.
+    /// No reference to the data elements is created.
+    #[inline]
+    pub(crate) fn get_ptr(&self, range: &Range<usize>) -> *mut T {
+        if range.is_empty() {
+            // Empty ranges are not tracked by the lock.
+            // The Vec may be modified concurrently. See modify_unlocked().
+            // Therefore, don't access the Vec.
+            return NonNull::dangling().as_ptr();
+        }
+        // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+        //         The Vec is only modified while no range is locked.
+        let data = unsafe { &*self.data.get() };
+        // We trust the slicing machinery of Vec to work correctly.
+        // It must return the slice range that we requested.
+        // Otherwise our non-overlap guarantees are gone.
+        assert!(range.start <= range.end && range.end <= data.len());
+        // The pointer returned by as_ptr() carries the provenance of the data buffer.
+        // It does not carry the shared provenance of the `data` reference.
+        // Therefore, it may be used for mutable access.
+        let ptr = data.as_ptr().cast_mut();
+        // SAFETY: The range has been checked against the data length.
+        //         Therefore, the resulting pointer is in bounds of the data buffer
+        //         or one past its end.
+        //         A Vec never allocates more than isize::MAX bytes.
+        //         Therefore, the byte offset never exceeds isize::MAX.
+        //         Zero sized elements may exceed isize::MAX elements,
+        //         but the byte offset is zero then.
+        unsafe { ptr.add(range.start) }
+    }
+
+    /// Get an immutable slice to the specified range.
+    ///
+    /// # SAFETY
+    ///
+    /// See get_mut_slice().
+    #[inline]
+    pub(crate) unsafe fn get_slice(&self, range: &Range<usize>) -> &[T] {
+        slice::from_raw_parts(self.get_ptr(range), range.len())
+    }
+
+    /// Get a mutable slice to the specified range.
+    ///
+    /// # SAFETY
+    ///
+    /// The caller must ensure that:
+    /// * No overlapping slices must coexist on multiple threads.
+    /// * Immutable slices to overlapping ranges may only coexist on a single thread.
+    /// * Immutable and mutable slices must not coexist.
+    #[inline]
+    #[allow(clippy::mut_from_ref)] // Slices won't overlap. See SAFETY.
+    pub(crate) unsafe fn get_mut_slice(&self, range: &Range<usize>) -> &mut [T] {
+        // The mutable slice is derived directly from the mutable data pointer.
+        // It is never derived from an immutable slice.
+        slice::from_raw_parts_mut(self.get_ptr(range), range.len())
+    }
+}
+
+impl<'a, T> VecRangeLock<T>
+where
+    T: Sync,
+{
+    /// Try to lock the given data `range` for shared read access.
+    ///
+    /// Multiple threads may hold overlapping shared read locks at the same time.
+    /// A shared read lock conflicts with overlapping exclusive locks only.
+    ///
+    /// * On success: Returns a [VecRangeLockReadGuard] that can be used to read the locked region.
+    ///   Dereferencing [VecRangeLockReadGuard] yields an immutable slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is exclusively locked.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let r0 = lock.try_read(0..3).expect("Failed to read-lock 0..3");
+    /// let r1 = lock.try_read(2..4).expect("Failed to read-lock 2..4");
+    /// assert_eq!(r0[2], r1[0]);
+    /// assert!(lock.try_lock(3..4).is_err());
+    /// ```
+    pub fn try_read(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<VecRangeLockReadGuard<'a, T>> {
+        let range = self.check_range(&range);
+        match self.try_insert_all(
+            slice::from_ref(&range),
+            LockedRanges::insert_shared,
+            LockedRanges::remove_shared,
+        ) {
+            Ok(()) => Ok(VecRangeLockReadGuard::new(self, range)),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => Err(TryLockError::Poisoned(PoisonError::new(
+                VecRangeLockReadGuard::new(self, range),
+            ))),
+        }
+    }
+
+    /// Try to lock the given data `range` for shared read access with identical ranges only.
+    ///
+    /// Multiple threads may hold exact shared locks of the identical range at the same time.
+    /// The range stays locked, until the last of these guards is dropped.
+    /// An exact shared lock conflicts with all other overlapping locks,
+    /// including shared read locks and exact shared locks of a different range.
+    ///
+    /// * On success: Returns a [VecRangeLockExactReadGuard] that can be used to read the locked region.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let r0 = lock.try_lock_shared_exact(1..3).expect("Failed to read-lock 1..3");
+    /// let r1 = lock.try_lock_shared_exact(1..3).expect("Failed to read-lock 1..3");
+    /// assert_eq!(*r0, *r1);
+    /// assert!(lock.try_lock_shared_exact(2..4).is_err());
+    /// ```
+    pub fn try_lock_shared_exact(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<VecRangeLockExactReadGuard<'a, T>> {
+        let range = self.check_range(&range);
+        let guard = |range| VecRangeLockExactReadGuard {
+            lock: self,
+            range,
+            _p: PhantomData,
+        };
+        match self.try_insert_all(
+            slice::from_ref(&range),
+            LockedRanges::insert_shared_exact,
+            LockedRanges::remove_shared_exact,
+        ) {
+            Ok(()) => Ok(guard(range)),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => {
+                Err(TryLockError::Poisoned(PoisonError::new(guard(range))))
+            }
+        }
+    }
+
+    /// Copy the data `range` into `dst`.
+    ///
+    /// The range is read-locked only for the duration of the copy.
+    /// Panics, if the length of `dst` differs from the length of `range`.
+    ///
+    /// * On success: Returns `Ok(())` after the copy.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   `dst` is not modified in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The data has been copied in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut snapshot = [0; 2];
+    /// lock.read_range_into(1..3, &mut snapshot).expect("Failed to read 1..3");
+    /// assert_eq!(snapshot, [2, 3]);
+    /// ```
+    pub fn read_range_into(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        dst: &mut [T],
+    ) -> TryLockResult<()>
+    where
+        T: Copy,
+    {
+        let range = self.check_range(&range);
+        if range.len() != dst.len() {
+            panic!("VecRangeLock::read_range_into: Destination length does not match.");
+        }
+        match self.try_read(range) {
+            Ok(guard) => {
+                dst.copy_from_slice(&guard);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                dst.copy_from_slice(&e.into_inner());
+                Err(TryLockError::Poisoned(PoisonError::new(())))
+            }
+        }
+    }
+
+    /// Clone the data `range` into a new [Vec].
+    ///
+    /// The range is read-locked only for the duration of the clone.
+    ///
+    /// * On success: Returns the cloned data.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The error contains the cloned data in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let snapshot = lock.clone_region(1..3).expect("Failed to read 1..3");
+    /// assert_eq!(snapshot, vec![2, 3]);
+    /// assert!(lock.try_lock(0..4).is_ok());
+    /// ```
+    pub fn clone_region(&'a self, range: impl RangeBounds<usize>) -> TryLockResult<Vec<T>>
+    where
+        T: Clone,
+    {
+        match self.try_read(range) {
+            Ok(guard) => Ok(guard.to_vec()),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(
+                e.into_inner().to_vec(),
+            ))),
+        }
+    }
+
+    /// Copy the data `range` of exactly `N` elements into an array.
+    ///
+    /// The range is read-locked only for the duration of the copy.
+    /// No allocation takes place.
+    /// Use [VecRangeLock::clone_region] for ranges with a dynamic length.
+    ///
+    /// See [VecRangeLock::clone_region] for the return values.
+    ///
+    /// Panics, if the length of `range` is not `N`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let snapshot: [i32; 2] = lock.snapshot_region(1..3).expect("Failed to read 1..3");
+    /// assert_eq!(snapshot, [2, 3]);
+    /// ```
+    pub fn snapshot_region<const N: usize>(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<[T; N]>
+    where
+        T: Copy,
+    {
+        let range = self.check_range(&range);
+        if range.len() != N {
+            panic!("VecRangeLock::snapshot_region: Range length does not match N.");
+        }
+        let copy = |guard: &[T]| -> [T; N] {
+            guard
+                .try_into()
+                .expect("VecRangeLock::snapshot_region: Invalid guard length.")
+        };
+        match self.try_read(range) {
+            Ok(guard) => Ok(copy(&guard)),
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(copy(
+                &e.into_inner(),
+            )))),
+        }
+    }
+
+    /// Feed the elements of the data `range` into `hasher`.
+    ///
+    /// The range is read-locked only for the duration of the hashing pass.
+    /// The data is not copied.
+    /// The elements are hashed with [Hash::hash_slice].
+    ///
+    /// * On success: Returns `Ok(())`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Nothing is fed into the hasher in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The elements have been fed into the hasher in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::{collections::hash_map::DefaultHasher, hash::Hasher};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut hasher = DefaultHasher::new();
+    /// lock.hash_region(1..3, &mut hasher).expect("Failed to read 1..3");
+    /// let digest = hasher.finish();
+    /// ```
+    pub fn hash_region<H: Hasher>(
+        &'a self,
+        range: impl RangeBounds<usize>,
+        hasher: &mut H,
+    ) -> TryLockResult<()>
+    where
+        T: Hash,
+    {
+        match self.try_read(range) {
+            Ok(guard) => {
+                T::hash_slice(&guard, hasher);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                T::hash_slice(&e.into_inner(), hasher);
+                Err(TryLockError::Poisoned(PoisonError::new(())))
+            }
+        }
+    }
+
+    /// Try to lock the given data `range` for upgradable shared read access.
+    ///
+    /// An upgradable read lock conflicts with overlapping exclusive and upgradable locks,
+    /// but not with overlapping shared read locks.
+    /// It can be upgraded to an exclusive lock with [VecRangeLockUpgradableReadGuard::try_upgrade].
+    ///
+    /// See [VecRangeLock::try_read] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{VecRangeLock, VecRangeLockUpgradableReadGuard};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let guard = lock.try_upgradable_read(0..2).expect("Failed to lock 0..2");
+    /// let reader = lock.try_read(1..3).expect("Failed to read-lock 1..3");
+    /// assert!(lock.try_upgradable_read(1..2).is_err());
+    ///
+    /// // Upgrading fails while the reader is active.
+    /// let guard = VecRangeLockUpgradableReadGuard::try_upgrade(guard).unwrap_err();
+    /// drop(reader);
+    /// let mut guard = VecRangeLockUpgradableReadGuard::try_upgrade(guard).unwrap();
+    /// guard[1] = 20;
+    /// ```
+    pub fn try_upgradable_read(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<VecRangeLockUpgradableReadGuard<'a, T>> {
+        let range = self.check_range(&range);
+        match self.try_insert_all(
+            slice::from_ref(&range),
+            LockedRanges::insert_upgradable,
+            LockedRanges::remove_upgradable,
+        ) {
+            Ok(()) => Ok(VecRangeLockUpgradableReadGuard::new(self, range)),
+            Err(InsertError::Contended(_)) => Err(TryLockError::WouldBlock),
+            Err(InsertError::Poisoned) => Err(TryLockError::Poisoned(PoisonError::new(
+                VecRangeLockUpgradableReadGuard::new(self, range),
+            ))),
+        }
+    }
+}
+
+impl<'a, T> VecRangeLock<T>
+where
+    T: Copy,
+{
+    /// Try to copy the data range `src` to the range starting at `dst_start`.
+    ///
+    /// The source range and the destination range `dst_start..dst_start + src.len()`
+    /// are locked at once for the duration of the copy.
+    /// Therefore, no other thread can modify the source during the copy
+    /// or observe a partially copied destination.
+    ///
+    /// Panics, if the ranges overlap with each other or if the destination is out of bounds.
+    ///
+    /// * On success: Returns `Ok(())`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the ranges is contended.
+    ///   Nothing is copied in this case.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The data has been copied in this case.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4, 5, 6]);
+    /// lock.try_copy_within(3..5, 0).expect("Failed to copy");
+    /// assert_eq!(lock.into_inner(), vec![4, 5, 3, 4, 5, 6]);
+    /// ```
+    pub fn try_copy_within(&'a self, src: Range<usize>, dst_start: usize) -> TryLockResult<()> {
+        let src = self.check_range(&src);
+        let Some(dst_end) = dst_start.checked_add(src.len()) else {
+            panic!("Range is out of bounds.");
+        };
+        let dst = self.check_range(&(dst_start..dst_end));
+        if !src.is_empty() && overlaps(&src, &dst) {
+            panic!("VecRangeLock::try_copy_within: The source and destination ranges overlap.");
+        }
+        match self.try_lock2(src, dst) {
+            Ok((src, mut dst)) => {
+                dst.copy_from_slice(&src);
+                Ok(())
+            }
+            Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                let (src, mut dst) = e.into_inner();
+                dst.copy_from_slice(&src);
+                Err(TryLockError::Poisoned(PoisonError::new(())))
+            }
+        }
+    }
+}
+
+impl<T> VecRangeLock<T> {
+    /// Try to lock the given data `range` with a guard that owns a reference to the lock.
+    ///
+    /// In contrast to [VecRangeLock::try_lock] the returned [OwnedVecRangeLockGuard]
+    /// does not borrow the lock.
+    /// It keeps the lock alive via the [Arc].
+    /// Therefore, it can be moved into spawned threads.
+    ///
+    /// Returns [TryLockError::WouldBlock], if the range is contended.
+    /// Returns [TryLockError::Poisoned], if the lock is poisoned.
+    /// The guard contained in the error holds a valid lock of the range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(VecRangeLock::new(vec![1, 2, 3, 4]));
+    /// let mut guard = Arc::clone(&lock).try_lock_owned(2..4).expect("Failed to lock 2..4");
+    /// thread::spawn(move || {
+    ///     guard[0] = 30;
+    /// })
+    /// .join()
+    /// .unwrap();
+    /// assert_eq!(lock.try_lock(2..3).expect("Failed to lock 2..3")[0], 30);
+    /// ```
+    pub fn try_lock_owned(
+        self: Arc<Self>,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<OwnedVecRangeLockGuard<T>> {
+        let (range, poisoned) = match self.try_lock_poisonable(range) {
+            Ok(guard) => (ManuallyDrop::new(guard).range.clone(), false),
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                (ManuallyDrop::new(e.into_inner()).range.clone(), true)
+            }
+        };
+        // The borrowing guard has been forgotten.
+        // The range is unlocked, when the owned guard is dropped.
+        let guard = OwnedVecRangeLockGuard {
+            lock: self,
+            range,
+            _p: PhantomData,
+        };
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// Try to lock the whole data as `n` disjoint partitions at once
+    /// and return owned guards.
+    ///
+    /// This is the owned variant of [VecRangeLock::partition].
+    /// Each [OwnedVecRangeLockGuard] holds a clone of the [Arc].
+    /// Therefore, the guards can be moved into spawned threads.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(VecRangeLock::new(vec![0; 4]));
+    /// let parts = Arc::clone(&lock).partition_into(2).expect("Failed to partition");
+    /// let threads: Vec<_> = parts
+    ///     .into_iter()
+    ///     .map(|mut part| thread::spawn(move || part.fill(1)))
+    ///     .collect();
+    /// threads.into_iter().for_each(|t| t.join().unwrap());
+    /// assert_eq!(Arc::try_unwrap(lock).ok().unwrap().into_inner(), vec![1; 4]);
+    /// ```
+    pub fn partition_into(
+        self: Arc<Self>,
+        n: usize,
+    ) -> TryLockResult<Vec<OwnedVecRangeLockGuard<T>>> {
+        let into_ranges = |guards: Vec<VecRangeLockGuard<'_, T>>| -> Vec<Range<usize>> {
+            guards
+                .into_iter()
+                .map(|guard| ManuallyDrop::new(guard).range.clone())
+                .collect()
+        };
+        let (ranges, poisoned) = match self.partition(n) {
+            Ok(guards) => (into_ranges(guards), false),
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => (into_ranges(e.into_inner()), true),
+        };
+        // The borrowing guards have been forgotten.
+        self.into_owned_guards(ranges, poisoned)
+    }
+
+    /// Try to lock all data `ranges` at once and return one owned guard per range.
+    ///
+    /// This is the owned variant of [VecRangeLock::try_lock_many].
+    /// Either all ranges are locked or none of them.
+    /// Panics, if any two of the `ranges` overlap with each other.
+    /// The guards are returned in the order of `ranges`.
+    /// Each [OwnedVecRangeLockGuard] holds a clone of the [Arc]
+    /// and unlocks its range independently of the others.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(VecRangeLock::new(vec![0; 6]));
+    /// let guards = Arc::clone(&lock)
+    ///     .try_lock_many_owned(&[4..6, 0..2])
+    ///     .expect("Failed to lock");
+    /// let threads: Vec<_> = guards
+    ///     .into_iter()
+    ///     .map(|mut guard| thread::spawn(move || guard.fill(1)))
+    ///     .collect();
+    /// threads.into_iter().for_each(|t| t.join().unwrap());
+    /// assert_eq!(Arc::try_unwrap(lock).ok().unwrap().into_inner(), vec![1, 1, 0, 0, 1, 1]);
+    /// ```
+    pub fn try_lock_many_owned(
+        self: Arc<Self>,
+        ranges: &[Range<usize>],
+    ) -> TryLockResult<Vec<OwnedVecRangeLockGuard<T>>> {
+        let into_ranges = |guard: VecRangeLockMultiGuard<'_, T>| -> Vec<Range<usize>> {
+            mem::take(&mut ManuallyDrop::new(guard).ranges)
+        };
+        let (ranges, poisoned) = match self.try_lock_many(ranges) {
+            Ok(guard) => (into_ranges(guard), false),
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => (into_ranges(e.into_inner()), true),
+        };
+        // The borrowing guard has been forgotten.
+        self.into_owned_guards(ranges, poisoned)
+    }
+
+    /// Wrap the locked `ranges` into owned guards.
+    ///
+    /// The ranges are unlocked, when the owned guards are dropped.
+    fn into_owned_guards(
+        self: Arc<Self>,
+        ranges: Vec<Range<usize>>,
+        poisoned: bool,
+    ) -> TryLockResult<Vec<OwnedVecRangeLockGuard<T>>> {
+        let guards = ranges
+            .into_iter()
+            .map(|range| OwnedVecRangeLockGuard {
+                lock: Arc::clone(&self),
+                range,
+                _p: PhantomData,
+            })
+            .collect();
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(guards)))
+        } else {
+            Ok(guards)
+        }
+    }
+}
+
+impl<T> fmt::Debug for VecRangeLock<T> {
+    /// Format the data length and the sorted list of currently locked ranges.
+    ///
+    /// The locked ranges are shown as `<locked>`,
+    /// if the internal mutexes can't be taken without blocking.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut locked: Option<Vec<Range<usize>>> = Some(Vec::new());
+        for shard in &self.ranges {
+            let ranges = match shard.try_lock() {
+                Ok(ranges) => ranges,
+                Err(TryLockError::Poisoned(e)) => e.into_inner(),
+                Err(TryLockError::WouldBlock) => {
+                    locked = None;
+                    break;
+                }
+            };
+            if let Some(locked) = &mut locked {
+                locked.extend(ranges.iter());
+            }
+        }
+        if let (Some(locked), Some(range)) = (&mut locked, self.fast.snapshot()) {
+            locked.push(range);
+        }
+        let mut d = f.debug_struct("VecRangeLock");
+        d.field("len", &self.data_len());
+        match &mut locked {
+            Some(locked) => {
+                // Ranges spanning multiple shards are contained in each of them.
+                locked.sort_unstable_by_key(|r| (r.start, r.end));
+                locked.dedup();
+                d.field("locked", locked)
+            }
+            None => d.field("locked", &format_args!("<locked>")),
+        };
+        d.finish()
+    }
+}
+
+impl<T> Default for VecRangeLock<T> {
+    /// Construct a new [VecRangeLock] with an empty data [Vec].
+    #[inline]
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl<T> FromIterator<T> for VecRangeLock<T> {
+    /// Construct a new [VecRangeLock] from the elements of an iterator.
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Self::new(iter.into_iter().collect())
+    }
+}
+
+impl<T> Extend<T> for VecRangeLock<T> {
+    /// Append the elements of an iterator to the data.
+    ///
+    /// The mutable borrow statically guarantees that no guards exist.
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        let data = self.data.get_mut();
+        let old_len = data.len();
+        data.extend(iter);
+        if data.len() != old_len {
+            *self.len.get_mut() = data.len();
+            *self.generation.get_mut() += 1;
+        }
+        // No range is locked. Therefore, the shard regions can be redistributed.
+        self.shard_stride = data.len().div_ceil(self.ranges.len()).max(1);
+    }
+}
+
+/// Lock guard variable type for [VecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See the documentation of [VecRangeLock] for usage examples of [VecRangeLockGuard].
+///
+/// The guard is [Send], if `T` is [Send].
+/// Therefore, a thread can lock a range and hand the guard over to another thread.
+///
+/// ```
+/// use range_lock::VecRangeLock;
+/// use std::thread;
+///
+/// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+/// let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+/// thread::scope(|s| {
+///     s.spawn(move || guard[0] = 10);
+/// });
+/// assert_eq!(lock.into_inner(), vec![10, 2, 3, 4]);
+/// ```
+#[derive(Debug)]
+pub struct VecRangeLockGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The locked range.
+    range: Range<usize>,
+    /// The layout generation at the time of locking.
+    generation: u64,
+    /// This guard is a reborrow of another guard and doesn't own the locked range.
+    /// See [VecRangeLockGuard::reborrow].
+    borrowed: bool,
+
+    /// The guard behaves like a mutable slice reference with respect to autotraits.
+    /// It is Send, if T is Send. It is needed to send guards to worker threads.
+    _p: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> VecRangeLockGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a VecRangeLock<T>, range: Range<usize>) -> VecRangeLockGuard<'a, T> {
+        VecRangeLockGuard {
+            lock,
+            range,
+            generation: lock.generation(),
+            borrowed: false,
+            _p: PhantomData,
+        }
+    }
+
+    /// Get a temporary guard of the same locked range.
+    ///
+    /// The returned guard mutably borrows this guard.
+    /// It can be passed by value to functions that only need the range briefly.
+    ///
+    /// The reborrowed guard shares the reservation of this guard.
+    /// The range is not locked again and dropping the reborrowed guard doesn't unlock it.
+    /// The range stays locked, until this guard is dropped.
+    /// Therefore, a reborrowed guard can't be downgraded with [VecRangeLockGuard::downgrade].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{VecRangeLock, VecRangeLockGuard};
+    ///
+    /// fn set_first(mut guard: VecRangeLockGuard<'_, i32>) {
+    ///     guard[0] = 10;
+    /// }
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+    /// set_first(guard.reborrow());
+    /// // The range is still locked by `guard`.
+    /// assert!(lock.try_lock(1..2).is_err());
+    /// assert_eq!(guard[0], 10);
+    /// ```
+    #[inline]
+    pub fn reborrow(&mut self) -> VecRangeLockGuard<'_, T> {
+        // The mutable borrow of self ensures that self can't be used
+        // or dropped while the reborrowed guard exists.
+        VecRangeLockGuard {
+            lock: self.lock,
+            range: self.range.clone(),
+            generation: self.generation,
+            borrowed: true,
+            _p: PhantomData,
+        }
+    }
+
+    /// Get the layout generation of the lock at the time this guard was locked.
+    ///
+    /// The layout can't change while a non-empty range is locked.
+    /// Compare this to a previously observed [VecRangeLock::generation]
+    /// to detect layout changes between two locking operations.
+    #[inline]
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Get the length (in number of elements) of the locked range.
+    ///
+    /// This is not the length of the whole data of the lock.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.range.len()
+    }
+
+    /// Returns `true`, if the locked range is empty.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.range.is_empty()
+    }
+
+    /// Project the guard to a component of the locked slice.
+    ///
+    /// The closure `f` gets the locked slice and returns a reference into it.
+    /// The returned [MappedVecRangeLockGuard] keeps the range locked and dereferences to `U`.
+    ///
+    /// The reference returned by `f` must be derived from the passed slice.
+    /// The lifetimes enforce this for references with a non-static lifetime.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{VecRangeLock, VecRangeLockGuard};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let guard = lock.try_lock(..).expect("Failed to lock");
+    /// let mut second_half = VecRangeLockGuard::map(guard, |s| &mut s[2..]);
+    /// second_half[0] = 30;
+    /// drop(second_half);
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 30, 4]);
+    /// ```
+    pub fn map<U, F>(mut guard: Self, f: F) -> MappedVecRangeLockGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut [T]) -> &mut U,
+    {
+        let data = NonNull::from(f(&mut guard));
+        MappedVecRangeLockGuard {
+            _guard: guard,
+            data,
+            _p: PhantomData,
+        }
+    }
+
+    /// Fill the locked slice with the values returned by calling `f` repeatedly.
+    #[inline]
+    pub fn fill_with<F>(&mut self, f: F)
+    where
+        F: FnMut() -> T,
+    {
+        self[..].fill_with(f);
+    }
+
+    /// Split the locked slice into chunks of `N` elements and a remainder.
+    ///
+    /// This is equivalent to [slice::as_chunks_mut] on the locked slice.
+    /// The remainder has less than `N` elements.
+    ///
+    /// Panics, if `N` is 0.
+    #[inline]
+    pub fn as_chunks_mut<const N: usize>(&mut self) -> (&mut [[T; N]], &mut [T]) {
+        self[..].as_chunks_mut::<N>()
+    }
+
+    /// Iterate over chunks of `chunk_size` elements of the locked slice.
+    ///
+    /// This is equivalent to [slice::chunks_mut] on the locked slice.
+    /// The last chunk has less than `chunk_size` elements,
+    /// if the length is not divisible by `chunk_size`.
+    ///
+    /// Panics, if `chunk_size` is 0.
+    #[inline]
+    pub fn chunks_mut(&mut self, chunk_size: usize) -> slice::ChunksMut<'_, T> {
+        self[..].chunks_mut(chunk_size)
+    }
+
+    /// Split the locked slice into the first element and the rest.
+    ///
+    /// This is equivalent to [slice::split_first_mut] on the locked slice.
+    /// Returns `None`, if the locked range is empty.
+    #[inline]
+    pub fn split_first_mut(&mut self) -> Option<(&mut T, &mut [T])> {
+        self[..].split_first_mut()
+    }
+
+    /// Split the locked slice into the last element and the rest.
+    ///
+    /// This is equivalent to [slice::split_last_mut] on the locked slice.
+    /// Returns `None`, if the locked range is empty.
+    #[inline]
+    pub fn split_last_mut(&mut self) -> Option<(&mut T, &mut [T])> {
+        self[..].split_last_mut()
+    }
+}
+
+impl<'a, T> VecRangeLockGuard<'a, T>
+where
+    T: Clone,
+{
+    /// Clone the locked slice into a new [Vec].
+    ///
+    /// This allows to take a snapshot of the locked range,
+    /// drop the guard early and process the copy afterwards.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let snapshot = lock.try_lock(1..3).expect("Failed to lock 1..3").to_vec();
+    /// assert_eq!(snapshot, vec![2, 3]);
+    /// ```
+    #[inline]
+    pub fn to_vec(&self) -> Vec<T> {
+        self[..].to_vec()
+    }
+
+    /// Clone the elements of `src` into the locked slice.
+    ///
+    /// # Panics
+    ///
+    /// Panics, if the length of `src` differs from the length of the locked range.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// lock.try_lock(1..3).expect("Failed to lock 1..3").fill_from(&[20, 30]);
+    /// assert_eq!(lock.into_inner(), vec![1, 20, 30, 4]);
+    /// ```
+    #[inline]
+    pub fn fill_from(&mut self, src: &[T]) {
+        assert_eq!(
+            src.len(),
+            self.len(),
+            "VecRangeLockGuard::fill_from: Source length does not match the locked range."
+        );
+        self.clone_from_slice(src);
+    }
+
+    /// Fill the locked slice with clones of `value`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// lock.try_lock(1..3).expect("Failed to lock 1..3").fill(0);
+    /// assert_eq!(lock.into_inner(), vec![1, 0, 0, 4]);
+    /// ```
+    #[inline]
+    pub fn fill(&mut self, value: T) {
+        self[..].fill(value);
+    }
+}
+
+impl<'a, T> VecRangeLockGuard<'a, T>
+where
+    T: Sync,
+{
+    /// Convert the exclusive lock into a shared read lock of the same range.
+    ///
+    /// The conversion is atomic.
+    /// No other thread can take an exclusive lock of the range in between.
+    ///
+    /// Panics, if the guard has been created by [VecRangeLockGuard::reborrow].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{VecRangeLock, VecRangeLockGuard};
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+    /// guard[0] = 10;
+    /// let read_guard = VecRangeLockGuard::downgrade(guard);
+    /// assert_eq!(lock.try_read(0..1).expect("Failed to read-lock 0..1")[0], 10);
+    /// assert!(lock.try_lock(0..1).is_err());
+    /// drop(read_guard);
+    /// ```
+    pub fn downgrade(guard: Self) -> VecRangeLockReadGuard<'a, T> {
+        if guard.borrowed {
+            panic!("VecRangeLockGuard::downgrade: The guard is reborrowed.");
+        }
+        let guard = ManuallyDrop::new(guard);
+        let (lock, range) = (guard.lock, guard.range.clone());
+        if !range.is_empty() {
+            // Move the range into the shard sets, if it is locked via the fast path.
+            let slow = lock.enter_slow();
+            // Take all shard mutexes before converting the range.
+            // Therefore, the conversion is atomic with respect to all other lockers.
+            let mut locked_shards: Vec<_> = lock
+                .shards(&range)
+                .iter()
+                .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+                .collect();
+            for ranges in &mut locked_shards {
+                ranges.downgrade(&range);
+            }
+            drop(locked_shards);
+            drop(slow);
+        }
+        VecRangeLockReadGuard::new(lock, range)
+    }
+}
+
+impl<'a, T> Drop for VecRangeLockGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        // A reborrowed guard doesn't own the range.
+        // The original guard unlocks it.
+        if !self.borrowed {
+            self.lock.poison_data_on_panic(&self.range);
+            self.lock.unlock(&self.range);
+        }
+    }
+}
+
+impl<'a, T> Deref for VecRangeLockGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: See deref_mut().
+        unsafe { self.lock.get_slice(&self.range) }
+    }
+}
+
+impl<'a, T> DerefMut for VecRangeLockGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // The lifetime of the slice is bounded by the lifetime of the guard.
+        // The lifetime of the guard is bounded by the lifetime of the range lock.
+        // The underlying data is owned by the range lock.
+        // Therefore the slice cannot outlive the data.
+        // The range lock ensures that no overlapping/conflicting guards
+        // can be constructed.
+        // The compiler ensures that the DerefMut result cannot be used,
+        // if there's also an immutable Deref result.
+        unsafe { self.lock.get_mut_slice(&self.range) }
+    }
+}
+
+impl<'a, 'g, T> IntoIterator for &'g VecRangeLockGuard<'a, T> {
+    type Item = &'g T;
+    type IntoIter = slice::Iter<'g, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl<'a, 'g, T> IntoIterator for &'g mut VecRangeLockGuard<'a, T> {
+    type Item = &'g mut T;
+    type IntoIter = slice::IterMut<'g, T>;
+
+    /// Iterate mutably over the locked slice.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let mut guard = lock.try_lock(1..3).expect("Failed to lock 1..3");
+    /// for x in &mut guard {
+    ///     *x += 10;
+    /// }
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![1, 12, 13, 4]);
+    /// ```
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_mut()
+    }
+}
+
+/// Exclusive lock guard variable type for [VecRangeLock].
+///
+/// This is the guard returned by [VecRangeLock::try_lock].
+pub type VecRangeLockWriteGuard<'a, T> = VecRangeLockGuard<'a, T>;
+
+/// Shared read lock guard variable type for [VecRangeLock].
+///
+/// The [Deref] trait is implemented for this struct.
+/// Dereferencing yields an immutable slice of the locked region.
+/// See [VecRangeLock::try_read] for usage examples.
+///
+/// Like [std::sync::RwLockReadGuard], the guard is [Sync] for `T: Send + Sync`.
+/// Therefore, a reference to the guard can be shared with multiple reading threads.
+#[derive(Debug)]
+pub struct VecRangeLockReadGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The locked range.
+    range: Range<usize>,
+    /// Liveness flag shared with the weak guards. Created by the first weak guard.
+    alive: OnceLock<Arc<Mutex<bool>>>,
+
+    /// The guard behaves like an immutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> VecRangeLockReadGuard<'a, T> {
+    #[inline]
+    fn new(lock: &'a VecRangeLock<T>, range: Range<usize>) -> VecRangeLockReadGuard<'a, T> {
+        VecRangeLockReadGuard {
+            lock,
+            range,
+            alive: OnceLock::new(),
+            _p: PhantomData,
+        }
+    }
+
+    /// Create a weak reference to the range held by this guard.
+    ///
+    /// The [WeakVecRangeLockReadGuard] does not keep the range locked.
+    /// It can be upgraded to a new read guard of the same range,
+    /// as long as this guard is alive.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let guard = lock.try_read(1..3).expect("Failed to read-lock 1..3");
+    /// let weak = guard.weak();
+    /// assert_eq!(*weak.upgrade().expect("Guard is alive"), [2, 3]);
+    /// drop(guard);
+    /// assert!(weak.upgrade().is_none());
+    /// assert!(lock.try_lock(0..4).is_ok());
+    /// ```
+    pub fn weak(&self) -> WeakVecRangeLockReadGuard<'a, T> {
+        let alive = self.alive.get_or_init(|| Arc::new(Mutex::new(true)));
+        WeakVecRangeLockReadGuard {
+            lock: self.lock,
+            range: self.range.clone(),
+            alive: Arc::clone(alive),
+        }
+    }
+}
+
+impl<'a, T> Clone for VecRangeLockReadGuard<'a, T> {
+    /// Add another reader of the same range.
+    ///
+    /// The clone holds its own shared lock of the range.
+    /// The range stays read-locked, until the last clone is dropped.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+    /// let guard = lock.try_read(1..3).expect("Failed to read-lock 1..3");
+    /// let clone = guard.clone();
+    /// drop(guard);
+    /// assert_eq!(*clone, [2, 3]);
+    /// assert!(lock.try_lock(1..2).is_err());
+    /// drop(clone);
+    /// assert!(lock.try_lock(1..2).is_ok());
+    /// ```
+    fn clone(&self) -> Self {
+        // The range is read-locked by this guard. Adding a reader can't be contended.
+        // A poisoned lock has already been reported to the owner of this guard.
+        let res = self.lock.try_insert_all(
+            slice::from_ref(&self.range),
+            LockedRanges::insert_shared,
+            LockedRanges::remove_shared,
+        );
+        debug_assert!(!matches!(res, Err(InsertError::Contended(_))));
+        VecRangeLockReadGuard::new(self.lock, self.range.clone())
+    }
+}
+
+impl<'a, T> Drop for VecRangeLockReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(alive) = self.alive.get() {
+            // Mark the guard as dead before unlocking the range.
+            // See WeakVecRangeLockReadGuard::upgrade().
+            *alive.lock().unwrap_or_else(PoisonError::into_inner) = false;
+        }
+        self.lock
+            .unlock_with(&self.range, LockedRanges::remove_shared);
+    }
+}
+
+impl<'a, T> Deref for VecRangeLockReadGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // The range lock ensures that no exclusive guard overlaps with this range.
+        // Shared read guards only hand out immutable slices.
+        // Read guards can only be constructed for T: Sync.
+        unsafe { self.lock.get_slice(&self.range) }
+    }
+}
+
+/// Exact shared read lock guard variable type for [VecRangeLock].
+///
+/// It is created by [VecRangeLock::try_lock_shared_exact].
+/// The [Deref] trait is implemented for this struct.
+/// Dereferencing yields an immutable slice of the locked region.
+#[derive(Debug)]
+pub struct VecRangeLockExactReadGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The locked range.
+    range: Range<usize>,
+
+    /// The guard behaves like an immutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> Drop for VecRangeLockExactReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock
+            .unlock_with(&self.range, LockedRanges::remove_shared_exact);
+    }
+}
+
+impl<'a, T> Deref for VecRangeLockExactReadGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // The range lock ensures that no exclusive guard overlaps with this range.
+        // Exact shared read guards only hand out immutable slices.
+        // Exact shared read guards can only be constructed for T: Sync.
+        unsafe { self.lock.get_slice(&self.range) }
+    }
+}
+
+/// Weak reference to the range of a [VecRangeLockReadGuard].
+///
+/// It is created by [VecRangeLockReadGuard::weak].
+/// It does not keep the range locked.
+/// Upgrading it only succeeds, while the original guard is alive.
+///
+/// Exclusive guards don't have weak references,
+/// because their range can't be shared while they are alive.
+#[derive(Debug)]
+pub struct WeakVecRangeLockReadGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The range of the original guard.
+    range: Range<usize>,
+    /// Liveness flag of the original guard.
+    alive: Arc<Mutex<bool>>,
+}
+
+impl<'a, T> WeakVecRangeLockReadGuard<'a, T> {
+    /// Get the range of the original guard.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+
+    /// Check whether the original guard is still alive.
+    ///
+    /// The result is a momentary snapshot.
+    /// The original guard may be dropped at any time.
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        *self.alive.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<'a, T> WeakVecRangeLockReadGuard<'a, T>
+where
+    T: Sync,
+{
+    /// Upgrade to a new read guard of the range.
+    ///
+    /// Returns `None`, if the original guard has been dropped.
+    /// The new guard stays valid, even if the original guard is dropped afterwards.
+    /// The poisoned state of the lock is ignored,
+    /// because the original guard already holds the range.
+    pub fn upgrade(&self) -> Option<VecRangeLockReadGuard<'a, T>> {
+        // The original guard can't release the range, while we hold the flag mutex.
+        let alive = self.alive.lock().unwrap_or_else(PoisonError::into_inner);
+        if !*alive {
+            return None;
+        }
+        // The range is read-locked by the original guard.
+        // Therefore, no exclusive lock can be in the way of an additional reader.
+        match self.lock.try_read(self.range.clone()) {
+            Ok(guard) => Some(guard),
+            Err(TryLockError::Poisoned(e)) => Some(e.into_inner()),
+            Err(TryLockError::WouldBlock) => unreachable!(),
+        }
+    }
+}
+
+/// Upgradable shared read lock guard variable type for [VecRangeLock].
+///
+/// The [Deref] trait is implemented for this struct.
+/// Dereferencing yields an immutable slice of the locked region.
+/// See [VecRangeLock::try_upgradable_read] for usage examples.
+#[derive(Debug)]
+pub struct VecRangeLockUpgradableReadGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The locked range.
+    range: Range<usize>,
+
+    /// The guard behaves like an immutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a [T]>,
+}
+
+impl<'a, T> VecRangeLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    fn new(
+        lock: &'a VecRangeLock<T>,
+        range: Range<usize>,
+    ) -> VecRangeLockUpgradableReadGuard<'a, T> {
+        VecRangeLockUpgradableReadGuard {
+            lock,
+            range,
+            _p: PhantomData,
+        }
+    }
+
+    /// Try to convert the upgradable read lock into an exclusive lock of the same range.
+    ///
+    /// The conversion is atomic.
+    /// It succeeds, if no shared read lock overlaps with the range.
+    /// Otherwise, the upgradable guard is returned as error.
+    pub fn try_upgrade(guard: Self) -> Result<VecRangeLockGuard<'a, T>, Self> {
+        let (lock, range) = (guard.lock, guard.range.clone());
+        if !range.is_empty() {
+            // The upgradable range is in the shard sets already.
+            // Entering the slow path keeps the shard access rule nevertheless.
+            let slow = lock.enter_slow();
+            // Take all shard mutexes before converting the range.
+            // Therefore, the conversion is atomic with respect to all other lockers.
+            let mut locked_shards: Vec<_> = lock
+                .shards(&range)
+                .iter()
+                .map(|shard| shard.lock().unwrap_or_else(PoisonError::into_inner))
+                .collect();
+            if locked_shards.iter().any(|ranges| ranges.is_shared(&range)) {
+                drop(locked_shards);
+                drop(slow);
+                return Err(guard);
+            }
+            for ranges in &mut locked_shards {
+                ranges.upgrade(&range);
+            }
+            drop(locked_shards);
+            drop(slow);
+        }
+        mem::forget(guard);
+        Ok(VecRangeLockGuard::new(lock, range))
+    }
+}
+
+impl<'a, T> Drop for VecRangeLockUpgradableReadGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock
+            .unlock_with(&self.range, LockedRanges::remove_upgradable);
+    }
+}
+
+impl<'a, T> Deref for VecRangeLockUpgradableReadGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: See VecRangeLockReadGuard::deref().
+        unsafe { self.lock.get_slice(&self.range) }
+    }
+}
+
+/// Owned lock guard variable type for [VecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See [VecRangeLock::try_lock_owned] for usage examples.
+#[derive(Debug)]
+pub struct OwnedVecRangeLockGuard<T> {
+    /// Reference to the underlying lock.
+    lock: Arc<VecRangeLock<T>>,
+    /// The locked range.
+    range: Range<usize>,
+
+    /// The guard behaves like an owned mutable slice with respect to autotraits.
+    _p: PhantomData<Box<[T]>>,
+}
+
+impl<T> Drop for OwnedVecRangeLockGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.poison_data_on_panic(&self.range);
+        self.lock.unlock(&self.range);
+    }
+}
+
+impl<T> Deref for OwnedVecRangeLockGuard<T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: See VecRangeLockGuard::deref_mut().
+        unsafe { self.lock.get_slice(&self.range) }
+    }
+}
+
+impl<T> DerefMut for OwnedVecRangeLockGuard<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // The guard keeps the range lock and the underlying data alive.
+        // See VecRangeLockGuard::deref_mut().
+        unsafe { self.lock.get_mut_slice(&self.range) }
+    }
+}
+
+/// Projected lock guard variable type for [VecRangeLock].
+///
+/// This guard is created by [VecRangeLockGuard::map] or [VecRangeLockElementGuard::map].
+/// It keeps the range locked and dereferences to the projected value.
+#[derive(Debug)]
+pub struct MappedVecRangeLockGuard<'a, T, U: ?Sized> {
+    /// The guard of the locked range.
+    _guard: VecRangeLockGuard<'a, T>,
+    /// The projected value within the locked range.
+    data: NonNull<U>,
+
+    /// The guard behaves like a mutable reference to `U` with respect to autotraits.
+    _p: PhantomData<&'a mut U>,
+}
+
+impl<'a, T, U: ?Sized> Deref for MappedVecRangeLockGuard<'a, T, U> {
+    type Target = U;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: See deref_mut().
+        unsafe { self.data.as_ref() }
+    }
+}
+
+impl<'a, T, U: ?Sized> DerefMut for MappedVecRangeLockGuard<'a, T, U> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // The pointer has been derived from the mutable slice of the locked range.
+        // The range stays locked for the lifetime of this guard.
+        // The data buffer does not move, when the guard is moved.
+        unsafe { self.data.as_mut() }
+    }
+}
+
+/// Iterator over the mutable elements of a locked range of [VecRangeLock].
+///
+/// This iterator is created by [VecRangeLock::try_lock_iter_mut].
+/// It owns the guard of the range.
+/// The range is unlocked, when the iterator is exhausted or dropped.
+///
+/// This is a lending iterator.
+/// Each element returned by [VecRangeLockIterMut::next] borrows the iterator.
+/// Therefore, no element reference can outlive the lock of the range
+/// and [Iterator] can't be implemented.
+///
+/// ```compile_fail
+/// use range_lock::VecRangeLock;
+///
+/// let lock = VecRangeLock::new(vec![1, 2, 3, 4]);
+/// let mut iter = lock.try_lock_iter_mut(..).expect("Failed to lock");
+/// let x = iter.next().unwrap();
+/// drop(iter); // Unlocks the range.
+/// *x = 10; // Error: `x` borrows `iter`.
+/// ```
+#[derive(Debug)]
+pub struct VecRangeLockIterMut<'a, T> {
+    /// The guard of the locked range. `None` after exhaustion.
+    guard: Option<VecRangeLockGuard<'a, T>>,
+    /// Index of the next element within the locked range.
+    pos: usize,
+}
+
+impl<'a, T> VecRangeLockIterMut<'a, T> {
+    /// Get the next element of the locked range.
+    ///
+    /// Returns `None` and unlocks the range, if all elements have been returned.
+    #[allow(clippy::should_implement_trait)] // Lending iterator. See struct doc.
+    pub fn next(&mut self) -> Option<&mut T> {
+        let len = self.guard.as_ref().map_or(0, |guard| guard.len());
+        if self.pos < len {
+            let index = self.pos;
+            self.pos += 1;
+            self.guard.as_mut().map(|guard| &mut guard[index])
+        } else {
+            self.guard = None;
+            None
+        }
+    }
+
+    /// Get the number of remaining elements.
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        self.guard
+            .as_ref()
+            .map_or(0, |guard| guard.len() - self.pos)
+    }
+
+    /// Check whether the range is still locked by this iterator.
+    #[inline]
+    pub fn is_locked(&self) -> bool {
+        self.guard.is_some()
+    }
+}
+
+/// Lock guard variable type for a single element of [VecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// Dereferencing yields a reference to the locked element.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::VecRangeLock;
+///
+/// let lock = VecRangeLock::new(vec![1, 2, 3]);
+/// let mut guard = lock.try_lock_index(1).expect("Failed to lock element 1");
+/// assert_eq!(*guard, 2);
+/// *guard = 20;
+/// drop(guard);
+/// assert_eq!(lock.into_inner(), vec![1, 20, 3]);
+/// ```
+#[derive(Debug)]
+pub struct VecRangeLockElementGuard<'a, T> {
+    /// The guard of the one-element range.
+    guard: VecRangeLockGuard<'a, T>,
+}
+
+impl<'a, T> VecRangeLockElementGuard<'a, T> {
+    /// Project the guard to a component of the locked element, such as a struct field.
+    ///
+    /// The returned [MappedVecRangeLockGuard] keeps the element locked and dereferences to `U`.
+    /// See [VecRangeLockGuard::map].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{VecRangeLock, VecRangeLockElementGuard};
+    ///
+    /// struct Item {
+    ///     id: u32,
+    ///     value: f32,
+    /// }
+    ///
+    /// let lock = VecRangeLock::new(vec![Item { id: 0, value: 0.0 }, Item { id: 1, value: 1.0 }]);
+    /// let guard = lock.try_lock_index(1).expect("Failed to lock element 1");
+    /// let mut value = VecRangeLockElementGuard::map(guard, |item| &mut item.value);
+    /// *value = 10.0;
+    /// drop(value);
+    /// assert_eq!(lock.into_inner()[1].value, 10.0);
+    /// ```
+    pub fn map<U, F>(guard: Self, f: F) -> MappedVecRangeLockGuard<'a, T, U>
+    where
+        U: ?Sized,
+        F: FnOnce(&mut T) -> &mut U,
+    {
+        VecRangeLockGuard::map(guard.guard, |s| f(&mut s[0]))
+    }
+}
+
+impl<'a, T> Deref for VecRangeLockElementGuard<'a, T> {
+    type Target = T;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.guard[0]
+    }
+}
+
+impl<'a, T> DerefMut for VecRangeLockElementGuard<'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.guard[0]
+    }
+}
+
+/// Lock guard variable type for multiple ranges of [VecRangeLock].
+///
+/// The ranges are accessed by their index in the slice passed to [VecRangeLock::try_lock_many].
+/// All ranges are unlocked together, when the guard is dropped.
+#[derive(Debug)]
+pub struct VecRangeLockMultiGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// The locked ranges, in the order of the request.
+    ranges: Vec<Range<usize>>,
+
+    /// The guard behaves like a mutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> VecRangeLockMultiGuard<'a, T> {
+    /// Get the number of locked ranges.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    /// Returns `true`, if the guard holds no range.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Get the data slice of the `i`-th range.
+    ///
+    /// Panics, if `i` is out of bounds.
+    #[inline]
+    pub fn get(&self, i: usize) -> &[T] {
+        // SAFETY: See get_mut().
+        unsafe { self.lock.get_slice(&self.ranges[i]) }
+    }
+
+    /// Get the mutable data slice of the `i`-th range.
+    ///
+    /// Panics, if `i` is out of bounds.
+    #[inline]
+    pub fn get_mut(&mut self, i: usize) -> &mut [T] {
+        // SAFETY:
+        // The range is locked by this guard.
+        // The range lock ensures that no overlapping/conflicting guards
+        // can be constructed.
+        // The lifetime of the slice is bounded by the lifetime of the guard.
+        // The compiler ensures that the mutable slice cannot be used,
+        // if there's also an immutable slice.
+        unsafe { self.lock.get_mut_slice(&self.ranges[i]) }
+    }
+}
+
+impl<'a, T> Drop for VecRangeLockMultiGuard<'a, T> {
+    fn drop(&mut self) {
+        for range in &self.ranges {
+            self.lock.poison_data_on_panic(range);
+        }
+        self.lock.unlock_all(&self.ranges);
+    }
+}
+
+/// Lock guard variable type for the strided elements of [VecRangeLock].
+///
+/// Indexing the guard with `i` yields the `i`-th element of the stride,
+/// which is the element `start + i * step` of the data.
+/// See [VecRangeLock::try_lock_strided].
+#[derive(Debug)]
+pub struct VecRangeLockStridedGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a VecRangeLock<T>,
+    /// Index of the first locked element.
+    start: usize,
+    /// Distance between the locked elements.
+    step: usize,
+    /// Number of locked elements.
+    len: usize,
+
+    /// The guard behaves like a mutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a mut [T]>,
+}
+
+impl<'a, T> VecRangeLockStridedGuard<'a, T> {
+    /// Get the number of locked elements.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true`, if no element is locked.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Get the one-element data range of the `index`-th element of the stride.
+    #[inline]
+    fn element_range(&self, index: usize) -> Range<usize> {
+        if index >= self.len {
+            panic!("Index is out of bounds.");
+        }
+        let index = self.start + index * self.step;
+        index..index + 1
+    }
+}
+
+impl<'a, T> Drop for VecRangeLockStridedGuard<'a, T> {
+    fn drop(&mut self) {
+        let ranges = VecRangeLock::<T>::strided_ranges(self.start, self.step, self.len);
+        for range in &ranges {
+            self.lock.poison_data_on_panic(range);
+        }
+        self.lock.unlock_all(&ranges);
+    }
+}
+
+impl<'a, T> Index<usize> for VecRangeLockStridedGuard<'a, T> {
+    type Output = T;
+
+    #[inline]
+    fn index(&self, index: usize) -> &Self::Output {
+        // SAFETY: See index_mut().
+        unsafe { &self.lock.get_slice(&self.element_range(index))[0] }
+    }
+}
+
+impl<'a, T> IndexMut<usize> for VecRangeLockStridedGuard<'a, T> {
+    #[inline]
+    fn index_mut(&mut self, index: usize) -> &mut Self::Output {
+        // SAFETY:
+        // The element is one of the elements locked by this guard.
+        // The range lock ensures that no conflicting guards can be constructed.
+        // The lifetime of the reference is bounded by the lifetime of the guard.
+        // The compiler ensures that the mutable reference cannot be used,
+        // if there's also an immutable reference.
+        unsafe { &mut self.lock.get_mut_slice(&self.element_range(index))[0] }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::sync::{Arc, Barrier};
+    use std::thread;
+
+    #[test]
+    fn test_base() {
+        {
+            // Range
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            {
+                let mut g = a.try_lock(2..4).unwrap();
+                assert!(!a.is_all_unlocked());
+                assert_eq!(g[0..2], [3, 4]);
+                g[1] = 10;
+                assert_eq!(g[0..2], [3, 10]);
+            }
+            assert!(a.is_all_unlocked());
+        }
+        {
+            // RangeInclusive
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            let g = a.try_lock(2..=4).unwrap();
+            assert_eq!(g[0..3], [3, 4, 5]);
+        }
+        {
+            // RangeTo
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            let g = a.try_lock(..4).unwrap();
+            assert_eq!(g[0..4], [1, 2, 3, 4]);
+        }
+        {
+            // RangeToInclusive
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            let g = a.try_lock(..=4).unwrap();
+            assert_eq!(g[0..5], [1, 2, 3, 4, 5]);
+        }
+        {
+            // RangeFrom
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            let g = a.try_lock(2..).unwrap();
+            assert_eq!(g[0..4], [3, 4, 5, 6]);
+        }
+        {
+            // RangeFull
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+            let g = a.try_lock(..).unwrap();
+            assert_eq!(g[0..6], [1, 2, 3, 4, 5, 6]);
+        }
+    }
+
+    #[test]
+    fn test_empty_range() {
+        // Empty range doesn't cause conflicts.
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let g0 = a.try_lock(2..2).unwrap();
+        assert!(a.is_all_unlocked());
+        assert_eq!(g0[0..0], []);
+        let g1 = a.try_lock(2..2).unwrap();
+        assert!(a.is_all_unlocked());
         assert_eq!(g1[0..0], []);
     }
 
     #[test]
-    #[should_panic(expected = "index out of bounds")]
-    fn test_base_oob_read() {
+    fn test_empty_range_boundary() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        let _g = a.try_lock(..).unwrap();
+        for g in [
+            a.try_lock(0..0).unwrap(),
+            a.try_lock(4..4).unwrap(),
+            a.try_lock(4..).unwrap(),
+            a.try_lock(..0).unwrap(),
+        ] {
+            assert!(g.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_default() {
+        let a: VecRangeLock<i32> = Default::default();
+        assert_eq!(a.data_len(), 0);
+        let g0 = a.try_lock(..).unwrap();
+        let g1 = a.try_lock(0..0).unwrap();
+        assert!(g0.is_empty());
+        assert!(g1.is_empty());
+        drop(g0);
+        drop(g1);
+        // All empty ranges of an empty lock are valid.
+        assert!(a.lock(..).unwrap().is_empty());
+        assert!(a.try_read(..).unwrap().is_empty());
+        assert!(a.try_lock_checked(0..).unwrap().unwrap().is_empty());
+        assert!(a.try_lock_checked(0..1).is_err());
+        assert!(a.is_all_unlocked());
+        assert!(a.into_inner().is_empty());
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let mut a: VecRangeLock<i32> = (0..100).collect();
+        assert_eq!(a.data_len(), 100);
+        assert_eq!(a.try_lock(10..12).unwrap()[..], [10, 11]);
+        a.extend(100..110);
+        assert_eq!(a.data_len(), 110);
+        assert_eq!(a.try_lock(105..).unwrap()[..], [105, 106, 107, 108, 109]);
+        assert_eq!(a.into_inner(), (0..110).collect::<Vec<i32>>());
+
+        let mut a = VecRangeLock::with_shards(vec![0_i32; 8], 4);
+        assert_eq!(a.shard_stride, 2);
+        a.extend([0; 8]);
+        assert_eq!(a.shard_stride, 4);
+    }
+
+    #[test]
+    fn test_empty_range_oob() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        assert_eq!(a.try_lock(4..4).unwrap_err(), LockError::OutOfBounds);
+        assert_eq!(
+            a.try_lock(2..=usize::MAX).unwrap_err(),
+            LockError::OutOfBounds
+        );
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_try_lock_start_after_end() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        assert_eq!(a.try_lock(2..1).unwrap_err(), LockError::InvalidRange);
+        assert_eq!(
+            TryLockError::try_from(LockError::InvalidRange).unwrap_err(),
+            LockError::InvalidRange
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Start is bigger than end")]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_try_lock_detailed_start_after_end() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        let _ = a.try_lock_detailed(2..1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_get_unlocked_mut_oob() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        let _ = a.get_unlocked_mut(2..4);
+    }
+
+    #[test]
+    fn test_lock_error() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let _g = a.try_lock(3..6).unwrap();
+        let e = a.try_lock(5..7).unwrap_err();
+        assert!(e.is_would_block());
+        assert_eq!(
+            e,
+            LockError::WouldBlock {
+                conflicting: Some(3..6)
+            }
+        );
+        assert_eq!(e.to_string(), "range is contended by locked range 3..6");
+        assert!(matches!(
+            TryLockError::try_from(e),
+            Ok(TryLockError::WouldBlock)
+        ));
+        assert_eq!(
+            LockError::from(TryLockError::<()>::WouldBlock),
+            LockError::WouldBlock { conflicting: None }
+        );
+        assert!(!LockError::OutOfBounds.is_would_block());
+        assert_eq!(
+            TryLockError::try_from(LockError::OutOfBounds).unwrap_err(),
+            LockError::OutOfBounds
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_base_oob_read() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let g = a.try_lock(2..4).unwrap();
+        let _ = g[2];
+    }
+
+    #[test]
+    #[should_panic(expected = "index out of bounds")]
+    fn test_base_oob_write() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let mut g = a.try_lock(2..4).unwrap();
+        g[2] = 10;
+    }
+
+    #[test]
+    #[should_panic(expected = "guard 1 panicked")]
+    fn test_overlap0() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let _g0 = a.try_lock(2..4).expect("guard 0 panicked");
+        let _g1 = a.try_lock(3..5).expect("guard 1 panicked");
+    }
+
+    #[test]
+    #[should_panic(expected = "guard 0 panicked")]
+    fn test_overlap1() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let _g1 = a.try_lock(3..5).expect("guard 1 panicked");
+        let _g0 = a.try_lock(2..4).expect("guard 0 panicked");
+    }
+
+    #[test]
+    fn test_thread_no_overlap() {
+        let a = Arc::new(VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]));
+        let b = Arc::clone(&a);
+        let c = Arc::clone(&a);
+        let ba0 = Arc::new(Barrier::new(2));
+        let ba1 = Arc::clone(&ba0);
+        let j0 = thread::spawn(move || {
+            {
+                let mut g = b.try_lock(2..4).unwrap();
+                assert!(!b.is_all_unlocked());
+                assert_eq!(g[0..2], [3, 4]);
+                g[1] = 10;
+                assert_eq!(g[0..2], [3, 10]);
+            }
+            ba0.wait();
+        });
+        let j1 = thread::spawn(move || {
+            {
+                let g = c.try_lock(4..6).unwrap();
+                assert!(!c.is_all_unlocked());
+                assert_eq!(g[0..2], [5, 6]);
+            }
+            ba1.wait();
+            let g = c.try_lock(3..5).unwrap();
+            assert_eq!(g[0..2], [10, 5]);
+        });
+        j1.join().expect("Thread 1 panicked.");
+        j0.join().expect("Thread 0 panicked.");
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_lock_all() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        {
+            let mut g = a.try_lock_all().unwrap();
+            assert_eq!(g.len(), 4);
+            g[3] = 40;
+            assert!(a.try_lock(0..1).is_err());
+            assert!(a.try_lock(3..4).is_err());
+            assert!(a.try_lock_all().is_err());
+        }
+        {
+            let _g = a.try_lock(2..3).unwrap();
+            assert!(a.try_lock_all().is_err());
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 40]);
+
+        let a: VecRangeLock<i32> = VecRangeLock::new(vec![]);
+        let g0 = a.try_lock_all().unwrap();
+        let g1 = a.try_lock_all().unwrap();
+        assert!(g0.is_empty());
+        assert!(g1.is_empty());
+    }
+
+    #[test]
+    fn test_get_mut() {
+        let mut a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        a.get_mut()[1] = 20;
+        assert_eq!(a.get_mut(), [1, 20, 3, 4]);
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.try_lock(1..2).unwrap()[0], 20);
+    }
+
+    #[test]
+    fn test_lock_index() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        {
+            let mut g = a.try_lock_index(3).unwrap();
+            assert_eq!(*g, 4);
+            *g = 40;
+            assert!(a.try_lock(2..4).is_err());
+            assert!(a.try_lock_index(3).is_err());
+            let _g0 = a.try_lock_index(0).unwrap();
+            let _g1 = a.try_lock(1..3).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 40]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Index is out of bounds")]
+    fn test_lock_index_oob() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        let _ = a.try_lock_index(4);
+    }
+
+    #[test]
+    fn test_lock_detailed() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        let _g0 = a.try_lock(10..20).unwrap();
+        let _g1 = a.try_lock(60..70).unwrap();
+        for (range, expected) in [(15..16, 10..20), (0..11, 10..20), (30..61, 60..70)] {
+            match a.try_lock_detailed(range) {
+                Err(DetailedLockError::Contended { conflicting }) => {
+                    assert_eq!(conflicting, expected)
+                }
+                _ => panic!("Range must be contended"),
+            }
+        }
+        assert!(a.try_lock_detailed(20..60).is_ok());
+    }
+
+    #[test]
+    fn test_map() {
+        let a = VecRangeLock::new(vec![(1_i32, 2_i32), (3, 4), (5, 6)]);
+        {
+            let g = a.try_lock(1..3).unwrap();
+            let mut m = VecRangeLockGuard::map(g, |s| &mut s[1].0);
+            assert_eq!(*m, 5);
+            *m = 50;
+            assert!(a.try_lock(1..2).is_err());
+            assert!(a.try_lock(0..1).is_ok());
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![(1, 2), (3, 4), (50, 6)]);
+    }
+
+    #[test]
+    fn test_element_map() {
+        let a = VecRangeLock::new(vec![(1_i32, 2_i32), (3, 4), (5, 6)]);
+        {
+            let g = a.try_lock_index(1).unwrap();
+            let mut m = VecRangeLockElementGuard::map(g, |e| &mut e.1);
+            assert_eq!(*m, 4);
+            *m = 40;
+            assert!(a.try_lock(1..2).is_err());
+            assert!(a.try_lock(0..1).is_ok());
+            assert!(a.try_lock(2..3).is_ok());
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![(1, 2), (3, 40), (5, 6)]);
+    }
+
+    #[test]
+    fn test_lock2() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<i32>>(), 4);
+        {
+            let (g0, g1) = a.try_lock2(10..30, 70..90).unwrap();
+            assert_eq!(g0[0], 10);
+            assert_eq!(g1[0], 70);
+            assert!(a.try_lock(29..30).is_err());
+            assert!(a.try_lock(70..71).is_err());
+            let _g = a.try_lock(30..70).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+        {
+            // Failed lock of the second range rolls back the first range.
+            let _g = a.try_lock(80..81).unwrap();
+            assert!(a.try_lock2(0..60, 75..85).is_err());
+            assert!(a.ranges[0].lock().unwrap().is_empty());
+            assert!(a.ranges[1].lock().unwrap().is_empty());
+            assert!(a.ranges[2].lock().unwrap().is_empty());
+            let (_g0, _g1) = a.try_lock2(0..60, 90..90).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap with each other")]
+    fn test_lock2_overlap() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let _ = a.try_lock2(0..5, 4..6);
+    }
+
+    #[test]
+    fn test_read() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<i32>>(), 4);
+        {
+            let r0 = a.try_read(10..60).unwrap();
+            let r1 = a.try_read(50..80).unwrap();
+            let r2 = a.try_read(50..80).unwrap();
+            assert_eq!(r0[45], 55);
+            assert_eq!(r1[5], 55);
+            assert_eq!(r2[..], r1[..]);
+            assert!(a.try_lock(5..11).is_err());
+            assert!(a.try_lock(79..90).is_err());
+            let mut w = a.try_lock(80..90).unwrap();
+            assert!(a.try_read(85..95).is_err());
+            w[0] = 800;
+            drop(r1);
+            assert!(a.try_lock(70..71).is_err());
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner()[80], 800);
+    }
+
+    #[test]
+    fn test_downgrade() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        {
+            let mut w = a.try_lock(20..60).unwrap();
+            w[0] = 20;
+            let r0 = VecRangeLockGuard::downgrade(w);
+            assert_eq!(r0[0], 20);
+            let r1 = a.try_read(0..100).unwrap();
+            assert_eq!(r1[20], 20);
+            assert!(a.try_lock(59..60).is_err());
+            drop(r0);
+            assert!(a.try_lock(59..60).is_err());
+            drop(r1);
+            let _w = a.try_lock(0..100).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_upgradable_read() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<i32>>(), 4);
+        {
+            let u = a.try_upgradable_read(20..60).unwrap();
+            assert_eq!(u[0], 20);
+            assert!(a.try_upgradable_read(59..70).is_err());
+            assert!(a.try_lock(0..21).is_err());
+            let r = a.try_read(50..70).unwrap();
+            let u = VecRangeLockUpgradableReadGuard::try_upgrade(u).unwrap_err();
+            assert!(a.try_read(0..30).is_ok());
+            drop(r);
+            let mut w = VecRangeLockUpgradableReadGuard::try_upgrade(u).unwrap();
+            w[0] = 200;
+            assert!(a.try_read(59..60).is_err());
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner()[20], 200);
+    }
+
+    #[test]
+    fn test_lock_owned() {
+        let a = Arc::new(VecRangeLock::new(vec![1_i32, 2, 3, 4]));
+        let mut g0 = Arc::clone(&a).try_lock_owned(0..2).unwrap();
+        assert!(Arc::clone(&a).try_lock_owned(1..3).is_err());
+        let j = thread::spawn(move || {
+            g0[1] = 20;
+        });
+        j.join().unwrap();
+        assert!(a.is_all_unlocked());
+        assert_eq!(Arc::strong_count(&a), 1);
+        assert_eq!(Arc::try_unwrap(a).unwrap().into_inner(), vec![1, 20, 3, 4]);
+    }
+
+    #[test]
+    #[cfg(any(feature = "parking_lot", feature = "spinlock"))]
+    fn test_parking_lot_no_poison() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ranges = a.ranges[0].lock().unwrap();
+            panic!("Panicking while holding the mutex");
+        }));
+        assert!(res.is_err());
+        assert!(!a.is_poisoned());
+        let _g = a.try_lock(0..1).unwrap();
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "parking_lot", feature = "spinlock")))]
+    fn test_clear_poison() {
+        let mut a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert!(!a.is_poisoned());
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ranges = a.ranges[0].lock().unwrap();
+            panic!("Poisoning the lock");
+        }));
+        assert!(res.is_err());
+        assert!(a.is_poisoned());
+        // The range is not locked on a poisoned error.
+        assert_eq!(a.try_lock(0..1).unwrap_err(), LockError::Poisoned);
+        assert_eq!(a.free_ranges(), vec![0..4]);
+        match a.try_lock_poisonable(0..1) {
+            Err(TryLockError::Poisoned(e)) => {
+                // The poisoned guard holds the lock.
+                let _g = e.into_inner();
+                assert!(a.try_lock(0..1).is_err());
+            }
+            _ => panic!("Lock must be poisoned"),
+        }
+        a.clear_poison();
+        assert!(!a.is_poisoned());
+        let _g = a.try_lock(0..1).unwrap();
+        drop(_g);
+
+        mem::forget(a.try_lock(2..3).unwrap());
+        assert!(a.try_lock(2..3).is_err());
+        a.force_reset();
+        assert!(a.is_all_unlocked());
+        let _g = a.try_lock(2..3).unwrap();
+    }
+
+    #[test]
+    fn test_blocking_lock() {
+        let a = VecRangeLock::with_shards(vec![0_u32; 100], 4);
+        thread::scope(|s| {
+            for t in 0..8 {
+                let a = &a;
+                s.spawn(move || {
+                    for i in 0..200 {
+                        let begin = (i * 7 + t * 13) % 90;
+                        let mut g = a.lock(begin..begin + 10).unwrap();
+                        g[0] += 1;
+                        g[9] += 1;
+                    }
+                });
+            }
+        });
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.waiters.num_waiting(), 0);
+        assert_eq!(a.into_inner().iter().sum::<u32>(), 8 * 200 * 2);
+    }
+
+    #[test]
+    fn test_blocking_lock_fair() {
+        let a = VecRangeLock::new_fair(vec![0_u32; 4]);
+        let order = Mutex::new(Vec::new());
+        let g = a.try_lock(0..4).unwrap();
+        thread::scope(|s| {
+            for t in 0..4 {
+                let (a, order) = (&a, &order);
+                // Wait for the previous thread to be queued.
+                while a.waiters.num_waiting() != t {
+                    thread::yield_now();
+                }
+                s.spawn(move || {
+                    let _g = a.lock(1..3).unwrap();
+                    order.lock().unwrap().push(t);
+                });
+            }
+            while a.waiters.num_waiting() != 4 {
+                thread::yield_now();
+            }
+            drop(g);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_blocking_lock_priority() {
+        let a = VecRangeLock::new(vec![0_u32; 4]);
+        let order = Mutex::new(Vec::new());
+        let g = a.try_lock(0..4).unwrap();
+        thread::scope(|s| {
+            for (t, priority) in [1, 5, 3].into_iter().enumerate() {
+                let (a, order) = (&a, &order);
+                // Wait for the previous thread to be queued.
+                while a.waiters.num_waiting() != t {
+                    thread::yield_now();
+                }
+                s.spawn(move || {
+                    let _g = a.lock_with_priority(1..3, priority).unwrap();
+                    order.lock().unwrap().push(priority);
+                });
+            }
+            while a.waiters.num_waiting() != 3 {
+                thread::yield_now();
+            }
+            drop(g);
+        });
+        assert_eq!(*order.lock().unwrap(), vec![5, 3, 1]);
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_blocking_lock_backoff() {
+        use crate::backoff::{ParkBackoff, SpinBackoff};
+
+        let a = VecRangeLock::new(vec![0_u32; 10]);
+        thread::scope(|s| {
+            for t in 0..4 {
+                let a = &a;
+                s.spawn(move || {
+                    for _ in 0..200 {
+                        let mut g = if t % 2 == 0 {
+                            a.lock_with_backoff(2..8, SpinBackoff).unwrap()
+                        } else {
+                            a.lock_with_backoff(0..5, ParkBackoff).unwrap()
+                        };
+                        g[2] += 1;
+                    }
+                });
+            }
+        });
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner()[2..5], [400, 0, 400]);
+    }
+
+    #[test]
+    fn test_lock_spin() {
+        let a = VecRangeLock::with_shards(vec![0_u32; 10], 2);
+        thread::scope(|s| {
+            for t in 0..4 {
+                let a = &a;
+                s.spawn(move || {
+                    for _ in 0..500 {
+                        let mut g = if t % 2 == 0 {
+                            a.lock_spin(2..8).unwrap()
+                        } else {
+                            a.lock(0..5).unwrap()
+                        };
+                        g[2] += 1;
+                    }
+                });
+            }
+        });
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner()[2..5], [1000, 0, 1000]);
+    }
+
+    #[test]
+    fn test_try_lock_aligned() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        {
+            let (g, r) = a.try_lock_aligned(3..5, 4).unwrap();
+            assert_eq!(r, 0..8);
+            assert_eq!(g.len(), 8);
+            assert!(a.try_lock(7..8).is_err());
+            assert!(matches!(
+                a.try_lock_aligned(9..10, 16),
+                Err(TryLockError::WouldBlock)
+            ));
+            let (_g, r) = a.try_lock_aligned(8..16, 8).unwrap();
+            assert_eq!(r, 8..16);
+            let (_g, r) = a.try_lock_aligned(97..98, 64).unwrap();
+            assert_eq!(r, 64..100);
+            let (_g, r) = a.try_lock_aligned(50..50, 16).unwrap();
+            assert_eq!(r, 50..50);
+            let (_g, r) = a.try_lock_aligned(20..21, 1).unwrap();
+            assert_eq!(r, 20..21);
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    #[should_panic(expected = "align is not a power of two")]
+    fn test_try_lock_aligned_not_pow2() {
+        let a = VecRangeLock::new(vec![0_i32; 100]);
+        let _ = a.try_lock_aligned(3..5, 6);
+    }
+
+    #[test]
+    fn test_try_lock_iter_mut() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        {
+            let mut it = a.try_lock_iter_mut(2..5).unwrap();
+            assert!(it.is_locked());
+            assert_eq!(it.remaining(), 3);
+            assert!(a.try_lock(4..5).is_err());
+            assert!(matches!(
+                a.try_lock_iter_mut(..3),
+                Err(TryLockError::WouldBlock)
+            ));
+            *it.next().unwrap() = 20;
+            assert_eq!(it.remaining(), 2);
+        }
+        assert!(a.is_all_unlocked());
+        let mut it = a.try_lock_iter_mut(7..).unwrap();
+        let mut sum = 0;
+        while let Some(x) = it.next() {
+            *x += 1;
+            sum += *x;
+        }
+        assert_eq!(sum, 27);
+        assert!(!it.is_locked());
+        assert!(a.is_all_unlocked());
+        assert!(it.next().is_none());
+        let mut empty = a.try_lock_iter_mut(5..5).unwrap();
+        assert!(empty.next().is_none());
+        drop((it, empty));
+        assert_eq!(a.into_inner(), vec![0, 1, 20, 3, 4, 5, 6, 8, 9, 10]);
+    }
+
+    #[test]
+    fn test_try_lock_opt() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        let g = a.try_lock_opt(1..3).unwrap();
+        assert!(a.try_lock_opt(2..4).is_none());
+        assert!(a.try_lock_opt(3..5).is_none());
+        assert_eq!(a.try_lock_opt(3..).unwrap()[..], [4]);
+        assert_eq!(g[..], [2, 3]);
+        drop(g);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _g = a.try_lock(0..1).unwrap();
+            panic!("Panicking while holding the guard");
+        }));
+        assert!(res.is_err());
+        assert!(a.try_lock_opt(0..1).is_none());
+    }
+
+    #[test]
+    fn test_try_lock_retry() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert!(matches!(
+            a.try_lock_retry(0..2, 0),
+            Err(TryLockError::WouldBlock)
+        ));
+        let g = a.try_lock_retry(0..2, 1).unwrap();
+        assert!(matches!(
+            a.try_lock_retry(1..3, 10),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert_eq!(a.try_lock_retry(2..4, 10).unwrap()[..], [3, 4]);
+        drop(g);
+        thread::scope(|s| {
+            let g = a.try_lock(..).unwrap();
+            let h = s.spawn(|| a.try_lock_retry(1..2, usize::MAX).unwrap()[0]);
+            thread::sleep(std::time::Duration::from_millis(10));
+            drop(g);
+            assert_eq!(h.join().unwrap(), 2);
+        });
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_for_each_locked_range() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        let locked = || {
+            let mut locked = Vec::new();
+            a.for_each_locked_range(|range| locked.push(range));
+            locked
+        };
+        assert!(locked().is_empty());
+        let _g0 = a.try_lock(60..70).unwrap();
+        let _g1 = a.try_lock(20..55).unwrap();
+        let _g2 = a.try_read(80..90).unwrap();
+        let _g3 = a.try_read(85..95).unwrap();
+        let _g4 = a.try_lock(0..1).unwrap();
+        assert_eq!(locked(), vec![0..1, 20..55, 60..70, 80..85, 85..90, 90..95]);
+        drop(_g4);
+        assert_eq!(locked()[0], 20..55);
+    }
+
+    #[test]
+    fn test_locked_element_count() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        assert_eq!(a.locked_element_count(), 0);
+        assert_eq!(a.utilization(), 0.0);
+        let _g0 = a.try_lock(10..40).unwrap();
+        let _g1 = a.try_read(50..60).unwrap();
+        let _g2 = a.try_read(55..70).unwrap();
+        let _g3 = a.try_lock(70..70).unwrap();
+        assert_eq!(a.locked_element_count(), 50);
+        assert_eq!(a.utilization(), 0.5);
+        drop(_g0);
+        assert_eq!(a.locked_element_count(), 20);
+
+        let a: VecRangeLock<u8> = VecRangeLock::new(vec![]);
+        assert_eq!(a.utilization(), 0.0);
+    }
+
+    #[test]
+    fn test_debug_entries() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        assert_eq!(a.debug_entries().count(), 0);
+        let _g0 = a.try_lock(10..40).unwrap();
+        let _g1 = a.try_read(50..60).unwrap();
+        let entries = a.debug_entries();
+        // Locking is possible while the entries are iterated.
+        let _g2 = a.try_lock(0..5).unwrap();
+        assert_eq!(entries.collect::<Vec<_>>(), vec![(10, 40), (50, 60)]);
+    }
+
+    #[test]
+    fn test_free_ranges() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        assert_eq!(a.free_ranges(), vec![0..100]);
+        let _g0 = a.try_lock(0..10).unwrap();
+        let _g1 = a.try_lock(20..60).unwrap();
+        let _g2 = a.try_read(70..80).unwrap();
+        let _g3 = a.try_read(75..90).unwrap();
+        assert_eq!(a.free_ranges(), vec![10..20, 60..70, 90..100]);
+        assert_eq!(a.find_free(10), Some(10..20));
+        assert_eq!(a.find_free(11), None);
+        let _g4 = a.try_lock(90..100).unwrap();
+        assert_eq!(a.free_ranges(), vec![10..20, 60..70]);
+    }
+
+    #[test]
+    fn test_try_lock_biased_free() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        let _g0 = a.try_lock(10..30).unwrap();
+        let _g1 = a.try_read(50..60).unwrap();
+        let (g2, r2) = a.try_lock_biased_free(10).unwrap();
+        assert_eq!(r2, 0..10);
+        assert_eq!(g2.len(), 10);
+        let (_g3, r3) = a.try_lock_biased_free(15).unwrap();
+        assert_eq!(r3, 30..45);
+        let (_g4, r4) = a.try_lock_biased_free(5).unwrap();
+        assert_eq!(r4, 45..50);
+        assert!(matches!(
+            a.try_lock_biased_free(41),
+            Err(TryLockError::WouldBlock)
+        ));
+        let (_g5, r5) = a.try_lock_biased_free(40).unwrap();
+        assert_eq!(r5, 60..100);
+        drop(g2);
+        let (_g6, r6) = a.try_lock_biased_free(3).unwrap();
+        assert_eq!(r6, 0..3);
+        let (g7, r7) = a.try_lock_biased_free(0).unwrap();
+        assert_eq!(r7.len(), 0);
+        assert!(g7.is_empty());
+    }
+
+    #[test]
+    fn test_try_lock_strided() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        {
+            let mut even = a.try_lock_strided(0, 2).unwrap();
+            let odd = a.try_lock_strided(1, 2).unwrap();
+            assert_eq!(even.len(), 5);
+            assert_eq!(odd.len(), 5);
+            assert_eq!(odd[4], 9);
+            assert!(a.try_lock_strided(4, 2).is_err());
+            assert!(a.try_lock(5..6).is_err());
+            drop(odd);
+            assert!(a.try_lock(5..6).is_ok());
+            assert!(a.try_lock(4..6).is_err());
+            even[2] = 40;
+        }
+        assert!(a.try_lock(..).is_ok());
+        {
+            let _g = a.try_lock(3..4).unwrap();
+            assert!(matches!(
+                a.try_lock_strided(0, 3),
+                Err(TryLockError::WouldBlock)
+            ));
+            // The failed attempt did not lock anything.
+            assert!(a.try_lock(0..3).is_ok());
+            let g = a.try_lock_strided(1, 3).unwrap();
+            assert_eq!(g.len(), 3);
+            assert_eq!((g[0], g[1], g[2]), (1, 40, 7));
+            let all = a.try_lock_strided(8, 1).unwrap();
+            assert_eq!(all.len(), 2);
+        }
+        let g = a.try_lock_strided(10, 4).unwrap();
+        assert!(g.is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Index is out of bounds")]
+    fn test_try_lock_strided_oob() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let g = a.try_lock_strided(1, 3).unwrap();
+        let _ = g[3];
+    }
+
+    #[test]
+    fn test_read_range_into() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5]);
+        let mut dst = [0; 3];
+        a.read_range_into(2.., &mut dst).unwrap();
+        assert_eq!(dst, [3, 4, 5]);
+        {
+            let _r = a.try_read(0..2).unwrap();
+            a.read_range_into(1..4, &mut dst).unwrap();
+            assert_eq!(dst, [2, 3, 4]);
+            let _g = a.try_lock(4..5).unwrap();
+            assert!(matches!(
+                a.read_range_into(2..5, &mut dst),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert_eq!(dst, [2, 3, 4]);
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    #[should_panic(expected = "Destination length does not match")]
+    fn test_read_range_into_len_mismatch() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        let mut dst = [0; 2];
+        let _ = a.read_range_into(.., &mut dst);
+    }
+
+    #[test]
+    fn test_write_range_from() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5]);
+        a.write_range_from(..2, &[10, 20]).unwrap();
+        {
+            let _r = a.try_read(4..5).unwrap();
+            assert!(matches!(
+                a.write_range_from(3..5, &[40, 50]),
+                Err(TryLockError::WouldBlock)
+            ));
+            a.write_range_from(2..4, &[30, 40]).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![10, 20, 30, 40, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source length does not match")]
+    fn test_write_range_from_len_mismatch() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3]);
+        let _ = a.write_range_from(1.., &[1]);
+    }
+
+    #[test]
+    #[cfg(feature = "stats")]
+    fn test_max_concurrent_ranges() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        assert_eq!(a.max_concurrent_ranges(), 0);
+        {
+            // Spans multiple shards.
+            let _g0 = a.try_lock(0..60).unwrap();
+            let _g1 = a.try_read(70..80).unwrap();
+            let _g2 = a.try_read(75..90).unwrap();
+            assert!(a.try_lock(50..70).is_err());
+            assert_eq!(a.max_concurrent_ranges(), 3);
+        }
+        {
+            let parts = a.partition(4).unwrap();
+            assert_eq!(a.max_concurrent_ranges(), 4);
+            drop(parts);
+            let _s = a.try_lock_strided(0, 20).unwrap();
+            assert_eq!(a.max_concurrent_ranges(), 5);
+        }
+        let g = a.try_lock(0..1).unwrap();
+        mem::forget(g);
+        unsafe { a.force_unlock(&(0..1)) };
+        let _g = a.try_lock(..).unwrap();
+        assert_eq!(a.max_concurrent_ranges(), 5);
+        assert_eq!(a.stats.held(), 1);
+    }
+
+    #[test]
+    fn test_try_lock_many() {
+        let a = VecRangeLock::with_shards((0..20).collect::<Vec<i32>>(), 4);
+        {
+            let mut g = a.try_lock_many(&[15..20, 2..3, 7..7, 5..12]).unwrap();
+            assert_eq!(g.len(), 4);
+            assert_eq!(g.get(1), [2]);
+            assert!(g.get(2).is_empty());
+            g.get_mut(3)[0] = 50;
+            assert!(a.try_lock(11..15).is_err());
+            assert!(a.try_lock(12..15).is_ok());
+            assert!(matches!(
+                a.try_lock_many(&[0..2, 12..16]),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert!(a.try_lock(0..2).is_ok());
+        }
+        assert!(a.is_all_unlocked());
+        assert!(a.try_lock_many(&[]).unwrap().is_empty());
+        assert_eq!(a.into_inner()[5], 50);
+    }
+
+    #[test]
+    fn test_try_lock_sorted() {
+        let a = VecRangeLock::with_shards((0_i32..20).collect(), 4);
+        let mut ranges = [15..18, 2..4, 8..8, 5..10];
+        {
+            let mut g = a.try_lock_sorted(&mut ranges).unwrap();
+            assert_eq!(ranges, [2..4, 5..10, 8..8, 15..18]);
+            assert_eq!(g.get(0), [2, 3]);
+            assert_eq!(g.get(3), [15, 16, 17]);
+            g.get_mut(1)[0] = 50;
+            assert!(a.try_lock(9..10).is_err());
+            let mut again = ranges.clone();
+            assert!(matches!(
+                a.try_lock_sorted(&mut again),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner()[5], 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "try_lock_sorted: The ranges overlap")]
+    fn test_try_lock_sorted_overlap() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let _ = a.try_lock_sorted(&mut [6..9, 0..2, 1..3]);
+    }
+
+    #[test]
+    fn test_try_lock_all_or_none() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        assert_eq!(
+            a.try_lock_all_or_none(&[6..8, 0..2]).unwrap().ranges,
+            [6..8, 0..2]
+        );
+    }
+
+    #[test]
+    fn test_try_lock_all_or_none_concurrent() {
+        const ITERATIONS: usize = 2000;
+        let a = VecRangeLock::with_shards(vec![0_usize; 32], 4);
+        let sets = [
+            vec![0..2, 10..12, 20..22],
+            vec![1..3, 21..23],
+            vec![11..13, 30..32],
+            vec![2..11, 31..32],
+        ];
+        let successes: Vec<usize> = thread::scope(|s| {
+            let handles: Vec<_> = sets
+                .iter()
+                .map(|set| {
+                    let a = &a;
+                    s.spawn(move || {
+                        let mut count = 0;
+                        for _ in 0..ITERATIONS {
+                            match a.try_lock_all_or_none(set) {
+                                Ok(mut g) => {
+                                    for i in 0..g.len() {
+                                        g.get_mut(i).iter_mut().for_each(|x| *x += 1);
+                                    }
+                                    count += 1;
+                                }
+                                Err(TryLockError::WouldBlock) => (),
+                                Err(TryLockError::Poisoned(_)) => panic!("Poisoned"),
+                            }
+                        }
+                        count
+                    })
+                })
+                .collect();
+            handles.into_iter().map(|h| h.join().unwrap()).collect()
+        });
+        // Failed attempts must not leave any locked range behind.
+        assert!(a.is_all_unlocked());
+        // Each element is written by exactly the successful attempts of the sets covering it.
+        let mut expected = vec![0_usize; 32];
+        for (set, count) in sets.iter().zip(&successes) {
+            for range in set {
+                expected[range.clone()].iter_mut().for_each(|x| *x += count);
+            }
+        }
+        let data = a.into_inner();
+        assert_eq!(data, expected);
+        // These elements are covered by one set only
+        // and carry the exact success count of that set.
+        assert_eq!(data[20], successes[0]);
+        assert_eq!(data[12], successes[2]);
+        assert_eq!(data[5], successes[3]);
+        assert_eq!(data[22], successes[1]);
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap with each other")]
+    fn test_try_lock_many_overlap() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let _ = a.try_lock_many(&[6..8, 0..2, 1..3]);
+    }
+
+    #[test]
+    fn test_poison_data() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 2);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut g = a.try_lock(2..4).unwrap();
+            g[0] = 30;
+            panic!("Panicking while holding the guard");
+        }));
+        assert!(res.is_err());
+        assert!(a.is_all_unlocked());
+        assert!(a.is_poisoned());
+        assert_eq!(a.try_lock(3..6).unwrap_err(), LockError::Poisoned);
+        assert!(matches!(a.try_read(0..3), Err(TryLockError::Poisoned(_))));
+        assert!(a.try_lock(0..2).is_ok());
+        assert!(a.try_lock(4..6).is_ok());
+        match a.try_lock_poisonable(2..3) {
+            Err(TryLockError::Poisoned(e)) => assert_eq!(*e.into_inner(), [30]),
+            _ => panic!("Data must be poisoned"),
+        }
+        assert!(a.is_all_unlocked());
+        a.clear_poison();
+        assert!(!a.is_poisoned());
+        assert!(a.try_lock(0..6).is_ok());
+    }
+
+    #[test]
+    fn test_huge_zst() {
+        let a = VecRangeLock::new(vec![(); usize::MAX]);
+        let g = a.try_lock(usize::MAX - 2..usize::MAX).unwrap();
+        assert_eq!(g.len(), 2);
+        assert!(a.try_lock(usize::MAX - 1..usize::MAX).is_err());
+        assert!(a.try_lock(0..usize::MAX - 2).is_ok());
+    }
+
+    #[test]
+    fn test_clone_region() {
+        let a = VecRangeLock::new(vec![String::from("a"), "b".into(), "c".into()]);
+        assert_eq!(a.clone_region(1..).unwrap(), vec!["b", "c"]);
+        assert!(a.clone_region(1..1).unwrap().is_empty());
+        {
+            let _g = a.try_lock(0..2).unwrap();
+            assert!(matches!(
+                a.clone_region(1..3),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert_eq!(a.clone_region(2..3).unwrap(), vec!["c"]);
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_snapshot_region() {
+        let a = VecRangeLock::with_shards(vec![1_u8, 2, 3, 4, 5], 2);
+        assert_eq!(a.snapshot_region::<3>(2..).unwrap(), [3, 4, 5]);
+        assert_eq!(a.snapshot_region::<0>(5..).unwrap(), []);
+        {
+            let _g = a.try_lock(0..2).unwrap();
+            assert!(matches!(
+                a.snapshot_region::<2>(1..3),
+                Err(TryLockError::WouldBlock)
+            ));
+            let _r = a.try_read(2..4).unwrap();
+            assert_eq!(a.snapshot_region(2..4).unwrap(), [3, 4]);
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    #[should_panic(expected = "Range length does not match")]
+    fn test_snapshot_region_len() {
+        let a = VecRangeLock::new(vec![1_u8, 2, 3]);
+        let _ = a.snapshot_region::<2>(..);
+    }
+
+    #[test]
+    fn test_hash_region() {
+        use std::collections::hash_map::DefaultHasher;
+
+        let digest = |data: &[u32]| {
+            let mut hasher = DefaultHasher::new();
+            u32::hash_slice(data, &mut hasher);
+            hasher.finish()
+        };
+        let region = |a: &VecRangeLock<u32>, range: Range<usize>| {
+            let mut hasher = DefaultHasher::new();
+            a.hash_region(range, &mut hasher).map(|()| hasher.finish())
+        };
+        let a = VecRangeLock::with_shards(vec![1_u32, 2, 3, 4, 5], 2);
+        assert_eq!(region(&a, 1..4).unwrap(), digest(&[2, 3, 4]));
+        assert_ne!(region(&a, 1..4).unwrap(), region(&a, 0..3).unwrap());
+        {
+            let _r = a.try_read(0..2).unwrap();
+            assert_eq!(region(&a, 0..2).unwrap(), digest(&[1, 2]));
+            let _g = a.try_lock(4..5).unwrap();
+            assert!(matches!(region(&a, 3..5), Err(TryLockError::WouldBlock)));
+        }
+        a.try_lock(2..3).unwrap()[0] = 30;
+        assert_eq!(region(&a, 1..4).unwrap(), digest(&[2, 30, 4]));
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_try_lock_shared_exact() {
+        let a = VecRangeLock::with_shards((0_u32..20).collect(), 4);
+        let r0 = a.try_lock_shared_exact(4..12).unwrap();
+        let r1 = a.try_lock_shared_exact(4..12).unwrap();
+        assert_eq!(r1[..2], [4, 5]);
+        assert!(matches!(
+            a.try_lock_shared_exact(4..13),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert!(a.try_read(11..13).is_err());
+        assert!(a.try_lock(0..5).is_err());
+        let r2 = a.try_read(12..14).unwrap();
+        assert!(a.try_lock_shared_exact(12..14).is_err());
+        drop(r0);
+        assert!(a.try_lock(11..12).is_err());
+        drop(r1);
+        assert!(a.try_lock(4..12).is_ok());
+        drop(r2);
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_read_guard_clone() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<u32>>(), 4);
+        let g0 = a.try_read(10..60).unwrap();
+        let g1 = g0.clone();
+        let g2 = g1.clone();
+        assert_eq!(g2[..], g0[..]);
+        thread::scope(|s| {
+            for g in [g1.clone(), g2.clone()] {
+                s.spawn(move || assert_eq!(g[0], 10));
+            }
+        });
+        drop(g0);
+        drop(g1);
+        assert!(a.try_lock(59..60).is_err());
+        let g3 = a.try_read(50..70).unwrap();
+        drop(g2);
+        assert!(a.try_lock(10..50).is_ok());
+        assert!(a.try_lock(55..56).is_err());
+        drop(g3);
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_weak_read_guard() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        let weak = {
+            let g = a.try_read(1..4).unwrap();
+            let weak = g.weak();
+            assert_eq!(weak.range(), 1..4);
+            assert!(weak.is_alive());
+            let g2 = weak.upgrade().unwrap();
+            drop(g);
+            // The upgraded guard holds the range on its own.
+            assert!(!weak.is_alive());
+            assert!(weak.upgrade().is_none());
+            assert_eq!(*g2, [2, 3, 4]);
+            assert!(a.try_lock(0..2).is_err());
+            weak
+        };
+        assert!(weak.upgrade().is_none());
+        assert!(a.is_all_unlocked());
+        // A weak guard does not keep the range locked.
+        drop(a.try_read(..).unwrap().weak());
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_into_rep() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 2);
+        mem::forget(a.try_lock(2..5).unwrap());
+        let mut a = a.into_rep(1, 3).unwrap_err();
+        a.force_reset();
+        let a = a.into_rep(1, 3).unwrap();
+        assert_eq!(a.try_lock(2).unwrap()[1], [6]);
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn test_fast_path() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 10], 2);
+        {
+            // The first range is locked via the fast path.
+            let _g = a.try_lock(2..8).unwrap();
+            assert_eq!(format!("{a:?}"), "VecRangeLock { len: 10, locked: [2..8] }");
+            assert!(matches!(
+                a.try_append(&mut vec![1]),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert_eq!(a.free_ranges(), vec![0..2, 8..10]);
+            assert!(a.try_lock(7..9).is_err());
+            assert!(a.try_read(1..3).is_err());
+        }
+        assert!(a.is_all_unlocked());
+        {
+            let g = a.try_lock(0..2).unwrap();
+            let r = VecRangeLockGuard::downgrade(g);
+            assert!(a.try_read(0..1).is_ok());
+            assert!(a.try_lock(1..2).is_err());
+            drop(r);
+            assert!(a.try_lock(0..10).is_ok());
+        }
+        assert!(a.is_all_unlocked());
+        a.try_append(&mut vec![1]).unwrap();
+        assert_eq!(a.data_len(), 11);
+    }
+
+    #[test]
+    fn test_get_unlocked_mut() {
+        let a = VecRangeLock::new(vec![1_u32, 2, 3, 4]);
+        {
+            let mut guard = a.get_unlocked_mut(1..3);
+            guard[1] = 30;
+            assert!(a.try_lock(2..3).is_err());
+        }
+        assert!(a.try_lock(..).is_ok());
+        assert_eq!(a.into_inner(), vec![1, 2, 30, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range 2..4 is contended by 1..3")]
+    fn test_get_unlocked_mut_contended() {
+        let a = VecRangeLock::new(vec![1_u32, 2, 3, 4]);
+        let _guard = a.try_lock(1..3).unwrap();
+        let _ = a.get_unlocked_mut(2..4);
+    }
+
+    #[test]
+    fn test_try_lock_with() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<u32>>(), 4);
+        let g0 = a.try_lock(10..30).unwrap();
+        let g1 = a.try_read(60..80).unwrap();
+        let g2 = a.try_read(70..90).unwrap();
+        let mut calls = 0;
+        let mut policy = |conflicting: &[Range<usize>]| {
+            calls += 1;
+            assert_eq!(conflicting, [10..30, 60..70, 70..80, 80..90]);
+            if calls < 3 {
+                Decision::Retry
+            } else {
+                Decision::GiveUp
+            }
+        };
+        assert!(matches!(
+            a.try_lock_with(0..100, &mut policy),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert_eq!(calls, 3);
+
+        // Uncontended ranges don't call the policy.
+        let g3 = a
+            .try_lock_with(30..60, &mut |_| panic!("Not contended"))
+            .unwrap();
+        drop(g3);
+
+        thread::scope(|s| {
+            s.spawn(|| {
+                let guard = a.try_lock_with(25..65, &mut |_| Decision::Wait).unwrap();
+                assert_eq!(guard[0], 25);
+            });
+            while a.waiters.num_waiting() == 0 {
+                thread::yield_now();
+            }
+            drop(g0);
+            drop(g1);
+        });
+        drop(g2);
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_with_locked() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert_eq!(a.with_locked(1..3, |s| s.len()).unwrap(), 2);
+        {
+            let _g = a.try_lock(2..3).unwrap();
+            assert!(a
+                .with_locked(1..3, |_| panic!("Must not be called"))
+                .is_err());
+        }
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ = a.with_locked(0..4, |s| {
+                s[0] = 10;
+                panic!("Closure panicked");
+            });
+        }));
+        assert!(res.is_err());
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![10, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_try_with_locked() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert_eq!(a.try_with_locked(0..2, |s| Ok::<_, ()>(s[1])), Ok(2));
+        assert_eq!(
+            a.try_with_locked(0..2, |_| Err::<(), _>(42)),
+            Err(LockOrUserError::User(42))
+        );
+        assert!(a.is_all_unlocked());
+        {
+            let _g = a.try_lock(1..2).unwrap();
+            let res =
+                a.try_with_locked(0..2, |_| -> Result<(), ()> { panic!("Must not be called") });
+            assert!(res.unwrap_err().is_would_block());
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_guard_autotraits() {
+        fn assert_send<S: Send>() {}
+        fn assert_sync<S: Sync>() {}
+        assert_send::<VecRangeLockGuard<'static, i32>>();
+        assert_sync::<VecRangeLockGuard<'static, i32>>();
+        assert_send::<VecRangeLockReadGuard<'static, i32>>();
+        assert_sync::<VecRangeLockReadGuard<'static, i32>>();
+        assert_send::<VecRangeLockUpgradableReadGuard<'static, i32>>();
+        assert_sync::<VecRangeLockUpgradableReadGuard<'static, i32>>();
+
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        {
+            let g = a.try_read(0..4).unwrap();
+            let g = &g;
+            let sums: Vec<i32> = thread::scope(|s| {
+                let handles: Vec<_> = (0..2)
+                    .map(|i| s.spawn(move || g[i * 2..i * 2 + 2].iter().sum()))
+                    .collect();
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+            assert_eq!(sums, vec![3, 7]);
+        }
+        let mut g = a.try_lock(1..3).unwrap();
+        thread::scope(|s| {
+            s.spawn(move || {
+                g[0] = 20;
+                g[1] = 30;
+            });
+        });
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![1, 20, 30, 4]);
+    }
+
+    #[test]
+    fn test_to_vec_fill_from() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        {
+            let mut g = a.try_lock(2..4).unwrap();
+            assert_eq!(g.to_vec(), vec![3, 4]);
+            g.fill_from(&[30, 40]);
+            assert_eq!(g.to_vec(), vec![30, 40]);
+        }
+        assert_eq!(a.into_inner(), vec![1, 2, 30, 40]);
+    }
+
+    #[test]
+    fn test_fill() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        a.try_lock(..2).unwrap().fill(7);
+        let mut n = 0;
+        a.try_lock(2..).unwrap().fill_with(|| {
+            n += 1;
+            n
+        });
+        assert_eq!(a.into_inner(), vec![7, 7, 1, 2]);
+    }
+
+    #[test]
+    fn test_guard_len() {
+        let a = VecRangeLock::with_shards(vec![0_u8; 10], 2);
+        let g0 = a.try_lock(2..7).unwrap();
+        assert_eq!(VecRangeLockGuard::len(&g0), 5);
+        assert!(!VecRangeLockGuard::is_empty(&g0));
+        let g1 = a.try_lock(7..7).unwrap();
+        assert_eq!(VecRangeLockGuard::len(&g1), 0);
+        assert!(VecRangeLockGuard::is_empty(&g1));
+        assert_eq!(g0.len(), g0[..].len());
+    }
+
+    #[test]
+    fn test_guard_reborrow() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 2);
+        {
+            let mut g = a.try_lock(2..7).unwrap();
+            {
+                let mut r = g.reborrow();
+                assert_eq!(r[..], [2, 3, 4, 5, 6]);
+                r[0] = 20;
+                let mut rr = r.reborrow();
+                rr[4] = 60;
+            }
+            assert!(a.try_lock(6..7).is_err());
+            let r = VecRangeLockGuard::map(g.reborrow(), |s| &mut s[1]);
+            drop(r);
+            assert!(a.try_lock(2..3).is_err());
+            assert_eq!(g[..], [20, 3, 4, 5, 60]);
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    #[should_panic(expected = "The guard is reborrowed")]
+    fn test_guard_reborrow_downgrade() {
+        let a = VecRangeLock::new(vec![0_i32; 10]);
+        let mut g = a.try_lock(2..7).unwrap();
+        let _ = VecRangeLockGuard::downgrade(g.reborrow());
+    }
+
+    #[test]
+    fn test_guard_chunks() {
+        let a = VecRangeLock::new((0_i32..10).collect());
+        {
+            let mut g = a.try_lock(1..8).unwrap();
+            let (chunks, rem) = g.as_chunks_mut::<3>();
+            assert_eq!(chunks, [[1, 2, 3], [4, 5, 6]]);
+            assert_eq!(rem, [7]);
+            chunks[1][0] = 40;
+            rem[0] = 70;
+            let (chunks, rem) = g.as_chunks_mut::<8>();
+            assert!(chunks.is_empty());
+            assert_eq!(rem.len(), 7);
+            let lens: Vec<usize> = g.chunks_mut(2).map(|c| c.len()).collect();
+            assert_eq!(lens, [2, 2, 2, 1]);
+            for c in g.chunks_mut(4) {
+                c[0] *= 10;
+            }
+        }
+        assert_eq!(a.into_inner(), vec![0, 10, 2, 3, 40, 50, 6, 70, 8, 9]);
+    }
+
+    #[test]
+    fn test_guard_split_first_last() {
+        let a = VecRangeLock::new((0_i32..6).collect());
+        {
+            let mut g = a.try_lock(1..5).unwrap();
+            let (first, rest) = g.split_first_mut().unwrap();
+            assert_eq!((*first, &*rest), (1, &[2, 3, 4][..]));
+            *first = 10;
+            let (last, rest) = g.split_last_mut().unwrap();
+            assert_eq!((*last, &*rest), (4, &[10, 2, 3][..]));
+            *last = 40;
+            let mut g = a.try_lock(5..5).unwrap();
+            assert!(g.split_first_mut().is_none());
+            assert!(g.split_last_mut().is_none());
+        }
+        assert_eq!(a.into_inner(), vec![0, 10, 2, 3, 40, 5]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Source length does not match")]
+    fn test_fill_from_len_mismatch() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        a.try_lock(2..4).unwrap().fill_from(&[1]);
+    }
+
+    #[test]
+    fn test_split_off() {
+        let mut a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 3);
+        let b = a.split_off(4);
+        assert_eq!(a.data_len(), 4);
+        assert_eq!(b.data_len(), 2);
+        assert_eq!(b.ranges.len(), 3);
+        {
+            let _g0 = a.try_lock(3..4).unwrap();
+            let _g1 = b.try_lock(0..1).unwrap();
+            let _g2 = a.try_lock(0..3).unwrap();
+        }
+        let c = a.split_off(4);
+        assert_eq!(c.data_len(), 0);
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 4]);
+        assert_eq!(b.into_inner(), vec![5, 6]);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "parking_lot", feature = "spinlock")))]
+    fn test_split_off_poisoned() {
+        let mut a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _ranges = a.ranges[0].lock().unwrap();
+            panic!("Poisoning the lock");
+        }));
+        assert!(res.is_err());
+        let b = a.split_off(2);
+        assert!(a.is_poisoned());
+        assert_eq!(a.data_len(), 2);
+        assert_eq!(*b.try_lock(..).unwrap(), [3, 4]);
+        a.clear_poison();
+        // The slow path operation of the check has been left.
+        let _g = a.try_lock(0..2).unwrap();
+        assert!(a.fast.snapshot().is_some());
+    }
+
+    #[test]
+    fn test_merge_into() {
+        let mut a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        let b = a.split_off(4);
+        let generation = a.generation();
+        a.merge_into(b);
+        assert_eq!(a.data_len(), 10);
+        assert_ne!(a.generation(), generation);
+        {
+            let _g0 = a.try_lock(0..5).unwrap();
+            let _g1 = a.try_lock(5..).unwrap();
+            assert_eq!(_g1[..], [5, 6, 7, 8, 9]);
+        }
+        a.merge_into(VecRangeLock::new(vec![]));
+        assert_eq!(a.data_len(), 10);
+
+        let b = VecRangeLock::new(vec![10_i32, 11, 12]);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _g = b.try_lock(1..2).unwrap();
+            panic!("Panicking while holding the guard");
+        }));
+        assert!(res.is_err());
+        a.merge_into(b);
+        assert!(a.try_lock(10..11).is_ok());
+        assert_eq!(a.try_lock(11..12).unwrap_err(), LockError::Poisoned);
+        assert_eq!(a.into_inner(), (0..13).collect::<Vec<i32>>());
+    }
+
+    #[test]
+    #[should_panic(expected = "Ranges are still locked")]
+    fn test_merge_into_leaked() {
+        let mut a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        let b = VecRangeLock::new(vec![5_i32]);
+        std::mem::forget(b.try_lock(0..1).unwrap());
+        a.merge_into(b);
+    }
+
+    #[test]
+    #[should_panic(expected = "Ranges are still locked")]
+    fn test_split_off_leaked() {
+        let mut a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        std::mem::forget(a.try_lock(0..1).unwrap());
+        a.split_off(2);
+    }
+
+    #[test]
+    fn test_try_append() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        {
+            let _g = a.try_lock(3..4).unwrap();
+            let mut other = vec![5];
+            assert!(matches!(
+                a.try_append(&mut other),
+                Err(TryLockError::WouldBlock)
+            ));
+            assert_eq!(other, vec![5]);
+        }
+        let empty = a.try_lock(4..4).unwrap();
+        a.try_append(&mut vec![5, 6]).unwrap();
+        a.try_extend([7, 8]).unwrap();
+        assert!(empty.is_empty());
+        drop(empty);
+        assert_eq!(a.data_len(), 8);
+        {
+            let g0 = a.try_lock(3..6).unwrap();
+            let _g1 = a.try_lock(6..).unwrap();
+            assert!(a.try_lock(5..7).is_err());
+            assert_eq!(g0[..], [4, 5, 6]);
+        }
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 4, 5, 6, 7, 8]);
+    }
+
+    #[test]
+    fn test_capacity() {
+        let mut data = Vec::with_capacity(10);
+        data.extend([1_i32, 2]);
+        let a = VecRangeLock::with_shards(data, 2);
+        assert!(a.capacity() >= 10);
+        {
+            let _g = a.try_read(0..1).unwrap();
+            assert!(a.capacity() >= 10);
+            assert!(matches!(
+                a.try_shrink_to_fit(),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        a.try_shrink_to_fit().unwrap();
+        assert!(a.capacity() >= 2 && a.capacity() < 10);
+        assert_eq!(a.into_inner(), vec![1, 2]);
+    }
+
+    #[test]
+    fn test_partition() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 7], 3);
+        {
+            let parts = a.partition(3).unwrap();
+            let ranges: Vec<_> = parts.iter().map(|p| p.range.clone()).collect();
+            assert_eq!(ranges, vec![0..3, 3..5, 5..7]);
+            assert!(a.try_lock(1..2).is_err());
+        }
+        {
+            let parts = a.partition(10).unwrap();
+            assert_eq!(parts.iter().filter(|p| !p.is_empty()).count(), 7);
+            assert_eq!(parts[6].len(), 1);
+            assert!(parts[7].is_empty());
+        }
+        {
+            let _g = a.try_lock(6..7).unwrap();
+            assert!(matches!(a.partition(2), Err(TryLockError::WouldBlock)));
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_partition_ranges_aligned() {
+        let a = VecRangeLock::new(vec![0_u8; 1000]);
+        let ranges = a.partition_ranges_aligned(4, 64);
+        assert_eq!(ranges.len(), 4);
+        assert_eq!(ranges[0].start, 0);
+        assert_eq!(ranges[3].end, 1000);
+        for w in ranges.windows(2) {
+            assert_eq!(w[0].end, w[1].start);
+            assert_eq!((a.data_ptr() as usize + w[1].start) % 64, 0);
+            assert!(w[1].start.abs_diff(w[0].start) <= 250 + 64);
+        }
+
+        // More partitions than cache lines.
+        let a = VecRangeLock::new(vec![0_u64; 10]);
+        let ranges = a.partition_ranges_aligned(5, 64);
+        assert_eq!(ranges.len(), 5);
+        assert_eq!(ranges.iter().map(|r| r.len()).sum::<usize>(), 10);
+        assert!(ranges.windows(2).all(|w| w[0].end == w[1].start));
+
+        // Zero sized elements fall back to partition_ranges().
+        let a = VecRangeLock::new(vec![(); 10]);
+        assert_eq!(a.partition_ranges_aligned(3, 64), a.partition_ranges(3));
+    }
+
+    #[test]
+    fn test_partition_into() {
+        let a = Arc::new(VecRangeLock::new(vec![0_i32; 5]));
+        {
+            let parts = Arc::clone(&a).partition_into(2).unwrap();
+            assert_eq!(Arc::strong_count(&a), 3);
+            thread::scope(|s| {
+                for (i, mut part) in parts.into_iter().enumerate() {
+                    s.spawn(move || part.fill(i as i32 + 1));
+                }
+            });
+        }
+        assert!(a.is_all_unlocked());
+        assert!(matches!(
+            Arc::clone(&a).partition_into(1),
+            Ok(ref p) if p.len() == 1
+        ));
+        let a = Arc::try_unwrap(a).unwrap();
+        assert_eq!(a.into_inner(), vec![1, 1, 1, 2, 2]);
+    }
+
+    #[test]
+    fn test_try_lock_many_owned() {
+        let a = Arc::new(VecRangeLock::with_shards(vec![0_i32; 10], 2));
+        let guards = Arc::clone(&a)
+            .try_lock_many_owned(&[6..9, 0..2, 4..4])
+            .unwrap();
+        assert_eq!(Arc::strong_count(&a), 4);
+        assert_eq!(
+            guards.iter().map(|g| g.len()).collect::<Vec<_>>(),
+            [3, 2, 0]
+        );
+        assert!(matches!(
+            Arc::clone(&a).try_lock_many_owned(&[2..4, 8..10]),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert!(a.try_lock(2..6).is_ok());
+        let threads: Vec<_> = guards
+            .into_iter()
+            .enumerate()
+            .map(|(i, mut g)| thread::spawn(move || g.fill(i as i32 + 1)))
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        assert!(a.is_all_unlocked());
+        let a = Arc::try_unwrap(a).unwrap();
+        assert_eq!(a.into_inner(), vec![2, 2, 0, 0, 0, 0, 1, 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_try_lock_partition() {
+        let a = VecRangeLock::new(vec![0_i32; 11]);
+        assert_eq!(a.partition_ranges(4), vec![0..3, 3..6, 6..9, 9..11]);
+        assert_eq!(a.partition_ranges(1), vec![0..11]);
+        let g1 = a.try_lock_partition(4, 1).unwrap();
+        assert_eq!(g1.range, 3..6);
+        assert!(a.try_lock_partition(4, 1).is_err());
+        assert!(a.try_lock_partition(2, 0).is_err());
+        let g3 = a.try_lock_partition(4, 3).unwrap();
+        assert_eq!(g3.range, 9..11);
+    }
+
+    #[test]
+    #[should_panic(expected = "i must be smaller than n")]
+    fn test_try_lock_partition_oob() {
+        let a = VecRangeLock::new(vec![0_i32; 5]);
+        let _ = a.try_lock_partition(2, 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "n must not be 0")]
+    fn test_partition_zero() {
+        let a = VecRangeLock::new(vec![0_i32; 5]);
+        let _ = a.partition(0);
+    }
+
+    #[test]
+    #[allow(clippy::reversed_empty_ranges)]
+    fn test_try_lock_checked() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert_eq!(
+            a.try_lock_checked(3..2).unwrap_err(),
+            RangeError::StartAfterEnd { start: 3, end: 2 }
+        );
+        assert_eq!(
+            a.try_lock_checked(0..5).unwrap_err(),
+            RangeError::OutOfBounds { data_len: 4 }
+        );
+        assert_eq!(
+            a.try_lock_checked(..=usize::MAX).unwrap_err(),
+            RangeError::OutOfBounds { data_len: 4 }
+        );
+        assert_eq!(
+            a.try_lock_checked((Bound::Excluded(usize::MAX), Bound::Unbounded))
+                .unwrap_err(),
+            RangeError::OutOfBounds { data_len: 4 }
+        );
+        let g = a.try_lock_checked(4..4).unwrap().unwrap();
+        assert!(g.is_empty());
+        let g = a
+            .try_lock_checked((Bound::Excluded(0), Bound::Included(2)))
+            .unwrap()
+            .unwrap();
+        assert_eq!(g[..], [2, 3]);
+        assert!(a.try_lock_checked(2..3).unwrap().is_err());
+    }
+
+    #[test]
+    fn test_force_unlock() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 3);
+        mem::forget(a.try_lock(1..5).unwrap());
+        mem::forget(a.try_read(5..6).unwrap());
+        assert!(a.try_lock(0..2).is_err());
+        // SAFETY: The guards have been forgotten.
+        unsafe { a.force_unlock(&(4..5)) };
+        assert!(a.try_lock(5..6).is_err());
+        let _g = a.try_lock(0..5).unwrap();
+        // SAFETY: The guard has been forgotten.
+        unsafe { a.force_unlock(&(5..6)) };
+        let _g = a.try_lock(5..6).unwrap();
+    }
+
+    #[test]
+    fn test_force_unlock_all() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 3);
+        // SAFETY: No guards exist.
+        unsafe { a.force_unlock(&(0..usize::MAX)) };
+        mem::forget(a.try_lock(1..5).unwrap());
+        mem::forget(a.try_read(5..6).unwrap());
+        mem::forget(a.try_upgradable_read(0..1).unwrap());
+        assert!(!a.is_all_unlocked());
+        // SAFETY: The guards have been forgotten.
+        unsafe { a.force_unlock(&(0..usize::MAX)) };
+        assert!(a.is_all_unlocked());
+        let _g = a.try_lock(..).unwrap();
+    }
+
+    #[test]
+    fn test_data_ptr() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        {
+            let g = a.try_lock(1..3).unwrap();
+            assert_eq!(a.data_ptr().wrapping_add(1).cast_const(), g.as_ptr());
+            // SAFETY: The range 1..3 is locked.
+            unsafe { *a.data_ptr().add(2) = 30 };
+        }
+        assert_eq!(a.into_inner(), vec![1, 2, 30, 4]);
+    }
+
+    #[test]
+    fn test_as_mut_slice_unchecked() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4], 2);
+        let _g = a.try_lock(0..1).unwrap();
+        // SAFETY: No guard or other slice overlaps with the ranges.
+        let (s0, s1) = unsafe {
+            (
+                a.as_mut_slice_unchecked(1..3),
+                a.as_mut_slice_unchecked(3..),
+            )
+        };
+        s0[1] = 30;
+        s1[0] = 40;
+        assert_eq!(s0, [2, 30]);
+        // The locked ranges are not modified.
+        assert_eq!(a.debug_entries().collect::<Vec<_>>(), vec![(0, 1)]);
+        drop(_g);
+        assert_eq!(a.into_inner(), vec![1, 2, 30, 40]);
+    }
+
+    #[test]
+    fn test_debug() {
+        let a = VecRangeLock::with_shards(vec![1_i32; 10], 2);
+        let _g0 = a.try_lock(2..4).unwrap();
+        let _g1 = a.try_lock(4..8).unwrap();
+        let _g2 = a.try_read(9..10).unwrap();
+        assert_eq!(
+            format!("{a:?}"),
+            "VecRangeLock { len: 10, locked: [2..4, 4..8, 9..10] }"
+        );
+        let _ranges = a.ranges[1].lock().unwrap();
+        assert_eq!(
+            format!("{a:?}"),
+            "VecRangeLock { len: 10, locked: <locked> }"
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "tracing")]
+    fn test_tracing() {
+        use std::sync::atomic::AtomicUsize;
+        use tracing::{span, Event, Metadata, Subscriber};
+
+        /// Subscriber that counts the events.
+        struct Counter(Arc<AtomicUsize>);
+
+        impl Subscriber for Counter {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &span::Attributes<'_>) -> span::Id {
+                span::Id::from_u64(1)
+            }
+            fn record(&self, _: &span::Id, _: &span::Record<'_>) {}
+            fn record_follows_from(&self, _: &span::Id, _: &span::Id) {}
+            fn event(&self, _: &Event<'_>) {
+                self.0.fetch_add(1, Ordering::Relaxed);
+            }
+            fn enter(&self, _: &span::Id) {}
+            fn exit(&self, _: &span::Id) {}
+        }
+
+        let count = Arc::new(AtomicUsize::new(0));
+        tracing::subscriber::with_default(Counter(Arc::clone(&count)), || {
+            let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+            let g = a.try_lock(0..2).unwrap();
+            assert!(a.try_lock(1..2).is_err());
+            drop(g);
+        });
+        // Locked, contended and unlocked.
+        assert_eq!(count.load(Ordering::Relaxed), 3);
+    }
+
+    #[test]
+    fn test_guard_into_iter() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        {
+            let mut g = a.try_lock(2..).unwrap();
+            for x in &mut g {
+                *x *= 10;
+            }
+            let mut sum = 0;
+            for x in &g {
+                sum += x;
+            }
+            assert_eq!(sum, 70);
+        }
+        assert_eq!(a.into_inner(), vec![1, 2, 30, 40]);
+    }
+
+    #[test]
+    fn test_swap_ranges() {
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3, 4, 5, 6], 3);
+        a.try_swap_ranges(0..3, 3..).unwrap();
+        a.try_swap_ranges(2..2, 6..6).unwrap();
+        {
+            let _g = a.try_lock(5..6).unwrap();
+            assert!(matches!(
+                a.try_swap_ranges(0..1, 5..6),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![4, 5, 6, 1, 2, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "different lengths")]
+    fn test_swap_ranges_len() {
         let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-        let g = a.try_lock(2..4).unwrap();
-        let _ = g[2];
+        let _ = a.try_swap_ranges(0..3, 3..5);
     }
 
     #[test]
-    #[should_panic(expected = "index out of bounds")]
-    fn test_base_oob_write() {
+    #[should_panic(expected = "overlap with each other")]
+    fn test_swap_ranges_overlap() {
         let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-        let mut g = a.try_lock(2..4).unwrap();
-        g[2] = 10;
+        let _ = a.try_swap_ranges(0..3, 2..5);
     }
 
     #[test]
-    #[should_panic(expected = "guard 1 panicked")]
-    fn test_overlap0() {
+    fn test_copy_within() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        a.try_copy_within(6..9, 1).unwrap();
+        a.try_copy_within(4..4, 10).unwrap();
+        {
+            let _g = a.try_read(8..9).unwrap();
+            assert!(matches!(
+                a.try_copy_within(7..9, 0),
+                Err(TryLockError::WouldBlock)
+            ));
+            let _g = a.try_lock(1..2).unwrap();
+            assert!(matches!(
+                a.try_copy_within(8..9, 0),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), vec![0, 6, 7, 8, 4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ranges overlap")]
+    fn test_copy_within_overlap() {
         let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-        let _g0 = a.try_lock(2..4).expect("guard 0 panicked");
-        let _g1 = a.try_lock(3..5).expect("guard 1 panicked");
+        let _ = a.try_copy_within(0..3, 2);
     }
 
     #[test]
-    #[should_panic(expected = "guard 0 panicked")]
-    fn test_overlap1() {
+    #[should_panic(expected = "out of bounds")]
+    fn test_copy_within_oob() {
         let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
-        let _g1 = a.try_lock(3..5).expect("guard 1 panicked");
-        let _g0 = a.try_lock(2..4).expect("guard 0 panicked");
+        let _ = a.try_copy_within(0..3, 4);
     }
 
     #[test]
-    fn test_thread_no_overlap() {
-        let a = Arc::new(VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]));
-        let b = Arc::clone(&a);
-        let c = Arc::clone(&a);
-        let ba0 = Arc::new(Barrier::new(2));
-        let ba1 = Arc::clone(&ba0);
-        let j0 = thread::spawn(move || {
-            {
-                let mut g = b.try_lock(2..4).unwrap();
-                assert!(!b.ranges.lock().unwrap().is_empty());
-                assert_eq!(g[0..2], [3, 4]);
-                g[1] = 10;
-                assert_eq!(g[0..2], [3, 10]);
-            }
-            ba0.wait();
-        });
-        let j1 = thread::spawn(move || {
-            {
-                let g = c.try_lock(4..6).unwrap();
-                assert!(!c.ranges.lock().unwrap().is_empty());
-                assert_eq!(g[0..2], [5, 6]);
+    fn test_truncate() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        {
+            let _g = a.try_lock(8..9).unwrap();
+            assert!(matches!(a.try_truncate(5), Err(TryLockError::WouldBlock)));
+        }
+        let generation = a.generation();
+        a.try_truncate(5).unwrap();
+        assert_eq!(a.data_len(), 5);
+        assert_ne!(a.generation(), generation);
+        assert!(matches!(
+            a.try_lock_checked(4..6),
+            Err(RangeError::OutOfBounds { data_len: 5 })
+        ));
+        assert_eq!(*a.try_lock(3..5).unwrap(), [3, 4]);
+        a.try_truncate(8).unwrap();
+        assert_eq!(a.into_inner(), vec![0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_drain_region() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        {
+            let _g = a.try_lock(8..9).unwrap();
+            assert!(matches!(
+                a.drain_region(2..4),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        let generation = a.generation();
+        assert_eq!(a.drain_region(2..4).unwrap(), vec![2, 3]);
+        assert_eq!(a.data_len(), 8);
+        assert_ne!(a.generation(), generation);
+        let generation = a.generation();
+        assert!(a.drain_region(5..5).unwrap().is_empty());
+        assert_eq!(a.generation(), generation);
+        assert_eq!(*a.try_lock(6..).unwrap(), [8, 9]);
+        assert_eq!(a.drain_region(..=1).unwrap(), vec![0, 1]);
+        assert_eq!(a.into_inner(), vec![4, 5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_drain_region_oob() {
+        let a = VecRangeLock::new(vec![0_i32; 4]);
+        let _ = a.drain_region(2..5);
+    }
+
+    #[test]
+    fn test_retain() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        {
+            let _g = a.try_lock(8..9).unwrap();
+            assert!(matches!(
+                a.retain(|_| panic!("Called while locked.")),
+                Err(TryLockError::WouldBlock)
+            ));
+        }
+        let generation = a.generation();
+        a.retain(|_| true).unwrap();
+        assert_eq!(a.generation(), generation);
+        a.retain(|x| x % 3 != 0).unwrap();
+        assert_eq!(a.data_len(), 6);
+        assert_ne!(a.generation(), generation);
+        assert_eq!(*a.try_lock(4..).unwrap(), [7, 8]);
+        assert!(a.try_lock(5..7).is_err());
+        assert_eq!(a.into_inner(), vec![1, 2, 4, 5, 7, 8]);
+    }
+
+    #[test]
+    fn test_retain_panic() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        let generation = a.generation();
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            a.retain(|&x| {
+                assert!(x < 5, "Predicate panic");
+                x % 2 == 0
+            })
+        }));
+        assert!(res.is_err());
+        // The elements that have not been checked are retained.
+        assert_eq!(a.data_len(), 8);
+        assert_ne!(a.generation(), generation);
+        a.clear_poison();
+        assert_eq!(*a.try_lock(..).unwrap(), [0, 2, 4, 5, 6, 7, 8, 9]);
+        // The slow path operation has been left.
+        let _g = a.try_lock(0..1).unwrap();
+        assert!(a.fast.snapshot().is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_truncated_range() {
+        let a = VecRangeLock::with_shards((0..10).collect::<Vec<i32>>(), 3);
+        let range = a.check_range(&(6..8));
+        a.try_truncate(5).unwrap();
+        // The range has been checked before the truncation.
+        let _ = a.try_lock_checked_range(range);
+    }
+
+    #[test]
+    fn test_generation() {
+        let mut a = VecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        assert_eq!(a.generation(), 0);
+        assert_eq!(a.try_lock(0..1).unwrap().generation(), 0);
+        a.try_shrink_to_fit().unwrap();
+        a.try_extend([]).unwrap();
+        assert_eq!(a.generation(), 0);
+        a.try_extend([5]).unwrap();
+        assert_eq!(a.generation(), 1);
+        assert_eq!(a.try_lock(0..1).unwrap().generation(), 1);
+        a.extend([6]);
+        assert_eq!(a.generation(), 2);
+        let _b = a.split_off(3);
+        assert_eq!(a.generation(), 3);
+    }
+
+    /// Regression test for mutable slice provenance.
+    /// Run this test with `cargo miri test` to check the aliasing model.
+    #[test]
+    fn test_mut_provenance() {
+        let a = VecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let mut g0 = a.try_lock(0..2).unwrap();
+        let mut g1 = a.try_lock(2..6).unwrap();
+        let s0: &mut [i32] = &mut g0;
+        let s1: &mut [i32] = &mut g1;
+        s0[1] = 20;
+        s1[0] = 30;
+        s0[0] = 10;
+        s1[3] = 60;
+        assert_eq!(*s0, [10, 20]);
+        assert_eq!(*s1, [30, 4, 5, 60]);
+        drop(g0);
+        drop(g1);
+        assert_eq!(a.into_inner(), vec![10, 20, 30, 4, 5, 60]);
+    }
+
+    #[test]
+    fn test_shards() {
+        let a = VecRangeLock::with_shards(vec![0_i32; 100], 4);
+        assert_eq!(a.ranges.len(), 4);
+        assert_eq!(a.shard_stride, 25);
+        assert_eq!(a.shards(&(0..1)).len(), 1);
+        assert_eq!(a.shards(&(24..25)).len(), 1);
+        assert_eq!(a.shards(&(24..26)).len(), 2);
+        assert_eq!(a.shards(&(0..100)).len(), 4);
+        {
+            // Ranges in distinct shards.
+            let _g0 = a.try_lock(0..10).unwrap();
+            let _g1 = a.try_lock(30..40).unwrap();
+            assert!(!a.ranges[0].lock().unwrap().is_empty());
+            assert!(a.ranges[0].lock().unwrap().insert(&(50..51)).is_ok());
+            a.ranges[0].lock().unwrap().remove(&(50..51));
+            assert!(!a.ranges[1].lock().unwrap().is_empty());
+            assert!(a.ranges[2].lock().unwrap().is_empty());
+            assert!(a.ranges[3].lock().unwrap().is_empty());
+        }
+        assert!(a.is_all_unlocked());
+        {
+            // Range spanning multiple shards conflicts with ranges in each of them.
+            let _g0 = a.try_lock(20..80).unwrap();
+            assert!(a.try_lock(0..21).is_err());
+            assert!(a.try_lock(40..41).is_err());
+            assert!(a.try_lock(79..100).is_err());
+            assert!(a.try_lock(0..100).is_err());
+            let _g1 = a.try_lock(0..20).unwrap();
+            let _g2 = a.try_lock(80..100).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+        {
+            // Failed multi-shard lock rolls back all insertions.
+            let _g0 = a.try_lock(60..61).unwrap();
+            assert!(a.try_lock(10..70).is_err());
+            assert!(a.ranges[0].lock().unwrap().is_empty());
+            assert!(a.ranges[1].lock().unwrap().is_empty());
+            let _g1 = a.try_lock(10..60).unwrap();
+        }
+        assert!(a.is_all_unlocked());
+    }
+
+    #[test]
+    fn test_shards_small() {
+        // More shards than elements.
+        let a = VecRangeLock::with_shards(vec![1_i32, 2, 3], 8);
+        assert_eq!(a.shard_stride, 1);
+        let g0 = a.try_lock(0..2).unwrap();
+        let g1 = a.try_lock(2..3).unwrap();
+        assert!(a.try_lock(1..3).is_err());
+        assert_eq!(g0[0..2], [1, 2]);
+        assert_eq!(g1[0..1], [3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "shard_count must not be 0")]
+    fn test_shards_zero() {
+        let _ = VecRangeLock::with_shards(vec![1_i32, 2, 3], 0);
+    }
+
+    #[test]
+    fn test_thread_shards() {
+        let a = VecRangeLock::with_shards((0..1000).collect::<Vec<i32>>(), 8);
+        thread::scope(|s| {
+            for t in 0..8 {
+                let a = &a;
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        let begin = (i * 7 + t * 13) % 990;
+                        if let Ok(mut g) = a.try_lock(begin..begin + 10) {
+                            g[0] += 1;
+                            g[0] -= 1;
+                        }
+                    }
+                });
             }
-            ba1.wait();
-            let g = c.try_lock(3..5).unwrap();
-            assert_eq!(g[0..2], [10, 5]);
         });
-        j1.join().expect("Thread 1 panicked.");
-        j0.join().expect("Thread 0 panicked.");
-        assert!(a.ranges.lock().unwrap().is_empty());
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.into_inner(), (0..1000).collect::<Vec<i32>>());
     }
 
     #[allow(dead_code)]
@@ -388,17 +6488,17 @@ mod tests {
         let ba1 = Arc::clone(&ba0);
         let j0 = thread::spawn(move || {
             let _g = b.try_lock(0..1).unwrap();
-            assert!(!b.ranges.lock().unwrap().is_empty());
+            assert!(!b.is_all_unlocked());
             ba0.wait();
         });
         let j1 = thread::spawn(move || {
             let _g = c.try_lock(1..2).unwrap();
-            assert!(!c.ranges.lock().unwrap().is_empty());
+            assert!(!c.is_all_unlocked());
             ba1.wait();
         });
         j1.join().expect("Thread 1 panicked.");
         j0.join().expect("Thread 0 panicked.");
-        assert!(a.ranges.lock().unwrap().is_empty());
+        assert!(a.is_all_unlocked());
     }
 }
 
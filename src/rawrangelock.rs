@@ -0,0 +1,380 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Common implementation of the exclusive range locks
+//! over different data storages and sets of locked ranges.
+//!
+//! [crate::ArrayRangeLock], [crate::SliceRangeLock], [crate::RangeLock]
+//! and friends are thin wrappers around [RawRangeLock].
+
+use crate::{lockedranges::LockedRanges, mutex::Mutex, util::get_bounds};
+use std::{
+    cell::UnsafeCell,
+    fmt,
+    marker::PhantomData,
+    ops::{Deref, DerefMut, Range, RangeBounds},
+    slice,
+    sync::{LockResult, PoisonError, TryLockError, TryLockResult},
+};
+
+/// Set of the exclusively locked ranges of a [RawRangeLock].
+pub(crate) trait RangeSet {
+    /// Location of an inserted range. It is needed to remove the range again.
+    type Entry: Copy + fmt::Debug;
+
+    /// Construct an empty set.
+    fn new() -> Self;
+
+    /// Try to insert the non-empty `range`.
+    ///
+    /// Returns `None`, if the range conflicts with an inserted range.
+    /// Returns [PoisonError], if the set is poisoned.
+    /// The insertion has been attempted anyway then.
+    fn insert(&self, range: &Range<usize>) -> LockResult<Option<Self::Entry>>;
+
+    /// Remove the `range` that has been inserted at `entry`.
+    fn remove(&self, range: &Range<usize>, entry: Self::Entry);
+
+    /// Check whether no range is inserted.
+    fn is_empty(&self) -> bool;
+}
+
+impl RangeSet for Mutex<LockedRanges> {
+    type Entry = ();
+
+    #[inline]
+    fn new() -> Self {
+        Mutex::new(LockedRanges::new())
+    }
+
+    fn insert(&self, range: &Range<usize>) -> LockResult<Option<()>> {
+        match self.lock() {
+            Ok(mut ranges) => Ok(ranges.insert(range).ok()),
+            Err(e) => Err(PoisonError::new(e.into_inner().insert(range).ok())),
+        }
+    }
+
+    fn remove(&self, range: &Range<usize>, _entry: ()) {
+        let mut ranges = self.lock().unwrap_or_else(PoisonError::into_inner);
+        ranges.remove(range);
+    }
+
+    fn is_empty(&self) -> bool {
+        self.lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty()
+    }
+}
+
+/// Contiguous data storage of a [RawRangeLock].
+///
+/// # Safety
+///
+/// [Storage::as_ptr] must return a pointer to [Storage::data_len] contiguous elements.
+/// Disjoint ranges of the elements must be mutably accessible through the pointer
+/// from multiple threads simultaneously.
+/// The pointer and the length must not change, while the storage is shared.
+/// If the storage is [Send], calling these methods from multiple threads must be safe.
+pub(crate) unsafe trait Storage {
+    /// The type of the elements.
+    type Elem;
+
+    /// Get the length (in number of elements) of the data.
+    fn data_len(&self) -> usize;
+
+    /// Get a raw pointer to the first element.
+    ///
+    /// No reference to the elements must be created.
+    fn as_ptr(&self) -> *mut Self::Elem;
+}
+
+// SAFETY: The array is not moved while it is shared.
+unsafe impl<T, const N: usize> Storage for UnsafeCell<[T; N]> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        self.get().cast()
+    }
+}
+
+// SAFETY: The slice is not moved while it is shared.
+unsafe impl<T> Storage for UnsafeCell<[T]> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        self.get().len()
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        self.get().cast()
+    }
+}
+
+// SAFETY: The Vec is never modified while it is shared.
+unsafe impl<T> Storage for UnsafeCell<Vec<T>> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+        //         The Vec is never modified while the lock is shared.
+        unsafe { (*self.get()).len() }
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        // SAFETY: See data_len().
+        // The pointer returned by as_ptr() carries the provenance of the data buffer.
+        // Therefore, it may be used for mutable access.
+        unsafe { (*self.get()).as_ptr().cast_mut() }
+    }
+}
+
+/// Multi-thread range lock for the data in the storage `S`.
+///
+/// The locked ranges are tracked in the set `R`.
+#[derive(Debug)]
+#[repr(C)]
+pub(crate) struct RawRangeLock<S: ?Sized, R = Mutex<LockedRanges>> {
+    /// Set of the currently locked ranges.
+    pub(crate) ranges: R,
+    /// The protected data.
+    /// This must be the last field, because it may be unsized.
+    pub(crate) data: S,
+}
+
+// SAFETY:
+// It is safe to access RawRangeLock and the contained data (via RawRangeLockGuard)
+// from multiple threads simultaneously.
+// The lock ensures that access to the data is strictly serialized.
+// The storage (and therefore its elements) must be Send-able to other threads.
+unsafe impl<S, R> Sync for RawRangeLock<S, R>
+where
+    S: ?Sized + Storage + Send,
+    R: RangeSet + Sync,
+{
+}
+
+impl<S, R: RangeSet> RawRangeLock<S, R> {
+    /// Construct a new [RawRangeLock].
+    #[inline]
+    pub fn new(data: S) -> RawRangeLock<S, R> {
+        RawRangeLock {
+            ranges: R::new(),
+            data,
+        }
+    }
+}
+
+impl<'a, S: ?Sized + Storage, R: RangeSet> RawRangeLock<S, R> {
+    /// Get the length (in number of elements) of the data.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.data.data_len()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// Panics, if the range is out of bounds of the data.
+    ///
+    /// * On success: Returns a [RawRangeLockGuard] that can be used to access the locked region.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Returns [TryLockError::Poisoned] with a guard holding the range, if the lock is poisoned.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<RawRangeLockGuard<'a, S, R>> {
+        let data_len = self.data_len();
+        let (range_start, range_end) = get_bounds(&range, data_len);
+        if range_start > range_end {
+            panic!("Invalid range. Start is bigger than end.");
+        }
+        // Empty ranges are valid anywhere in the data, including at its end.
+        if range_end > data_len {
+            panic!("Range is out of bounds.");
+        }
+        let range = range_start..range_end;
+
+        if range.is_empty() {
+            return Ok(RawRangeLockGuard::new(self, range, None));
+        }
+        let (entry, poisoned) = match self.ranges.insert(&range) {
+            Ok(entry) => (entry, false),
+            Err(e) => (e.into_inner(), true),
+        };
+        let Some(entry) = entry else {
+            return Err(TryLockError::WouldBlock);
+        };
+        let guard = RawRangeLockGuard::new(self, range, Some(entry));
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            Ok(guard)
+        }
+    }
+
+    /// Check whether no range is locked.
+    #[inline]
+    pub fn is_all_unlocked(&self) -> bool {
+        self.ranges.is_empty()
+    }
+
+    /// Get a raw pointer to the first element of the specified range.
+    #[inline]
+    fn get_ptr(&self, range: &Range<usize>) -> *mut S::Elem {
+        assert!(range.start <= range.end && range.end <= self.data_len());
+        // SAFETY: The range has been checked against the data length.
+        //         Therefore, the resulting pointer is in bounds of the data
+        //         or one past its end.
+        unsafe { self.data.as_ptr().add(range.start) }
+    }
+}
+
+/// Wrap the guard of a [RawRangeLock::try_lock] result with `wrap`.
+#[inline]
+pub(crate) fn wrap_guard<G, W>(
+    result: TryLockResult<G>,
+    wrap: impl FnOnce(G) -> W,
+) -> TryLockResult<W> {
+    match result {
+        Ok(guard) => Ok(wrap(guard)),
+        Err(TryLockError::WouldBlock) => Err(TryLockError::WouldBlock),
+        Err(TryLockError::Poisoned(e)) => Err(TryLockError::Poisoned(PoisonError::new(wrap(
+            e.into_inner(),
+        )))),
+    }
+}
+
+/// Lock guard of [RawRangeLock].
+#[derive(Debug)]
+pub(crate) struct RawRangeLockGuard<'a, S: ?Sized + Storage, R: RangeSet = Mutex<LockedRanges>> {
+    /// Reference to the underlying lock.
+    lock: &'a RawRangeLock<S, R>,
+    /// The locked range.
+    range: Range<usize>,
+    /// Location of the range in the set of locked ranges.
+    /// None for the empty range.
+    entry: Option<R::Entry>,
+
+    /// The guard behaves like a mutable slice reference with respect to autotraits.
+    _p: PhantomData<&'a mut [S::Elem]>,
+}
+
+impl<'a, S: ?Sized + Storage, R: RangeSet> RawRangeLockGuard<'a, S, R> {
+    #[inline]
+    fn new(
+        lock: &'a RawRangeLock<S, R>,
+        range: Range<usize>,
+        entry: Option<R::Entry>,
+    ) -> RawRangeLockGuard<'a, S, R> {
+        RawRangeLockGuard {
+            lock,
+            range,
+            entry,
+            _p: PhantomData,
+        }
+    }
+
+    /// Get the locked range.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.range.clone()
+    }
+}
+
+impl<'a, S: ?Sized + Storage, R: RangeSet> Drop for RawRangeLockGuard<'a, S, R> {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(entry) = self.entry {
+            self.lock.ranges.remove(&self.range, entry);
+        }
+    }
+}
+
+impl<'a, S: ?Sized + Storage, R: RangeSet> Deref for RawRangeLockGuard<'a, S, R> {
+    type Target = [S::Elem];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: See deref_mut().
+        unsafe { slice::from_raw_parts(self.lock.get_ptr(&self.range), self.range.len()) }
+    }
+}
+
+impl<'a, S: ?Sized + Storage, R: RangeSet> DerefMut for RawRangeLockGuard<'a, S, R> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        // SAFETY:
+        // The lifetime of the slice is bounded by the lifetime of the guard.
+        // The lifetime of the guard is bounded by the lifetime of the lock,
+        // which owns or borrows the storage.
+        // The range lock ensures that no overlapping/conflicting guards
+        // can be constructed.
+        // The slice is derived from the raw data pointer and not from a shared reference.
+        unsafe { slice::from_raw_parts_mut(self.lock.get_ptr(&self.range), self.range.len()) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_raw() {
+        let a: RawRangeLock<_> = RawRangeLock::new(UnsafeCell::new([1_i32, 2, 3, 4]));
+        let mut g0 = a.try_lock(1..3).unwrap();
+        assert!(matches!(a.try_lock(2..), Err(TryLockError::WouldBlock)));
+        let g1 = a.try_lock(4..).unwrap();
+        assert_eq!(g0.range(), 1..3);
+        assert!(g1.is_empty());
+        g0[1] = 30;
+        assert!(!a.is_all_unlocked());
+        drop(g0);
+        drop(g1);
+        assert!(a.is_all_unlocked());
+        assert_eq!(a.data.into_inner(), [1, 2, 30, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_raw_oob() {
+        let a: RawRangeLock<_> = RawRangeLock::new(UnsafeCell::new([1_i32, 2, 3]));
+        let _ = a.try_lock(2..4);
+    }
+
+    #[test]
+    #[cfg(not(any(feature = "parking_lot", feature = "spinlock")))]
+    fn test_raw_poisoned() {
+        let a: RawRangeLock<_> = RawRangeLock::new(UnsafeCell::new([1_i32, 2, 3, 4]));
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _r = a.ranges.lock().unwrap();
+            panic!("Poisoning the ranges");
+        }));
+        assert!(res.is_err());
+        // The poisoned guard holds the range.
+        let g0 = match a.try_lock(0..2) {
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            _ => panic!("Lock must be poisoned"),
+        };
+        assert_eq!(g0[..], [1, 2]);
+        assert!(matches!(a.try_lock(1..3), Err(TryLockError::WouldBlock)));
+        drop(g0);
+        assert!(a.is_all_unlocked());
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
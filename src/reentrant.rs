@@ -0,0 +1,326 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::VecRangeLock;
+use std::{
+    collections::BTreeMap,
+    marker::PhantomData,
+    mem,
+    ops::{Deref, Range, RangeBounds},
+    sync::{Mutex, PoisonError, TryLockError, TryLockResult},
+    thread::{self, ThreadId},
+};
+
+/// The owner of a reentrantly locked range.
+#[derive(Debug)]
+struct RangeOwner {
+    /// End of the locked range.
+    end: usize,
+    /// The thread holding the range.
+    thread: ThreadId,
+    /// Number of guards of the owner thread.
+    count: usize,
+}
+
+/// Reentrant multi-thread range lock for [std::vec::Vec].
+///
+/// This is a variant of [VecRangeLock](crate::VecRangeLock)
+/// that allows the thread holding a range to lock the identical range again.
+/// The range is unlocked, when the outermost guard is dropped.
+///
+/// Locking a range that overlaps with, but is not identical to,
+/// a range held by the same thread fails, like it does for any other thread.
+///
+/// Multiple guards of the same thread may alias each other.
+/// Therefore, the guards only give immutable access to the data.
+/// Use types with interior mutability, such as [std::cell::Cell], to modify the data.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::ReentrantVecRangeLock;
+/// use std::cell::Cell;
+///
+/// fn recurse(lock: &ReentrantVecRangeLock<Cell<u32>>, depth: u32) {
+///     let guard = lock.try_lock(1..3).expect("Failed to lock 1..3");
+///     guard[0].set(guard[0].get() + 1);
+///     if depth > 0 {
+///         recurse(lock, depth - 1);
+///     }
+/// }
+///
+/// let lock = ReentrantVecRangeLock::new(vec![Cell::new(0), Cell::new(0), Cell::new(0)]);
+/// recurse(&lock, 3);
+///
+/// let guard = lock.try_lock(1..3).expect("Failed to lock 1..3");
+/// assert!(lock.try_lock(0..2).is_err()); // Overlaps the held range 1..3.
+/// drop(guard);
+///
+/// let data: Vec<u32> = lock.into_inner().into_iter().map(Cell::into_inner).collect();
+/// assert_eq!(data, vec![0, 4, 0]);
+/// ```
+#[derive(Debug)]
+pub struct ReentrantVecRangeLock<T> {
+    /// The underlying range lock.
+    lock: VecRangeLock<T>,
+    /// Owners of the currently locked ranges, keyed by range start.
+    owners: Mutex<BTreeMap<usize, RangeOwner>>,
+}
+
+impl<'a, T> ReentrantVecRangeLock<T> {
+    /// Construct a new [ReentrantVecRangeLock].
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new(data: Vec<T>) -> ReentrantVecRangeLock<T> {
+        ReentrantVecRangeLock {
+            lock: VecRangeLock::new(data),
+            owners: Mutex::new(BTreeMap::new()),
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec].
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.lock.data_len()
+    }
+
+    /// Unwrap this [ReentrantVecRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        self.lock.into_inner()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// If the calling thread already holds the identical range,
+    /// the lock is taken again and a new guard is returned.
+    ///
+    /// * On success: Returns a [ReentrantVecRangeLockGuard] that can be used to access the locked region.
+    ///   Dereferencing [ReentrantVecRangeLockGuard] yields an immutable slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<ReentrantVecRangeLockGuard<'a, T>> {
+        let range = self.lock.check_range(&range);
+        if range.is_empty() {
+            return TryLockResult::Ok(ReentrantVecRangeLockGuard::new(self, range));
+        }
+
+        // The owners map is updated atomically with respect to panics.
+        // Therefore, it is consistent, even if the mutex is poisoned.
+        // The poison is reported together with a valid guard.
+        let (mut owners, owners_poisoned) = match self.owners.lock() {
+            Ok(owners) => (owners, false),
+            Err(e) => (e.into_inner(), true),
+        };
+        let this_thread = thread::current().id();
+        if let Some(owner) = owners.get_mut(&range.start) {
+            if owner.end == range.end && owner.thread == this_thread {
+                // We already hold this range. Lock it again.
+                owner.count += 1;
+                let guard = ReentrantVecRangeLockGuard::new(self, range);
+                return if owners_poisoned {
+                    TryLockResult::Err(TryLockError::Poisoned(PoisonError::new(guard)))
+                } else {
+                    TryLockResult::Ok(guard)
+                };
+            }
+        }
+
+        let poisoned = match self.lock.try_lock_poisonable(range.clone()) {
+            Ok(guard) => {
+                mem::forget(guard);
+                owners_poisoned
+            }
+            Err(TryLockError::WouldBlock) => return TryLockResult::Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                mem::forget(e.into_inner());
+                true
+            }
+        };
+        // The underlying guard has been forgotten.
+        // The range is unlocked, when the last ReentrantVecRangeLockGuard is dropped.
+        owners.insert(
+            range.start,
+            RangeOwner {
+                end: range.end,
+                thread: this_thread,
+                count: 1,
+            },
+        );
+        let guard = ReentrantVecRangeLockGuard::new(self, range);
+        if poisoned {
+            TryLockResult::Err(TryLockError::Poisoned(PoisonError::new(guard)))
+        } else {
+            TryLockResult::Ok(guard)
+        }
+    }
+
+    /// Drop one reference to the locked range.
+    /// Unlock the range, if this was the last reference.
+    fn unlock(&self, range: &Range<usize>) {
+        if !range.is_empty() {
+            let mut owners = self.owners.lock().unwrap_or_else(PoisonError::into_inner);
+            let owner = owners
+                .get_mut(&range.start)
+                .expect("ReentrantVecRangeLock: Range is not locked.");
+            owner.count -= 1;
+            if owner.count == 0 {
+                owners.remove(&range.start);
+                self.lock.unlock(range);
+            }
+        }
+    }
+}
+
+/// Lock guard variable type for [ReentrantVecRangeLock].
+///
+/// The [Deref] trait is implemented for this struct.
+/// See the documentation of [ReentrantVecRangeLock] for usage examples.
+#[derive(Debug)]
+pub struct ReentrantVecRangeLockGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a ReentrantVecRangeLock<T>,
+    /// The locked range.
+    range: Range<usize>,
+
+    /// Suppresses Send and Sync autotraits for ReentrantVecRangeLockGuard.
+    /// The lock is owned by the thread that created the guard.
+    _p: PhantomData<*mut T>,
+}
+
+impl<'a, T> ReentrantVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn new(
+        lock: &'a ReentrantVecRangeLock<T>,
+        range: Range<usize>,
+    ) -> ReentrantVecRangeLockGuard<'a, T> {
+        ReentrantVecRangeLockGuard {
+            lock,
+            range,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<'a, T> Drop for ReentrantVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock(&self.range);
+    }
+}
+
+impl<'a, T> Deref for ReentrantVecRangeLockGuard<'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        // SAFETY:
+        // The range lock ensures that only the owning thread holds guards to the range.
+        // The guards of the owning thread only hand out immutable slices.
+        unsafe { self.lock.lock.get_slice(&self.range) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::sync::Barrier;
+
+    #[test]
+    fn test_reentrant() {
+        let a = ReentrantVecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        {
+            let g0 = a.try_lock(2..4).unwrap();
+            let g1 = a.try_lock(2..4).unwrap();
+            assert_eq!(a.owners.lock().unwrap()[&2].count, 2);
+            assert_eq!(g0[..], [3, 4]);
+            assert_eq!(g1[..], [3, 4]);
+            drop(g0);
+            assert!(a.lock.try_lock(2..3).is_err());
+            let g2 = a.try_lock(2..=3).unwrap();
+            drop(g1);
+            assert!(a.lock.try_lock(2..3).is_err());
+            drop(g2);
+            let _g = a.lock.try_lock(2..3).unwrap();
+        }
+        assert!(a.owners.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reentrant_poisoned() {
+        let a = ReentrantVecRangeLock::new(vec![1_i32, 2, 3, 4]);
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _owners = a.owners.lock().unwrap();
+            panic!("Poisoning the owners");
+        }));
+        assert!(res.is_err());
+        // The poisoned guard holds the range.
+        let g0 = match a.try_lock(1..3) {
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            _ => panic!("Lock must be poisoned"),
+        };
+        assert_eq!(g0[..], [2, 3]);
+        assert!(a.lock.try_lock(2..3).is_err());
+        let g1 = match a.try_lock(1..3) {
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            _ => panic!("Lock must be poisoned"),
+        };
+        drop(g0);
+        assert!(a.lock.try_lock(2..3).is_err());
+        drop(g1);
+        let _g = a.lock.try_lock(1..3).unwrap();
+        assert!(a
+            .owners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_reentrant_overlap() {
+        let a = ReentrantVecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6]);
+        let _g0 = a.try_lock(2..4).unwrap();
+        assert!(a.try_lock(2..3).is_err());
+        assert!(a.try_lock(3..5).is_err());
+        assert!(a.try_lock(0..6).is_err());
+        let _g1 = a.try_lock(4..6).unwrap();
+    }
+
+    #[test]
+    fn test_reentrant_other_thread() {
+        let a = ReentrantVecRangeLock::new(vec![Cell::new(1_i32), Cell::new(2)]);
+        let ba = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let g = a.try_lock(0..1).unwrap();
+                g[0].set(10);
+                ba.wait();
+                ba.wait();
+            });
+            s.spawn(|| {
+                ba.wait();
+                assert!(a.try_lock(0..1).is_err());
+                let g = a.try_lock(1..2).unwrap();
+                g[0].set(20);
+                ba.wait();
+            });
+        });
+        assert!(a.owners.lock().unwrap().is_empty());
+        let data: Vec<i32> = a.into_inner().into_iter().map(Cell::into_inner).collect();
+        assert_eq!(data, vec![10, 20]);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
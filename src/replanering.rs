@@ -0,0 +1,141 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::reprangelock::{RepVecRangeLock, RepVecRangeLockGuard};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    TryLockError, TryLockResult,
+};
+
+/// Round-robin distribution of the cycle offsets of a [RepVecRangeLock].
+///
+/// The ring hands out the offsets `0, 1, ..., cycle_len - 1, 0, ...` in order.
+/// Contended offsets are skipped.
+/// The ring can be shared between threads.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::{RepLaneRing, RepVecRangeLock};
+///
+/// let ring = RepLaneRing::new(RepVecRangeLock::new(vec![0; 6], 1, 3));
+/// let g0 = ring.acquire_next().expect("Failed to lock lane");
+/// let g1 = ring.acquire_next().expect("Failed to lock lane");
+/// assert_eq!((g0.cycle_offset(), g1.cycle_offset()), (0, 1));
+/// drop(g0);
+/// let g2 = ring.acquire_next().expect("Failed to lock lane");
+/// let g3 = ring.acquire_next().expect("Failed to lock lane");
+/// // Offset 1 is still locked and has been skipped.
+/// assert_eq!((g2.cycle_offset(), g3.cycle_offset()), (2, 0));
+/// assert!(ring.acquire_next().is_err());
+/// ```
+#[derive(Debug)]
+pub struct RepLaneRing<T> {
+    /// The underlying lock.
+    lock: RepVecRangeLock<T>,
+    /// The cycle offset to try first.
+    next: AtomicUsize,
+}
+
+impl<'a, T> RepLaneRing<T> {
+    /// Construct a new [RepLaneRing] starting at the offset 0.
+    pub fn new(lock: RepVecRangeLock<T>) -> RepLaneRing<T> {
+        RepLaneRing {
+            lock,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Get the underlying [RepVecRangeLock].
+    #[inline]
+    pub fn as_rep_vec_range_lock(&self) -> &RepVecRangeLock<T> {
+        &self.lock
+    }
+
+    /// Unwrap this [RepLaneRing] into the underlying [RepVecRangeLock].
+    #[inline]
+    pub fn into_inner(self) -> RepVecRangeLock<T> {
+        self.lock
+    }
+
+    /// Get the cycle offset that [RepLaneRing::acquire_next] tries first.
+    #[inline]
+    pub fn next_offset(&self) -> usize {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// Try to lock the next free cycle offset in round-robin order.
+    ///
+    /// All offsets are tried once, beginning at [RepLaneRing::next_offset].
+    /// On success, the ring continues after the locked offset.
+    ///
+    /// * On success: Returns the [RepVecRangeLockGuard] of the locked offset.
+    /// * On failure: Returns [TryLockError::WouldBlock], if all offsets are contended.
+    pub fn acquire_next(&'a self) -> TryLockResult<RepVecRangeLockGuard<'a, T>> {
+        let cycle_len = self.lock.cycle_len();
+        let start = self.next.load(Ordering::Relaxed);
+        for i in 0..cycle_len {
+            let cycle_offset = (start + i) % cycle_len;
+            match self.lock.try_lock(cycle_offset) {
+                Err(TryLockError::WouldBlock) => (),
+                res => {
+                    self.next
+                        .store((cycle_offset + 1) % cycle_len, Ordering::Relaxed);
+                    return res;
+                }
+            }
+        }
+        Err(TryLockError::WouldBlock)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_lane_ring() {
+        let ring = RepLaneRing::new(RepVecRangeLock::new(vec![0_u32; 8], 2, 4));
+        let g: Vec<_> = (0..4).map(|_| ring.acquire_next().unwrap()).collect();
+        let offsets: Vec<usize> = g.iter().map(|g| g.cycle_offset()).collect();
+        assert_eq!(offsets, vec![0, 1, 2, 3]);
+        assert_eq!(ring.next_offset(), 0);
+        assert!(matches!(ring.acquire_next(), Err(TryLockError::WouldBlock)));
+        drop(g);
+        let g2 = ring.acquire_next().unwrap();
+        assert_eq!(g2.cycle_offset(), 0);
+        let _g = ring.as_rep_vec_range_lock().try_lock(1).unwrap();
+        assert_eq!(ring.acquire_next().unwrap().cycle_offset(), 2);
+        assert_eq!(ring.next_offset(), 3);
+    }
+
+    #[test]
+    fn test_lane_ring_threads() {
+        let ring = RepLaneRing::new(RepVecRangeLock::new(vec![0_u32; 12], 1, 3));
+        thread::scope(|s| {
+            for _ in 0..4 {
+                s.spawn(|| {
+                    for _ in 0..100 {
+                        if let Ok(mut g) = ring.acquire_next() {
+                            for slice in g.cycles_mut() {
+                                slice[0] += 1;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        let data = ring.into_inner().into_inner();
+        assert!(data.chunks(3).all(|cycle| cycle == data[..3].as_ref()));
+        assert!(data[..3].iter().sum::<u32>() <= 400);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
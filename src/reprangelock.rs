@@ -7,18 +7,66 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 //
 
-use std::{
+use crate::{
+    error::{LockError, RepLockError},
+    sync::{TryLockError, TryLockResult},
+};
+#[cfg(feature = "std")]
+use crate::{rangelock::VecRangeLock, sync::PoisonError};
+use alloc::{boxed::Box, rc::Rc, sync::Arc, vec::Vec};
+use core::{
     cell::UnsafeCell,
-    hint::unreachable_unchecked,
     marker::PhantomData,
-    ops::{Index, IndexMut},
-    rc::Rc,
-    sync::{
-        atomic::{AtomicU32, Ordering},
-        TryLockError, TryLockResult,
-    },
+    ops::{Index, IndexMut, Range},
+    slice,
+    sync::atomic::{AtomicU32, AtomicUsize, Ordering},
+};
+#[cfg(feature = "std")]
+use std::{
+    sync::{Condvar, Mutex},
+    time::{Duration, Instant},
 };
 
+/// Wait queue of the threads blocking on one word of the locked offsets bitmask.
+#[cfg(feature = "std")]
+#[derive(Debug, Default)]
+struct OffsetWaiters {
+    /// Number of threads waiting for an offset in this word.
+    count: AtomicU32,
+    /// Mutex for the condition variable.
+    mutex: Mutex<()>,
+    /// Condition that is notified, if an offset in this word is unlocked.
+    cond: Condvar,
+}
+
+/// Layout of the slices within one repeat cycle.
+#[derive(Debug)]
+enum SliceLayout {
+    /// All slices have the same length, in number of elements.
+    /// Consecutive slices start `stride` elements apart.
+    Uniform { slice_len: usize, stride: usize },
+    /// Start of each slice within the cycle, in number of elements.
+    /// The additional last entry is the cycle length, in number of elements.
+    Variable(Vec<usize>),
+}
+
+impl SliceLayout {
+    /// Get the `(start, len)` of the slice at 'cycle_offset' within the cycle.
+    ///
+    /// 'cycle_offset' must have been checked against cycle_len.
+    #[inline]
+    fn span(&self, cycle_offset: usize) -> (usize, usize) {
+        match self {
+            // Multiply cannot overflow due to stride, cycle_len and cycle_offset checks.
+            SliceLayout::Uniform { slice_len, stride } => (stride * cycle_offset, *slice_len),
+            SliceLayout::Variable(starts) => {
+                let start = starts[cycle_offset];
+                (start, starts[cycle_offset + 1] - start)
+            }
+        }
+    }
+}
+
 /// Interleaved multi-thread range lock for [std::vec::Vec].
 ///
 /// Each thread can lock a set of repeating slices of the data.
@@ -27,6 +75,12 @@ use std::{
 ///
 /// Offsets are not bound to one specific thread.
 ///
+/// The blocking methods, e.g. [RepVecRangeLock::lock],
+/// park the threads on a [Mutex](std::sync::Mutex) and [Condvar](std::sync::Condvar).
+/// They and the conversions into [VecRangeLock] require the default `std` feature.
+/// Without `std` the try-lock methods build on `core` and `alloc` only
+/// and return the crate-local equivalents of the [std::sync] result types.
+///
 /// Please see the example below.
 ///
 /// # Example
@@ -100,14 +154,19 @@ use std::{
 /// ```
 #[derive(Debug)]
 pub struct RepVecRangeLock<T> {
-    /// Range length, in number of data elements.
-    slice_len: usize,
+    /// Layout of the slices within the cycle.
+    layout: SliceLayout,
     /// Cycle length, in number of slices.
     cycle_len: usize,
     /// Cycle length, in number of data elements.
     cycle_num_elems: usize,
-    /// Bitmask of locked cycle offsets.
+    /// Bitmask of exclusively locked cycle offsets.
     locked_offsets: Vec<AtomicU32>,
+    /// Number of shared readers of each cycle offset.
+    readers: Vec<AtomicUsize>,
+    /// Wait queues for blocking lock, one per word of `locked_offsets`.
+    #[cfg(feature = "std")]
+    waiters: Vec<OffsetWaiters>,
     /// The protected data.
     data: UnsafeCell<Vec<T>>,
 }
@@ -125,28 +184,195 @@ impl<'a, T> RepVecRangeLock<T> {
     /// * `data`: The data [Vec] to protect.
     /// * `slice_len`: The length of the slices, in number of elements. Must be >0.
     /// * `cycle_len`: The length of the repeat cycle, in number of slices. Must be >0 and <=usize::MAX-31.
+    ///
+    /// Panics, if the arguments are invalid.
+    /// See [RepVecRangeLock::try_new] for a non-panicking variant.
     pub fn new(data: Vec<T>, slice_len: usize, cycle_len: usize) -> RepVecRangeLock<T> {
+        Self::with_stride(data, slice_len, cycle_len, slice_len)
+    }
+
+    /// Construct a new [RepVecRangeLock] and check the arguments.
+    ///
+    /// See [RepVecRangeLock::new] for the arguments.
+    /// Returns a [RepLockError], if the arguments are invalid.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::{RepLockError, RepVecRangeLock};
+    ///
+    /// assert_eq!(
+    ///     RepVecRangeLock::try_new(vec![0; 4], 0, 2).unwrap_err(),
+    ///     RepLockError::ZeroSliceLen
+    /// );
+    /// let lock = RepVecRangeLock::try_new(vec![0; 4], 1, 2).expect("Invalid layout");
+    /// assert_eq!(lock.cycle_len(), 2);
+    /// ```
+    pub fn try_new(
+        data: Vec<T>,
+        slice_len: usize,
+        cycle_len: usize,
+    ) -> Result<RepVecRangeLock<T>, RepLockError> {
+        Self::try_with_stride(data, slice_len, cycle_len, slice_len)
+    }
+
+    /// Construct a new [RepVecRangeLock] with gaps between the slices.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    /// * `slice_len`: The length of the slices, in number of elements. Must be >0.
+    /// * `cycle_len`: The length of the repeat cycle, in number of slices. Must be >0 and <=usize::MAX-31.
+    /// * `stride`: The distance between the starts of two consecutive slices,
+    ///   in number of elements. Must be >=slice_len.
+    ///
+    /// The repeat cycle is `stride * cycle_len` elements long.
+    /// The `stride - slice_len` elements after each slice are not part of any slice.
+    ///
+    /// Panics, if the arguments are invalid.
+    /// See [RepVecRangeLock::try_with_stride] for a non-panicking variant.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let data = vec![1, 2, 0,  3, 4, 0,   // <- cycle 0
+    ///                 5, 6, 0,  7, 8, 0];  // <- cycle 1
+    /// //              ^--^      ^--^
+    /// //                |         |
+    /// //          offset-0   offset-1
+    ///
+    /// let lock = RepVecRangeLock::with_stride(data, 2, 2, 3);
+    /// let guard = lock.try_lock(1).expect("Failed to lock offset.");
+    /// assert_eq!(guard[0], [3, 4]);
+    /// assert_eq!(guard[1], [7, 8]);
+    /// ```
+    pub fn with_stride(
+        data: Vec<T>,
+        slice_len: usize,
+        cycle_len: usize,
+        stride: usize,
+    ) -> RepVecRangeLock<T> {
+        match Self::try_with_stride(data, slice_len, cycle_len, stride) {
+            Ok(lock) => lock,
+            Err(RepLockError::ZeroSliceLen) => panic!("slice_len must not be 0."),
+            Err(RepLockError::CycleLenOutOfRange { .. }) => panic!("cycle_len out of range."),
+            Err(RepLockError::StrideTooSmall { .. }) => {
+                panic!("stride must not be smaller than slice_len.")
+            }
+            Err(RepLockError::CycleOverflow) => panic!("Repeat cycle overflow."),
+        }
+    }
+
+    /// Construct a new [RepVecRangeLock] with gaps between the slices and check the arguments.
+    ///
+    /// See [RepVecRangeLock::with_stride] for the arguments.
+    /// Returns a [RepLockError], if the arguments are invalid.
+    pub fn try_with_stride(
+        data: Vec<T>,
+        slice_len: usize,
+        cycle_len: usize,
+        stride: usize,
+    ) -> Result<RepVecRangeLock<T>, RepLockError> {
         if slice_len == 0 {
+            return Err(RepLockError::ZeroSliceLen);
+        }
+        if cycle_len == 0 || cycle_len > usize::MAX - 31 {
+            return Err(RepLockError::CycleLenOutOfRange { cycle_len });
+        }
+        if stride < slice_len {
+            return Err(RepLockError::StrideTooSmall { stride, slice_len });
+        }
+        let Some(cycle_num_elems) = cycle_len.checked_mul(stride) else {
+            return Err(RepLockError::CycleOverflow);
+        };
+        Ok(Self::with_layout(
+            data,
+            SliceLayout::Uniform { slice_len, stride },
+            cycle_len,
+            cycle_num_elems,
+        ))
+    }
+
+    /// Construct a new [RepVecRangeLock] with variable slice lengths.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    /// * `slice_lens`: The length of the slice at each cycle offset, in number of elements.
+    ///   Each length must be >0.
+    ///   The number of lengths is the cycle length. It must be >0 and <=usize::MAX-31.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let data = vec![1, 2,  3,  4, 5, 6,   // <- cycle 0
+    ///                 7, 8,  9,  10, 11, 12]; // <- cycle 1
+    /// //              ^--^   ^   ^------^
+    /// //                |    |      |
+    /// //         offset-0 offset-1 offset-2
+    ///
+    /// let lock = RepVecRangeLock::with_slice_lens(data, vec![2, 1, 3]);
+    /// let mut guard = lock.try_lock(2).expect("Failed to lock offset.");
+    /// assert_eq!(guard[0], [4, 5, 6]);
+    /// guard[1][2] = 120;
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner()[11], 120);
+    /// ```
+    pub fn with_slice_lens(data: Vec<T>, slice_lens: Vec<usize>) -> RepVecRangeLock<T> {
+        let cycle_len = slice_lens.len();
+        if slice_lens.contains(&0) {
             panic!("slice_len must not be 0.");
         }
         if cycle_len == 0 || cycle_len > usize::MAX - 31 {
             panic!("cycle_len out of range.");
         }
-        let Some(cycle_num_elems) = cycle_len.checked_mul(slice_len) else {
-            panic!("Repeat cycle overflow.");
-        };
+        let mut starts = Vec::with_capacity(cycle_len + 1);
+        let mut cycle_num_elems: usize = 0;
+        for slice_len in slice_lens {
+            starts.push(cycle_num_elems);
+            let Some(end) = cycle_num_elems.checked_add(slice_len) else {
+                panic!("Repeat cycle overflow.");
+            };
+            cycle_num_elems = end;
+        }
+        starts.push(cycle_num_elems);
+        Self::with_layout(
+            data,
+            SliceLayout::Variable(starts),
+            cycle_len,
+            cycle_num_elems,
+        )
+    }
 
-        let num = (cycle_len + 31) / 32;
+    /// Construct a new [RepVecRangeLock] with a checked slice layout.
+    fn with_layout(
+        data: Vec<T>,
+        layout: SliceLayout,
+        cycle_len: usize,
+        cycle_num_elems: usize,
+    ) -> RepVecRangeLock<T> {
+        let num = cycle_len.div_ceil(32);
         let mut locked_offsets = Vec::with_capacity(num);
         locked_offsets.resize_with(num, || AtomicU32::new(0));
+        let mut readers = Vec::with_capacity(cycle_len);
+        readers.resize_with(cycle_len, || AtomicUsize::new(0));
+        #[cfg(feature = "std")]
+        let waiters = {
+            let mut waiters = Vec::with_capacity(num);
+            waiters.resize_with(num, OffsetWaiters::default);
+            waiters
+        };
 
         let data = UnsafeCell::new(data);
 
         RepVecRangeLock {
-            slice_len,
+            layout,
             cycle_len,
             cycle_num_elems,
             locked_offsets,
+            readers,
+            #[cfg(feature = "std")]
+            waiters,
             data,
         }
     }
@@ -158,169 +384,1217 @@ impl<'a, T> RepVecRangeLock<T> {
         unsafe { (*self.data.get()).len() }
     }
 
+    /// Get the length of the slices, in number of elements.
+    ///
+    /// Panics, if the lock has been constructed with variable slice lengths.
+    /// See [RepVecRangeLock::slice_len_at] for locks with variable slice lengths.
+    #[inline]
+    pub fn slice_len(&self) -> usize {
+        match self.layout {
+            SliceLayout::Uniform { slice_len, .. } => slice_len,
+            SliceLayout::Variable(_) => {
+                panic!("RepVecRangeLock::slice_len: The slices have variable lengths.")
+            }
+        }
+    }
+
+    /// Get the length of the slice at 'cycle_offset', in number of elements.
+    #[inline]
+    pub fn slice_len_at(&self, cycle_offset: usize) -> usize {
+        self.check_offset(cycle_offset);
+        self.layout.span(cycle_offset).1
+    }
+
+    /// Get the length of the repeat cycle, in number of slices.
+    #[inline]
+    pub fn cycle_len(&self) -> usize {
+        self.cycle_len
+    }
+
+    /// Change the length of the repeat cycle, in number of slices.
+    ///
+    /// The slice length and the stride are kept.
+    /// `new_cycle_len` must be >0 and <=usize::MAX-31.
+    /// Panics, if the lock has been constructed with variable slice lengths.
+    ///
+    /// Returns [LockError::WouldBlock], if any offset is locked (e.g. by a leaked guard).
+    /// Returns [LockError::OutOfBounds], if the new repeat cycle exceeds the data.
+    /// The layout is not changed in both cases.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let mut lock = RepVecRangeLock::new(vec![0; 64], 1, 2);
+    /// lock.set_cycle_len(40).expect("Failed to resize the cycle");
+    /// assert_eq!(lock.num_cycles(), 1);
+    /// lock.try_lock(39).expect("Failed to lock offset 39")[0][0] = 1;
+    /// assert_eq!(lock.into_inner()[39], 1);
+    /// ```
+    pub fn set_cycle_len(&mut self, new_cycle_len: usize) -> Result<(), LockError> {
+        let SliceLayout::Uniform { stride, .. } = self.layout else {
+            panic!("RepVecRangeLock::set_cycle_len: The slices have variable lengths.");
+        };
+        if new_cycle_len == 0 || new_cycle_len > usize::MAX - 31 {
+            panic!("cycle_len out of range.");
+        }
+        let Some(cycle_num_elems) = new_cycle_len.checked_mul(stride) else {
+            panic!("Repeat cycle overflow.");
+        };
+        if !self.all_offsets_free() {
+            return Err(LockError::WouldBlock { conflicting: None });
+        }
+        if cycle_num_elems > self.data_len() {
+            return Err(LockError::OutOfBounds);
+        }
+        let num = new_cycle_len.div_ceil(32);
+        self.locked_offsets.resize_with(num, || AtomicU32::new(0));
+        self.readers
+            .resize_with(new_cycle_len, || AtomicUsize::new(0));
+        #[cfg(feature = "std")]
+        self.waiters.resize_with(num, OffsetWaiters::default);
+        self.cycle_len = new_cycle_len;
+        self.cycle_num_elems = cycle_num_elems;
+        Ok(())
+    }
+
+    /// Get the number of complete repeat cycles in the embedded [Vec].
+    ///
+    /// Valid `cycle` indices into a guard are `0..num_cycles()`.
+    #[inline]
+    pub fn num_cycles(&self) -> usize {
+        self.data_len() / self.cycle_num_elems
+    }
+
+    /// Get a raw pointer to the first element of the data.
+    ///
+    /// This is intended for handing the data to foreign code, e.g. via FFI.
+    /// Getting the pointer is safe, but dereferencing it is not.
+    /// The pointer must only be used to access elements of the cycle offsets
+    /// that the caller has locked and only while the guards exist.
+    ///
+    /// With uniform slice lengths, the `i`-th element of `cycle_offset` in `cycle`
+    /// is at the index `(cycle * cycle_len + cycle_offset) * stride + i`.
+    /// The `stride` is the one passed to [RepVecRangeLock::with_stride].
+    /// It is the slice length for [RepVecRangeLock::new].
+    ///
+    /// With the variable slice lengths of [RepVecRangeLock::with_slice_lens],
+    /// there is no closed formula.
+    /// The slices of one cycle are laid out back to back in the order of their offsets
+    /// and their lengths are given by [RepVecRangeLock::slice_len_at].
+    /// The slice of `cycle_offset` starts after the slices of all lower offsets
+    /// and one cycle spans the sum of all slice lengths.
+    ///
+    /// The data is never reallocated while the lock exists.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![1, 2, 3, 4, 5, 6, 7, 8], 2, 2);
+    /// let guard = lock.try_lock(1).expect("Failed to lock offset 1");
+    /// let base = lock.data_ptr();
+    /// // The stride is the slice length here.
+    /// let index = (lock.cycle_len() + 1) * lock.slice_len();
+    /// // SAFETY: Offset 1 is locked by `guard`.
+    /// unsafe { *base.add(index) = 70 };
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 3, 4, 5, 6, 70, 8]);
+    /// ```
+    #[inline]
+    pub fn data_ptr(&self) -> *mut T {
+        // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+        //         The Vec is never modified while the lock is shared.
+        let data = unsafe { &*self.data.get() };
+        // The pointer carries the provenance of the data buffer. See get_ptr().
+        data.as_ptr().cast_mut()
+    }
+
     /// Unwrap this [RepVecRangeLock] into the contained data.
     /// This method consumes self.
+    ///
+    /// Panics, if any offset is still locked.
+    /// That can only happen, if a guard has been leaked.
+    /// See [RepVecRangeLock::try_into_inner] for a non-panicking variant.
     #[inline]
     pub fn into_inner(self) -> Vec<T> {
-        debug_assert!(self
-            .locked_offsets
-            .iter()
-            .all(|x| x.load(Ordering::Acquire) == 0));
-        self.data.into_inner()
+        match self.try_into_inner() {
+            Ok(data) => data,
+            Err(_) => panic!("RepVecRangeLock::into_inner: Offsets are still locked."),
+        }
+    }
+
+    /// Unwrap this [RepVecRangeLock] into the contained data.
+    /// This method consumes self.
+    ///
+    /// Returns `Err(self)`, if any offset is still locked.
+    /// That can only happen, if a guard has been leaked.
+    #[allow(clippy::result_large_err)]
+    pub fn try_into_inner(self) -> Result<Vec<T>, Self> {
+        if self.all_offsets_free() {
+            Ok(self.data.into_inner())
+        } else {
+            Err(self)
+        }
+    }
+
+    /// Convert this [RepVecRangeLock] into a [VecRangeLock] of the same data.
+    /// This method consumes self.
+    /// The data is moved without copying.
+    ///
+    /// Returns `Err(self)`, if any offset is still locked.
+    /// That can only happen, if a guard has been leaked.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![1, 2, 3, 4], 1, 2);
+    /// let lock = lock.into_general().expect("Offsets are locked");
+    /// assert_eq!(*lock.try_lock(1..3).expect("Failed to lock 1..3"), [2, 3]);
+    /// ```
+    #[cfg(feature = "std")]
+    #[allow(clippy::result_large_err)]
+    pub fn into_general(self) -> Result<VecRangeLock<T>, Self> {
+        self.try_into_inner().map(VecRangeLock::new)
+    }
+
+    /// Convert this [RepVecRangeLock] into a [VecRangeLock] of the same data.
+    ///
+    /// This is the same as [RepVecRangeLock::into_general].
+    /// See [VecRangeLock::into_rep] for the reverse direction.
+    #[cfg(feature = "std")]
+    #[allow(clippy::result_large_err)]
+    #[inline]
+    pub fn into_vec_range_lock(self) -> Result<VecRangeLock<T>, Self> {
+        self.into_general()
     }
 
     /// Try to lock the given data slice at 'cycle_offset'.
     ///
     /// * On success: Returns a [RepVecRangeLockGuard] that can be used to access the locked region.
-    ///               Indexing [RepVecRangeLockGuard] yields a slice of the `data`.
+    ///   Indexing [RepVecRangeLockGuard] yields a slice of the `data`.
     /// * On failure: Returns [TryLockError::WouldBlock], if the slice is contended.
-    ///               The locking attempt may be retried by the caller upon contention.
-    ///               Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
     #[inline]
     pub fn try_lock(&'a self, cycle_offset: usize) -> TryLockResult<RepVecRangeLockGuard<'a, T>> {
+        self.check_offset(cycle_offset);
+        if self.try_set_offset(cycle_offset, false) {
+            // Successfully acquired the lock.
+            TryLockResult::Ok(self.make_guard(cycle_offset))
+        } else {
+            // Already locked by another thread.
+            TryLockResult::Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Try to lock the given data slice at 'cycle_offset' and discard the reason of a failure.
+    ///
+    /// Returns `None`, if [RepVecRangeLock::try_lock] fails.
+    /// Panics, if 'cycle_offset' is not within the cycle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 4], 1, 2);
+    /// if let Some(mut guard) = lock.try_lock_opt(1) {
+    ///     guard[0][0] = 1;
+    /// }
+    /// assert_eq!(lock.into_inner(), vec![0, 1, 0, 0]);
+    /// ```
+    #[inline]
+    pub fn try_lock_opt(&'a self, cycle_offset: usize) -> Option<RepVecRangeLockGuard<'a, T>> {
+        self.try_lock(cycle_offset).ok()
+    }
+
+    /// Check whether the slice at 'cycle_offset' is currently locked.
+    ///
+    /// Returns true, if the offset is exclusively locked or read-locked.
+    /// The result is a momentary snapshot.
+    /// Other threads may lock or unlock the offset at any time.
+    ///
+    /// Panics, if 'cycle_offset' is not within the cycle.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 8], 1, 4);
+    /// let _guard = lock.try_lock(1).expect("Failed to lock offset 1.");
+    /// let free: Vec<usize> = (0..lock.cycle_len())
+    ///     .filter(|&offset| !lock.offset_is_locked(offset))
+    ///     .collect();
+    /// assert_eq!(free, vec![0, 2, 3]);
+    /// ```
+    pub fn offset_is_locked(&self, cycle_offset: usize) -> bool {
+        self.check_offset(cycle_offset);
+        let idx = cycle_offset / 32;
+        let mask = 1 << (cycle_offset % 32);
+        self.locked_offsets[idx].load(Ordering::Acquire) & mask != 0
+            || self.readers[cycle_offset].load(Ordering::Acquire) != 0
+    }
+
+    /// Check whether no cycle offset is currently locked.
+    ///
+    /// Returns false, if any offset is exclusively locked or read-locked.
+    /// The result is a momentary snapshot.
+    /// Other threads may lock offsets at any time.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 8], 1, 4);
+    /// let guard = lock.try_lock(1).expect("Failed to lock offset 1.");
+    /// assert!(!lock.all_offsets_free());
+    /// drop(guard);
+    /// assert!(lock.all_offsets_free());
+    /// ```
+    pub fn all_offsets_free(&self) -> bool {
+        self.locked_offsets
+            .iter()
+            .all(|x| x.load(Ordering::Acquire) == 0)
+            && self.readers.iter().all(|x| x.load(Ordering::Acquire) == 0)
+    }
+
+    /// Try to lock the given data slice at 'cycle_offset' with an owned guard.
+    ///
+    /// In contrast to [RepVecRangeLock::try_lock] the returned [OwnedRepVecRangeLockGuard]
+    /// does not borrow the lock.
+    /// It keeps the lock alive via the [Arc].
+    /// Therefore, it can be moved into spawned threads.
+    ///
+    /// Returns [TryLockError::WouldBlock], if the slice is contended.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    /// use std::{sync::Arc, thread};
+    ///
+    /// let lock = Arc::new(RepVecRangeLock::new(vec![1, 2, 3, 4], 1, 2));
+    /// let threads: Vec<_> = (0..lock.cycle_len())
+    ///     .map(|offset| {
+    ///         let mut guard = Arc::clone(&lock)
+    ///             .try_lock_owned(offset)
+    ///             .expect("Failed to lock offset.");
+    ///         thread::spawn(move || {
+    ///             guard[1][0] *= 10; // Cycle 1, Slice element 0
+    ///         })
+    ///     })
+    ///     .collect();
+    /// for t in threads {
+    ///     t.join().unwrap();
+    /// }
+    /// let lock = Arc::try_unwrap(lock).expect("Arc unwrap failed");
+    /// assert_eq!(lock.into_inner(), vec![1, 2, 30, 40]);
+    /// ```
+    pub fn try_lock_owned(
+        self: Arc<Self>,
+        cycle_offset: usize,
+    ) -> TryLockResult<OwnedRepVecRangeLockGuard<T>> {
+        self.check_offset(cycle_offset);
+        if self.try_set_offset(cycle_offset, false) {
+            let (slice_start, slice_len) = self.layout.span(cycle_offset);
+            Ok(OwnedRepVecRangeLockGuard {
+                lock: self,
+                cycle_offset,
+                slice_start,
+                slice_len,
+                _p: PhantomData,
+            })
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Get an iterator over lock attempts of all cycle offsets `0..cycle_len()`.
+    ///
+    /// Each item is the result of [RepVecRangeLock::try_lock] of the next offset.
+    /// The offset is locked lazily, when the iterator advances.
+    /// A single thread that drops each guard before advancing never contends with itself.
+    /// Offsets locked concurrently by other threads yield [TryLockError::WouldBlock].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![1, 2, 3, 4, 5, 6], 1, 3);
+    /// for guard in lock.iter_offsets() {
+    ///     let mut guard = guard.expect("Failed to lock offset.");
+    ///     let offset = guard.cycle_offset() as i32;
+    ///     for slice in guard.cycles_mut() {
+    ///         slice[0] += offset * 10;
+    ///     }
+    /// }
+    /// assert_eq!(lock.into_inner(), vec![1, 12, 23, 4, 15, 26]);
+    /// ```
+    pub fn iter_offsets(
+        &'a self,
+    ) -> impl Iterator<Item = TryLockResult<RepVecRangeLockGuard<'a, T>>> + 'a {
+        (0..self.cycle_len).map(move |cycle_offset| self.try_lock(cycle_offset))
+    }
+
+    /// Try to exclusively lock the given data slice at 'cycle_offset'.
+    ///
+    /// This is the same as [RepVecRangeLock::try_lock].
+    /// The slice is contended, if it is exclusively locked or read-locked.
+    #[inline]
+    pub fn try_write_lock(
+        &'a self,
+        cycle_offset: usize,
+    ) -> TryLockResult<RepVecRangeLockGuard<'a, T>> {
+        self.try_lock(cycle_offset)
+    }
+
+    /// Lock the given data slice at 'cycle_offset'.
+    ///
+    /// If the slice is contended, this blocks the calling thread
+    /// until the slice has been unlocked by its holder.
+    ///
+    /// Returns a [RepVecRangeLockGuard] that can be used to access the locked region.
+    /// Indexing [RepVecRangeLockGuard] yields a slice of the `data`.
+    #[cfg(feature = "std")]
+    pub fn lock(&'a self, cycle_offset: usize) -> RepVecRangeLockGuard<'a, T> {
+        self.check_offset(cycle_offset);
+        let locked = self.lock_until(cycle_offset, None);
+        debug_assert!(locked);
+        self.make_guard(cycle_offset)
+    }
+
+    /// Lock the given data slice at 'cycle_offset' with a `timeout`.
+    ///
+    /// If the slice is contended, this blocks the calling thread
+    /// until the slice has been unlocked by its holder or until the `timeout` has elapsed.
+    ///
+    /// * On success: Returns a [RepVecRangeLockGuard] that can be used to access the locked region.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the slice is still contended
+    ///   after the `timeout`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    /// use std::time::Duration;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 4], 1, 2);
+    /// let guard = lock.try_lock(1).expect("Failed to lock offset.");
+    /// assert!(lock.try_lock_for(1, Duration::from_millis(10)).is_err());
+    /// drop(guard);
+    /// assert!(lock.try_lock_for(1, Duration::from_millis(10)).is_ok());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn try_lock_for(
+        &'a self,
+        cycle_offset: usize,
+        timeout: Duration,
+    ) -> TryLockResult<RepVecRangeLockGuard<'a, T>> {
+        self.check_offset(cycle_offset);
+        // A deadline beyond the representable time waits forever.
+        let deadline = Instant::now().checked_add(timeout);
+        if self.lock_until(cycle_offset, deadline) {
+            Ok(self.make_guard(cycle_offset))
+        } else {
+            Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Set the lock bit of 'cycle_offset'.
+    /// Blocks, until the offset is available or until the `deadline` has passed.
+    /// Returns true, if the lock bit has been set.
+    ///
+    /// 'cycle_offset' must have been checked against cycle_len.
+    #[cfg(feature = "std")]
+    fn lock_until(&self, cycle_offset: usize, deadline: Option<Instant>) -> bool {
+        if self.try_set_offset(cycle_offset, false) {
+            return true;
+        }
+        let waiters = &self.waiters[cycle_offset / 32];
+        let mut mutex = waiters.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+        // Announce the waiter before trying again.
+        // See unlock() for the corresponding check.
+        waiters.count.fetch_add(1, Ordering::SeqCst);
+        let locked = loop {
+            if self.try_set_offset(cycle_offset, true) {
+                break true;
+            }
+            // All offsets of the word share the condition.
+            // The wakeup might have been caused by an unrelated offset.
+            // Just check our offset again and go back to sleep, if it's still locked.
+            // The remaining time is recomputed on every wakeup.
+            mutex = match deadline {
+                None => waiters
+                    .cond
+                    .wait(mutex)
+                    .unwrap_or_else(PoisonError::into_inner),
+                Some(deadline) => {
+                    let now = Instant::now();
+                    if now >= deadline {
+                        break false;
+                    }
+                    waiters
+                        .cond
+                        .wait_timeout(mutex, deadline - now)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+            };
+        };
+        waiters.count.fetch_sub(1, Ordering::SeqCst);
+        locked
+    }
+
+    /// Check that 'cycle_offset' is within the cycle.
+    #[inline]
+    fn check_offset(&self, cycle_offset: usize) {
         if cycle_offset >= self.cycle_len {
             panic!("Invalid cycle_offset. It must be 0 <= cycle_offset < cycle_len.");
         }
+    }
+
+    /// Try to set the lock bit of 'cycle_offset'.
+    /// Returns true, if the bit was not set before and the offset has no readers.
+    ///
+    /// 'cycle_offset' must have been checked against cycle_len.
+    /// `waiters_locked` must be true, if the caller holds the waiters mutex of the word.
+    #[inline]
+    fn try_set_offset(&self, cycle_offset: usize, waiters_locked: bool) -> bool {
         let idx = cycle_offset / 32;
         let mask = 1 << (cycle_offset % 32);
         // SAFETY: cycle_offset has been checked against cycle_len.
+        let word = unsafe { self.locked_offsets.get_unchecked(idx) };
+        let prev = word.fetch_or(mask, Ordering::SeqCst);
+        if prev & mask != 0 {
+            return false;
+        }
+        // The bit operations and the reader count operations are sequentially consistent.
+        // Therefore, either the reader sees our bit or we see the reader.
+        // SAFETY: cycle_offset has been checked against cycle_len.
+        let readers = unsafe { self.readers.get_unchecked(cycle_offset) };
+        if readers.load(Ordering::SeqCst) != 0 {
+            // The offset is read-locked. Roll back.
+            // Other writers may have seen our bit. Wake them up.
+            #[cfg(feature = "std")]
+            if waiters_locked {
+                word.fetch_xor(mask, Ordering::SeqCst);
+                // We already hold the mutex. Notify directly.
+                // SAFETY: cycle_offset has been checked against cycle_len.
+                unsafe { self.waiters.get_unchecked(idx) }.cond.notify_all();
+                return false;
+            }
+            // There are no blocking waiters without std.
+            #[cfg(not(feature = "std"))]
+            debug_assert!(!waiters_locked);
+            self.unlock_mask(idx, mask);
+            return false;
+        }
+        true
+    }
+
+    /// Try to add a shared reader to 'cycle_offset'.
+    /// Returns true, if the offset is not exclusively locked.
+    ///
+    /// 'cycle_offset' must have been checked against cycle_len.
+    #[inline]
+    fn try_add_reader(&self, cycle_offset: usize) -> bool {
+        let idx = cycle_offset / 32;
+        let mask = 1 << (cycle_offset % 32);
+        self.readers[cycle_offset].fetch_add(1, Ordering::SeqCst);
+        if self.locked_offsets[idx].load(Ordering::SeqCst) & mask != 0 {
+            // The offset is exclusively locked. Roll back.
+            self.remove_reader(cycle_offset);
+            return false;
+        }
+        true
+    }
+
+    /// Remove a shared reader from 'cycle_offset'.
+    #[inline]
+    fn remove_reader(&self, cycle_offset: usize) {
+        let prev = self.readers[cycle_offset].fetch_sub(1, Ordering::SeqCst);
+        debug_assert!(prev > 0);
+        self.wake_waiters(cycle_offset / 32);
+    }
+
+    /// Construct the guard for the locked 'cycle_offset'.
+    #[inline]
+    fn make_guard(&'a self, cycle_offset: usize) -> RepVecRangeLockGuard<'a, T> {
+        let (slice_start, slice_len) = self.layout.span(cycle_offset);
+        RepVecRangeLockGuard::new(self, cycle_offset, slice_start, slice_len)
+    }
+
+    /// Try to lock the data slices at all of the given `cycle_offsets` at once.
+    ///
+    /// Either all offsets are locked or none of them.
+    /// Offsets that are contained multiple times in `cycle_offsets` are locked once.
+    ///
+    /// * On success: Returns a [RepVecRangeLockMultiGuard] that can be used to access the locked regions.
+    ///   Indexing [RepVecRangeLockMultiGuard] with `(cycle_offset, cycle)` yields a slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if any of the slices is contended.
+    ///   None of the offsets is locked in this case.
+    ///   The locking attempt may be retried by the caller upon contention.
+    pub fn try_lock_multi(
+        &'a self,
+        cycle_offsets: &[usize],
+    ) -> TryLockResult<RepVecRangeLockMultiGuard<'a, T>> {
+        let masks = self.offset_masks(cycle_offsets, true);
+        self.try_lock_masks(masks)
+    }
+
+    /// Try to lock the data slices at all of the given `cycle_offsets` at once.
+    ///
+    /// This is a strict variant of [RepVecRangeLock::try_lock_multi].
+    /// Panics, if `cycle_offsets` contains an offset multiple times.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 16], 1, 8);
+    /// let mut guard = lock.try_lock_offsets(&[0, 3, 7]).expect("Failed to lock offsets.");
+    /// guard[(3, 1)][0] = 1; // Offset 3, Cycle 1, Slice element 0
+    /// assert!(lock.try_lock_offsets(&[1, 3]).is_err());
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner()[11], 1);
+    /// ```
+    pub fn try_lock_offsets(
+        &'a self,
+        cycle_offsets: &[usize],
+    ) -> TryLockResult<RepVecRangeLockMultiGuard<'a, T>> {
+        let masks = self.offset_masks(cycle_offsets, false);
+        self.try_lock_masks(masks)
+    }
+
+    /// Try to lock the data slices at the contiguous block of `cycle_offsets` at once.
+    ///
+    /// Either all offsets are locked or none of them.
+    /// The block may span multiple words of the internal lock bitmap.
+    /// Panics, if the start of the block is bigger than its end
+    /// or if the block exceeds cycle_len.
+    ///
+    /// See [RepVecRangeLock::try_lock_multi] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 128], 1, 64);
+    /// let mut guard = lock.try_lock_offset_range(30..34).expect("Failed to lock offsets.");
+    /// guard[(33, 1)][0] = 1; // Offset 33, Cycle 1, Slice element 0
+    /// assert!(lock.try_lock(31).is_err());
+    /// assert!(lock.try_lock_offset_range(33..40).is_err());
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner()[97], 1);
+    /// ```
+    pub fn try_lock_offset_range(
+        &'a self,
+        cycle_offsets: Range<usize>,
+    ) -> TryLockResult<RepVecRangeLockMultiGuard<'a, T>> {
+        let masks = self.offset_range_masks(cycle_offsets);
+        self.try_lock_masks(masks)
+    }
+
+    /// Try to lock the data slices at all cycle offsets at once.
+    ///
+    /// Either all offsets are locked or none of them.
+    /// This is the same as [RepVecRangeLock::try_lock_offset_range] of `0..cycle_len()`.
+    /// Dropping the guard unlocks all offsets.
+    ///
+    /// See [RepVecRangeLock::try_lock_multi] for the return values.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![0; 6], 1, 3);
+    /// let mut guard = lock.try_lock_all().expect("Failed to lock all offsets.");
+    /// guard[(2, 1)][0] = 1; // Offset 2, Cycle 1, Slice element 0
+    /// assert!(lock.try_lock(0).is_err());
+    /// drop(guard);
+    /// assert_eq!(lock.into_inner()[5], 1);
+    /// ```
+    #[inline]
+    pub fn try_lock_all(&'a self) -> TryLockResult<RepVecRangeLockMultiGuard<'a, T>> {
+        self.try_lock_offset_range(0..self.cycle_len)
+    }
+
+    /// Build the `(word index, mask)` pairs of the block `cycle_offsets`, sorted by word index.
+    fn offset_range_masks(&self, cycle_offsets: Range<usize>) -> Vec<(usize, u32)> {
+        if cycle_offsets.start > cycle_offsets.end {
+            panic!("Invalid cycle_offset range. Start is bigger than end.");
+        }
+        if cycle_offsets.is_empty() {
+            return Vec::new();
+        }
+        self.check_offset(cycle_offsets.end - 1);
+        (cycle_offsets.start / 32..cycle_offsets.end.div_ceil(32))
+            .map(|idx| {
+                let lo = cycle_offsets.start.max(idx * 32) - idx * 32;
+                let hi = cycle_offsets.end.min(idx * 32 + 32) - idx * 32;
+                let mask = ((1_u64 << hi) - (1_u64 << lo)) as u32;
+                (idx, mask)
+            })
+            .collect()
+    }
+
+    /// Build the `(word index, mask)` pairs of `cycle_offsets`, sorted by word index.
+    fn offset_masks(&self, cycle_offsets: &[usize], allow_duplicates: bool) -> Vec<(usize, u32)> {
+        let mut masks: Vec<(usize, u32)> = Vec::new();
+        for &cycle_offset in cycle_offsets {
+            self.check_offset(cycle_offset);
+            let idx = cycle_offset / 32;
+            let mask = 1 << (cycle_offset % 32);
+            match masks.binary_search_by_key(&idx, |&(i, _)| i) {
+                Ok(pos) => {
+                    if !allow_duplicates && masks[pos].1 & mask != 0 {
+                        panic!("Duplicate cycle_offset {cycle_offset}.");
+                    }
+                    masks[pos].1 |= mask;
+                }
+                Err(pos) => masks.insert(pos, (idx, mask)),
+            }
+        }
+        masks
+    }
+
+    /// Try to lock all offsets of `masks` at once.
+    fn try_lock_masks(
+        &'a self,
+        masks: Vec<(usize, u32)>,
+    ) -> TryLockResult<RepVecRangeLockMultiGuard<'a, T>> {
+        if self.try_set_masks(&masks) {
+            TryLockResult::Ok(RepVecRangeLockMultiGuard::new(self, masks))
+        } else {
+            TryLockResult::Err(TryLockError::WouldBlock)
+        }
+    }
+
+    /// Try to set all bits of the `(word index, mask)` pairs in `masks`.
+    /// Returns true, if none of the bits was set before.
+    /// If any bit was set before, the bits set by this call are rolled back.
+    fn try_set_masks(&self, masks: &[(usize, u32)]) -> bool {
+        for (i, &(idx, mask)) in masks.iter().enumerate() {
+            let prev = self.locked_offsets[idx].fetch_or(mask, Ordering::SeqCst);
+            if prev & mask != 0 {
+                // Conflict. Roll back the bits that we have set.
+                let ours = mask & !prev;
+                if ours != 0 {
+                    self.unlock_mask(idx, ours);
+                }
+                for &(idx, mask) in &masks[..i] {
+                    self.unlock_mask(idx, mask);
+                }
+                return false;
+            }
+        }
+        // Check for readers of the offsets. See try_set_offset().
+        let has_readers = masks.iter().any(|&(idx, mask)| {
+            (0..32)
+                .filter(|bit| mask & (1 << bit) != 0)
+                .any(|bit| self.readers[idx * 32 + bit].load(Ordering::SeqCst) != 0)
+        });
+        if has_readers {
+            for &(idx, mask) in masks {
+                self.unlock_mask(idx, mask);
+            }
+            return false;
+        }
+        true
+    }
+
+    /// Unlock a slice at 'cycle_offset'.
+    #[inline]
+    fn unlock(&self, cycle_offset: usize) {
+        self.unlock_mask(cycle_offset / 32, 1 << (cycle_offset % 32));
+    }
+
+    /// Unlock all offsets in `mask` of the word at `idx`.
+    #[inline]
+    fn unlock_mask(&self, idx: usize, mask: u32) {
+        // SAFETY: idx has been checked against cycle_len in try_lock().
         let prev =
-            unsafe { self.locked_offsets.get_unchecked(idx) }.fetch_or(mask, Ordering::AcqRel);
-        if prev & mask == 0 {
-            // Multiply cannot overflow due to slice_len, cycle_len and cycle_offset checks.
-            let cycle_offset_slices = self.slice_len * cycle_offset;
-            // Successfully acquired the lock.
-            TryLockResult::Ok(RepVecRangeLockGuard::new(
-                self,
+            unsafe { self.locked_offsets.get_unchecked(idx) }.fetch_xor(mask, Ordering::SeqCst);
+        debug_assert!(prev & mask == mask);
+        self.wake_waiters(idx);
+    }
+
+    /// Wake up the threads blocking in lock() on the word at `idx`.
+    #[cfg(feature = "std")]
+    #[inline]
+    fn wake_waiters(&self, idx: usize) {
+        // The bit operations, the reader count operations and the waiter count operations
+        // are sequentially consistent.
+        // Therefore, either the waiter sees the unlocked offset
+        // or we see the waiter count increment.
+        // SAFETY: idx has been checked against cycle_len in try_lock().
+        let waiters = unsafe { self.waiters.get_unchecked(idx) };
+        if waiters.count.load(Ordering::SeqCst) != 0 {
+            let _mutex = waiters.mutex.lock().unwrap_or_else(PoisonError::into_inner);
+            waiters.cond.notify_all();
+        }
+    }
+
+    /// There are no threads blocking in lock() without std.
+    #[cfg(not(feature = "std"))]
+    #[inline]
+    fn wake_waiters(&self, _idx: usize) {}
+
+    /// Get a raw pointer to the first element of the slice at 'cycle' / 'slice_start'.
+    ///
+    /// The pointer is derived from the [Vec]'s data buffer.
+    /// No reference to the data elements is created.
+    #[inline]
+    fn get_ptr(&self, slice_start: usize, slice_len: usize, cycle: usize) -> *mut T {
+        if let Some(cycle_elemidx) = self.cycle_num_elems.checked_mul(cycle) {
+            if let Some(begin) = cycle_elemidx.checked_add(slice_start) {
+                if let Some(end) = begin.checked_add(slice_len) {
+                    // SAFETY: Multithreaded access to the Vec itself (not its elements) is safe.
+                    //         The Vec is never modified while the lock is shared.
+                    let data = unsafe { &*self.data.get() };
+                    if end <= data.len() {
+                        // The pointer returned by as_ptr() carries the provenance of the data buffer.
+                        // It does not carry the shared provenance of the `data` reference.
+                        // Therefore, it may be used for mutable access.
+                        let ptr = data.as_ptr().cast_mut();
+                        // SAFETY: The slice has been checked against the data length.
+                        //         Therefore, the resulting pointer is in bounds of the data buffer.
+                        return unsafe { ptr.add(begin) };
+                    }
+                }
+            }
+        }
+        panic!("RepVecRangeLock cycle index out of range.");
+    }
+
+    /// Get the number of cycles that contain the slice at 'slice_start'.
+    ///
+    /// This includes a trailing incomplete cycle, if it contains the whole slice.
+    #[inline]
+    fn slice_num_cycles(&self, slice_start: usize, slice_len: usize) -> usize {
+        let data_len = self.data_len();
+        let slice_end = slice_start + slice_len;
+        if data_len < slice_end {
+            0
+        } else {
+            (data_len - slice_end) / self.cycle_num_elems + 1
+        }
+    }
+
+    /// Get an immutable slice at 'cycle' / 'slice_start'.
+    ///
+    /// # SAFETY
+    ///
+    /// See get_mut_slice().
+    #[inline]
+    unsafe fn get_slice(&self, slice_start: usize, slice_len: usize, cycle: usize) -> &[T] {
+        slice::from_raw_parts(self.get_ptr(slice_start, slice_len, cycle), slice_len)
+    }
+
+    /// Get a mutable slice at 'cycle' / 'slice_start'.
+    ///
+    /// # SAFETY
+    ///
+    /// The caller must ensure that:
+    /// * No overlapping slices must coexist on multiple threads.
+    /// * Immutable slices to overlapping ranges may only coexist on a single thread.
+    /// * Immutable and mutable slices must not coexist.
+    #[inline]
+    #[allow(clippy::mut_from_ref)] // Slices won't overlap. See SAFETY.
+    unsafe fn get_mut_slice(&self, slice_start: usize, slice_len: usize, cycle: usize) -> &mut [T] {
+        // The mutable slice is derived directly from the mutable data pointer.
+        // It is never derived from an immutable slice.
+        slice::from_raw_parts_mut(self.get_ptr(slice_start, slice_len, cycle), slice_len)
+    }
+}
+
+/// Lock guard variable type for [RepVecRangeLock].
+///
+/// The [Index] and [IndexMut] traits are implemented for this struct.
+/// See the documentation of [RepVecRangeLock] for usage examples of [RepVecRangeLockGuard].
+#[derive(Debug)]
+pub struct RepVecRangeLockGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a RepVecRangeLock<T>,
+    /// The locked cycle offset.
+    cycle_offset: usize,
+    /// The locked slice start within the cycle.
+    slice_start: usize,
+    /// The locked slice length.
+    slice_len: usize,
+    /// Suppresses Send and Sync autotraits for RepVecRangeLockGuard.
+    /// The &mut suppresses Sync and the Rc suppresses Send.
+    #[allow(clippy::redundant_allocation)]
+    _p: PhantomData<Rc<&'a mut T>>,
+}
+
+impl<'a, T> RepVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn new(
+        lock: &'a RepVecRangeLock<T>,
+        cycle_offset: usize,
+        slice_start: usize,
+        slice_len: usize,
+    ) -> RepVecRangeLockGuard<'a, T> {
+        RepVecRangeLockGuard {
+            lock,
+            cycle_offset,
+            slice_start,
+            slice_len,
+            _p: PhantomData,
+        }
+    }
+
+    /// Get the cycle offset locked by this guard.
+    #[inline]
+    pub fn cycle_offset(&self) -> usize {
+        self.cycle_offset
+    }
+
+    /// Get the number of cycles that contain the locked slice.
+    ///
+    /// Valid `cycle` indices into this guard are `0..num_cycles()`.
+    /// This includes a trailing incomplete cycle, if it contains the whole slice.
+    #[inline]
+    pub fn num_cycles(&self) -> usize {
+        self.lock.slice_num_cycles(self.slice_start, self.slice_len)
+    }
+
+    /// Get an iterator over the locked slices of all cycles.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![1, 2, 3, 4, 5, 6, 7], 1, 2);
+    /// let mut guard = lock.try_lock(0).expect("Failed to lock offset 0.");
+    /// assert_eq!(guard.num_cycles(), 4);
+    /// for slice in guard.cycles_mut() {
+    ///     slice[0] *= 10;
+    /// }
+    /// let sum: i32 = guard.cycles().map(|slice| slice[0]).sum();
+    /// assert_eq!(sum, 10 + 30 + 50 + 70);
+    /// ```
+    pub fn cycles(&self) -> impl Iterator<Item = &[T]> + '_ {
+        (0..self.num_cycles()).map(|cycle| &self[cycle])
+    }
+
+    /// Get an iterator over the mutable locked slices of all cycles.
+    ///
+    /// See [RepVecRangeLockGuard::cycles].
+    pub fn cycles_mut(&mut self) -> impl Iterator<Item = &mut [T]> + '_ {
+        let (lock, slice_start, slice_len) = (self.lock, self.slice_start, self.slice_len);
+        // SAFETY:
+        // The slices of different cycles don't overlap.
+        // The guard is mutably borrowed as long as the iterator and the slices exist.
+        // See index_mut().
+        (0..self.num_cycles())
+            .map(move |cycle| unsafe { lock.get_mut_slice(slice_start, slice_len, cycle) })
+    }
+}
+
+impl<'a, T> Drop for RepVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock(self.cycle_offset);
+    }
+}
+
+impl<'a, T> Index<usize> for RepVecRangeLockGuard<'a, T> {
+    type Output = [T];
+
+    #[inline]
+    fn index(&self, cycle: usize) -> &Self::Output {
+        // SAFETY: See index_mut().
+        unsafe { self.lock.get_slice(self.slice_start, self.slice_len, cycle) }
+    }
+}
+
+impl<'a, T> IndexMut<usize> for RepVecRangeLockGuard<'a, T> {
+    #[inline]
+    fn index_mut(&mut self, cycle: usize) -> &mut Self::Output {
+        // SAFETY:
+        // The lifetime of the slice is bounded by the lifetime of the guard.
+        // The lifetime of the guard is bounded by the lifetime of the range lock.
+        // The underlying data is owned by the range lock.
+        // Therefore the slice cannot outlive the data.
+        // The range lock ensures that no overlapping/conflicting guards
+        // can be constructed.
+        // The compiler ensures that the DerefMut result cannot be used,
+        // if there's also an immutable Deref result.
+        unsafe {
+            self.lock
+                .get_mut_slice(self.slice_start, self.slice_len, cycle)
+        }
+    }
+}
+
+/// Owned lock guard variable type for [RepVecRangeLock].
+///
+/// The [Index] and [IndexMut] traits are implemented for this struct.
+/// See [RepVecRangeLock::try_lock_owned] for usage examples.
+#[derive(Debug)]
+pub struct OwnedRepVecRangeLockGuard<T> {
+    /// Reference to the underlying lock.
+    lock: Arc<RepVecRangeLock<T>>,
+    /// The locked cycle offset.
+    cycle_offset: usize,
+    /// The locked slice start within the cycle.
+    slice_start: usize,
+    /// The locked slice length.
+    slice_len: usize,
+
+    /// The guard behaves like owned mutable slices with respect to autotraits.
+    _p: PhantomData<Box<[T]>>,
+}
+
+impl<T> OwnedRepVecRangeLockGuard<T> {
+    /// Get the cycle offset locked by this guard.
+    #[inline]
+    pub fn cycle_offset(&self) -> usize {
+        self.cycle_offset
+    }
+
+    /// Get the number of cycles that contain the locked slice.
+    ///
+    /// See [RepVecRangeLockGuard::num_cycles].
+    #[inline]
+    pub fn num_cycles(&self) -> usize {
+        self.lock.slice_num_cycles(self.slice_start, self.slice_len)
+    }
+}
+
+impl<T> Drop for OwnedRepVecRangeLockGuard<T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.lock.unlock(self.cycle_offset);
+    }
+}
+
+impl<T> Index<usize> for OwnedRepVecRangeLockGuard<T> {
+    type Output = [T];
+
+    #[inline]
+    fn index(&self, cycle: usize) -> &Self::Output {
+        // SAFETY: See index_mut().
+        unsafe { self.lock.get_slice(self.slice_start, self.slice_len, cycle) }
+    }
+}
+
+impl<T> IndexMut<usize> for OwnedRepVecRangeLockGuard<T> {
+    #[inline]
+    fn index_mut(&mut self, cycle: usize) -> &mut Self::Output {
+        // SAFETY:
+        // The guard keeps the lock and the underlying data alive.
+        // See RepVecRangeLockGuard::index_mut().
+        unsafe {
+            self.lock
+                .get_mut_slice(self.slice_start, self.slice_len, cycle)
+        }
+    }
+}
+
+impl<'a, T> RepVecRangeLock<T>
+where
+    T: Sync,
+{
+    /// Try to lock the given data slice at 'cycle_offset' for shared reading.
+    ///
+    /// Multiple read guards of the same offset may exist at the same time.
+    /// The slice is contended, if it is exclusively locked.
+    ///
+    /// * On success: Returns a [RepVecRangeLockReadGuard].
+    ///   Indexing [RepVecRangeLockReadGuard] yields an immutable slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the slice is contended.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::RepVecRangeLock;
+    ///
+    /// let lock = RepVecRangeLock::new(vec![1, 2, 3, 4], 1, 2);
+    /// let r0 = lock.try_read_lock(1).expect("Failed to read-lock offset 1.");
+    /// let r1 = lock.try_read_lock(1).expect("Failed to read-lock offset 1.");
+    /// assert_eq!(r0[1][0], 4); // Cycle 1, Slice element 0
+    /// assert_eq!(r1[0][0], 2); // Cycle 0, Slice element 0
+    /// assert!(lock.try_write_lock(1).is_err());
+    /// drop((r0, r1));
+    /// assert!(lock.try_write_lock(1).is_ok());
+    /// ```
+    pub fn try_read_lock(
+        &'a self,
+        cycle_offset: usize,
+    ) -> TryLockResult<RepVecRangeLockReadGuard<'a, T>> {
+        self.check_offset(cycle_offset);
+        if self.try_add_reader(cycle_offset) {
+            let (slice_start, slice_len) = self.layout.span(cycle_offset);
+            TryLockResult::Ok(RepVecRangeLockReadGuard {
+                lock: self,
                 cycle_offset,
-                cycle_offset_slices,
-            ))
+                slice_start,
+                slice_len,
+                _p: PhantomData,
+            })
         } else {
-            // Already locked by another thread.
             TryLockResult::Err(TryLockError::WouldBlock)
         }
     }
+}
 
-    /// Unlock a slice at 'cycle_offset'.
-    #[inline]
-    fn unlock(&self, cycle_offset: usize) {
-        let idx = cycle_offset / 32;
-        let mask = 1 << (cycle_offset % 32);
-        // SAFETY: cycle_offset has been checked against cycle_len in try_lock().
-        let prev =
-            unsafe { self.locked_offsets.get_unchecked(idx) }.fetch_xor(mask, Ordering::Release);
-        debug_assert!(prev & mask != 0);
-    }
+/// Shared read lock guard variable type for [RepVecRangeLock].
+///
+/// The [Index] trait is implemented for this struct.
+/// Indexing with the cycle yields an immutable slice.
+/// See [RepVecRangeLock::try_read_lock] for usage examples.
+#[derive(Debug)]
+pub struct RepVecRangeLockReadGuard<'a, T> {
+    /// Reference to the underlying lock.
+    lock: &'a RepVecRangeLock<T>,
+    /// The read-locked cycle offset.
+    cycle_offset: usize,
+    /// The locked slice start within the cycle.
+    slice_start: usize,
+    /// The locked slice length.
+    slice_len: usize,
+    /// Suppresses Send and Sync autotraits for RepVecRangeLockReadGuard.
+    #[allow(clippy::redundant_allocation)]
+    _p: PhantomData<Rc<&'a T>>,
+}
 
-    /// Get an immutable slice at 'cycle' / 'cycle_offset'.
-    ///
-    /// # SAFETY
-    ///
-    /// See get_mut_slice().
+impl<'a, T> Drop for RepVecRangeLockReadGuard<'a, T> {
     #[inline]
-    unsafe fn get_slice(&self, cycle_offset_slices: usize, cycle: usize) -> &[T] {
-        if let Some(cycle_elemidx) = self.cycle_num_elems.checked_mul(cycle) {
-            if let Some(begin) = cycle_elemidx.checked_add(cycle_offset_slices) {
-                if let Some(end) = begin.checked_add(self.slice_len) {
-                    let dataptr = self.data.get();
-                    if end <= (*dataptr).len() {
-                        // SAFETY: We trust the slicing machinery of Vec to work correctly.
-                        //         It must return the slice range that we requested.
-                        //         Otherwise our non-overlap guarantees are gone.
-                        return &(*dataptr)[begin..end];
-                    }
-                }
-            }
-        }
-        panic!("RepVecRangeLock cycle index out of range.");
+    fn drop(&mut self) {
+        self.lock.remove_reader(self.cycle_offset);
     }
+}
+
+impl<'a, T> Index<usize> for RepVecRangeLockReadGuard<'a, T> {
+    type Output = [T];
 
-    /// Get a mutable slice at 'cycle' / 'cycle_offset'.
-    ///
-    /// # SAFETY
-    ///
-    /// The caller must ensure that:
-    /// * No overlapping slices must coexist on multiple threads.
-    /// * Immutable slices to overlapping ranges may only coexist on a single thread.
-    /// * Immutable and mutable slices must not coexist.
     #[inline]
-    #[allow(clippy::mut_from_ref)] // Slices won't overlap. See SAFETY.
-    unsafe fn get_mut_slice(&self, cycle_offset_slices: usize, cycle: usize) -> &mut [T] {
-        let cptr = self.get_slice(cycle_offset_slices, cycle) as *const [T];
-        let mut_slice = (cptr as *mut [T]).as_mut();
-        // SAFETY: The pointer is never null, because it has been casted from a slice.
-        mut_slice.unwrap_or_else(|| unreachable_unchecked())
+    fn index(&self, cycle: usize) -> &Self::Output {
+        // SAFETY:
+        // The lock ensures that no exclusive guard of the offset exists.
+        // Read guards only hand out immutable slices.
+        // Read guards can only be constructed for T: Sync.
+        unsafe { self.lock.get_slice(self.slice_start, self.slice_len, cycle) }
     }
 }
 
-/// Lock guard variable type for [RepVecRangeLock].
+/// Lock guard variable type for multiple offsets of [RepVecRangeLock].
 ///
 /// The [Index] and [IndexMut] traits are implemented for this struct.
-/// See the documentation of [RepVecRangeLock] for usage examples of [RepVecRangeLockGuard].
+/// The index is the tuple `(cycle_offset, cycle)`.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::RepVecRangeLock;
+///
+/// let lock = RepVecRangeLock::new(vec![1, 2, 3,
+///                                      4, 5, 6], 1, 3);
+/// let mut guard = lock.try_lock_multi(&[0, 2]).expect("Failed to lock offsets.");
+/// assert_eq!(guard[(0, 1)][0], 4);     // Offset 0, Cycle 1, Slice element 0
+/// guard[(2, 0)][0] = 30;              // Offset 2, Cycle 0, Slice element 0
+/// // let _ = guard[(1, 0)];           // Would panic: Offset 1 is not locked.
+/// drop(guard);
+/// assert_eq!(lock.into_inner(), vec![1, 2, 30, 4, 5, 6]);
+/// ```
 #[derive(Debug)]
-pub struct RepVecRangeLockGuard<'a, T> {
+pub struct RepVecRangeLockMultiGuard<'a, T> {
     /// Reference to the underlying lock.
     lock: &'a RepVecRangeLock<T>,
-    /// The locked cycle offset.
-    cycle_offset: usize,
-    /// The locked slice start.
-    cycle_offset_slices: usize,
-    /// Suppresses Send and Sync autotraits for RepVecRangeLockGuard.
+    /// The locked cycle offsets as `(word index, mask)` pairs.
+    masks: Vec<(usize, u32)>,
+    /// Suppresses Send and Sync autotraits for RepVecRangeLockMultiGuard.
     /// The &mut suppresses Sync and the Rc suppresses Send.
     #[allow(clippy::redundant_allocation)]
     _p: PhantomData<Rc<&'a mut T>>,
 }
 
-impl<'a, T> RepVecRangeLockGuard<'a, T> {
+impl<'a, T> RepVecRangeLockMultiGuard<'a, T> {
     #[inline]
     fn new(
         lock: &'a RepVecRangeLock<T>,
-        cycle_offset: usize,
-        cycle_offset_slices: usize,
-    ) -> RepVecRangeLockGuard<'a, T> {
-        RepVecRangeLockGuard {
+        masks: Vec<(usize, u32)>,
+    ) -> RepVecRangeLockMultiGuard<'a, T> {
+        RepVecRangeLockMultiGuard {
             lock,
-            cycle_offset,
-            cycle_offset_slices,
+            masks,
             _p: PhantomData,
         }
     }
+
+    /// Check whether 'cycle_offset' is locked by this guard.
+    pub fn holds_offset(&self, cycle_offset: usize) -> bool {
+        let idx = cycle_offset / 32;
+        let mask = 1 << (cycle_offset % 32);
+        self.masks.iter().any(|&(i, m)| i == idx && m & mask != 0)
+    }
+
+    /// Get an iterator over the cycle offsets locked by this guard, in ascending order.
+    pub fn cycle_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        self.masks.iter().flat_map(|&(idx, mask)| {
+            (0..32)
+                .filter(move |bit| mask & (1 << bit) != 0)
+                .map(move |bit| idx * 32 + bit)
+        })
+    }
+
+    /// Get the `(start, len)` of the slice of the locked 'cycle_offset'.
+    #[inline]
+    fn slice_span(&self, cycle_offset: usize) -> (usize, usize) {
+        if !self.holds_offset(cycle_offset) {
+            panic!("RepVecRangeLockMultiGuard: cycle_offset is not locked by this guard.");
+        }
+        self.lock.layout.span(cycle_offset)
+    }
 }
 
-impl<'a, T> Drop for RepVecRangeLockGuard<'a, T> {
+impl<'a, T> Drop for RepVecRangeLockMultiGuard<'a, T> {
     #[inline]
     fn drop(&mut self) {
-        self.lock.unlock(self.cycle_offset);
+        for &(idx, mask) in &self.masks {
+            self.lock.unlock_mask(idx, mask);
+        }
     }
 }
 
-impl<'a, T> Index<usize> for RepVecRangeLockGuard<'a, T> {
+impl<'a, T> Index<(usize, usize)> for RepVecRangeLockMultiGuard<'a, T> {
     type Output = [T];
 
     #[inline]
-    fn index(&self, cycle: usize) -> &Self::Output {
+    fn index(&self, (cycle_offset, cycle): (usize, usize)) -> &Self::Output {
+        let (slice_start, slice_len) = self.slice_span(cycle_offset);
         // SAFETY: See index_mut().
-        unsafe { self.lock.get_slice(self.cycle_offset_slices, cycle) }
+        unsafe { self.lock.get_slice(slice_start, slice_len, cycle) }
     }
 }
 
-impl<'a, T> IndexMut<usize> for RepVecRangeLockGuard<'a, T> {
+impl<'a, T> IndexMut<(usize, usize)> for RepVecRangeLockMultiGuard<'a, T> {
     #[inline]
-    fn index_mut(&mut self, cycle: usize) -> &mut Self::Output {
+    fn index_mut(&mut self, (cycle_offset, cycle): (usize, usize)) -> &mut Self::Output {
+        let (slice_start, slice_len) = self.slice_span(cycle_offset);
         // SAFETY:
-        // The lifetime of the slice is bounded by the lifetime of the guard.
-        // The lifetime of the guard is bounded by the lifetime of the range lock.
-        // The underlying data is owned by the range lock.
-        // Therefore the slice cannot outlive the data.
-        // The range lock ensures that no overlapping/conflicting guards
-        // can be constructed.
-        // The compiler ensures that the DerefMut result cannot be used,
-        // if there's also an immutable Deref result.
-        unsafe { self.lock.get_mut_slice(self.cycle_offset_slices, cycle) }
+        // The guard holds the lock of cycle_offset.
+        // See RepVecRangeLockGuard::index_mut().
+        unsafe { self.lock.get_mut_slice(slice_start, slice_len, cycle) }
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "std"))]
 mod tests {
     use super::*;
     use std::cell::RefCell;
@@ -351,6 +1625,132 @@ mod tests {
         let _ = RepVecRangeLock::new(vec![0; 100], usize::MAX, 2);
     }
 
+    #[test]
+    fn test_try_lock_opt() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 2, 2);
+        let mut g = a.try_lock_opt(0).unwrap();
+        assert!(a.try_lock_opt(0).is_none());
+        let _r = a.try_read_lock(1).unwrap();
+        assert!(a.try_lock_opt(1).is_none());
+        g[1][0] = 5;
+        drop(g);
+        assert_eq!(a.try_lock_opt(0).unwrap()[1], [5, 0]);
+    }
+
+    #[test]
+    fn test_with_stride() {
+        let a = RepVecRangeLock::with_stride((0..20).collect::<Vec<i32>>(), 2, 3, 3);
+        assert_eq!((a.slice_len(), a.cycle_len(), a.num_cycles()), (2, 3, 2));
+        {
+            let g0 = a.try_lock(0).unwrap();
+            let mut g2 = a.try_lock(2).unwrap();
+            // The trailing incomplete cycle contains the whole slice of offset 0.
+            assert_eq!(
+                g0.cycles().collect::<Vec<_>>(),
+                vec![&[0, 1][..], &[9, 10], &[18, 19]]
+            );
+            assert_eq!(g2[0], [6, 7]);
+            assert_eq!(g2[1], [15, 16]);
+            g2[1][1] = 160;
+            assert!(a.try_lock(2).is_err());
+        }
+        assert_eq!(
+            RepVecRangeLock::try_with_stride(vec![0; 20], 3, 2, 2).unwrap_err(),
+            RepLockError::StrideTooSmall {
+                stride: 2,
+                slice_len: 3
+            }
+        );
+        assert_eq!(
+            RepVecRangeLock::try_with_stride(vec![0; 20], 1, 2, usize::MAX).unwrap_err(),
+            RepLockError::CycleOverflow
+        );
+        let mut a = RepVecRangeLock::with_stride(a.into_inner(), 1, 2, 4);
+        a.set_cycle_len(5).unwrap();
+        assert_eq!(a.num_cycles(), 1);
+        assert_eq!(a.try_lock(3).unwrap()[0], [12]);
+        assert_eq!(a.try_lock(4).unwrap()[0], [160]);
+    }
+
+    #[test]
+    #[should_panic(expected = "stride must not be smaller than slice_len")]
+    fn test_with_stride_too_small() {
+        let _ = RepVecRangeLock::with_stride(vec![0; 100], 3, 2, 2);
+    }
+
+    #[test]
+    fn test_try_new() {
+        assert_eq!(
+            RepVecRangeLock::try_new(vec![0; 100], 0, 1).unwrap_err(),
+            RepLockError::ZeroSliceLen
+        );
+        assert_eq!(
+            RepVecRangeLock::try_new(vec![0; 100], 1, 0).unwrap_err(),
+            RepLockError::CycleLenOutOfRange { cycle_len: 0 }
+        );
+        assert_eq!(
+            RepVecRangeLock::try_new(vec![0; 100], 1, usize::MAX - 30).unwrap_err(),
+            RepLockError::CycleLenOutOfRange {
+                cycle_len: usize::MAX - 30
+            }
+        );
+        assert_eq!(
+            RepVecRangeLock::try_new(vec![0; 100], usize::MAX, 2).unwrap_err(),
+            RepLockError::CycleOverflow
+        );
+        let a = RepVecRangeLock::try_new((0..12).collect::<Vec<i32>>(), 2, 3).unwrap();
+        assert_eq!((a.slice_len(), a.cycle_len()), (2, 3));
+        assert_eq!(a.try_lock(1).unwrap()[1], [8, 9]);
+    }
+
+    #[test]
+    fn test_slice_lens() {
+        let a = RepVecRangeLock::with_slice_lens((0..14).collect::<Vec<i32>>(), vec![4, 1, 2]);
+        assert_eq!(a.cycle_len(), 3);
+        assert_eq!(a.num_cycles(), 2);
+        assert_eq!(a.slice_len_at(0), 4);
+        assert_eq!(a.slice_len_at(1), 1);
+        assert_eq!(a.slice_len_at(2), 2);
+        {
+            let g0 = a.try_lock(0).unwrap();
+            let mut g2 = a.try_lock(2).unwrap();
+            assert_eq!(g0[0], [0, 1, 2, 3]);
+            assert_eq!(g0[1], [7, 8, 9, 10]);
+            assert_eq!(g2[0], [5, 6]);
+            g2[1][1] = 130;
+            let m = a.try_lock_multi(&[1]).unwrap();
+            assert_eq!(m[(1, 0)], [4]);
+            assert_eq!(m[(1, 1)], [11]);
+        }
+        let data = a.into_inner();
+        assert_eq!(data[13], 130);
+    }
+
+    #[test]
+    #[should_panic(expected = "slice_len must not be 0")]
+    fn test_slice_lens_zero() {
+        let _ = RepVecRangeLock::with_slice_lens(vec![0; 100], vec![1, 0, 2]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle_len out of range")]
+    fn test_slice_lens_empty() {
+        let _ = RepVecRangeLock::with_slice_lens(vec![0; 100], vec![]);
+    }
+
+    #[test]
+    #[should_panic(expected = "cycle overflow")]
+    fn test_slice_lens_overflow() {
+        let _ = RepVecRangeLock::with_slice_lens(vec![0; 100], vec![1, usize::MAX]);
+    }
+
+    #[test]
+    #[should_panic(expected = "variable lengths")]
+    fn test_slice_lens_slice_len() {
+        let a = RepVecRangeLock::with_slice_lens(vec![0; 100], vec![1, 2]);
+        let _ = a.slice_len();
+    }
+
     #[test]
     #[should_panic(expected = "must be 0 <= cycle_offset < cycle_len")]
     fn test_oob_lock_offset() {
@@ -382,6 +1782,26 @@ mod tests {
         let _g1 = a.try_lock(1).expect("guard 1 panicked");
     }
 
+    /// Regression test for mutable slice provenance.
+    /// Run this test with `cargo miri test` to check the aliasing model.
+    #[test]
+    fn test_mut_provenance() {
+        let a = RepVecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6], 1, 3);
+        let mut g0 = a.try_lock(0).unwrap();
+        let mut g2 = a.try_lock(2).unwrap();
+        g0[0][0] = 10;
+        g2[1][0] = 60;
+        g0[1][0] = 40;
+        g2[0][0] = 30;
+        assert_eq!(g0[0], [10]);
+        assert_eq!(g0[1], [40]);
+        assert_eq!(g2[0], [30]);
+        assert_eq!(g2[1], [60]);
+        drop(g0);
+        drop(g2);
+        assert_eq!(a.into_inner(), vec![10, 2, 30, 40, 5, 60]);
+    }
+
     #[test]
     fn test_big_cycle() {
         let a = Arc::new(RepVecRangeLock::new(
@@ -487,6 +1907,464 @@ mod tests {
             .all(|x| x.load(Ordering::Acquire) == 0));
     }
 
+    #[test]
+    fn test_blocking_lock() {
+        let a = RepVecRangeLock::new(vec![1_i32, 2, 3, 4], 1, 2);
+        let ba = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut g = a.lock(1);
+                ba.wait();
+                thread::sleep(std::time::Duration::from_millis(50));
+                g[0][0] = 20;
+            });
+            s.spawn(|| {
+                ba.wait();
+                assert!(a.try_lock(1).is_err());
+                let mut g = a.lock(1);
+                assert_eq!(g[0][0], 20);
+                g[1][0] = 40;
+            });
+        });
+        assert_eq!(a.into_inner(), vec![1, 20, 3, 40]);
+    }
+
+    #[test]
+    fn test_blocking_lock_unrelated_wakeup() {
+        let a = RepVecRangeLock::new(vec![0_i32; 64], 1, 32);
+        let ba = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let g5 = a.lock(5);
+                let g6 = a.lock(6);
+                ba.wait();
+                // Wait for the other thread to block in lock().
+                while a.waiters[0].count.load(Ordering::SeqCst) == 0 {
+                    thread::yield_now();
+                }
+                // Unlocking an unrelated offset in the same word wakes the waiter.
+                // It must go back to sleep.
+                drop(g6);
+                thread::sleep(std::time::Duration::from_millis(20));
+                assert_eq!(a.waiters[0].count.load(Ordering::SeqCst), 1);
+                assert!(a.locked_offsets[0].load(Ordering::Acquire) & (1 << 5) != 0);
+                drop(g5);
+            });
+            s.spawn(|| {
+                ba.wait();
+                let mut g = a.lock(5);
+                g[1][0] = 5;
+            });
+        });
+        assert_eq!(a.waiters[0].count.load(Ordering::SeqCst), 0);
+        assert!(a
+            .locked_offsets
+            .iter()
+            .all(|x| x.load(Ordering::Acquire) == 0));
+        assert_eq!(a.into_inner()[37], 5);
+    }
+
+    #[test]
+    fn test_blocking_lock_many_threads() {
+        let a = RepVecRangeLock::new(vec![0_u32; 8], 1, 4);
+        thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for i in 0..200 {
+                        let mut g = a.lock(i % 4);
+                        g[0][0] += 1;
+                        g[1][0] += 1;
+                    }
+                });
+            }
+        });
+        assert_eq!(a.into_inner(), vec![400; 8]);
+    }
+
+    #[test]
+    fn test_lock_multi() {
+        let a = RepVecRangeLock::new(vec![0_i32; 128], 1, 64);
+        {
+            let mut g = a.try_lock_multi(&[2, 0, 33, 2]).unwrap();
+            assert_eq!(g.masks, vec![(0, 0b101), (1, 0b10)]);
+            assert_eq!(g.cycle_offsets().collect::<Vec<_>>(), vec![0, 2, 33]);
+            assert!(g.holds_offset(33));
+            assert!(!g.holds_offset(1));
+            assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 0b101);
+            assert_eq!(a.locked_offsets[1].load(Ordering::Acquire), 0b10);
+            assert!(a.try_lock(0).is_err());
+            assert!(a.try_lock(33).is_err());
+            let _g1 = a.try_lock(1).unwrap();
+            g[(0, 0)][0] = 1;
+            g[(2, 1)][0] = 2;
+            g[(33, 1)][0] = 3;
+        }
+        assert!(a
+            .locked_offsets
+            .iter()
+            .all(|x| x.load(Ordering::Acquire) == 0));
+        let data = a.into_inner();
+        assert_eq!(data[0], 1);
+        assert_eq!(data[66], 2);
+        assert_eq!(data[97], 3);
+    }
+
+    #[test]
+    fn test_lock_multi_rollback() {
+        let a = RepVecRangeLock::new(vec![0_i32; 128], 1, 64);
+        let _g = a.try_lock(40).unwrap();
+        assert!(a.try_lock_multi(&[1, 3, 40, 41, 63]).is_err());
+        assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 0);
+        assert_eq!(a.locked_offsets[1].load(Ordering::Acquire), 1 << 8);
+        let _g = a.try_lock_multi(&[1, 3, 41, 63]).unwrap();
+        assert!(a.try_lock_multi(&[0, 3]).is_err());
+        assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 0b1010);
+    }
+
+    #[test]
+    fn test_lock_offset_range() {
+        let a = RepVecRangeLock::new(vec![0_i32; 200], 1, 100);
+        {
+            let g = a.try_lock_offset_range(30..34).unwrap();
+            assert_eq!(g.masks, vec![(0, 0b11 << 30), (1, 0b11)]);
+            assert!(a.try_lock(29).is_ok());
+            assert!(a.try_lock(30).is_err());
+            assert!(a.try_lock(33).is_err());
+            assert!(a.try_lock(34).is_ok());
+        }
+        {
+            let g = a.try_lock_offset_range(0..100).unwrap();
+            assert_eq!(
+                g.masks,
+                vec![(0, u32::MAX), (1, u32::MAX), (2, u32::MAX), (3, 0b1111)]
+            );
+        }
+        let _g0 = a.try_lock(70).unwrap();
+        assert!(a.try_lock_offset_range(10..71).is_err());
+        assert!(a
+            .locked_offsets
+            .iter()
+            .enumerate()
+            .all(|(i, x)| x.load(Ordering::Acquire) == if i == 2 { 1 << 6 } else { 0 }));
+        let g1 = a.try_lock_offset_range(32..64).unwrap();
+        assert_eq!(g1.masks, vec![(1, u32::MAX)]);
+        let g2 = a.try_lock_offset_range(5..5).unwrap();
+        assert_eq!(g2.cycle_offsets().count(), 0);
+    }
+
+    #[test]
+    fn test_try_lock_all() {
+        let a = RepVecRangeLock::new((0..80).collect::<Vec<i32>>(), 1, 40);
+        {
+            let g = a.try_lock_all().unwrap();
+            assert_eq!(g.cycle_offsets().count(), 40);
+            assert_eq!(g[(39, 1)], [79]);
+            assert!(a.try_lock(0).is_err());
+            assert!(a.try_lock(39).is_err());
+            assert!(a.try_lock_all().is_err());
+        }
+        assert!(a.all_offsets_free());
+        let _r = a.try_read_lock(33).unwrap();
+        assert!(a.try_lock_all().is_err());
+        // Nothing is left locked after the failed attempt.
+        assert!(a.try_lock(0).is_ok());
+        assert!(a.try_lock(39).is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid cycle_offset")]
+    fn test_lock_offset_range_oob() {
+        let a = RepVecRangeLock::new(vec![0_i32; 200], 1, 100);
+        let _ = a.try_lock_offset_range(90..101);
+    }
+
+    #[test]
+    fn test_try_lock_for() {
+        let a = RepVecRangeLock::new(vec![0_i32; 128], 1, 64);
+        let g = a.try_lock(5).unwrap();
+        let begin = Instant::now();
+        assert!(matches!(
+            a.try_lock_for(5, Duration::from_millis(50)),
+            Err(TryLockError::WouldBlock)
+        ));
+        assert!(begin.elapsed() >= Duration::from_millis(50));
+        assert_eq!(a.waiters[0].count.load(Ordering::SeqCst), 0);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let mut g = a.try_lock_for(5, Duration::from_secs(60)).unwrap();
+                g[0][0] = 1;
+            });
+            // Unrelated wakeups of the same word don't time out early.
+            for _ in 0..5 {
+                drop(a.try_lock(6).unwrap());
+                thread::sleep(Duration::from_millis(5));
+            }
+            drop(g);
+        });
+        assert!(a.try_lock_for(5, Duration::MAX).is_ok());
+        assert_eq!(a.into_inner()[5], 1);
+    }
+
+    #[test]
+    fn test_lock_offsets() {
+        let a = RepVecRangeLock::new(vec![0_i32; 128], 1, 64);
+        let _g0 = a.try_lock(7).unwrap();
+        assert!(a.try_lock_offsets(&[0, 3, 7]).is_err());
+        assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 1 << 7);
+        {
+            let g1 = a.try_lock_offsets(&[40, 3, 0]).unwrap();
+            assert_eq!(g1.cycle_offsets().collect::<Vec<_>>(), vec![0, 3, 40]);
+            assert!(a.try_lock(40).is_err());
+        }
+        assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 1 << 7);
+        assert_eq!(a.locked_offsets[1].load(Ordering::Acquire), 0);
+    }
+
+    #[test]
+    fn test_read_lock() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        {
+            let r0 = a.try_read_lock(2).unwrap();
+            let r1 = a.try_read_lock(2).unwrap();
+            assert_eq!(a.readers[2].load(Ordering::Acquire), 2);
+            assert!(a.try_lock(2).is_err());
+            assert!(a.try_lock_multi(&[1, 2]).is_err());
+            assert_eq!(a.locked_offsets[0].load(Ordering::Acquire), 0);
+            let _w = a.try_write_lock(1).unwrap();
+            assert!(a.try_read_lock(1).is_err());
+            assert_eq!(r0[1][..], r1[1][..]);
+        }
+        assert_eq!(a.readers[2].load(Ordering::Acquire), 0);
+        assert_eq!(a.readers[1].load(Ordering::Acquire), 0);
+        let _w = a.try_lock_multi(&[1, 2]).unwrap();
+    }
+
+    #[test]
+    fn test_guard_cycles() {
+        let a = RepVecRangeLock::with_slice_lens((0..11).collect::<Vec<i32>>(), vec![1, 2, 1]);
+        {
+            let g0 = a.try_lock(0).unwrap();
+            let g1 = a.try_lock(1).unwrap();
+            let mut g2 = a.try_lock(2).unwrap();
+            assert_eq!(g0.num_cycles(), 3);
+            assert_eq!(g1.num_cycles(), 3);
+            assert_eq!(g2.num_cycles(), 2);
+            assert_eq!(
+                g1.cycles().collect::<Vec<_>>(),
+                vec![&[1, 2], &[5, 6], &[9, 10]]
+            );
+            for s in g2.cycles_mut() {
+                s[0] = -s[0];
+            }
+            assert_eq!(g2.cycles().flatten().collect::<Vec<_>>(), vec![&-3, &-7]);
+        }
+        let b = RepVecRangeLock::new(vec![0_i32; 1], 1, 2);
+        assert_eq!(b.try_lock(1).unwrap().num_cycles(), 0);
+        assert_eq!(b.try_lock(0).unwrap().cycles().count(), 1);
+    }
+
+    #[test]
+    fn test_iter_offsets() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        let g = a.try_lock(2).unwrap();
+        let res: Vec<bool> = a.iter_offsets().map(|r| r.is_ok()).collect();
+        assert_eq!(res, vec![true, true, false, true]);
+        drop(g);
+        let offsets: Vec<usize> = a
+            .iter_offsets()
+            .map(|r| r.unwrap().cycle_offset())
+            .collect();
+        assert_eq!(offsets, vec![0, 1, 2, 3]);
+        assert!(a.all_offsets_free());
+    }
+
+    #[test]
+    fn test_try_lock_owned() {
+        fn assert_send<S: Send>() {}
+        assert_send::<OwnedRepVecRangeLockGuard<i32>>();
+
+        let a = Arc::new(RepVecRangeLock::new(vec![1_i32, 2, 3, 4, 5, 6], 1, 2));
+        let mut g = Arc::clone(&a).try_lock_owned(1).unwrap();
+        assert_eq!(g.cycle_offset(), 1);
+        assert_eq!(g.num_cycles(), 3);
+        assert!(a.try_lock(1).is_err());
+        assert!(Arc::clone(&a).try_lock_owned(1).is_err());
+        assert!(a.try_lock(0).is_ok());
+        thread::spawn(move || g[2][0] = 60).join().unwrap();
+        assert!(a.all_offsets_free());
+        let a = Arc::try_unwrap(a).unwrap();
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 4, 5, 60]);
+    }
+
+    #[test]
+    fn test_offset_is_locked() {
+        let a = RepVecRangeLock::new(vec![0_i32; 80], 1, 40);
+        assert!(!(0..40).any(|o| a.offset_is_locked(o)));
+        let g0 = a.try_lock(35).unwrap();
+        let g1 = a.try_read_lock(2).unwrap();
+        let locked: Vec<usize> = (0..40).filter(|&o| a.offset_is_locked(o)).collect();
+        assert_eq!(locked, vec![2, 35]);
+        assert!(!a.all_offsets_free());
+        drop(g0);
+        assert!(!a.all_offsets_free());
+        drop(g1);
+        assert!(!(0..40).any(|o| a.offset_is_locked(o)));
+        assert!(a.all_offsets_free());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid cycle_offset")]
+    fn test_offset_is_locked_invalid() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        a.offset_is_locked(4);
+    }
+
+    #[test]
+    fn test_read_lock_blocking_writer() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        let ba = Barrier::new(2);
+        thread::scope(|s| {
+            s.spawn(|| {
+                let r = a.try_read_lock(3).unwrap();
+                ba.wait();
+                thread::sleep(std::time::Duration::from_millis(50));
+                drop(r);
+            });
+            s.spawn(|| {
+                ba.wait();
+                // Blocks, until the reader is gone.
+                let mut g = a.lock(3);
+                assert_eq!(a.readers[3].load(Ordering::Acquire), 0);
+                g[0][0] = 30;
+            });
+        });
+        assert_eq!(a.into_inner()[3], 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "Duplicate cycle_offset 3")]
+    fn test_lock_offsets_duplicate() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        let _ = a.try_lock_offsets(&[3, 0, 3]);
+    }
+
+    #[test]
+    #[should_panic(expected = "not locked by this guard")]
+    fn test_lock_multi_not_held() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        let g = a.try_lock_multi(&[0, 2]).unwrap();
+        let _ = g[(1, 0)];
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid cycle_offset")]
+    fn test_lock_multi_invalid_offset() {
+        let a = RepVecRangeLock::new(vec![0_i32; 8], 1, 4);
+        let _ = a.try_lock_multi(&[0, 4]);
+    }
+
+    #[test]
+    fn test_config() {
+        let a = RepVecRangeLock::new(vec![0_i32; 14], 2, 3);
+        assert_eq!(a.slice_len(), 2);
+        assert_eq!(a.cycle_len(), 3);
+        assert_eq!(a.num_cycles(), 2);
+        let g = a.try_lock(2).unwrap();
+        let _ = &g[a.num_cycles() - 1];
+        assert_eq!(g.cycle_offset(), 2);
+    }
+
+    #[test]
+    fn test_set_cycle_len() {
+        let mut a = RepVecRangeLock::new((0..80).collect::<Vec<i32>>(), 2, 3);
+        a.set_cycle_len(33).unwrap();
+        assert_eq!(a.cycle_len(), 33);
+        assert_eq!(a.num_cycles(), 1);
+        assert_eq!(a.locked_offsets.len(), 2);
+        {
+            let g0 = a.try_lock(32).unwrap();
+            assert_eq!(g0[0], [64, 65]);
+            let _g1 = a.try_read_lock(1).unwrap();
+            assert!(a.try_lock(32).is_err());
+        }
+        assert_eq!(a.set_cycle_len(41), Err(LockError::OutOfBounds));
+        std::mem::forget(a.try_lock(5).unwrap());
+        assert_eq!(
+            a.set_cycle_len(4),
+            Err(LockError::WouldBlock { conflicting: None })
+        );
+        a.unlock(5);
+        a.set_cycle_len(4).unwrap();
+        assert_eq!(a.locked_offsets.len(), 1);
+        assert_eq!(a.num_cycles(), 10);
+        assert_eq!(a.try_lock(3).unwrap()[9], [78, 79]);
+    }
+
+    #[test]
+    fn test_try_into_inner() {
+        let a = RepVecRangeLock::new(vec![1_i32, 2, 3, 4], 1, 2);
+        std::mem::forget(a.try_lock(1).unwrap());
+        let a = a.try_into_inner().unwrap_err();
+        a.unlock(1);
+        assert_eq!(a.try_into_inner().unwrap(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_data_ptr_layout() {
+        // Uniform layout with a stride.
+        let a = RepVecRangeLock::with_stride((0..12).collect::<Vec<i32>>(), 2, 2, 3);
+        let g = a.try_lock(1).unwrap();
+        for cycle in 0..2 {
+            let index = (cycle * a.cycle_len() + 1) * 3;
+            assert_eq!(
+                a.data_ptr().wrapping_add(index).cast_const(),
+                g[cycle].as_ptr()
+            );
+        }
+        drop(g);
+
+        // Variable layout.
+        let a = RepVecRangeLock::with_slice_lens((0..12).collect::<Vec<i32>>(), vec![1, 3, 2]);
+        let g = a.try_lock(2).unwrap();
+        let cycle_elems: usize = (0..a.cycle_len()).map(|o| a.slice_len_at(o)).sum();
+        let start = a.slice_len_at(0) + a.slice_len_at(1);
+        for cycle in 0..2 {
+            let index = cycle * cycle_elems + start;
+            assert_eq!(
+                a.data_ptr().wrapping_add(index).cast_const(),
+                g[cycle].as_ptr()
+            );
+        }
+    }
+
+    #[test]
+    fn test_into_general() {
+        let a = RepVecRangeLock::new(vec![1_i32, 2, 3, 4], 1, 2);
+        let ptr = a.data_ptr();
+        std::mem::forget(a.try_lock(1).unwrap());
+        let a = a.into_general().unwrap_err();
+        a.unlock(1);
+        let a = a.into_general().unwrap();
+        assert_eq!(a.data_ptr(), ptr);
+        let a = a.into_rep(2, 1).unwrap();
+        assert_eq!(a.data_ptr(), ptr);
+        assert_eq!(a.num_cycles(), 2);
+        std::mem::forget(a.try_lock(0).unwrap());
+        let a = a.into_vec_range_lock().unwrap_err();
+        a.unlock(0);
+        let a = a.into_vec_range_lock().unwrap();
+        assert_eq!(a.data_ptr(), ptr);
+        assert_eq!(a.into_inner(), vec![1, 2, 3, 4]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Offsets are still locked")]
+    fn test_into_inner_leaked() {
+        let a = RepVecRangeLock::new(vec![1_i32, 2, 3, 4], 1, 2);
+        std::mem::forget(a.try_lock(0).unwrap());
+        let _ = a.into_inner();
+    }
+
     #[allow(dead_code)]
     struct NoSyncStruct(RefCell<u32>); // No Sync auto-trait.
 
@@ -0,0 +1,213 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::{VecRangeLock, VecRangeLockGuard};
+use std::{
+    num::NonZeroUsize,
+    panic,
+    sync::{Mutex, PoisonError, TryLockError},
+    thread::{self, ScopedJoinHandle},
+};
+
+/// Join all scoped threads.
+///
+/// If any of the threads panicked, the panic of the first one is resumed
+/// after all threads have been joined.
+fn join_all(handles: Vec<ScopedJoinHandle<'_, ()>>) {
+    let mut first_panic = None;
+    for handle in handles {
+        if let Err(payload) = handle.join() {
+            first_panic.get_or_insert(payload);
+        }
+    }
+    if let Some(payload) = first_panic {
+        panic::resume_unwind(payload);
+    }
+}
+
+impl<T> VecRangeLock<T>
+where
+    T: Send,
+{
+    /// Call `f` for each disjoint chunk of the data in parallel.
+    ///
+    /// The chunks are `0..chunk_len`, `chunk_len..2*chunk_len` and so on.
+    /// The last chunk may be shorter than `chunk_len`.
+    /// `f` gets the index of the chunk and exclusive access to its elements.
+    /// `chunk_len` must be >0.
+    ///
+    /// The whole data is locked at once, before any chunk is processed.
+    /// The chunks are processed by scoped threads of the standard library.
+    /// The number of threads is limited by [thread::available_parallelism].
+    /// This method returns after all chunks have been processed.
+    ///
+    /// Panics, if any element is locked elsewhere or if the lock is poisoned.
+    /// A panic in `f` is propagated after all threads have been joined.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// lock.par_for_each_chunk(4, |i, chunk| chunk.fill(i));
+    /// assert_eq!(lock.into_inner(), vec![0, 0, 0, 0, 1, 1, 1, 1, 2, 2]);
+    /// ```
+    pub fn par_for_each_chunk<F>(&self, chunk_len: usize, f: F)
+    where
+        F: Fn(usize, &mut [T]) + Sync,
+    {
+        if chunk_len == 0 {
+            panic!("chunk_len must not be 0.");
+        }
+        let mut guard = match self.try_lock_poisonable(..) {
+            Ok(guard) => guard,
+            Err(TryLockError::WouldBlock) => {
+                panic!("VecRangeLock::par_for_each_chunk: Data is contended.")
+            }
+            Err(TryLockError::Poisoned(_)) => {
+                panic!("VecRangeLock::par_for_each_chunk: Lock is poisoned.")
+            }
+        };
+        let num_chunks = guard.len().div_ceil(chunk_len);
+        let num_threads = thread::available_parallelism()
+            .map_or(1, NonZeroUsize::get)
+            .min(num_chunks);
+        let chunks = Mutex::new(guard.chunks_mut(chunk_len).enumerate());
+        thread::scope(|s| {
+            let handles = (0..num_threads)
+                .map(|_| {
+                    s.spawn(|| loop {
+                        let next = chunks.lock().unwrap_or_else(PoisonError::into_inner).next();
+                        match next {
+                            Some((i, chunk)) => f(i, chunk),
+                            None => break,
+                        }
+                    })
+                })
+                .collect();
+            join_all(handles);
+        });
+    }
+
+    /// Split the data into `n` disjoint partitions and process them in parallel.
+    ///
+    /// One scoped thread is spawned per partition.
+    /// Each thread calls `f` with the index of its partition and the partition's guard.
+    /// This method returns after all threads have been joined.
+    /// See [VecRangeLock::partition] for the partition boundaries.
+    /// Panics, if `n` is 0.
+    ///
+    /// All partitions are locked at once, before any thread is spawned.
+    /// Panics, if any element is locked elsewhere or if the lock is poisoned.
+    /// A panic in `f` is propagated after all threads have been joined.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// lock.scoped_partition(3, |i, mut part| part.fill(i));
+    /// assert_eq!(lock.into_inner(), vec![0, 0, 0, 0, 1, 1, 1, 2, 2, 2]);
+    /// ```
+    pub fn scoped_partition<F>(&self, n: usize, f: F)
+    where
+        F: Fn(usize, VecRangeLockGuard<'_, T>) + Sync,
+    {
+        let parts = match self.partition(n) {
+            Ok(parts) => parts,
+            Err(TryLockError::WouldBlock) => {
+                panic!("VecRangeLock::scoped_partition: Data is contended.")
+            }
+            Err(TryLockError::Poisoned(_)) => {
+                panic!("VecRangeLock::scoped_partition: Lock is poisoned.")
+            }
+        };
+        let f = &f;
+        thread::scope(|s| {
+            let handles = parts
+                .into_iter()
+                .enumerate()
+                .map(|(i, part)| s.spawn(move || f(i, part)))
+                .collect();
+            join_all(handles);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_par_for_each_chunk() {
+        let a = VecRangeLock::with_shards(vec![0_usize; 1001], 4);
+        let calls = AtomicUsize::new(0);
+        a.par_for_each_chunk(100, |i, chunk| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            assert_eq!(chunk.len(), if i == 10 { 1 } else { 100 });
+            chunk.fill(i);
+        });
+        assert_eq!(calls.into_inner(), 11);
+        assert!(a.is_all_unlocked());
+        let data = a.into_inner();
+        assert!(data.iter().enumerate().all(|(j, &x)| x == j / 100));
+
+        let a: VecRangeLock<u8> = VecRangeLock::new(vec![]);
+        a.par_for_each_chunk(10, |_, _| panic!("No chunk expected."));
+    }
+
+    #[test]
+    #[should_panic(expected = "Data is contended")]
+    fn test_par_for_each_chunk_contended() {
+        let a = VecRangeLock::new(vec![0_u32; 100]);
+        let _g = a.try_lock(55..56).unwrap();
+        a.par_for_each_chunk(10, |_, _| ());
+    }
+
+    #[test]
+    fn test_scoped_partition() {
+        let a = VecRangeLock::with_shards(vec![0_usize; 100], 4);
+        a.scoped_partition(7, |i, mut part| {
+            assert!(part.len() == 14 || part.len() == 15);
+            part.fill(i + 1);
+        });
+        assert!(a.is_all_unlocked());
+        let data = a.into_inner();
+        assert_eq!(data[0], 1);
+        assert_eq!(data[99], 7);
+        assert!(data.windows(2).all(|w| w[1] == w[0] || w[1] == w[0] + 1));
+    }
+
+    #[test]
+    #[should_panic(expected = "Worker panic")]
+    fn test_scoped_partition_panic() {
+        let a = VecRangeLock::new(vec![0_u32; 100]);
+        a.scoped_partition(4, |i, _| {
+            if i == 2 {
+                panic!("Worker panic");
+            }
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Worker panic")]
+    fn test_par_for_each_chunk_panic() {
+        let a = VecRangeLock::new(vec![0_u32; 100]);
+        a.par_for_each_chunk(10, |i, _| {
+            if i == 3 {
+                panic!("Worker panic");
+            }
+        });
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
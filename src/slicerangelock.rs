@@ -0,0 +1,194 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard, Storage};
+use std::{
+    marker::PhantomData,
+    ops::{Deref, DerefMut, RangeBounds},
+    ptr::NonNull,
+    sync::TryLockResult,
+};
+
+/// Exclusively borrowed data of a [SliceRangeLock].
+#[derive(Debug)]
+struct BorrowedSlice<'d, T> {
+    /// Pointer to the borrowed data.
+    data: NonNull<[T]>,
+
+    /// The storage exclusively borrows the data.
+    _p: PhantomData<&'d mut [T]>,
+}
+
+// SAFETY:
+// The storage behaves like the exclusive borrow &'d mut [T].
+// It can be sent to other threads, if T is Send.
+unsafe impl<'d, T> Send for BorrowedSlice<'d, T> where T: Send {}
+
+// SAFETY: The borrowed slice is not moved while it is borrowed.
+unsafe impl<'d, T> Storage for BorrowedSlice<'d, T> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        self.data.len()
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        self.data.as_ptr().cast()
+    }
+}
+
+/// General purpose multi-thread range lock for a borrowed mutable slice.
+///
+/// This is a variant of [VecRangeLock](crate::VecRangeLock)
+/// that does not own the data.
+/// It exclusively borrows the data for the lifetime `'d`.
+/// The borrow ends, when the lock is dropped.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::SliceRangeLock;
+/// use std::thread;
+///
+/// let mut data = [1, 2, 3, 4];
+/// {
+///     let lock = SliceRangeLock::new(&mut data);
+///     thread::scope(|s| {
+///         s.spawn(|| {
+///             let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+///             guard[0] = 10;
+///         });
+///         s.spawn(|| {
+///             let mut guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+///             guard[1] = 40;
+///         });
+///     });
+/// }
+/// assert_eq!(data, [10, 2, 3, 40]);
+/// ```
+#[derive(Debug)]
+pub struct SliceRangeLock<'d, T> {
+    /// The lock state and the borrowed data.
+    raw: RawRangeLock<BorrowedSlice<'d, T>>,
+}
+
+impl<'a, 'd, T> SliceRangeLock<'d, T> {
+    /// Construct a new [SliceRangeLock].
+    ///
+    /// * `data`: The borrowed data slice to protect.
+    pub fn new(data: &'d mut [T]) -> SliceRangeLock<'d, T> {
+        SliceRangeLock {
+            raw: RawRangeLock::new(BorrowedSlice {
+                data: NonNull::from(data),
+                _p: PhantomData,
+            }),
+        }
+    }
+
+    /// Get the length (in number of elements) of the borrowed slice.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// Panics, if the range is out of bounds of the slice.
+    ///
+    /// * On success: Returns a [SliceRangeLockGuard] that can be used to access the locked region.
+    ///   Dereferencing [SliceRangeLockGuard] yields a slice of the `data`.
+    /// * On failure: Returns [TryLockError::WouldBlock](std::sync::TryLockError::WouldBlock),
+    ///   if the range is contended.
+    ///   The locking attempt may be retried by the caller upon contention.
+    ///   Returns [TryLockError::Poisoned](std::sync::TryLockError::Poisoned),
+    ///   if the lock is poisoned. The poisoned guard holds the range.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<SliceRangeLockGuard<'a, 'd, T>> {
+        wrap_guard(self.raw.try_lock(range), SliceRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [SliceRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See the documentation of [SliceRangeLock] for usage examples of [SliceRangeLockGuard].
+#[derive(Debug)]
+pub struct SliceRangeLockGuard<'a, 'd, T>(RawRangeLockGuard<'a, BorrowedSlice<'d, T>>);
+
+impl<'a, 'd, T> Deref for SliceRangeLockGuard<'a, 'd, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, 'd, T> DerefMut for SliceRangeLockGuard<'a, 'd, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slice() {
+        let mut data = vec![1_i32, 2, 3, 4, 5, 6];
+        {
+            let a = SliceRangeLock::new(&mut data[1..5]);
+            assert_eq!(a.data_len(), 4);
+            let mut g0 = a.try_lock(0..2).unwrap();
+            assert!(a.try_lock(1..3).is_err());
+            let mut g1 = a.try_lock(2..).unwrap();
+            assert_eq!(g0[..], [2, 3]);
+            assert_eq!(g1[..], [4, 5]);
+            g0[0] = 20;
+            g1[1] = 50;
+            drop(g0);
+            drop(g1);
+            assert!(a.raw.is_all_unlocked());
+        }
+        assert_eq!(data, vec![1, 20, 3, 4, 50, 6]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_slice_oob() {
+        let mut data = [1_i32, 2, 3];
+        let a = SliceRangeLock::new(&mut data);
+        let _ = a.try_lock(2..4);
+    }
+
+    #[test]
+    fn test_slice_max_len() {
+        let mut data = vec![(); usize::MAX];
+        let a = SliceRangeLock::new(&mut data);
+        let g = a.try_lock(..=usize::MAX - 1).unwrap();
+        assert_eq!(g.len(), usize::MAX);
+        drop(g);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_slice_max_inclusive_end() {
+        let mut data = vec![(); usize::MAX];
+        let a = SliceRangeLock::new(&mut data);
+        let _ = a.try_lock(1..=usize::MAX);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
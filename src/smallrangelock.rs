@@ -0,0 +1,193 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    atomicranges::AtomicRanges,
+    rangelock::VecRangeLock,
+    rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard},
+};
+use std::{
+    cell::UnsafeCell,
+    ops::{Deref, DerefMut, RangeBounds},
+    sync::TryLockResult,
+};
+
+impl<T> VecRangeLock<T> {
+    /// Construct a new range lock for few simultaneously locked ranges.
+    ///
+    /// Up to 4 locked ranges are tracked without taking any mutex.
+    /// See [SmallVecRangeLock] for details.
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new_small(data: Vec<T>) -> SmallVecRangeLock<T> {
+        SmallVecRangeLock::new(data)
+    }
+}
+
+/// Multi-thread range lock for [std::vec::Vec] that holds few ranges at a time.
+///
+/// This is a variant of [VecRangeLock]
+/// for the common case of only a handful of simultaneously locked ranges.
+/// Up to `N` locked ranges are tracked in atomic slots without taking any mutex.
+/// More ranges are tracked in a mutex protected set.
+///
+/// It is constructed with [VecRangeLock::new_small] or [SmallVecRangeLock::new].
+///
+/// Locking attempts may fail spuriously with [TryLockError::WouldBlock](std::sync::TryLockError::WouldBlock),
+/// if an overlapping range is being locked concurrently.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::VecRangeLock;
+/// use std::thread;
+///
+/// let lock = VecRangeLock::new_small(vec![1, 2, 3, 4]);
+/// thread::scope(|s| {
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(0..2).expect("Failed to lock 0..2");
+///         guard[0] = 10;
+///     });
+///     s.spawn(|| {
+///         let mut guard = lock.try_lock(2..4).expect("Failed to lock 2..4");
+///         guard[1] = 40;
+///     });
+/// });
+/// assert_eq!(lock.into_inner(), vec![10, 2, 3, 40]);
+/// ```
+#[derive(Debug)]
+pub struct SmallVecRangeLock<T, const N: usize = 4> {
+    /// The atomic set of locked ranges and the protected data.
+    raw: RawRangeLock<UnsafeCell<Vec<T>>, AtomicRanges<N>>,
+}
+
+impl<'a, T, const N: usize> SmallVecRangeLock<T, N> {
+    /// Construct a new [SmallVecRangeLock].
+    ///
+    /// * `data`: The data [Vec] to protect.
+    pub fn new(data: Vec<T>) -> SmallVecRangeLock<T, N> {
+        SmallVecRangeLock {
+            raw: RawRangeLock::new(UnsafeCell::new(data)),
+        }
+    }
+
+    /// Get the length (in number of elements) of the embedded [Vec].
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Unwrap the [SmallVecRangeLock] into the contained data.
+    /// This method consumes self.
+    #[inline]
+    pub fn into_inner(self) -> Vec<T> {
+        debug_assert!(self.raw.is_all_unlocked());
+        self.raw.data.into_inner()
+    }
+
+    /// Try to lock the given data `range`.
+    ///
+    /// See [VecRangeLock::try_lock] for the return values.
+    /// Panics, if the range is out of bounds of the data.
+    pub fn try_lock(
+        &'a self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<SmallVecRangeLockGuard<'a, T, N>> {
+        wrap_guard(self.raw.try_lock(range), SmallVecRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [SmallVecRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+#[derive(Debug)]
+pub struct SmallVecRangeLockGuard<'a, T, const N: usize = 4>(
+    RawRangeLockGuard<'a, UnsafeCell<Vec<T>>, AtomicRanges<N>>,
+);
+
+impl<'a, T, const N: usize> Deref for SmallVecRangeLockGuard<'a, T, N> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a, T, const N: usize> DerefMut for SmallVecRangeLockGuard<'a, T, N> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            TryLockError,
+        },
+        thread,
+    };
+
+    #[test]
+    fn test_overflow() {
+        let a: SmallVecRangeLock<i32, 2> = SmallVecRangeLock::new(vec![0; 10]);
+        let _g0 = a.try_lock(0..2).unwrap();
+        let _g1 = a.try_lock(2..4).unwrap();
+        // The slots are full. The range goes to the overflow set.
+        let mut g2 = a.try_lock(4..6).unwrap();
+        assert!(matches!(a.try_lock(5..7), Err(TryLockError::WouldBlock)));
+        assert!(matches!(a.try_lock(1..3), Err(TryLockError::WouldBlock)));
+        let g3 = a.try_lock(3..3).unwrap();
+        assert!(g3.is_empty());
+        g2[1] = 5;
+        drop((_g0, _g1, g2, g3));
+        assert!(a.raw.is_all_unlocked());
+        assert_eq!(a.into_inner()[5], 5);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_oob() {
+        let a = VecRangeLock::new_small(vec![0_i32; 10]);
+        let _ = a.try_lock(9..11);
+    }
+
+    #[test]
+    fn test_thread_no_overlap() {
+        let a: SmallVecRangeLock<usize, 2> = SmallVecRangeLock::new(vec![0; 8]);
+        let locked = AtomicUsize::new(0);
+        thread::scope(|s| {
+            for t in 0..8 {
+                let (a, locked) = (&a, &locked);
+                s.spawn(move || {
+                    for i in 0..1000 {
+                        // Overlapping ranges. A lost update means a broken lock.
+                        let start = (t + i) % 7;
+                        if let Ok(mut g) = a.try_lock(start..start + 2) {
+                            let v = (g[0], g[1]);
+                            thread::yield_now();
+                            g[0] = v.0 + 1;
+                            g[1] = v.1 + 1;
+                            locked.fetch_add(1, Ordering::Relaxed);
+                        }
+                    }
+                });
+            }
+        });
+        assert!(a.raw.is_all_unlocked());
+        let sum: usize = a.into_inner().iter().sum();
+        assert_eq!(sum, locked.load(Ordering::Relaxed) * 2);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,74 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Usage statistics of a range lock.
+//!
+//! The statistics are only collected with the `stats` feature.
+//! Without the feature [Stats] is empty and all updates compile to nothing.
+
+#[cfg(feature = "stats")]
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Usage statistics of a range lock.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    /// Number of currently held ranges.
+    #[cfg(feature = "stats")]
+    held: AtomicUsize,
+    /// Maximum number of simultaneously held ranges.
+    #[cfg(feature = "stats")]
+    max_held: AtomicUsize,
+}
+
+impl Stats {
+    /// Account for `count` ranges that have been locked.
+    /// Must be called after the ranges have been inserted.
+    #[inline]
+    pub(crate) fn locked(&self, count: usize) {
+        #[cfg(feature = "stats")]
+        {
+            let held = self.held.fetch_add(count, Ordering::Relaxed) + count;
+            self.max_held.fetch_max(held, Ordering::Relaxed);
+        }
+        let _ = count;
+    }
+
+    /// Account for `count` ranges that are about to be unlocked.
+    /// Must be called before the ranges are removed.
+    /// Therefore, the number of held ranges is never overestimated.
+    #[inline]
+    pub(crate) fn unlocking(&self, count: usize) {
+        #[cfg(feature = "stats")]
+        self.held.fetch_sub(count, Ordering::Relaxed);
+        let _ = count;
+    }
+
+    /// Reset the number of held ranges to zero.
+    /// Must only be called, if no range is held.
+    #[inline]
+    pub(crate) fn reset_held(&self) {
+        #[cfg(feature = "stats")]
+        self.held.store(0, Ordering::Relaxed);
+    }
+
+    /// Get the number of currently held ranges.
+    #[cfg(all(test, feature = "stats"))]
+    pub(crate) fn held(&self) -> usize {
+        self.held.load(Ordering::Relaxed)
+    }
+
+    /// Get the maximum number of simultaneously held ranges.
+    #[cfg(feature = "stats")]
+    #[inline]
+    pub(crate) fn max_held(&self) -> usize {
+        self.max_held.load(Ordering::Relaxed)
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
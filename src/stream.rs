@@ -0,0 +1,180 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::rangelock::{OwnedVecRangeLockGuard, VecRangeLock};
+use futures_core::Stream;
+use std::{
+    pin::Pin,
+    sync::{Arc, TryLockError},
+    task::{Context, Poll},
+};
+
+/// Stream of guards of successive disjoint chunks.
+struct ChunkStream<T> {
+    lock: Arc<VecRangeLock<T>>,
+    chunk_len: usize,
+    /// Start of the next chunk.
+    pos: usize,
+}
+
+impl<T> Stream for ChunkStream<T> {
+    type Item = OwnedVecRangeLockGuard<T>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let data_len = this.lock.data_len();
+        if this.pos >= data_len {
+            return Poll::Ready(None);
+        }
+        let range = this.pos..this.pos.saturating_add(this.chunk_len).min(data_len);
+        let lock = &this.lock;
+        let poll = lock.waiters().poll_acquire(cx, || {
+            match Arc::clone(lock).try_lock_owned(range.clone()) {
+                Ok(guard) => Some(guard),
+                Err(TryLockError::WouldBlock) => None,
+                Err(TryLockError::Poisoned(_)) => {
+                    panic!("VecRangeLock::lock_chunks_stream: Lock is poisoned.")
+                }
+            }
+        });
+        poll.map(|guard| {
+            this.pos = range.end;
+            Some(guard)
+        })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        // The data length may grow. See VecRangeLock::try_append().
+        let remaining = self
+            .lock
+            .data_len()
+            .saturating_sub(self.pos)
+            .div_ceil(self.chunk_len);
+        (remaining, None)
+    }
+}
+
+impl<T> VecRangeLock<T> {
+    /// Get a [Stream] of owned guards of successive disjoint chunks of the data.
+    ///
+    /// The chunks are `0..chunk_len`, `chunk_len..2*chunk_len` and so on.
+    /// The last chunk may be shorter than `chunk_len`.
+    /// `chunk_len` must be >0.
+    ///
+    /// Each chunk is locked, when it is polled from the stream.
+    /// If a chunk is contended, the task waits until the chunk is unlocked elsewhere.
+    /// Panics, if the lock is poisoned.
+    ///
+    /// This method is only available with the `futures` feature.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use futures::{executor::block_on, StreamExt};
+    /// use range_lock::VecRangeLock;
+    /// use std::sync::Arc;
+    ///
+    /// let lock = Arc::new(VecRangeLock::new(vec![1; 100]));
+    /// block_on(async {
+    ///     let mut stream = Arc::clone(&lock).lock_chunks_stream(10);
+    ///     while let Some(mut guard) = stream.next().await {
+    ///         for x in guard.iter_mut() {
+    ///             *x *= 2;
+    ///         }
+    ///     }
+    /// });
+    /// assert_eq!(Arc::into_inner(lock).unwrap().into_inner(), vec![2; 100]);
+    /// ```
+    pub fn lock_chunks_stream(
+        self: Arc<Self>,
+        chunk_len: usize,
+    ) -> impl Stream<Item = OwnedVecRangeLockGuard<T>> {
+        if chunk_len == 0 {
+            panic!("chunk_len must not be 0.");
+        }
+        ChunkStream {
+            lock: self,
+            chunk_len,
+            pos: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{
+        executor::block_on,
+        task::{waker, ArcWake},
+        StreamExt,
+    };
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[test]
+    fn test_lock_chunks_stream() {
+        let lock = Arc::new(VecRangeLock::new((0..25).collect::<Vec<u32>>()));
+        let chunks: Vec<Vec<u32>> = block_on(
+            Arc::clone(&lock)
+                .lock_chunks_stream(10)
+                .map(|guard| guard.to_vec())
+                .collect(),
+        );
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[1], (10..20).collect::<Vec<u32>>());
+        assert_eq!(chunks[2], (20..25).collect::<Vec<u32>>());
+        assert!(lock.try_lock(..).is_ok());
+    }
+
+    #[test]
+    fn test_lock_chunks_stream_contended() {
+        struct Flag(AtomicBool);
+        impl ArcWake for Flag {
+            fn wake_by_ref(arc_self: &Arc<Self>) {
+                arc_self.0.store(true, Ordering::SeqCst);
+            }
+        }
+        let flag = Arc::new(Flag(AtomicBool::new(false)));
+        let waker = waker(Arc::clone(&flag));
+        let mut cx = Context::from_waker(&waker);
+
+        let lock = Arc::new(VecRangeLock::new(vec![0_u32; 30]));
+        let mut blocker = Arc::clone(&lock).try_lock_owned(15..16).unwrap();
+        let mut stream = Box::pin(Arc::clone(&lock).lock_chunks_stream(10));
+        match stream.as_mut().poll_next(&mut cx) {
+            Poll::Ready(Some(guard)) => assert_eq!(guard.len(), 10),
+            _ => panic!("First chunk is not contended"),
+        }
+        // The second chunk is contended.
+        assert!(stream.as_mut().poll_next(&mut cx).is_pending());
+        assert!(!flag.0.load(Ordering::SeqCst));
+        blocker[0] = 100;
+        drop(blocker);
+        assert!(flag.0.load(Ordering::SeqCst));
+        match stream.as_mut().poll_next(&mut cx) {
+            Poll::Ready(Some(guard)) => assert_eq!(guard[5], 100),
+            _ => panic!("Second chunk is still contended"),
+        }
+        assert!(matches!(
+            stream.as_mut().poll_next(&mut cx),
+            Poll::Ready(Some(_))
+        ));
+        assert!(matches!(
+            stream.as_mut().poll_next(&mut cx),
+            Poll::Ready(None)
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "chunk_len must not be 0")]
+    fn test_lock_chunks_stream_zero() {
+        let _ = Arc::new(VecRangeLock::new(vec![0_u32; 3])).lock_chunks_stream(0);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -0,0 +1,230 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::{
+    rangelock::VecRangeLock,
+    rawrangelock::{wrap_guard, RawRangeLock, RawRangeLockGuard, Storage},
+};
+use std::{
+    mem,
+    ops::{Deref, DerefMut, Range, RangeBounds},
+    sync::{PoisonError, TryLockError, TryLockResult},
+};
+
+impl<'a, T> VecRangeLock<T> {
+    /// Lock the data `range` and return a child lock for this region.
+    ///
+    /// The returned [SubRangeLock] keeps `range` locked in this lock for its whole lifetime.
+    /// It has its own set of locked ranges within the region.
+    /// The ranges passed to the [SubRangeLock] are relative to the start of `range`.
+    ///
+    /// * On success: Returns the [SubRangeLock].
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use range_lock::VecRangeLock;
+    /// use std::thread;
+    ///
+    /// let lock = VecRangeLock::new(vec![0; 10]);
+    /// {
+    ///     let sub = lock.subview(4..8).expect("Failed to lock 4..8");
+    ///     assert_eq!(sub.data_len(), 4);
+    ///     thread::scope(|s| {
+    ///         s.spawn(|| sub.try_lock(0..2).expect("Failed to lock 0..2")[0] = 1);
+    ///         s.spawn(|| sub.try_lock(2..4).expect("Failed to lock 2..4")[1] = 2);
+    ///     });
+    ///     assert!(lock.try_lock(7..8).is_err());
+    /// }
+    /// assert_eq!(lock.into_inner(), vec![0, 0, 0, 0, 1, 0, 0, 2, 0, 0]);
+    /// ```
+    #[allow(clippy::result_large_err)]
+    pub fn subview(&'a self, range: impl RangeBounds<usize>) -> TryLockResult<SubRangeLock<'a, T>> {
+        let range = self.check_range(&range);
+        let poisoned = match self.try_lock_poisonable(range.clone()) {
+            Ok(guard) => {
+                mem::forget(guard);
+                false
+            }
+            Err(TryLockError::WouldBlock) => return Err(TryLockError::WouldBlock),
+            Err(TryLockError::Poisoned(e)) => {
+                mem::forget(e.into_inner());
+                true
+            }
+        };
+        // The guard has been forgotten.
+        // The range is unlocked, when the SubRangeLock is dropped.
+        let sub = SubRangeLock {
+            raw: RawRangeLock::new(Region {
+                parent: self,
+                range,
+            }),
+        };
+        if poisoned {
+            Err(TryLockError::Poisoned(PoisonError::new(sub)))
+        } else {
+            Ok(sub)
+        }
+    }
+}
+
+/// Region of a [VecRangeLock] that is locked by a [SubRangeLock].
+#[derive(Debug)]
+struct Region<'a, T> {
+    /// The parent lock.
+    parent: &'a VecRangeLock<T>,
+    /// The region locked in the parent lock.
+    range: Range<usize>,
+}
+
+impl<'a, T> Drop for Region<'a, T> {
+    #[inline]
+    fn drop(&mut self) {
+        self.parent.poison_data_on_panic(&self.range);
+        self.parent.unlock(&self.range);
+    }
+}
+
+// SAFETY: The region is locked in the parent lock for the lifetime of the storage.
+//         Therefore, the parent data is not modified or moved.
+unsafe impl<'a, T> Storage for Region<'a, T> {
+    type Elem = T;
+
+    #[inline]
+    fn data_len(&self) -> usize {
+        self.range.len()
+    }
+
+    #[inline]
+    fn as_ptr(&self) -> *mut T {
+        self.parent.get_ptr(&self.range)
+    }
+}
+
+/// Range lock for a locked region of a [VecRangeLock].
+///
+/// This lock is created by [VecRangeLock::subview].
+/// All ranges are relative to the start of the region.
+/// The region is unlocked in the parent lock, when this lock is dropped.
+#[derive(Debug)]
+pub struct SubRangeLock<'a, T> {
+    /// The set of locked ranges, relative to the start of the region, and the region.
+    raw: RawRangeLock<Region<'a, T>>,
+}
+
+impl<'a, 's, T> SubRangeLock<'a, T> {
+    /// Get the length (in number of elements) of the region.
+    #[inline]
+    pub fn data_len(&self) -> usize {
+        self.raw.data_len()
+    }
+
+    /// Get the region locked in the parent lock, in parent indices.
+    #[inline]
+    pub fn parent_range(&self) -> Range<usize> {
+        self.raw.data.range.clone()
+    }
+
+    /// Try to lock the given `range` of the region.
+    ///
+    /// The `range` is relative to the start of the region.
+    /// Panics, if the range is out of bounds of the region.
+    ///
+    /// * On success: Returns a [SubRangeLockGuard] that can be used to access the locked region.
+    /// * On failure: Returns [TryLockError::WouldBlock], if the range is contended.
+    ///   Returns [TryLockError::Poisoned], if the lock is poisoned.
+    ///   The poisoned guard holds the range.
+    pub fn try_lock(
+        &'s self,
+        range: impl RangeBounds<usize>,
+    ) -> TryLockResult<SubRangeLockGuard<'s, 'a, T>> {
+        wrap_guard(self.raw.try_lock(range), SubRangeLockGuard)
+    }
+}
+
+/// Lock guard variable type for [SubRangeLock].
+///
+/// The [Deref] and [DerefMut] traits are implemented for this struct.
+/// See [VecRangeLock::subview] for usage examples.
+#[derive(Debug)]
+pub struct SubRangeLockGuard<'s, 'a, T>(RawRangeLockGuard<'s, Region<'a, T>>);
+
+impl<'s, 'a, T> SubRangeLockGuard<'s, 'a, T> {
+    /// Get the locked range, relative to the start of the region.
+    #[inline]
+    pub fn range(&self) -> Range<usize> {
+        self.0.range()
+    }
+}
+
+impl<'s, 'a, T> Deref for SubRangeLockGuard<'s, 'a, T> {
+    type Target = [T];
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'s, 'a, T> DerefMut for SubRangeLockGuard<'s, 'a, T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_subview() {
+        let a = VecRangeLock::with_shards((0..100).collect::<Vec<u32>>(), 4);
+        {
+            let sub = a.subview(20..60).unwrap();
+            assert_eq!(sub.data_len(), 40);
+            assert_eq!(sub.parent_range(), 20..60);
+            assert!(a.try_lock(59..61).is_err());
+            assert!(a.subview(10..21).is_err());
+            let _outside = a.try_lock(60..61).unwrap();
+
+            let mut g0 = sub.try_lock(0..10).unwrap();
+            assert_eq!(g0[0], 20);
+            assert!(sub.try_lock(9..11).is_err());
+            let g1 = sub.try_lock(30..).unwrap();
+            assert_eq!(g1.range(), 30..40);
+            assert_eq!(g1[..], (50..60).collect::<Vec<u32>>());
+            g0[0] = 200;
+            thread::scope(|s| {
+                s.spawn(|| sub.try_lock(10..30).unwrap().fill(0));
+            });
+            drop(g0);
+            drop(g1);
+            assert!(sub.raw.is_all_unlocked());
+        }
+        assert!(a.is_all_unlocked());
+        let data = a.into_inner();
+        assert_eq!(data[20], 200);
+        assert!(data[30..50].iter().all(|x| *x == 0));
+        assert_eq!(data[50], 50);
+    }
+
+    #[test]
+    #[should_panic(expected = "Range is out of bounds")]
+    fn test_subview_oob() {
+        let a = VecRangeLock::new(vec![0_u32; 10]);
+        let sub = a.subview(2..5).unwrap();
+        let _ = sub.try_lock(2..4);
+    }
+}
+
+// vim: ts=4 sw=4 expandtab
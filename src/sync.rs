@@ -0,0 +1,106 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+//! Result types of the locking attempts.
+//!
+//! With the `std` feature these are the types of [std::sync].
+//! Without `std` these are crate-local equivalents with the same interface.
+
+#[cfg(feature = "std")]
+pub use std::sync::{PoisonError, TryLockError, TryLockResult};
+
+#[cfg(not(feature = "std"))]
+pub use nostd::{PoisonError, TryLockError, TryLockResult};
+
+#[cfg(not(feature = "std"))]
+mod nostd {
+    use core::{error::Error, fmt};
+
+    /// Equivalent of `std::sync::PoisonError` for builds without `std`.
+    pub struct PoisonError<T> {
+        guard: T,
+    }
+
+    impl<T> PoisonError<T> {
+        /// Construct a new [PoisonError] that carries the `guard`.
+        pub fn new(guard: T) -> PoisonError<T> {
+            PoisonError { guard }
+        }
+
+        /// Get the guard that is carried by this error.
+        pub fn into_inner(self) -> T {
+            self.guard
+        }
+
+        /// Get a reference to the guard that is carried by this error.
+        pub fn get_ref(&self) -> &T {
+            &self.guard
+        }
+
+        /// Get a mutable reference to the guard that is carried by this error.
+        pub fn get_mut(&mut self) -> &mut T {
+            &mut self.guard
+        }
+    }
+
+    impl<T> fmt::Debug for PoisonError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.debug_struct("PoisonError").finish_non_exhaustive()
+        }
+    }
+
+    impl<T> fmt::Display for PoisonError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str("poisoned lock: another task failed inside")
+        }
+    }
+
+    impl<T> Error for PoisonError<T> {}
+
+    /// Equivalent of `std::sync::TryLockError` for builds without `std`.
+    pub enum TryLockError<T> {
+        /// The lock is poisoned.
+        Poisoned(PoisonError<T>),
+        /// The lock is contended.
+        WouldBlock,
+    }
+
+    impl<T> From<PoisonError<T>> for TryLockError<T> {
+        fn from(e: PoisonError<T>) -> TryLockError<T> {
+            TryLockError::Poisoned(e)
+        }
+    }
+
+    impl<T> fmt::Debug for TryLockError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Poisoned(e) => e.fmt(f),
+                Self::WouldBlock => f.write_str("WouldBlock"),
+            }
+        }
+    }
+
+    impl<T> fmt::Display for TryLockError<T> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Poisoned(e) => e.fmt(f),
+                Self::WouldBlock => {
+                    f.write_str("try_lock failed because the operation would block")
+                }
+            }
+        }
+    }
+
+    impl<T> Error for TryLockError<T> {}
+
+    /// Equivalent of `std::sync::TryLockResult` for builds without `std`.
+    pub type TryLockResult<T> = Result<T, TryLockError<T>>;
+}
+
+// vim: ts=4 sw=4 expandtab
@@ -7,28 +7,84 @@
 // SPDX-License-Identifier: Apache-2.0 OR MIT
 //
 
-use std::ops::{Bound, RangeBounds};
+//! Range utilities.
+//!
+//! These helpers implement the overlap semantics that the locks of this crate use.
+//! A range conflicts with a locked range, if and only if [overlaps] returns true.
+
+pub use crate::lockedranges::overlaps;
+use std::ops::{Bound, Range, RangeBounds};
 
 /// Get the `(start, end)` bounds from a `RangeBounds<usize>` trait.
 /// `start` is inclusive and `end` is exclusive.
+///
+/// An inclusive end of [usize::MAX] has no exclusive representation.
+/// The index [usize::MAX] is beyond the end of any data,
+/// because the data length can't exceed [usize::MAX].
+/// Therefore, such a range is out of bounds and this function panics.
 #[inline]
-pub fn get_bounds(range: &impl RangeBounds<usize>, length: usize) -> (usize, usize) {
+pub(crate) fn get_bounds(range: &impl RangeBounds<usize>, length: usize) -> (usize, usize) {
     let start = match range.start_bound() {
         Bound::Included(x) => *x,
         Bound::Excluded(_) => panic!("get_bounds: Start bound must be inclusive or unbounded."),
         Bound::Unbounded => 0,
     };
     let end = match range.end_bound() {
-        Bound::Included(x) => {
-            assert!(*x < usize::MAX);
-            *x + 1 // to excluded
-        }
+        Bound::Included(x) => x
+            .checked_add(1) // to excluded
+            .unwrap_or_else(|| panic!("Range is out of bounds.")),
         Bound::Excluded(x) => *x,
         Bound::Unbounded => length,
     };
     (start, end)
 }
 
+/// Index type of the ranges of an [IndexRangeLock](crate::IndexRangeLock).
+pub trait LockIndex: Copy + Ord {
+    /// Convert the index to [usize].
+    /// Returns `None`, if the index does not fit into [usize].
+    fn to_usize(self) -> Option<usize>;
+}
+
+macro_rules! impl_lock_index {
+    ($($t:ty),*) => {
+        $(
+            impl LockIndex for $t {
+                #[inline]
+                fn to_usize(self) -> Option<usize> {
+                    self.try_into().ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_lock_index!(u8, u16, u32, u64, u128, usize);
+
+/// Check whether any two of the non-empty `ranges` overlap with each other.
+/// The `ranges` must be sorted by their start.
+/// Empty ranges never overlap with anything.
+///
+/// # Example
+///
+/// ```
+/// use range_lock::util::overlaps_any;
+///
+/// assert!(!overlaps_any(&[0..2, 2..4, 6..8]));
+/// assert!(overlaps_any(&[0..3, 2..4, 6..8]));
+/// ```
+pub fn overlaps_any(ranges: &[Range<usize>]) -> bool {
+    let mut prev: Option<&Range<usize>> = None;
+    for range in ranges.iter().filter(|r| !r.is_empty()) {
+        debug_assert!(prev.is_none_or(|p| p.start <= range.start));
+        if prev.is_some_and(|p| overlaps(p, range)) {
+            return true;
+        }
+        prev = Some(range);
+    }
+    false
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -42,12 +98,34 @@ mod tests {
         assert_eq!(get_bounds(&(..=20), 0), (0, 21));
         assert_eq!(get_bounds(&(10..), 42), (10, 42));
         assert_eq!(get_bounds(&(..), 42), (0, 42));
+        assert_eq!(
+            get_bounds(&(..=usize::MAX - 1), usize::MAX),
+            (0, usize::MAX)
+        );
+    }
+
+    #[test]
+    fn test_overlaps_any() {
+        assert!(!overlaps_any(&[]));
+        assert!(!overlaps_any(&[0..10, 10..10]));
+        assert!(!overlaps_any(&[0..10, 10..20, 30..40]));
+        assert!(overlaps_any(&[0..10, 9..20, 30..40]));
+        assert!(overlaps_any(&[0..10, 10..31, 30..40]));
+        assert!(!overlaps_any(&[0..10, 5..5, 10..20]));
+        assert!(overlaps_any(&[0..10, 5..5, 8..20]));
+    }
+
+    #[test]
+    fn test_lock_index() {
+        assert_eq!(42_u8.to_usize(), Some(42));
+        assert_eq!(42_u64.to_usize(), Some(42));
+        assert_eq!(u128::MAX.to_usize(), None);
     }
 
     #[test]
-    #[should_panic(expected = "< usize::MAX")]
+    #[should_panic(expected = "Range is out of bounds")]
     fn test_get_bounds_end_panic() {
-        get_bounds(&(..=usize::MAX), 0);
+        get_bounds(&(..=usize::MAX), usize::MAX);
     }
 }
 
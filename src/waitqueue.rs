@@ -0,0 +1,157 @@
+// -*- coding: utf-8 -*-
+//
+// Copyright 2021-2024 Michael Büsch <m@bues.ch>
+//
+// Licensed under the Apache License version 2.0
+// or the MIT license, at your option.
+// SPDX-License-Identifier: Apache-2.0 OR MIT
+//
+
+use crate::lockedranges::overlaps;
+use std::{
+    collections::BTreeMap,
+    ops::Range,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Condvar, Mutex, PoisonError,
+    },
+};
+#[cfg(feature = "futures")]
+use std::{
+    sync::MutexGuard,
+    task::{Context, Poll, Waker},
+};
+
+/// The threads waiting in a [WaitQueue].
+#[derive(Debug, Default)]
+struct WaitState {
+    /// The ticket of the next arriving thread.
+    next_ticket: u64,
+    /// The requested ranges and priorities of the waiting threads, keyed by arrival ticket.
+    waiting: BTreeMap<u64, (Range<usize>, u8)>,
+    /// The wakers of the waiting tasks.
+    #[cfg(feature = "futures")]
+    wakers: Vec<Waker>,
+}
+
+/// Wait queue of the threads blocking on contended ranges.
+#[derive(Debug, Default)]
+pub struct WaitQueue {
+    /// Number of threads and tasks waiting in the queue.
+    count: AtomicUsize,
+    /// The waiting threads.
+    state: Mutex<WaitState>,
+    /// Condition that is notified, if a range is unlocked.
+    cond: Condvar,
+}
+
+impl WaitQueue {
+    /// Block the calling thread until `try_acquire` succeeds.
+    ///
+    /// `try_acquire` shall try to lock `range` and return None on contention.
+    ///
+    /// A thread only tries to acquire its range,
+    /// if no thread with a higher `priority` is waiting for an overlapping range.
+    ///
+    /// If `fair` is true, the waiting threads of the same priority are served in arrival order.
+    /// A thread only tries to acquire its range,
+    /// if no thread of the same priority that arrived earlier is waiting for an overlapping range.
+    pub fn wait<R>(
+        &self,
+        range: &Range<usize>,
+        fair: bool,
+        priority: u8,
+        mut try_acquire: impl FnMut() -> Option<R>,
+    ) -> R {
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        let ticket = state.next_ticket;
+        state.next_ticket += 1;
+        state.waiting.insert(ticket, (range.clone(), priority));
+        // Announce the waiter before trying.
+        // See notify() for the corresponding check.
+        self.count.fetch_add(1, Ordering::SeqCst);
+        let result = loop {
+            let is_next = !state.waiting.iter().any(|(&other, (waiting, prio))| {
+                overlaps(waiting, range)
+                    && (*prio > priority || (fair && *prio == priority && other < ticket))
+            });
+            if is_next {
+                if let Some(result) = try_acquire() {
+                    break result;
+                }
+            }
+            // The wakeup might have been caused by an unrelated range.
+            // Just check our range again and go back to sleep, if it's still contended.
+            state = self
+                .cond
+                .wait(state)
+                .unwrap_or_else(PoisonError::into_inner);
+        };
+        state.waiting.remove(&ticket);
+        self.count.fetch_sub(1, Ordering::SeqCst);
+        result
+    }
+
+    /// Poll `try_acquire` from an async task.
+    ///
+    /// `try_acquire` shall try to lock a range and return None on contention.
+    /// On contention, the task is woken up, when a range is unlocked.
+    #[cfg(feature = "futures")]
+    pub fn poll_acquire<R>(
+        &self,
+        cx: &mut Context<'_>,
+        mut try_acquire: impl FnMut() -> Option<R>,
+    ) -> Poll<R> {
+        if let Some(result) = try_acquire() {
+            return Poll::Ready(result);
+        }
+        {
+            let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            if !state.wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                state.wakers.push(cx.waker().clone());
+                // Announce the waker before trying again.
+                // See notify() for the corresponding check.
+                self.count.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+        // The range might have been unlocked before the waker was registered.
+        // A succeeding try leaves a stale waker, which causes one spurious wakeup.
+        match try_acquire() {
+            Some(result) => Poll::Ready(result),
+            None => Poll::Pending,
+        }
+    }
+
+    /// Wake up all waiting tasks.
+    #[cfg(feature = "futures")]
+    fn wake_tasks(&self, mut state: MutexGuard<'_, WaitState>) {
+        let wakers = std::mem::take(&mut state.wakers);
+        self.count.fetch_sub(wakers.len(), Ordering::SeqCst);
+        drop(state);
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Get the number of threads waiting in the queue.
+    #[cfg(test)]
+    pub fn num_waiting(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+
+    /// Wake up the waiting threads, because a range has been unlocked.
+    #[inline]
+    pub fn notify(&self) {
+        // The range removal and the waiter count operations are sequentially consistent.
+        // Therefore, either the waiter sees the removed range
+        // or we see the waiter count increment.
+        if self.count.load(Ordering::SeqCst) != 0 {
+            let state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+            self.cond.notify_all();
+            #[cfg(feature = "futures")]
+            self.wake_tasks(state);
+            #[cfg(not(feature = "futures"))]
+            drop(state);
+        }
+    }
+}
+
+// vim: ts=4 sw=4 expandtab